@@ -1,6 +1,6 @@
 #!/bin/sh -ex
 
-FEATURES="base64 bitcoinconsensus use-serde rand"
+FEATURES="base64 bip39 bitcoinconsensus use-serde serde_json rand global-context"
 
 pin_common_verions() {
     cargo generate-lockfile --verbose
@@ -20,21 +20,147 @@
 //! these blocks and the blockchain.
 //!
 
-use std::fmt;
+use std::collections::HashMap;
+use std::{fmt, io};
 
 use util;
-use util::Error::{BlockBadTarget, BlockBadProofOfWork};
+use util::Error::{BlockBadTarget, BlockBadProofOfWork, BlockTimeTooNew, BlockTimeTooOld};
 use util::hash::bitcoin_merkle_root;
 use hashes::{Hash, HashEngine};
 use hash_types::{Wtxid, BlockHash, TxMerkleNode, WitnessMerkleNode, WitnessCommitment};
 use util::uint::Uint256;
-use consensus::encode::Encodable;
+use consensus::encode::{self, Decodable, Encodable};
+use consensus::params::{self, Params};
 use network::constants::Network;
-use blockdata::transaction::Transaction;
+use blockdata::transaction::{OutPoint, Transaction, TxOut};
 use blockdata::constants::{max_target, WITNESS_SCALE_FACTOR};
-use blockdata::script;
+use blockdata::script::{self, Script};
 use VarInt;
 
+/// The compact encoding of a proof-of-work target, as found in the `bits`
+/// (`nBits`) field of a block header.
+///
+/// The encoding is a floating point number with a one byte exponent and a
+/// three byte mantissa, so it can only represent targets approximately.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CompactTarget(u32);
+
+impl CompactTarget {
+    /// Wraps the `nBits` value as found in a serialized block header.
+    pub fn from_consensus(bits: u32) -> CompactTarget {
+        CompactTarget(bits)
+    }
+
+    /// Returns the `nBits` value as found in a serialized block header.
+    pub fn to_consensus(self) -> u32 {
+        self.0
+    }
+
+    /// Computes the compact representation of `target`, rounding it down.
+    pub fn from_target(target: &Uint256) -> CompactTarget {
+        CompactTarget(BlockHeader::compact_target_from_u256(target))
+    }
+
+    /// Expands the compact representation into the full target.
+    pub fn to_target(self) -> Uint256 {
+        BlockHeader::u256_from_compact_target(self.0)
+    }
+}
+
+impl fmt::Display for CompactTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#010x}", self.0)
+    }
+}
+
+impl fmt::LowerHex for CompactTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl Encodable for CompactTarget {
+    fn consensus_encode<S: io::Write>(&self, s: S) -> Result<usize, io::Error> {
+        self.0.consensus_encode(s)
+    }
+}
+
+impl Decodable for CompactTarget {
+    fn consensus_decode<D: io::Read>(d: D) -> Result<Self, encode::Error> {
+        Ok(CompactTarget(Decodable::consensus_decode(d)?))
+    }
+}
+
+/// A proof-of-work target: a block hash, read as a little-endian 256-bit
+/// number, must not exceed it for the block to be valid.
+///
+/// This is kept distinct from the work a block represents, which grows as the
+/// target shrinks, see [`Target::to_work`].
+///
+/// [`Target::to_work`]: #method.to_work
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Target(Uint256);
+
+impl Target {
+    /// Wraps a target given as a 256-bit number.
+    pub fn from_uint256(target: Uint256) -> Target {
+        Target(target)
+    }
+
+    /// Returns the target as a 256-bit number.
+    pub fn to_uint256(self) -> Uint256 {
+        self.0
+    }
+
+    /// Expands a compact target, as found in a block header.
+    pub fn from_compact(bits: CompactTarget) -> Target {
+        Target(bits.to_target())
+    }
+
+    /// Computes the compact representation of the target. This is lossy, as
+    /// the compact encoding only keeps the 23 most significant bits.
+    pub fn to_compact_lossy(self) -> CompactTarget {
+        CompactTarget::from_target(&self.0)
+    }
+
+    /// Computes the difficulty of the target relative to the network's
+    /// [`Params::pow_limit`], so a block at the lowest possible difficulty has
+    /// difficulty 1.
+    ///
+    /// [`Params::pow_limit`]: ../../consensus/params/struct.Params.html#structfield.pow_limit
+    pub fn difficulty(&self, params: &Params) -> u64 {
+        (params.pow_limit / self.0).low_u64()
+    }
+
+    /// Computes the difficulty like [`difficulty`], but without rounding it
+    /// down to an integer, as displayed by Bitcoin Core.
+    ///
+    /// [`difficulty`]: #method.difficulty
+    pub fn difficulty_float(&self, params: &Params) -> f64 {
+        params.pow_limit.to_f64() / self.0.to_f64()
+    }
+
+    /// Checks whether `hash` satisfies the target.
+    pub fn is_met_by(&self, hash: BlockHash) -> bool {
+        let mut ret = [0u64; 4];
+        util::endian::bytes_to_u64_slice_le(hash.as_inner(), &mut ret);
+        Uint256(ret) <= self.0
+    }
+
+    /// Computes the expected number of hashes needed to meet the target,
+    /// which is the work a block with this target adds to the chain.
+    pub fn to_work(self) -> Uint256 {
+        // 2**256 / (target + 1) == ~target / (target+1) + 1    (eqn shamelessly stolen from bitcoind)
+        let mut ret = !self.0;
+        let mut ret1 = self.0;
+        ret1.increment();
+        ret = ret / ret1;
+        ret.increment();
+        ret
+    }
+}
+
 /// A block header, which contains all the block's information except
 /// the actual transactions
 #[derive(Copy, PartialEq, Eq, Clone, Debug)]
@@ -50,14 +176,60 @@ pub struct BlockHeader {
     pub time: u32,
     /// The target value below which the blockhash must lie, encoded as a
     /// a float (with well-defined rounding, of course)
-    pub bits: u32,
+    pub bits: CompactTarget,
     /// The nonce, selected to obtain a low enough blockhash
     pub nonce: u32,
 }
 
 impl_consensus_encoding!(BlockHeader, version, prev_blockhash, merkle_root, time, bits, nonce);
+impl_consensus_hex!(BlockHeader);
 
 impl BlockHeader {
+    /// Decodes a block header directly from its 80-byte consensus encoding,
+    /// without going through a reader.
+    ///
+    /// Every 80-byte string is a well-formed header, so this does not
+    /// currently fail; it returns a `Result` like [`Decodable::consensus_decode`].
+    ///
+    /// [`Decodable::consensus_decode`]: ../../consensus/encode/trait.Decodable.html#tymethod.consensus_decode
+    pub fn from_bytes(bytes: &[u8; HEADER_SIZE]) -> Result<BlockHeader, encode::Error> {
+        let mut prev_blockhash = [0u8; 32];
+        prev_blockhash.copy_from_slice(&bytes[4..36]);
+        let mut merkle_root = [0u8; 32];
+        merkle_root.copy_from_slice(&bytes[36..68]);
+        Ok(BlockHeader {
+            version: util::endian::slice_to_i32_le(&bytes[0..4]),
+            prev_blockhash: BlockHash::from_inner(prev_blockhash),
+            merkle_root: TxMerkleNode::from_inner(merkle_root),
+            time: util::endian::slice_to_u32_le(&bytes[68..72]),
+            bits: CompactTarget::from_consensus(util::endian::slice_to_u32_le(&bytes[72..76])),
+            nonce: util::endian::slice_to_u32_le(&bytes[76..80]),
+        })
+    }
+
+    /// Checks whether the header's version signals readiness for the BIP9
+    /// soft fork using version bit `bit`: the top three bits have to be `001`
+    /// and the bit has to be set. Only bits 0 to 28 can be signalled.
+    pub fn signals_bit(&self, bit: u8) -> bool {
+        params::signals_bit(self.version, bit)
+    }
+
+    /// Splits the 80-byte encoding of the header at the boundary of the first
+    /// SHA-256 block, as done by mining protocols such as Stratum.
+    ///
+    /// The first 64 bytes only depend on the version, the previous block hash
+    /// and most of the merkle root, so their SHA-256 midstate can be computed
+    /// once per job. The other 16 bytes are the end of the merkle root, the
+    /// time, the target and the nonce, and are hashed for every attempt.
+    pub fn mining_chunks(&self) -> ([u8; 64], [u8; 16]) {
+        let bytes = encode::serialize(self);
+        let mut first = [0u8; 64];
+        first.copy_from_slice(&bytes[..64]);
+        let mut last = [0u8; 16];
+        last.copy_from_slice(&bytes[64..]);
+        (first, last)
+    }
+
     /// Return the block hash.
     pub fn block_hash(&self) -> BlockHash {
         let mut engine = BlockHash::engine();
@@ -66,8 +238,8 @@ impl BlockHeader {
     }
 
     /// Computes the target [0, T] that a blockhash must land in to be valid
-    pub fn target(&self) -> Uint256 {
-        Self::u256_from_compact_target(self.bits)
+    pub fn target(&self) -> Target {
+        Target::from_compact(self.bits)
     }
 
     /// Computes the target value in [`Uint256`] format, from a compact representation.
@@ -125,34 +297,130 @@ impl BlockHeader {
 
     /// Compute the popular "difficulty" measure for mining
     pub fn difficulty(&self, network: Network) -> u64 {
-        (max_target(network) / self.target()).low_u64()
+        (max_target(network) / self.target().to_uint256()).low_u64()
     }
 
     /// Checks that the proof-of-work for the block is valid, returning the block hash.
-    pub fn validate_pow(&self, required_target: &Uint256) -> Result<BlockHash, util::Error> {
-        let target = &self.target();
-        if target != required_target {
+    pub fn validate_pow(&self, required_target: &Target) -> Result<BlockHash, util::Error> {
+        let target = self.target();
+        if target != *required_target {
             return Err(BlockBadTarget);
         }
         let block_hash = self.block_hash();
-        let mut ret = [0u64; 4];
-        util::endian::bytes_to_u64_slice_le(block_hash.as_inner(), &mut ret);
-        let hash = &Uint256(ret);
-        if hash <= target { Ok(block_hash) } else { Err(BlockBadProofOfWork) }
+        if target.is_met_by(block_hash) { Ok(block_hash) } else { Err(BlockBadProofOfWork) }
+    }
+
+    /// Checks that the timestamp is after `mtp`, the median time past of the
+    /// previous blocks, and at most [`Params::max_future_block_time`] seconds
+    /// ahead of `now`.
+    ///
+    /// [`Params::max_future_block_time`]: ../../consensus/params/struct.Params.html#structfield.max_future_block_time
+    pub fn check_timestamp(&self, mtp: u32, now: u32, params: &Params) -> Result<(), util::Error> {
+        if self.time <= mtp {
+            return Err(BlockTimeTooOld);
+        }
+        if u64::from(self.time) > u64::from(now) + u64::from(params.max_future_block_time) {
+            return Err(BlockTimeTooNew);
+        }
+        Ok(())
+    }
+
+    /// Checks that the bits of this header, at `height` on top of
+    /// `prev_header`, are the ones [`Params::next_work_required`] demands.
+    ///
+    /// `first_header` is the first header of the difficulty adjustment period
+    /// ending with `prev_header`, at `height` minus the adjustment interval; it
+    /// is only used when `height` is an adjustment boundary. On networks
+    /// allowing minimum difficulty blocks, a block which follows a minimum
+    /// difficulty block and is not itself late enough to be one has to return
+    /// to the target of an earlier block; it is only checked against the
+    /// proof-of-work limit then.
+    ///
+    /// The genesis block has no previous target, so this always fails if
+    /// `height` is zero.
+    ///
+    /// [`Params::next_work_required`]: ../../consensus/params/struct.Params.html#method.next_work_required
+    pub fn check_difficulty_transition(
+        &self,
+        prev_header: &BlockHeader,
+        first_header: &BlockHeader,
+        height: u32,
+        params: &Params,
+    ) -> Result<(), util::Error> {
+        if height == 0 {
+            return Err(BlockBadTarget);
+        }
+        let interval = params.difficulty_adjustment_interval() as u32;
+        let retarget = height % interval == 0;
+        let min_difficulty_time = u64::from(prev_header.time) + 2 * params.pow_target_spacing;
+        // Without retargeting the walk back over minimum difficulty blocks ends
+        // on a block with the same target, so that case needs no shortcut.
+        if params.allow_min_difficulty_blocks && !retarget && !params.no_pow_retargeting
+            && prev_header.bits == params.pow_limit_bits()
+            && u64::from(self.time) <= min_difficulty_time
+        {
+            return if self.target().to_uint256() <= params.pow_limit { Ok(()) } else { Err(BlockBadTarget) };
+        }
+        let expected = params.next_bits_with(height, |h| {
+            if h == height {
+                *self
+            } else if h == height - 1 {
+                *prev_header
+            } else {
+                *first_header
+            }
+        });
+        if self.bits == expected { Ok(()) } else { Err(BlockBadTarget) }
     }
 
     /// Returns the total work of the block
     pub fn work(&self) -> Uint256 {
-        // 2**256 / (target + 1) == ~target / (target+1) + 1    (eqn shamelessly stolen from bitcoind)
-        let mut ret = !self.target();
-        let mut ret1 = self.target();
-        ret1.increment();
-        ret = ret / ret1;
-        ret.increment();
-        ret
+        self.target().to_work()
     }
 }
 
+/// Size in bytes of a consensus-encoded block header.
+const HEADER_SIZE: usize = 80;
+
+/// Decodes a batch of consensus-encoded 80-byte block headers.
+///
+/// Two layouts are accepted:
+///
+/// * the payload of a `headers` network message, that is a header count
+///   followed by each header and its (always zero) transaction count;
+/// * a flat concatenation of headers, whose length has to be a multiple of
+///   80 bytes.
+///
+/// The input is decoded as a `headers` payload if its leading count matches
+/// its length, and as flat headers otherwise.
+///
+/// The output is allocated once up front, based on the input length.
+pub fn deserialize_headers(data: &[u8]) -> Result<Vec<BlockHeader>, encode::Error> {
+    let mut cursor = io::Cursor::new(data);
+    if let Ok(VarInt(count)) = VarInt::consensus_decode(&mut cursor) {
+        let payload = &data[cursor.position() as usize..];
+        if payload.len() as u64 == count.saturating_mul(HEADER_SIZE as u64 + 1) {
+            let mut headers = Vec::with_capacity(count as usize);
+            for chunk in payload.chunks(HEADER_SIZE + 1) {
+                if chunk[HEADER_SIZE] != 0 {
+                    return Err(encode::Error::ParseFailed("headers message has a non-zero transaction count"));
+                }
+                headers.push(BlockHeader::consensus_decode(&chunk[..HEADER_SIZE])?);
+            }
+            return Ok(headers);
+        }
+    }
+
+    if data.len() % HEADER_SIZE != 0 {
+        return Err(encode::Error::ParseFailed("header data length is not a multiple of 80"));
+    }
+    let mut headers = Vec::with_capacity(data.len() / HEADER_SIZE);
+    for chunk in data.chunks(HEADER_SIZE) {
+        headers.push(BlockHeader::consensus_decode(chunk)?);
+    }
+    Ok(headers)
+}
+
 /// A Bitcoin block, which is a collection of transactions with an attached
 /// proof of work.
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -164,7 +432,29 @@ pub struct Block {
     pub txdata: Vec<Transaction>
 }
 
-impl_consensus_encoding!(Block, header, txdata);
+impl_consensus_hex!(Block);
+
+impl Encodable for Block {
+    fn consensus_encode<S: io::Write>(&self, mut s: S) -> Result<usize, io::Error> {
+        let mut len = 0;
+        len += self.header.consensus_encode(&mut s)?;
+        len += self.txdata.consensus_encode(&mut s)?;
+        Ok(len)
+    }
+
+    fn size_hint(&self) -> usize {
+        self.get_size()
+    }
+}
+
+impl Decodable for Block {
+    fn consensus_decode<D: io::Read>(mut d: D) -> Result<Self, encode::Error> {
+        Ok(Block {
+            header: Decodable::consensus_decode(&mut d)?,
+            txdata: Decodable::consensus_decode(&mut d)?,
+        })
+    }
+}
 
 impl Block {
     /// Return the block hash.
@@ -177,6 +467,20 @@ impl Block {
         self.header.merkle_root == self.merkle_root()
     }
 
+    /// Returns the witness commitment of the coinbase, if it has one.
+    ///
+    /// The commitment is in the last coinbase output whose script starts with
+    /// `OP_RETURN` followed by a push of `0xaa21a9ed` and the 32-byte commitment.
+    pub fn witness_commitment(&self) -> Option<WitnessCommitment> {
+        let coinbase = self.txdata.first().filter(|tx| tx.is_coin_base())?;
+        coinbase.output.iter()
+            .rev()
+            .find(|o| {
+                o.script_pubkey.len () >= 38 &&
+                o.script_pubkey[0..6] == [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed] })
+            .map(|o| WitnessCommitment::from_slice(&o.script_pubkey.as_bytes()[6..38]).unwrap())
+    }
+
     /// check if witness commitment in coinbase is matching the transaction list
     pub fn check_witness_commitment(&self) -> bool {
 
@@ -184,20 +488,12 @@ impl Block {
         if self.txdata.iter().all(|t| t.input.iter().all(|i| i.witness.is_empty())) {
             return true;
         }
-        if !self.txdata.is_empty() {
+        if let Some(commitment) = self.witness_commitment() {
             let coinbase = &self.txdata[0];
-            if coinbase.is_coin_base() {
-                // commitment is in the last output that starts with below magic
-                if let Some(pos) = coinbase.output.iter()
-                    .rposition(|o| {
-                        o.script_pubkey.len () >= 38 &&
-                        o.script_pubkey[0..6] == [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed] }) {
-                    let commitment = WitnessCommitment::from_slice(&coinbase.output[pos].script_pubkey.as_bytes()[6..38]).unwrap();
-                    // witness reserved value is in coinbase input witness
-                    if coinbase.input[0].witness.len() == 1 && coinbase.input[0].witness[0].len() == 32 {
-                        let witness_root = self.witness_root();
-                        return commitment == Self::compute_witness_commitment(&witness_root, coinbase.input[0].witness[0].as_slice())
-                    }
+            // witness reserved value is in coinbase input witness
+            if coinbase.input[0].witness.len() == 1 && coinbase.input[0].witness[0].len() == 32 {
+                if let Some(witness_root) = self.witness_root() {
+                    return commitment == Self::compute_witness_commitment(&witness_root, coinbase.input[0].witness[0].as_slice())
                 }
             }
         }
@@ -218,8 +514,15 @@ impl Block {
         WitnessCommitment::from_engine(encoder)
     }
 
-    /// Merkle root of transactions hashed for witness
-    pub fn witness_root(&self) -> WitnessMerkleNode {
+    /// Computes the merkle root of the wtxids of the transactions, which the
+    /// witness commitment commits to. The wtxid of the coinbase is taken to be
+    /// all zeros, as it cannot commit to its own witness.
+    ///
+    /// Returns `None` for a block without transactions.
+    pub fn witness_root(&self) -> Option<WitnessMerkleNode> {
+        if self.txdata.is_empty() {
+            return None;
+        }
         let hashes = self.txdata.iter().enumerate().map(|(i, t)|
             if i == 0 {
                 // Replace the first hash with zeroes.
@@ -228,7 +531,7 @@ impl Block {
                 t.wtxid().as_hash()
             }
         );
-        bitcoin_merkle_root(hashes).into()
+        Some(bitcoin_merkle_root(hashes).into())
     }
 
     /// Get the size of the block
@@ -246,11 +549,71 @@ impl Block {
         base_weight + txs_weight
     }
 
+    /// Computes the signature operation cost of the block like Bitcoin Core,
+    /// checking it against [`Params::max_block_sigops`].
+    ///
+    /// Legacy sigops in scriptSigs and output scripts cost
+    /// [`WITNESS_SCALE_FACTOR`] each, and so do those of P2SH redeem scripts.
+    /// Sigops of witness programs, including those nested in P2SH, cost one
+    /// each. P2SH and segwit are assumed to be active. `prevouts` has to
+    /// contain the outputs spent by the block.
+    ///
+    /// [`Params::max_block_sigops`]: ../../consensus/params/struct.Params.html#method.max_block_sigops
+    /// [`WITNESS_SCALE_FACTOR`]: ../constants/constant.WITNESS_SCALE_FACTOR.html
+    pub fn total_sigop_cost(&self, prevouts: &HashMap<OutPoint, TxOut>, params: &Params) -> Result<usize, util::Error> {
+        let mut cost = 0;
+        for tx in &self.txdata {
+            let scripts = tx.input.iter().map(|input| &input.script_sig)
+                .chain(tx.output.iter().map(|output| &output.script_pubkey));
+            cost += scripts.map(Script::count_sigops_legacy).sum::<usize>() * WITNESS_SCALE_FACTOR;
+            if tx.is_coin_base() {
+                continue;
+            }
+            for input in &tx.input {
+                let spent = prevouts.get(&input.previous_output)
+                    .ok_or(util::Error::UnknownSpentOutput(input.previous_output))?;
+                let mut program = spent.script_pubkey.clone();
+                if spent.script_pubkey.is_p2sh() {
+                    match redeem_script(&input.script_sig) {
+                        Some(redeem_script) => {
+                            cost += redeem_script.count_sigops() * WITNESS_SCALE_FACTOR;
+                            program = redeem_script;
+                        }
+                        None => continue,
+                    }
+                }
+                cost += witness_sigops(&program, &input.witness);
+            }
+        }
+        if cost > params.max_block_sigops() as usize {
+            return Err(util::Error::BlockTooManySigops);
+        }
+        Ok(cost)
+    }
+
     /// Get the coinbase transaction, if one is present.
     pub fn coinbase(&self) -> Option<&Transaction> {
         self.txdata.first()
     }
 
+    /// Iterates over the outputs created by the block's transactions, along
+    /// with the outpoints referring to them.
+    pub fn created_outputs<'a>(&'a self) -> impl Iterator<Item = (OutPoint, &'a TxOut)> + 'a {
+        self.txdata.iter().flat_map(|tx| {
+            let txid = tx.txid();
+            tx.output.iter().enumerate()
+                .map(move |(vout, txout)| (OutPoint::new(txid, vout as u32), txout))
+        })
+    }
+
+    /// Iterates over the outpoints spent by the block's transactions. The null
+    /// outpoint of a coinbase input is not included.
+    pub fn spent_outpoints<'a>(&'a self) -> impl Iterator<Item = &'a OutPoint> + 'a {
+        self.txdata.iter()
+            .filter(|tx| !tx.is_coin_base())
+            .flat_map(|tx| tx.input.iter().map(|input| &input.previous_output))
+    }
+
     /// Get the block height as encoded into the coinbase according to BIP34.
     /// Returns [None] if not present.
     pub fn bip34_block_height(&self) -> Result<u64, Bip34Error> {
@@ -285,6 +648,29 @@ impl Block {
     }
 }
 
+/// Returns the last push of a push-only scriptSig, which is the redeem script
+/// when spending a P2SH output.
+fn redeem_script(script_sig: &Script) -> Option<Script> {
+    if !script_sig.is_push_only() {
+        return None;
+    }
+    match script_sig.instructions().last() {
+        Some(Ok(script::Instruction::PushBytes(data))) => Some(Script::from(data.to_vec())),
+        _ => None,
+    }
+}
+
+/// Counts the sigops of spending a witness program with the given witness.
+fn witness_sigops(program: &Script, witness: &[Vec<u8>]) -> usize {
+    if program.is_v0_p2wpkh() {
+        1
+    } else if program.is_v0_p2wsh() {
+        witness.last().map_or(0, |script| Script::from(script.clone()).count_sigops())
+    } else {
+        0
+    }
+}
+
 /// An error when looking up a BIP34 block height.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Bip34Error {
@@ -312,13 +698,18 @@ impl ::std::error::Error for Bip34Error {}
 
 #[cfg(test)]
 mod tests {
+    use hashes::{Hash, HashEngine};
     use hashes::hex::FromHex;
+    use hash_types::{BlockHash, WitnessCommitment, WitnessMerkleNode};
 
-    use blockdata::block::{Block, BlockHeader};
-    use consensus::encode::{deserialize, serialize};
+    use blockdata::block::{Block, BlockHeader, CompactTarget, Target, deserialize_headers};
+    use blockdata::constants::genesis_block;
+    use blockdata::transaction::OutPoint;
+    use consensus::encode::{deserialize, serialize, serialize_hex};
     use util::uint::Uint256;
-    use util::Error::{BlockBadTarget, BlockBadProofOfWork};
+    use util::Error::{BlockBadTarget, BlockBadProofOfWork, BlockTimeTooNew, BlockTimeTooOld};
     use network::constants::Network;
+    use consensus::params::Params;
 
     #[test]
     fn test_coinbase_and_bip34() {
@@ -330,7 +721,7 @@ mod tests {
         assert_eq!(block.coinbase().unwrap().txid().to_string(), cb_txid);
 
         assert_eq!(block.bip34_block_height(), Ok(100_000));
-
+        assert!(Params::new(Network::Testnet).bip34_active(100_000));
 
         // block with 9-byte bip34 push
         let bad_hex = "0200000035ab154183570282ce9afc0b494c9fc6a3cfea05aa8c1add2ecc56490000000038ba3d78e4500a5a7570dbe61960398add4410d278b21cd9708e6d9743f374d544fc055227f1001c29c1ea3b0101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff3d09a08601112233445566000427f1001c046a510100522cfabe6d6d0000000000000000000068692066726f6d20706f6f6c7365727665726aac1eeeed88ffffffff0100f2052a010000001976a914912e2b234f941f30b18afbb4fa46171214bf66c888ac00000000";
@@ -338,6 +729,12 @@ mod tests {
 
         let push = Vec::<u8>::from_hex("a08601112233445566").unwrap();
         assert_eq!(bad.bip34_block_height(), Err(super::Bip34Error::UnexpectedPush(push)));
+
+        // mainnet block 1, a version 1 block from before BIP34 activation
+        let pre_hex = "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e362990101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0704ffff001d0104ffffffff0100f2052a0100000043410496b538e853519c726a2c91e61ec11600ae1390813a627c66fb8be7947be63c52da7589379515d4e0a604f8141781e62294721166bf621e73a82cbf2342c858eeac00000000";
+        let pre: Block = deserialize(&Vec::<u8>::from_hex(pre_hex).unwrap()).unwrap();
+        assert!(!Params::new(Network::Bitcoin).bip34_active(1));
+        assert_eq!(pre.bip34_block_height(), Err(super::Bip34Error::Unsupported));
     }
 
     #[test]
@@ -361,7 +758,7 @@ mod tests {
         assert_eq!(real_decode.header.merkle_root, real_decode.merkle_root());
         assert_eq!(serialize(&real_decode.header.merkle_root), merkle);
         assert_eq!(real_decode.header.time, 1231965655);
-        assert_eq!(real_decode.header.bits, 486604799);
+        assert_eq!(real_decode.header.bits, CompactTarget::from_consensus(486604799));
         assert_eq!(real_decode.header.nonce, 2067413810);
         assert_eq!(real_decode.header.work(), work);
         assert_eq!(real_decode.header.validate_pow(&real_decode.header.target()).unwrap(), real_decode.block_hash());
@@ -373,8 +770,19 @@ mod tests {
 
         // should be also ok for a non-witness block as commitment is optional in that case
         assert!(real_decode.check_witness_commitment());
+        assert_eq!(real_decode.witness_commitment(), None);
 
         assert_eq!(serialize(&real_decode), some_block);
+
+        let created: Vec<_> = real_decode.created_outputs().collect();
+        assert_eq!(created.len(), 2);
+        assert_eq!(created[0], (OutPoint::new(real_decode.txdata[0].txid(), 0), &real_decode.txdata[0].output[0]));
+        assert_eq!(created[1], (OutPoint::new(real_decode.txdata[1].txid(), 0), &real_decode.txdata[1].output[0]));
+        let spent: Vec<_> = real_decode.spent_outpoints().collect();
+        assert_eq!(spent.len(), 3);
+        assert!(spent.iter().all(|outpoint| !outpoint.is_null()));
+        assert_eq!(spent[0].txid.to_string(), "264299886446921c89e598ec2b1ec3eab6a2c9b0235b310ff513a039315ff721");
+        assert_eq!(spent[2].vout, 0);
     }
 
     // Check testnet block 000000000000045e0b1660b6445b5e5c5ab63c9a4f956be7e1e69be04fa4497b
@@ -395,7 +803,7 @@ mod tests {
         assert_eq!(serialize(&real_decode.header.merkle_root), merkle);
         assert_eq!(real_decode.header.merkle_root, real_decode.merkle_root());
         assert_eq!(real_decode.header.time, 1472004949);
-        assert_eq!(real_decode.header.bits, 0x1a06d450);
+        assert_eq!(real_decode.header.bits, CompactTarget::from_consensus(0x1a06d450));
         assert_eq!(real_decode.header.nonce, 1879759182);
         assert_eq!(real_decode.header.work(), work);
         assert_eq!(real_decode.header.validate_pow(&real_decode.header.target()).unwrap(), real_decode.block_hash());
@@ -406,6 +814,19 @@ mod tests {
         assert_eq!(real_decode.get_weight(), 17168);
 
         assert!(real_decode.check_witness_commitment());
+        let witness_root = WitnessMerkleNode::from_hex("c315536642fd4da70eea9118ce4edaac784a7cfce1555f6c6320965c9ed5915f").unwrap();
+        assert_eq!(real_decode.witness_root(), Some(witness_root));
+        assert_eq!(Block { header: real_decode.header, txdata: vec![] }.witness_root(), None);
+        let commitment = Vec::from_hex("f91c46b49eb8a29089980f02ee6b57e7d63d33b18b4fddac2bcd7db2a3983704").unwrap();
+        assert_eq!(real_decode.witness_commitment(), Some(WitnessCommitment::from_slice(&commitment).unwrap()));
+        // A different witness reserved value no longer matches the commitment.
+        let mut tampered = real_decode.clone();
+        tampered.txdata[0].input[0].witness[0][0] = 1;
+        assert!(!tampered.check_witness_commitment());
+        // Witnesses need a commitment.
+        tampered.txdata[0].output.pop();
+        assert_eq!(tampered.witness_commitment(), None);
+        assert!(!tampered.check_witness_commitment());
 
         assert_eq!(serialize(&real_decode), segwit_block);
     }
@@ -432,7 +853,7 @@ mod tests {
         assert_eq!(some_header.validate_pow(&some_header.target()).unwrap(), some_header.block_hash());
 
         // test with zero target
-        match some_header.validate_pow(&Uint256::default()) {
+        match some_header.validate_pow(&Target::default()) {
             Err(BlockBadTarget) => (),
             _ => assert!(false)
         }
@@ -446,13 +867,351 @@ mod tests {
         }
     }
 
+    #[test]
+    fn check_timestamp_test() {
+        let params = Params::new(Network::Bitcoin);
+        let header = genesis_block(Network::Bitcoin).header;
+        let time = header.time;
+        assert!(header.check_timestamp(time - 1, time, &params).is_ok());
+        assert!(header.check_timestamp(time - 1, time - 7200, &params).is_ok());
+
+        // Not after the median time past
+        match header.check_timestamp(time, time, &params) {
+            Err(BlockTimeTooOld) => (),
+            r => panic!("expected too old, got {:?}", r),
+        }
+        // More than two hours ahead of the local time
+        match header.check_timestamp(time - 1, time - 7201, &params) {
+            Err(BlockTimeTooNew) => (),
+            r => panic!("expected too new, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn check_difficulty_transition_test() {
+        let valid = |result| match result {
+            Ok(()) => true,
+            Err(BlockBadTarget) => false,
+            Err(e) => panic!("unexpected error {:?}", e),
+        };
+
+        // Retarget of mainnet block 32256.
+        let params = Params::new(Network::Bitcoin);
+        let genesis = genesis_block(Network::Bitcoin).header;
+        let first = BlockHeader { time: 1261130161, ..genesis };
+        let prev = BlockHeader { time: 1262152739, ..genesis };
+        let mut header = BlockHeader { prev_blockhash: prev.block_hash(), time: 1262153464, ..genesis };
+        header.bits = CompactTarget::from_consensus(0x1d00d86a);
+        assert!(valid(header.check_difficulty_transition(&prev, &first, 32256, &params)));
+        header.bits = CompactTarget::from_consensus(0x1d00ffff);
+        assert!(!valid(header.check_difficulty_transition(&prev, &first, 32256, &params)));
+        // Between adjustments the bits stay the same.
+        assert!(valid(header.check_difficulty_transition(&prev, &first, 32257, &params)));
+
+        // On testnet blocks over twenty minutes late are at minimum difficulty.
+        let params = Params::new(Network::Testnet);
+        let prev = BlockHeader { bits: CompactTarget::from_consensus(0x1c0fffff), ..genesis };
+        let mut header = BlockHeader { time: prev.time + 1201, ..genesis };
+        header.bits = params.pow_limit_bits();
+        assert!(valid(header.check_difficulty_transition(&prev, &first, 2017, &params)));
+        header.bits = prev.bits;
+        assert!(!valid(header.check_difficulty_transition(&prev, &first, 2017, &params)));
+        header.time = prev.time + 1200;
+        assert!(valid(header.check_difficulty_transition(&prev, &first, 2017, &params)));
+        header.bits = params.pow_limit_bits();
+        assert!(!valid(header.check_difficulty_transition(&prev, &first, 2017, &params)));
+        // After a minimum difficulty block, only the limit is checked.
+        let prev = BlockHeader { bits: params.pow_limit_bits(), ..prev };
+        header.bits = CompactTarget::from_consensus(0x1c0fffff);
+        assert!(valid(header.check_difficulty_transition(&prev, &first, 2017, &params)));
+        header.bits = CompactTarget::from_consensus(0x1e00ffff);
+        assert!(!valid(header.check_difficulty_transition(&prev, &first, 2017, &params)));
+
+        // Nothing comes before the genesis block.
+        assert!(!valid(genesis.check_difficulty_transition(&genesis, &genesis, 0, &params)));
+    }
+
+    #[test]
+    fn deserialize_headers_test() {
+        let genesis = genesis_block(Network::Bitcoin).header;
+        // mainnet block 1
+        let header1: BlockHeader = deserialize(&Vec::from_hex("010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299").unwrap()).unwrap();
+        // mainnet block 00000000b0c5a240b2a61d2e75692224efd4cbecdf6eaf4cc2cf477ca7c270e7
+        let header2: BlockHeader = deserialize(&Vec::from_hex("010000004ddccd549d28f385ab457e98d1b11ce80bfea2c5ab93015ade4973e400000000bf4473e53794beae34e64fccc471dace6ae544180816f89591894e0f417a914cd74d6e49ffff001d323b3a7b").unwrap()).unwrap();
+
+        let mut data = serialize(&genesis);
+        data.extend(serialize(&header1));
+        data.extend(serialize(&header2));
+        assert_eq!(data.len(), 240);
+
+        let headers = deserialize_headers(&data).unwrap();
+        assert_eq!(headers, vec![genesis, header1, header2]);
+        assert_eq!(headers[1].prev_blockhash, headers[0].block_hash());
+
+        assert!(deserialize_headers(&[]).unwrap().is_empty());
+        assert!(deserialize_headers(&data[..239]).is_err());
+        assert!(deserialize_headers(&data[..81]).is_err());
+
+        // The same headers as a `headers` message payload.
+        let mut message = vec![3];
+        for header in &headers {
+            message.extend(serialize(header));
+            message.push(0);
+        }
+        assert_eq!(deserialize_headers(&message).unwrap(), headers);
+        assert!(deserialize_headers(&[0]).unwrap().is_empty());
+        message[81] = 1;
+        assert!(deserialize_headers(&message).is_err());
+    }
+
+    #[test]
+    fn total_sigop_cost_test() {
+        use std::collections::HashMap;
+
+        use blockdata::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIG, OP_PUSHNUM_1, OP_PUSHNUM_2, OP_PUSHNUM_3};
+        use blockdata::script::{Builder, Script};
+        use blockdata::transaction::{Transaction, TxIn, TxOut, Sequence};
+        use hash_types::{PubkeyHash, Txid, WPubkeyHash};
+        use util::Error::{BlockTooManySigops, UnknownSpentOutput};
+
+        let params = Params::new(Network::Bitcoin);
+        let mut block = genesis_block(Network::Bitcoin);
+        let prevout = |vout| OutPoint::new(Txid::hash(&[1]), vout);
+        let key = [2u8; 33];
+        let multisig = |m, n| {
+            let mut builder = Builder::new().push_opcode(m);
+            for _ in 0..3 {
+                builder = builder.push_slice(&key);
+            }
+            builder.push_opcode(n).push_opcode(OP_CHECKMULTISIG).into_script()
+        };
+        let p2sh_redeem = multisig(OP_PUSHNUM_2, OP_PUSHNUM_3);
+        let p2wsh_script = multisig(OP_PUSHNUM_1, OP_PUSHNUM_2);
+        let p2wpkh = Script::new_v0_wpkh(&WPubkeyHash::hash(&key));
+        let p2pkh = Script::new_p2pkh(&PubkeyHash::hash(&key));
+
+        let mut prevouts = HashMap::new();
+        for (vout, script_pubkey) in [
+            p2pkh.clone(),
+            p2sh_redeem.to_p2sh(),
+            p2wpkh.clone(),
+            p2wsh_script.to_v0_p2wsh(),
+            p2wpkh.to_p2sh(),
+        ].iter().enumerate() {
+            prevouts.insert(prevout(vout as u32), TxOut { value: 1, script_pubkey: script_pubkey.clone() });
+        }
+        let input = |vout, script_sig: Script, witness: Vec<Vec<u8>>| TxIn {
+            previous_output: prevout(vout),
+            script_sig,
+            sequence: Sequence::MAX,
+            witness,
+        };
+        let sig = [0x30u8; 72];
+        block.txdata.push(Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![
+                input(0, Builder::new().push_slice(&sig).push_slice(&key).into_script(), vec![]),
+                input(1, Builder::new().push_int(0).push_slice(&sig).push_slice(&sig).push_slice(p2sh_redeem.as_bytes()).into_script(), vec![]),
+                input(2, Script::new(), vec![sig.to_vec(), key.to_vec()]),
+                input(3, Script::new(), vec![vec![], sig.to_vec(), p2wsh_script.to_bytes()]),
+                input(4, Builder::new().push_slice(p2wpkh.as_bytes()).into_script(), vec![sig.to_vec(), key.to_vec()]),
+            ],
+            output: vec![TxOut { value: 1, script_pubkey: p2pkh.clone() }],
+        });
+
+        // 4 for the coinbase output, 4 for the new output, 12 for the P2SH
+        // multisig, 1 for each P2WPKH and 2 for the P2WSH multisig.
+        assert_eq!(block.total_sigop_cost(&prevouts, &params).unwrap(), 24);
+
+        let mut missing = prevouts.clone();
+        missing.remove(&prevout(3));
+        match block.total_sigop_cost(&missing, &params) {
+            Err(UnknownSpentOutput(point)) => assert_eq!(point, prevout(3)),
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        // Bare multisigs cost 80 and checksigs 4, so this just fits.
+        let mut script = vec![OP_CHECKMULTISIG.into_u8(); 999];
+        script.extend_from_slice(&[OP_CHECKSIG.into_u8(); 14]);
+        let mut spend = block.txdata[1].clone();
+        spend.input.truncate(1);
+        spend.output[0].script_pubkey = Script::from(script.clone());
+        block.txdata.push(spend);
+        assert_eq!(block.total_sigop_cost(&prevouts, &params).unwrap(), 80_000);
+        script.push(OP_CHECKSIG.into_u8());
+        block.txdata[2].output[0].script_pubkey = Script::from(script);
+        match block.total_sigop_cost(&prevouts, &params) {
+            Err(BlockTooManySigops) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn signals_bit_test() {
+        let mut header = genesis_block(Network::Bitcoin).header;
+        assert!(!header.signals_bit(0));
+
+        header.version = 0x20000000;
+        assert!(!header.signals_bit(1));
+        header.version = 0x20000002;
+        assert!(header.signals_bit(1));
+        assert!(!header.signals_bit(0));
+        header.version = 0x30000000;
+        assert!(header.signals_bit(28));
+        assert!(!header.signals_bit(29));
+        assert!(!header.signals_bit(200));
+
+        // The top bits have to be 001.
+        header.version = 0x60000002;
+        assert!(!header.signals_bit(1));
+        header.version = 0x00000002;
+        assert!(!header.signals_bit(1));
+    }
+
+    #[test]
+    fn mining_chunks_test() {
+        let mut header = genesis_block(Network::Bitcoin).header;
+        let (first, last) = header.mining_chunks();
+        assert_eq!(&last[4..8], &serialize(&header.time)[..]);
+        assert_eq!(&last[12..], &serialize(&header.nonce)[..]);
+
+        let mut engine = BlockHash::engine();
+        engine.input(&first);
+        engine.input(&last);
+        assert_eq!(BlockHash::from_engine(engine), header.block_hash());
+
+        // Rolling the nonce and time leaves the first chunk unchanged.
+        header.nonce += 1;
+        header.time += 1;
+        let (rolled_first, rolled_last) = header.mining_chunks();
+        assert_eq!(&rolled_first[..], &first[..]);
+        assert_ne!(rolled_last, last);
+    }
+
+    #[test]
+    fn header_from_bytes_test() {
+        let raw = Vec::from_hex("010000004ddccd549d28f385ab457e98d1b11ce80bfea2c5ab93015ade4973e400000000bf4473e53794beae34e64fccc471dace6ae544180816f89591894e0f417a914cd74d6e49ffff001d323b3a7b").unwrap();
+        let mut bytes = [0u8; 80];
+        bytes.copy_from_slice(&raw);
+
+        let header = BlockHeader::from_bytes(&bytes).unwrap();
+        assert_eq!(header, deserialize::<BlockHeader>(&raw).unwrap());
+        assert_eq!(header.version, 1);
+        assert_eq!(header.time, 1231965655);
+        assert_eq!(header.bits, CompactTarget::from_consensus(0x1d00ffff));
+        assert_eq!(header.nonce, 0x7b3a3b32);
+        assert_eq!(serialize(&header), raw);
+
+        let mut genesis = [0u8; 80];
+        genesis.copy_from_slice(&serialize(&genesis_block(Network::Bitcoin).header));
+        assert_eq!(BlockHeader::from_bytes(&genesis).unwrap(), genesis_block(Network::Bitcoin).header);
+    }
+
+    #[test]
+    fn block_hex_test() {
+        let genesis = genesis_block(Network::Bitcoin);
+        let block_hex = serialize_hex(&genesis);
+        assert_eq!(genesis.to_hex(), block_hex);
+        assert_eq!(Block::from_hex(&block_hex).unwrap(), genesis);
+
+        let header_hex = genesis.header.to_hex();
+        assert_eq!(header_hex.len(), 160);
+        assert_eq!(BlockHeader::from_hex(&header_hex).unwrap(), genesis.header);
+
+        assert!(BlockHeader::from_hex(&header_hex[..159]).is_err());
+        assert!(BlockHeader::from_hex(&header_hex.replace("0", "g")).is_err());
+        assert!(BlockHeader::from_hex(&block_hex).is_err());
+        assert!(Block::from_hex(&header_hex).is_err());
+
+        assert_eq!(Block::from_core_hex(&format!("{}\n", block_hex)).unwrap(), genesis);
+        assert_eq!(BlockHeader::from_core_hex(&format!("{}\n", header_hex)).unwrap(), genesis.header);
+        assert!(Block::from_hex(&format!("{}\n", block_hex)).is_err());
+    }
+
+    #[test]
+    fn compact_target_test() {
+        let bits = CompactTarget::from_consensus(0x1d00ffff);
+        assert_eq!(bits.to_consensus(), 0x1d00ffff);
+        assert_eq!(bits.to_string(), "0x1d00ffff");
+        assert_eq!(format!("{:x}", bits), "1d00ffff");
+        assert_eq!(CompactTarget::from_consensus(0x207fffff).to_string(), "0x207fffff");
+        assert_eq!(CompactTarget::from_consensus(0x1ffff).to_string(), "0x0001ffff");
+
+        let target = bits.to_target();
+        assert_eq!(target, Uint256::from_u64(0xffff).unwrap() << 208);
+        assert_eq!(CompactTarget::from_target(&target), bits);
+        for &raw in &[0x1a06d450u32, 0x1e0377ae, 0x207fffff, 0x170e0408, 0x03123456] {
+            let bits = CompactTarget::from_consensus(raw);
+            assert_eq!(CompactTarget::from_target(&bits.to_target()), bits);
+        }
+
+        // Targets which are not representable round down to one which is.
+        let target = (Uint256::from_u64(0xffff).unwrap() << 208) + Uint256::from_u64(1).unwrap();
+        assert_eq!(CompactTarget::from_target(&target), bits);
+
+        assert_eq!(serialize(&bits), vec![0xff, 0xff, 0x00, 0x1d]);
+        assert_eq!(deserialize::<CompactTarget>(&[0xff, 0xff, 0x00, 0x1d]).unwrap(), bits);
+    }
+
+    #[test]
+    fn target_work_test() {
+        let params = Params::new(Network::Bitcoin);
+        let limit = Target::from_compact(params.pow_limit_bits());
+        assert_eq!(limit.to_uint256(), params.pow_limit);
+        assert_eq!(limit.to_compact_lossy(), params.pow_limit_bits());
+        assert_eq!(limit.difficulty(&params), 1);
+        assert_eq!(limit.difficulty_float(&params), 1.0);
+        // 2**256 / (0xffff * 2**208 + 1) rounds to 0x100010001
+        assert_eq!(limit.to_work(), Uint256([0x100010001u64, 0, 0, 0]));
+
+        // Halving the target doubles the difficulty and roughly doubles the work.
+        let half = Target::from_uint256(params.pow_limit >> 1);
+        assert!(half < limit);
+        assert_eq!(half.difficulty(&params), 2);
+        assert_eq!(half.difficulty_float(&params), 2.0);
+        for &(bits, difficulty) in &[
+            (0x1b0404cbu32, 16307.420938523983),
+            (0x170e0408, 20082460130830.844),
+            (0x207fffff, 4.6565423739069247e-10),
+        ] {
+            let target = Target::from_compact(CompactTarget::from_consensus(bits));
+            assert_eq!(target.difficulty_float(&params), difficulty);
+            assert_eq!(target.difficulty(&params), difficulty as u64);
+        }
+        assert!(half.to_work() > limit.to_work());
+        assert_eq!(half.to_work(), Uint256([0x200020002u64, 0, 0, 0]));
+
+        // Work is inversely related to the target, across a range of targets.
+        let mut prev: Option<Target> = None;
+        for &bits in &[0x207fffffu32, 0x1e0377ae, 0x1d00ffff, 0x1b0404cb, 0x1a06d450, 0x170e0408] {
+            let target = Target::from_compact(CompactTarget::from_consensus(bits));
+            assert_eq!(target.to_compact_lossy().to_consensus(), bits);
+            if let Some(prev) = prev {
+                assert!(target < prev);
+                assert!(target.to_work() > prev.to_work());
+            }
+            prev = Some(target);
+        }
+
+        // The genesis block meets its own target but not a lower one.
+        let genesis = genesis_block(Network::Bitcoin).header;
+        assert_eq!(genesis.target(), limit);
+        assert_eq!(genesis.work(), genesis.target().to_work());
+        assert!(genesis.target().is_met_by(genesis.block_hash()));
+        let lower = Target::from_compact(CompactTarget::from_consensus(0x1a06d450));
+        assert!(!lower.is_met_by(genesis.block_hash()));
+        assert_eq!(genesis.validate_pow(&limit).unwrap(), genesis.block_hash());
+    }
+
     #[test]
     fn compact_roundrtip_test() {
         let some_header = Vec::from_hex("010000004ddccd549d28f385ab457e98d1b11ce80bfea2c5ab93015ade4973e400000000bf4473e53794beae34e64fccc471dace6ae544180816f89591894e0f417a914cd74d6e49ffff001d323b3a7b").unwrap();
 
         let header: BlockHeader = deserialize(&some_header).expect("Can't deserialize correct block header");
 
-        assert_eq!(header.bits, BlockHeader::compact_target_from_u256(&header.target()));
+        assert_eq!(header.bits, header.target().to_compact_lossy());
+        assert_eq!(header.bits.to_consensus(), BlockHeader::compact_target_from_u256(&header.target().to_uint256()));
     }
 }
 
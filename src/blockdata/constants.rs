@@ -25,8 +25,8 @@ use hashes::hex::FromHex;
 use hashes::sha256d;
 use blockdata::opcodes;
 use blockdata::script;
-use blockdata::transaction::{OutPoint, Transaction, TxOut, TxIn};
-use blockdata::block::{Block, BlockHeader};
+use blockdata::transaction::{OutPoint, Transaction, TxOut, TxIn, Sequence};
+use blockdata::block::{Block, BlockHeader, CompactTarget};
 use network::constants::Network;
 use util::uint::Uint256;
 
@@ -42,10 +42,28 @@ pub const DIFFCHANGE_INTERVAL: u32 = 2016;
 pub const DIFFCHANGE_TIMESPAN: u32 = 14 * 24 * 3600;
 /// The maximum allowed weight for a block, see BIP 141 (network rule)
 pub const MAX_BLOCK_WEIGHT: u32 = 4_000_000;
+/// The maximum allowed signature operation cost for a block, see BIP 141 (network rule)
+pub const MAX_BLOCK_SIGOPS_COST: u32 = 80_000;
+/// The maximum weight of a transaction which is relayed by default (policy rule)
+pub const MAX_STANDARD_TX_WEIGHT: u32 = 400_000;
+/// The maximum size in bytes of the data pushed by an `OP_RETURN` output which
+/// is relayed by default (policy rule). Bitcoin Core expresses this as an 83
+/// byte limit on the whole script.
+pub const MAX_OP_RETURN_RELAY: usize = 80;
 /// The minimum transaction weight for a valid serialized transaction
 pub const MIN_TRANSACTION_WEIGHT: u32 = 4 * 60;
 /// The factor that non-witness serialization data is multiplied by during weight calculation
 pub const WITNESS_SCALE_FACTOR: usize = 4;
+/// The maximum size in bytes of a script which can be executed (network rule)
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/// The maximum size in bytes of a single element pushed in a script (network rule)
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+/// The maximum number of non-push opcodes in a script (network rule)
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+/// The minimum size in bytes of a coinbase scriptSig (network rule)
+pub const MIN_COINBASE_SCRIPTSIG_SIZE: usize = 2;
+/// The maximum size in bytes of a coinbase scriptSig (network rule)
+pub const MAX_COINBASE_SCRIPTSIG_SIZE: usize = 100;
 
 
 /// In Bitcoind this is insanely described as ~((u256)0 >> 32)
@@ -78,7 +96,7 @@ fn bitcoin_genesis_tx() -> Transaction {
     ret.input.push(TxIn {
         previous_output: OutPoint::null(),
         script_sig: in_script,
-        sequence: MAX_SEQUENCE,
+        sequence: Sequence::MAX,
         witness: vec![],
     });
 
@@ -109,7 +127,7 @@ pub fn genesis_block(network: Network) -> Block {
                     prev_blockhash: Default::default(),
                     merkle_root,
                     time: 1231006505,
-                    bits: 0x1d00ffff,
+                    bits: CompactTarget::from_consensus(0x1d00ffff),
                     nonce: 2083236893
                 },
                 txdata: txdata
@@ -122,7 +140,7 @@ pub fn genesis_block(network: Network) -> Block {
                     prev_blockhash: Default::default(),
                     merkle_root,
                     time: 1296688602,
-                    bits: 0x1d00ffff,
+                    bits: CompactTarget::from_consensus(0x1d00ffff),
                     nonce: 414098458
                 },
                 txdata: txdata
@@ -135,7 +153,7 @@ pub fn genesis_block(network: Network) -> Block {
                     prev_blockhash: Default::default(),
                     merkle_root,
                     time: 1598918400,
-                    bits: 0x1e0377ae,
+                    bits: CompactTarget::from_consensus(0x1e0377ae),
                     nonce: 52613770
                 },
                 txdata: txdata
@@ -148,7 +166,7 @@ pub fn genesis_block(network: Network) -> Block {
                     prev_blockhash: Default::default(),
                     merkle_root,
                     time: 1296688602,
-                    bits: 0x207fffff,
+                    bits: CompactTarget::from_consensus(0x207fffff),
                     nonce: 2
                 },
                 txdata: txdata
@@ -163,7 +181,8 @@ mod test {
     use hashes::hex::FromHex;
 
     use network::constants::Network;
-    use consensus::encode::serialize;
+    use consensus::encode::{deserialize, serialize};
+    use blockdata::block::Block;
     use blockdata::constants::{genesis_block, bitcoin_genesis_tx};
     use blockdata::constants::{MAX_SEQUENCE, COIN_VALUE};
 
@@ -178,7 +197,7 @@ mod test {
         assert_eq!(serialize(&gen.input[0].script_sig),
                    Vec::from_hex("4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73").unwrap());
 
-        assert_eq!(gen.input[0].sequence, MAX_SEQUENCE);
+        assert_eq!(gen.input[0].sequence.to_consensus(), MAX_SEQUENCE);
         assert_eq!(gen.output.len(), 1);
         assert_eq!(serialize(&gen.output[0].script_pubkey),
                    Vec::from_hex("434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac").unwrap());
@@ -198,12 +217,24 @@ mod test {
         assert_eq!(format!("{:x}", gen.header.merkle_root),
                    "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b".to_string());
         assert_eq!(gen.header.time, 1231006505);
-        assert_eq!(gen.header.bits, 0x1d00ffff);
+        assert_eq!(gen.header.bits.to_consensus(), 0x1d00ffff);
         assert_eq!(gen.header.nonce, 2083236893);
         assert_eq!(format!("{:x}", gen.header.block_hash()),
                    "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f".to_string());
     }
 
+    #[test]
+    fn bitcoin_genesis_serialization() {
+        let gen = genesis_block(Network::Bitcoin);
+        let hex = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c0101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+        let bytes = Vec::from_hex(hex).unwrap();
+
+        assert_eq!(serialize(&gen), bytes);
+        let decoded: Block = deserialize(&bytes).unwrap();
+        assert_eq!(decoded, gen);
+        assert!(decoded.check_merkle_root());
+    }
+
     #[test]
     fn testnet_genesis_full_block() {
         let gen = genesis_block(Network::Testnet);
@@ -212,7 +243,7 @@ mod test {
         assert_eq!(format!("{:x}", gen.header.merkle_root),
                   "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b".to_string());
         assert_eq!(gen.header.time, 1296688602);
-        assert_eq!(gen.header.bits, 0x1d00ffff);
+        assert_eq!(gen.header.bits.to_consensus(), 0x1d00ffff);
         assert_eq!(gen.header.nonce, 414098458);
         assert_eq!(format!("{:x}", gen.header.block_hash()),
                    "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943".to_string());
@@ -226,7 +257,7 @@ mod test {
         assert_eq!(format!("{:x}", gen.header.merkle_root),
                   "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b".to_string());
         assert_eq!(gen.header.time, 1598918400);
-        assert_eq!(gen.header.bits, 0x1e0377ae);
+        assert_eq!(gen.header.bits.to_consensus(), 0x1e0377ae);
         assert_eq!(gen.header.nonce, 52613770);
         assert_eq!(format!("{:x}", gen.header.block_hash()),
                    "00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6".to_string());
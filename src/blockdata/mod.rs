@@ -21,6 +21,7 @@
 pub mod constants;
 pub mod opcodes;
 pub mod script;
+pub mod scriptnum;
 pub mod transaction;
 pub mod block;
 
@@ -29,7 +29,7 @@ use std::fmt;
 //       write an #[inline] helper function which casts to u8s.
 
 /// A script Opcode
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct All {
     code: u8,
 }
@@ -695,6 +695,46 @@ impl All {
         }
     }
 
+    /// Checks whether the opcode counts as a push, as in `IsPushOnly` in
+    /// Bitcoin Core: the data pushes, `OP_PUSHNUM_NEG1`, `OP_PUSHNUM_1` to
+    /// `OP_PUSHNUM_16`, and `OP_RESERVED`.
+    #[inline]
+    pub fn is_push(self) -> bool {
+        self.code <= all::OP_PUSHNUM_16.code
+    }
+
+    /// Checks whether the opcode is disabled, which fails a script containing
+    /// it even in an unexecuted branch.
+    pub fn is_disabled(self) -> bool {
+        match self {
+            all::OP_CAT | all::OP_SUBSTR | all::OP_LEFT | all::OP_RIGHT
+            | all::OP_INVERT | all::OP_AND | all::OP_OR | all::OP_XOR
+            | all::OP_2MUL | all::OP_2DIV | all::OP_MUL | all::OP_DIV | all::OP_MOD
+            | all::OP_LSHIFT | all::OP_RSHIFT => true,
+            _ => false,
+        }
+    }
+
+    /// Checks whether the opcode is an `OP_SUCCESSx` of tapscript (BIP342),
+    /// which makes a tapscript containing it succeed unconditionally.
+    pub fn is_success(self) -> bool {
+        match self.code {
+            80 | 98 | 126..=129 | 131..=134 | 137..=138 | 141..=142 | 149..=153 | 187..=254 => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the number pushed by `OP_PUSHNUM_NEG1` and `OP_PUSHNUM_1` to
+    /// `OP_PUSHNUM_16`. `OP_PUSHBYTES_0` pushes an empty vector rather than
+    /// a number, so it returns `None` like all other opcodes.
+    #[inline]
+    pub fn push_value(self) -> Option<i64> {
+        match self.classify() {
+            Class::PushNum(n) => Some(n as i64),
+            _ => None,
+        }
+    }
+
     /// Encode as a byte
     #[inline]
     pub fn into_u8(self) -> u8 {
@@ -832,6 +872,38 @@ mod tests {
         }
     }
 
+    #[test]
+    fn classification() {
+        for op in &[all::OP_PUSHBYTES_0, all::OP_PUSHBYTES_75, all::OP_PUSHDATA4, all::OP_PUSHNUM_NEG1, all::OP_RESERVED, all::OP_PUSHNUM_16] {
+            assert!(op.is_push(), "{}", op);
+        }
+        for op in &[all::OP_NOP, all::OP_DUP, all::OP_RETURN, all::OP_CHECKSIG] {
+            assert!(!op.is_push(), "{}", op);
+        }
+
+        for op in &[all::OP_CAT, all::OP_XOR, all::OP_MUL, all::OP_RSHIFT] {
+            assert!(op.is_disabled(), "{}", op);
+        }
+        for op in &[all::OP_VERIF, all::OP_ADD, all::OP_RETURN, all::OP_RETURN_186] {
+            assert!(!op.is_disabled(), "{}", op);
+        }
+
+        for op in &[all::OP_RESERVED, all::OP_VER, all::OP_CAT, all::OP_RSHIFT, all::OP_RETURN_187, all::OP_RETURN_254] {
+            assert!(op.is_success(), "{}", op);
+        }
+        for op in &[all::OP_VERIF, all::OP_RETURN, all::OP_CHECKSIG, all::OP_RETURN_186, all::OP_RETURN_255] {
+            assert!(!op.is_success(), "{}", op);
+        }
+        assert_eq!((0..=255u8).filter(|&b| All::from(b).is_success()).count(), 87);
+
+        assert_eq!(all::OP_PUSHNUM_NEG1.push_value(), Some(-1));
+        assert_eq!(all::OP_PUSHNUM_1.push_value(), Some(1));
+        assert_eq!(all::OP_PUSHNUM_16.push_value(), Some(16));
+        assert_eq!(all::OP_PUSHBYTES_0.push_value(), None);
+        assert_eq!(all::OP_RESERVED.push_value(), None);
+        assert_eq!(all::OP_NOP.push_value(), None);
+    }
+
     #[test]
     fn str_roundtrip() {
         let mut unique = HashSet::new();
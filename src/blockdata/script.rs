@@ -24,19 +24,27 @@
 //! This module provides the structures and functions needed to support scripts.
 //!
 
+use std::collections::BTreeSet;
 use std::default::Default;
-use std::{error, fmt, io, str};
+use std::{error, fmt, io, ops, str};
 
 #[cfg(feature = "serde")] use serde;
 
 use hash_types::{PubkeyHash, WPubkeyHash, ScriptHash, WScriptHash};
 use blockdata::opcodes;
+use blockdata::scriptnum;
+use blockdata::constants::{MAX_OPS_PER_SCRIPT, MAX_SCRIPT_ELEMENT_SIZE, MAX_SCRIPT_SIZE};
 use consensus::{encode, Decodable, Encodable};
+use consensus::params::Params;
 use hashes::{Hash, hex};
+use hashes::hex::ToHex;
 #[cfg(feature="bitcoinconsensus")] use bitcoinconsensus;
 #[cfg(feature="bitcoinconsensus")] use std::convert;
 #[cfg(feature="bitcoinconsensus")] use OutPoint;
 
+use blockdata::transaction::SigHashType;
+use secp256k1::{schnorrsig, Signature};
+use util::ecdsa::is_strict_der;
 use util::key::PublicKey;
 
 #[derive(Clone, Default, PartialOrd, Ord, PartialEq, Eq, Hash)]
@@ -147,30 +155,284 @@ impl convert::From<bitcoinconsensus::Error> for Error {
         }
     }
 }
-/// Helper to encode an integer in script format
-fn build_scriptint(n: i64) -> Vec<u8> {
-    if n == 0 { return vec![] }
-
-    let neg = n < 0;
-
-    let mut abs = if neg { -n } else { n } as usize;
-    let mut v = vec![];
-    while abs > 0xFF {
-        v.push((abs & 0xFF) as u8);
-        abs >>= 8;
-    }
-    // If the number's value causes the sign bit to be set, we need an extra
-    // byte to get the correct value and correct sign bit
-    if abs & 0x80 != 0 {
-        v.push(abs as u8);
-        v.push(if neg { 0x80u8 } else { 0u8 });
-    }
-    // Otherwise we just set the sign bit ourselves
-    else {
-        abs |= if neg { 0x80 } else { 0 };
-        v.push(abs as u8);
-    }
-    v
+/// The ways a script can fail to verify, mirroring the `ScriptError` codes of
+/// Bitcoin Core so that callers can tell failures apart.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ScriptError {
+    /// The script evaluated to false or an empty stack.
+    EvalFalse,
+    /// `OP_RETURN` was executed.
+    OpReturn,
+    /// The script is larger than 10,000 bytes.
+    ScriptSize,
+    /// A push is larger than 520 bytes.
+    PushSize,
+    /// The script has more than 201 non-push opcodes.
+    OpCount,
+    /// The stack and altstack hold more than 1000 elements.
+    StackSize,
+    /// A multisig has a negative or too large signature count.
+    SigCount,
+    /// A multisig has a negative or too large public key count.
+    PubkeyCount,
+    /// `OP_VERIFY` failed.
+    Verify,
+    /// `OP_EQUALVERIFY` failed.
+    EqualVerify,
+    /// `OP_CHECKMULTISIGVERIFY` failed.
+    CheckMultisigVerify,
+    /// `OP_CHECKSIGVERIFY` failed.
+    CheckSigVerify,
+    /// `OP_NUMEQUALVERIFY` failed.
+    NumEqualVerify,
+    /// The script contains an invalid opcode or a truncated push.
+    BadOpcode,
+    /// The script contains a disabled opcode such as `OP_CAT`.
+    DisabledOpcode,
+    /// An operation needed more elements than the stack has.
+    InvalidStackOperation,
+    /// An operation needed more elements than the altstack has.
+    InvalidAltstackOperation,
+    /// An `OP_ELSE` or `OP_ENDIF` has no matching `OP_IF`, or an `OP_IF` is not closed.
+    UnbalancedConditional,
+    /// A locktime check was given a negative value.
+    NegativeLocktime,
+    /// The transaction does not satisfy a locktime check.
+    UnsatisfiedLocktime,
+    /// A signature has an undefined sighash type.
+    SigHashType,
+    /// A signature is not strictly DER-encoded.
+    SigDer,
+    /// A push or number is not minimally encoded.
+    MinimalData,
+    /// A scriptSig contains non-push opcodes.
+    SigPushOnly,
+    /// A signature has a high S value.
+    SigHighS,
+    /// The extra `OP_CHECKMULTISIG` argument is not empty.
+    SigNullDummy,
+    /// A public key is neither compressed nor uncompressed.
+    PubkeyType,
+    /// The stack holds more than one element after evaluation.
+    CleanStack,
+    /// The `OP_IF` argument is not empty or exactly `0x01`.
+    MinimalIf,
+    /// A failed signature check had a non-empty signature.
+    SigNullFail,
+    /// A reserved `OP_NOP` was executed.
+    DiscourageUpgradableNops,
+    /// A witness program has an unknown version.
+    DiscourageUpgradableWitnessProgram,
+    /// A v0 witness program is neither 20 nor 32 bytes.
+    WitnessProgramWrongLength,
+    /// A witness program was spent with an empty witness.
+    WitnessProgramWitnessEmpty,
+    /// The witness does not match the witness program.
+    WitnessProgramMismatch,
+    /// A native witness program was spent with a non-empty scriptSig.
+    WitnessMalleated,
+    /// A P2SH witness program was spent with a scriptSig other than the redeem script push.
+    WitnessMalleatedP2sh,
+    /// An input has a witness but spends no witness program.
+    WitnessUnexpected,
+    /// A segwit script uses an uncompressed public key.
+    WitnessPubkeyType,
+    /// `OP_CODESEPARATOR` was used in a non-segwit script.
+    OpCodeSeparator,
+    /// A signature is found in the script code of a non-segwit script.
+    SigFindAndDelete,
+    /// Some other error.
+    UnknownError,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ScriptError::EvalFalse => "script evaluated without error but finished with a false/empty top stack element",
+            ScriptError::OpReturn => "OP_RETURN was encountered",
+            ScriptError::ScriptSize => "script is too big",
+            ScriptError::PushSize => "push value size limit exceeded",
+            ScriptError::OpCount => "operation limit exceeded",
+            ScriptError::StackSize => "stack size limit exceeded",
+            ScriptError::SigCount => "signature count negative or greater than pubkey count",
+            ScriptError::PubkeyCount => "pubkey count negative or limit exceeded",
+            ScriptError::Verify => "script failed an OP_VERIFY operation",
+            ScriptError::EqualVerify => "script failed an OP_EQUALVERIFY operation",
+            ScriptError::CheckMultisigVerify => "script failed an OP_CHECKMULTISIGVERIFY operation",
+            ScriptError::CheckSigVerify => "script failed an OP_CHECKSIGVERIFY operation",
+            ScriptError::NumEqualVerify => "script failed an OP_NUMEQUALVERIFY operation",
+            ScriptError::BadOpcode => "opcode missing or not understood",
+            ScriptError::DisabledOpcode => "attempted to use a disabled opcode",
+            ScriptError::InvalidStackOperation => "operation not valid with the current stack size",
+            ScriptError::InvalidAltstackOperation => "operation not valid with the current altstack size",
+            ScriptError::UnbalancedConditional => "invalid OP_IF construction",
+            ScriptError::NegativeLocktime => "negative locktime",
+            ScriptError::UnsatisfiedLocktime => "locktime requirement not satisfied",
+            ScriptError::SigHashType => "signature hash type missing or not understood",
+            ScriptError::SigDer => "non-canonical DER signature",
+            ScriptError::MinimalData => "data push larger than necessary",
+            ScriptError::SigPushOnly => "only push operators allowed in signatures",
+            ScriptError::SigHighS => "non-canonical signature: S value is unnecessarily high",
+            ScriptError::SigNullDummy => "dummy CHECKMULTISIG argument must be zero",
+            ScriptError::PubkeyType => "public key is neither compressed or uncompressed",
+            ScriptError::CleanStack => "stack size must be exactly one after execution",
+            ScriptError::MinimalIf => "OP_IF/NOTIF argument must be minimal",
+            ScriptError::SigNullFail => "signature must be zero for failed CHECK(MULTI)SIG operation",
+            ScriptError::DiscourageUpgradableNops => "NOPx reserved for soft-fork upgrades",
+            ScriptError::DiscourageUpgradableWitnessProgram => "witness version reserved for soft-fork upgrades",
+            ScriptError::WitnessProgramWrongLength => "witness program has incorrect length",
+            ScriptError::WitnessProgramWitnessEmpty => "witness program was passed an empty witness",
+            ScriptError::WitnessProgramMismatch => "witness program hash mismatch",
+            ScriptError::WitnessMalleated => "witness requires empty scriptSig",
+            ScriptError::WitnessMalleatedP2sh => "witness requires only-redeemscript scriptSig",
+            ScriptError::WitnessUnexpected => "witness provided for non-witness script",
+            ScriptError::WitnessPubkeyType => "using non-compressed keys in segwit",
+            ScriptError::OpCodeSeparator => "using OP_CODESEPARATOR in non-witness script",
+            ScriptError::SigFindAndDelete => "signature is found in scriptCode",
+            ScriptError::UnknownError => "unknown error",
+        })
+    }
+}
+
+impl error::Error for ScriptError {}
+
+/// Script verification flags, with the values of the `SCRIPT_VERIFY_*` flags
+/// of Bitcoin Core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VerifyFlags(u32);
+
+impl VerifyFlags {
+    /// No flags, which is how scripts were evaluated originally.
+    pub const NONE: VerifyFlags = VerifyFlags(0);
+    /// Evaluate P2SH subscripts (BIP16).
+    pub const P2SH: VerifyFlags = VerifyFlags(1 << 0);
+    /// Require signatures and public keys to be well-encoded.
+    pub const STRICTENC: VerifyFlags = VerifyFlags(1 << 1);
+    /// Require signatures to be strict DER (BIP66).
+    pub const DERSIG: VerifyFlags = VerifyFlags(1 << 2);
+    /// Require signatures to have a low S value (BIP146).
+    pub const LOW_S: VerifyFlags = VerifyFlags(1 << 3);
+    /// Require the dummy element of `OP_CHECKMULTISIG` to be empty (BIP147).
+    pub const NULLDUMMY: VerifyFlags = VerifyFlags(1 << 4);
+    /// Require scriptSigs to only push data.
+    pub const SIGPUSHONLY: VerifyFlags = VerifyFlags(1 << 5);
+    /// Require pushes and numbers to use their minimal encoding.
+    pub const MINIMALDATA: VerifyFlags = VerifyFlags(1 << 6);
+    /// Fail on the `OP_NOP` opcodes reserved for soft forks.
+    pub const DISCOURAGE_UPGRADABLE_NOPS: VerifyFlags = VerifyFlags(1 << 7);
+    /// Require exactly one element on the stack after evaluation.
+    pub const CLEANSTACK: VerifyFlags = VerifyFlags(1 << 8);
+    /// Enforce `OP_CHECKLOCKTIMEVERIFY` (BIP65).
+    pub const CHECKLOCKTIMEVERIFY: VerifyFlags = VerifyFlags(1 << 9);
+    /// Enforce `OP_CHECKSEQUENCEVERIFY` (BIP112).
+    pub const CHECKSEQUENCEVERIFY: VerifyFlags = VerifyFlags(1 << 10);
+    /// Evaluate witness programs (BIP141).
+    pub const WITNESS: VerifyFlags = VerifyFlags(1 << 11);
+    /// Fail on witness versions reserved for soft forks.
+    pub const DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM: VerifyFlags = VerifyFlags(1 << 12);
+    /// Require the argument of `OP_IF` and `OP_NOTIF` in witness scripts to be
+    /// empty or `1`.
+    pub const MINIMALIF: VerifyFlags = VerifyFlags(1 << 13);
+    /// Require failing signature checks to have an empty signature (BIP146).
+    pub const NULLFAIL: VerifyFlags = VerifyFlags(1 << 14);
+    /// Require compressed public keys in segwit v0 scripts.
+    pub const WITNESS_PUBKEYTYPE: VerifyFlags = VerifyFlags(1 << 15);
+    /// Fail on `OP_CODESEPARATOR` and signatures found in legacy script codes.
+    pub const CONST_SCRIPTCODE: VerifyFlags = VerifyFlags(1 << 16);
+    /// Evaluate taproot spends (BIP341 and BIP342).
+    pub const TAPROOT: VerifyFlags = VerifyFlags(1 << 17);
+    /// Fail on taproot leaf versions reserved for soft forks.
+    pub const DISCOURAGE_UPGRADABLE_TAPROOT_VERSION: VerifyFlags = VerifyFlags(1 << 18);
+    /// Fail on the `OP_SUCCESS` opcodes of tapscript.
+    pub const DISCOURAGE_OP_SUCCESS: VerifyFlags = VerifyFlags(1 << 19);
+    /// Fail on tapscript public keys of unknown types.
+    pub const DISCOURAGE_UPGRADABLE_PUBKEYTYPE: VerifyFlags = VerifyFlags(1 << 20);
+
+    /// The flags enforced by consensus for the block at `height`, following
+    /// `GetBlockScriptFlags` in Bitcoin Core.
+    ///
+    /// P2SH and witness programs are evaluated in all blocks, like Bitcoin
+    /// Core does except for two historic blocks on mainnet. Taproot is a BIP9
+    /// deployment rather than a fixed height, so [`VerifyFlags::TAPROOT`] needs
+    /// to be added once [`Params::bip9_state`] reports it active.
+    ///
+    /// [`VerifyFlags::TAPROOT`]: #associatedconstant.TAPROOT
+    /// [`Params::bip9_state`]: ../../consensus/params/struct.Params.html#method.bip9_state
+    pub fn consensus(params: &Params, height: u32) -> VerifyFlags {
+        let mut flags = VerifyFlags::P2SH | VerifyFlags::WITNESS;
+        if height >= params.bip66_height {
+            flags |= VerifyFlags::DERSIG;
+        }
+        if height >= params.bip65_height {
+            flags |= VerifyFlags::CHECKLOCKTIMEVERIFY;
+        }
+        if height >= params.csv_height {
+            flags |= VerifyFlags::CHECKSEQUENCEVERIFY;
+        }
+        if params.segwit_active(height) {
+            flags |= VerifyFlags::NULLDUMMY;
+        }
+        flags
+    }
+
+    /// The flags enforced for relaying transactions, as
+    /// `STANDARD_SCRIPT_VERIFY_FLAGS` in Bitcoin Core, which are the same on
+    /// all networks.
+    pub fn standard() -> VerifyFlags {
+        VerifyFlags::P2SH | VerifyFlags::STRICTENC | VerifyFlags::DERSIG | VerifyFlags::LOW_S
+            | VerifyFlags::NULLDUMMY | VerifyFlags::MINIMALDATA | VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS
+            | VerifyFlags::CLEANSTACK | VerifyFlags::CHECKLOCKTIMEVERIFY | VerifyFlags::CHECKSEQUENCEVERIFY
+            | VerifyFlags::WITNESS | VerifyFlags::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM | VerifyFlags::MINIMALIF
+            | VerifyFlags::NULLFAIL | VerifyFlags::WITNESS_PUBKEYTYPE | VerifyFlags::CONST_SCRIPTCODE
+            | VerifyFlags::TAPROOT | VerifyFlags::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION
+            | VerifyFlags::DISCOURAGE_OP_SUCCESS | VerifyFlags::DISCOURAGE_UPGRADABLE_PUBKEYTYPE
+    }
+
+    /// Add [VerifyFlags] together.
+    ///
+    /// Returns itself.
+    pub fn add(&mut self, other: VerifyFlags) -> VerifyFlags {
+        self.0 |= other.0;
+        *self
+    }
+
+    /// Remove [VerifyFlags] from this.
+    ///
+    /// Returns itself.
+    pub fn remove(&mut self, other: VerifyFlags) -> VerifyFlags {
+        self.0 &= !other.0;
+        *self
+    }
+
+    /// Check whether [VerifyFlags] are included in this one.
+    pub fn has(self, flags: VerifyFlags) -> bool {
+        (self.0 | flags.0) == self.0
+    }
+
+    /// Get the integer representation of this [VerifyFlags].
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<u32> for VerifyFlags {
+    fn from(f: u32) -> Self {
+        VerifyFlags(f)
+    }
+}
+
+impl ops::BitOr for VerifyFlags {
+    type Output = Self;
+
+    fn bitor(mut self, rhs: Self) -> Self {
+        self.add(rhs)
+    }
+}
+
+impl ops::BitOrAssign for VerifyFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.add(rhs);
+    }
 }
 
 /// Helper to decode an integer in script format
@@ -188,17 +450,7 @@ fn build_scriptint(n: i64) -> Vec<u8> {
 /// simply say, anything in excess of 32 bits is no longer a number.
 /// This is basically a ranged type implementation.
 pub fn read_scriptint(v: &[u8]) -> Result<i64, Error> {
-    let len = v.len();
-    if len == 0 { return Ok(0); }
-    if len > 4 { return Err(Error::NumericOverflow); }
-
-    let (mut ret, sh) = v.iter()
-                         .fold((0, 0), |(acc, sh), n| (acc + ((*n as i64) << sh), sh + 8));
-    if v[len - 1] & 0x80 != 0 {
-        ret &= (1 << (sh - 1)) - 1;
-        ret = -ret;
-    }
-    Ok(ret)
+    scriptnum::decode_lax(v, scriptnum::DEFAULT_MAX_SIZE).map_err(|_| Error::NumericOverflow)
 }
 
 /// This is like "`read_scriptint` then map 0 to false and everything
@@ -260,6 +512,12 @@ impl Script {
         Script::new_witness_program(::bech32::u5::try_from_u8(0).unwrap(), &pubkey_hash.to_vec())
     }
 
+    /// Generates P2SH-type of scriptPubkey whose redeem script is the P2WPKH
+    /// program for the given key hash (nested segwit)
+    pub fn new_p2sh_p2wpkh(pubkey_hash: &WPubkeyHash) -> Script {
+        Script::new_v0_wpkh(pubkey_hash).to_p2sh()
+    }
+
     /// Generates P2WSH-type of scriptPubkey with a given hash of the redeem script
     pub fn new_v0_wsh(script_hash: &WScriptHash) -> Script {
         Script::new_witness_program(::bech32::u5::try_from_u8(0).unwrap(), &script_hash.to_vec())
@@ -373,6 +631,19 @@ impl Script {
             && self.0.len() - 2 == self.0[1] as usize
     }
 
+    /// Returns the witness version and program of a segwit output script, or `None` if the
+    /// script is not a witness program (see [`Script::is_witness_program`]).
+    pub fn witness_program(&self) -> Option<(u8, &[u8])> {
+        if !self.is_witness_program() {
+            return None;
+        }
+        let version = match self.0[0] {
+            0 => 0,
+            op => op - opcodes::all::OP_PUSHNUM_1.into_u8() + 1,
+        };
+        Some((version, &self.0[2..]))
+    }
+
     /// Checks whether a script pubkey is a p2wsh output
     #[inline]
     pub fn is_v0_p2wsh(&self) -> bool {
@@ -394,8 +665,173 @@ impl Script {
         !self.0.is_empty() && (opcodes::All::from(self.0[0]) == opcodes::all::OP_RETURN)
     }
 
-    /// Whether a script can be proven to have no satisfying input
+    /// Returns the data carried by an output of the form `OP_RETURN <push>`, if
+    /// the push is at most `max_len` bytes.
+    ///
+    /// A bare `OP_RETURN` carries no data. Any other script, including one
+    /// with several pushes after the `OP_RETURN`, returns `None`. Use
+    /// [`Params::max_op_return_relay`] as `max_len` for the default relay
+    /// policy.
+    ///
+    /// [`Params::max_op_return_relay`]: ../../consensus/params/struct.Params.html#method.max_op_return_relay
+    pub fn op_return_data(&self, max_len: usize) -> Option<&[u8]> {
+        if !self.is_op_return() {
+            return None;
+        }
+        let mut instructions = Instructions { data: &self.0[1..], enforce_minimal: false };
+        let data = match instructions.next() {
+            None => &[][..],
+            Some(Ok(Instruction::PushBytes(data))) => data,
+            _ => return None,
+        };
+        if instructions.next().is_some() || data.len() > max_len {
+            return None;
+        }
+        Some(data)
+    }
+
+    /// Checks whether the script only pushes data, as required for standard
+    /// scriptSigs. Like in Bitcoin Core, every opcode up to `OP_16`, including
+    /// `OP_RESERVED`, counts as a push; a script which fails to parse does not.
+    pub fn is_push_only(&self) -> bool {
+        self.instructions().all(|instruction| match instruction {
+            Ok(Instruction::PushBytes(_)) => true,
+            Ok(Instruction::Op(op)) => op.is_push(),
+            Err(_) => false,
+        })
+    }
+
+    /// Returns a copy of the script with every `OP_CODESEPARATOR` removed, as
+    /// done to the script code of a legacy signature hash. Pushed data is
+    /// left alone, even where it contains the opcode's byte.
+    pub fn without_code_separators(&self) -> Script {
+        let mut ret = Vec::with_capacity(self.len());
+        let mut instructions = self.instructions();
+        loop {
+            let start = self.len() - instructions.data.len();
+            match instructions.next() {
+                Some(Ok(Instruction::Op(opcodes::all::OP_CODESEPARATOR))) => {}
+                Some(Ok(_)) => ret.extend_from_slice(&self.0[start..self.len() - instructions.data.len()]),
+                // Like Bitcoin Core, keep anything after a parse error as is.
+                Some(Err(_)) | None => {
+                    ret.extend_from_slice(&self.0[start..]);
+                    break;
+                }
+            }
+        }
+        Script::from(ret)
+    }
+
+    /// Counts the signature operations in the script like Bitcoin Core's
+    /// legacy count, which assumes the maximum of 20 keys for every multisig.
+    pub fn count_sigops_legacy(&self) -> usize {
+        self.count_sigops_internal(false)
+    }
+
+    /// Counts the signature operations in the script, taking the number of
+    /// keys of a multisig from the `OP_1`-`OP_16` before it, as Bitcoin Core
+    /// does for P2SH redeem scripts and witness scripts. Multisigs with any
+    /// other number of keys count as 20.
+    pub fn count_sigops(&self) -> usize {
+        self.count_sigops_internal(true)
+    }
+
+    fn count_sigops_internal(&self, accurate: bool) -> usize {
+        let mut count = 0;
+        let mut last_op = None;
+        for instruction in self.instructions() {
+            let op = match instruction {
+                Ok(Instruction::Op(op)) => op,
+                Ok(Instruction::PushBytes(_)) => {
+                    last_op = None;
+                    continue;
+                }
+                Err(_) => break,
+            };
+            match op {
+                opcodes::all::OP_CHECKSIG | opcodes::all::OP_CHECKSIGVERIFY => count += 1,
+                opcodes::all::OP_CHECKMULTISIG | opcodes::all::OP_CHECKMULTISIGVERIFY => {
+                    count += match last_op {
+                        Some(opcodes::Class::PushNum(n)) if accurate && n >= 1 => n as usize,
+                        _ => 20,
+                    };
+                }
+                _ => {}
+            }
+            last_op = Some(op.classify());
+        }
+        count
+    }
+
+    /// Checks the script for errors which make it fail no matter which branches
+    /// are executed: its size, the size of each push, the number of non-push
+    /// opcodes, disabled and invalid opcodes, and unbalanced conditionals.
+    ///
+    /// Passing this check does not mean that the script can be satisfied.
+    pub fn check_static(&self) -> Result<(), ScriptError> {
+        use blockdata::opcodes::all::*;
+
+        if self.0.len() > MAX_SCRIPT_SIZE {
+            return Err(ScriptError::ScriptSize);
+        }
+        let mut op_count = 0;
+        let mut depth = 0usize;
+        for instruction in self.instructions() {
+            let op = match instruction {
+                Ok(Instruction::PushBytes(data)) => {
+                    if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                        return Err(ScriptError::PushSize);
+                    }
+                    continue;
+                }
+                Ok(Instruction::Op(op)) => op,
+                Err(_) => return Err(ScriptError::BadOpcode),
+            };
+            if !op.is_push() {
+                op_count += 1;
+                if op_count > MAX_OPS_PER_SCRIPT {
+                    return Err(ScriptError::OpCount);
+                }
+            }
+            if op.is_disabled() {
+                return Err(ScriptError::DisabledOpcode);
+            }
+            match op {
+                OP_VERIF | OP_VERNOTIF => return Err(ScriptError::BadOpcode),
+                OP_IF | OP_NOTIF => depth += 1,
+                OP_ELSE if depth == 0 => return Err(ScriptError::UnbalancedConditional),
+                OP_ENDIF => {
+                    if depth == 0 {
+                        return Err(ScriptError::UnbalancedConditional);
+                    }
+                    depth -= 1;
+                }
+                _ => {}
+            }
+        }
+        if depth != 0 {
+            return Err(ScriptError::UnbalancedConditional);
+        }
+        Ok(())
+    }
+
+    /// Checks whether the script is small enough to be relayed as a scriptSig,
+    /// see [`Params::max_standard_scriptsig_size`].
+    ///
+    /// [`Params::max_standard_scriptsig_size`]: ../../consensus/params/struct.Params.html#structfield.max_standard_scriptsig_size
+    pub fn scriptsig_size_standard(&self, params: &Params) -> bool {
+        self.0.len() <= params.max_standard_scriptsig_size
+    }
+
+    /// Whether a script can be proven to have no satisfying input. This is the case for scripts
+    /// starting with `OP_RETURN` (or an illegal opcode) and for scripts larger than
+    /// [`MAX_SCRIPT_SIZE`], which would fail to execute. Such outputs can be pruned from the UTXO set.
+    ///
+    /// [`MAX_SCRIPT_SIZE`]: ../constants/constant.MAX_SCRIPT_SIZE.html
     pub fn is_provably_unspendable(&self) -> bool {
+        if self.0.len() > MAX_SCRIPT_SIZE {
+            return true;
+        }
         !self.0.is_empty() && (opcodes::All::from(self.0[0]).classify() == opcodes::Class::ReturnOp ||
                                opcodes::All::from(self.0[0]).classify() == opcodes::Class::IllegalOp)
     }
@@ -442,6 +878,37 @@ impl Script {
         }
     }
 
+    /// Iterate over the opcodes of the script, skipping the data of pushes but
+    /// still returning the opcode of each push. This is meant for matching
+    /// scripts against templates. If the script ends in the middle of a push,
+    /// its opcode is the last one returned.
+    pub fn opcodes(&self) -> Opcodes<'_> {
+        Opcodes { data: &self.0[..] }
+    }
+
+    /// Returns the distinct non-push opcodes of the script, for checking it
+    /// against a policy such as a list of disabled opcodes. Pushes are
+    /// counted as in [`is_push_only`], so `OP_1`-`OP_16` are not included.
+    ///
+    /// [`is_push_only`]: #method.is_push_only
+    pub fn used_opcodes(&self) -> BTreeSet<opcodes::All> {
+        self.opcodes().filter(|op| !op.is_push()).collect()
+    }
+
+    /// Collects the data of every push in the script, skipping non-push opcodes. This is handy
+    /// for pulling signatures and public keys out of a scriptSig.
+    ///
+    /// All push forms are handled, including the `OP_PUSHDATA` variants. Note that `OP_0` pushes
+    /// an empty slice, whereas the small-number opcodes (`OP_1NEGATE`, `OP_1`-`OP_16`) are not
+    /// considered data pushes. Returns an error if the script can not be parsed.
+    pub fn instructions_data(&self) -> Result<Vec<&[u8]>, Error> {
+        self.instructions().filter_map(|instruction| match instruction {
+            Ok(Instruction::PushBytes(data)) => Some(Ok(data)),
+            Ok(Instruction::Op(_)) => None,
+            Err(e) => Some(Err(e)),
+        }).collect()
+    }
+
     #[cfg(feature="bitcoinconsensus")]
     /// verify spend of an input script
     /// # Parameters
@@ -527,6 +994,68 @@ impl Script {
         self.fmt_asm(&mut buf).unwrap();
         buf
     }
+
+    /// Returns the assembly of the script in the format of Bitcoin Core's
+    /// `ScriptToAsmStr`, as shown by its RPCs.
+    ///
+    /// Pushes of up to four bytes are shown as numbers. With `decode_sighash`,
+    /// pushes which are signatures are shown with their sighash type as a
+    /// suffix, like `<der>[ALL]`, which Bitcoin Core does for scriptSigs.
+    pub fn to_core_asm(&self, decode_sighash: bool) -> String {
+        let decode_sighash = decode_sighash && !(self.is_op_return() || self.0.len() > MAX_SCRIPT_SIZE);
+        let mut words = vec![];
+        for instruction in self.instructions() {
+            words.push(match instruction {
+                Ok(Instruction::PushBytes(data)) if data.len() <= scriptnum::DEFAULT_MAX_SIZE => {
+                    scriptnum::decode_lax(data, scriptnum::DEFAULT_MAX_SIZE).expect("short push").to_string()
+                }
+                Ok(Instruction::PushBytes(data)) => match sighash_suffix(data) {
+                    Some(suffix) if decode_sighash => format!("{}[{}]", data[..data.len() - 1].to_hex(), suffix),
+                    _ => data.to_hex(),
+                },
+                Ok(Instruction::Op(op)) => core_op_name(op),
+                Err(_) => {
+                    words.push("[error]".to_owned());
+                    break;
+                }
+            });
+        }
+        words.join(" ")
+    }
+}
+
+/// The sighash type of a strictly encoded signature, as Bitcoin Core names it in assembly.
+fn sighash_suffix(sig: &[u8]) -> Option<&'static str> {
+    if !is_strict_der(sig) {
+        return None;
+    }
+    Some(match sig[sig.len() - 1] {
+        0x01 => "ALL",
+        0x81 => "ALL|ANYONECANPAY",
+        0x02 => "NONE",
+        0x82 => "NONE|ANYONECANPAY",
+        0x03 => "SINGLE",
+        0x83 => "SINGLE|ANYONECANPAY",
+        _ => return None,
+    })
+}
+
+/// The name of a non-push opcode as in Bitcoin Core's `GetOpName`.
+fn core_op_name(op: opcodes::All) -> String {
+    use blockdata::opcodes::all::*;
+
+    match op {
+        OP_PUSHNUM_NEG1 => "-1".to_owned(),
+        op if op.into_u8() >= OP_PUSHNUM_1.into_u8() && op.into_u8() <= OP_PUSHNUM_16.into_u8() => {
+            (op.into_u8() - OP_PUSHNUM_1.into_u8() + 1).to_string()
+        }
+        OP_CLTV => "OP_CHECKLOCKTIMEVERIFY".to_owned(),
+        OP_CSV => "OP_CHECKSEQUENCEVERIFY".to_owned(),
+        OP_RETURN_186 => "OP_CHECKSIGADD".to_owned(),
+        OP_RETURN_255 => "OP_INVALIDOPCODE".to_owned(),
+        op if op.into_u8() > OP_RETURN_186.into_u8() => "OP_UNKNOWN".to_owned(),
+        op => format!("{:?}", op),
+    }
 }
 
 /// Creates a new script from an existing vector
@@ -658,6 +1187,34 @@ impl<'a> Iterator for Instructions<'a> {
     }
 }
 
+/// Iterator over the opcodes of a script, see [`Script::opcodes`].
+///
+/// [`Script::opcodes`]: struct.Script.html#method.opcodes
+pub struct Opcodes<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Opcodes<'a> {
+    type Item = opcodes::All;
+
+    fn next(&mut self) -> Option<opcodes::All> {
+        let (&byte, rest) = self.data.split_first()?;
+        let opcode = opcodes::All::from(byte);
+        let push_len = match opcode.classify() {
+            opcodes::Class::PushBytes(n) => Ok(n as usize),
+            opcodes::Class::Ordinary(opcodes::Ordinary::OP_PUSHDATA1) => read_uint(rest, 1).map(|n| n + 1),
+            opcodes::Class::Ordinary(opcodes::Ordinary::OP_PUSHDATA2) => read_uint(rest, 2).map(|n| n + 2),
+            opcodes::Class::Ordinary(opcodes::Ordinary::OP_PUSHDATA4) => read_uint(rest, 4).map(|n| n + 4),
+            _ => Ok(0),
+        };
+        self.data = match push_len {
+            Ok(len) if len <= rest.len() => &rest[len..],
+            _ => &[],
+        };
+        Some(opcode)
+    }
+}
+
 impl Builder {
     /// Creates a new empty script
     pub fn new() -> Self {
@@ -692,7 +1249,7 @@ impl Builder {
     /// Adds instructions to push an integer onto the stack, using the explicit
     /// encoding regardless of the availability of dedicated opcodes.
     pub fn push_scriptint(self, data: i64) -> Builder {
-        self.push_slice(&build_scriptint(data))
+        self.push_slice(&scriptnum::encode(data))
     }
 
     /// Adds instructions to push some arbitrary data onto the stack
@@ -733,6 +1290,19 @@ impl Builder {
         }
     }
 
+    /// Pushes a 32-byte x-only public key, as used by Taproot
+    pub fn push_x_only_key(self, key: &schnorrsig::PublicKey) -> Builder {
+        self.push_slice(&key.serialize())
+    }
+
+    /// Pushes a DER-encoded ECDSA signature followed by its sighash type byte,
+    /// as expected by `OP_CHECKSIG` in legacy and segwit v0 scripts
+    pub fn push_signature(self, sig: &Signature, sighash_type: SigHashType) -> Builder {
+        let mut data = sig.serialize_der().to_vec();
+        data.push(sighash_type.as_u32() as u8);
+        self.push_slice(&data)
+    }
+
     /// Adds a single opcode to the script
     pub fn push_opcode(mut self, data: opcodes::All) -> Builder {
         self.0.push(data.into_u8());
@@ -868,7 +1438,6 @@ mod test {
     use std::str::FromStr;
 
     use super::*;
-    use super::build_scriptint;
 
     use hashes::hex::{FromHex, ToHex};
     use consensus::encode::{deserialize, serialize};
@@ -912,6 +1481,287 @@ mod test {
         script = script.push_opcode(opcodes::all::OP_CHECKSIG); comp.push(0xACu8); assert_eq!(&script[..], &comp[..]);
     }
 
+    #[test]
+    fn script_builder_typed_pushes() {
+        use blockdata::transaction::SigHashType;
+        use secp256k1::{schnorrsig, Signature};
+
+        let keystr = "2e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af";
+        let key = schnorrsig::PublicKey::from_str(keystr).unwrap();
+        let script = Builder::new().push_x_only_key(&key).push_opcode(opcodes::all::OP_CHECKSIG).into_script();
+        assert_eq!(script.to_hex(), format!("20{}ac", keystr));
+        assert_eq!(script, Builder::new().push_slice(&key.serialize()).push_opcode(opcodes::all::OP_CHECKSIG).into_script());
+
+        // A 70-byte DER signature, pushed with a 71st sighash byte
+        let der = "30440220111a482aba6afba12a6f27de767dd4d06417def665bd100bc68c42845c752a8f02205e86f5e054b2c6cac5d663664e35779fb034387c07848bc7724442cacf659324";
+        let sig = Signature::from_der(&Vec::from_hex(der).unwrap()).unwrap();
+        let script = Builder::new().push_signature(&sig, SigHashType::All).into_script();
+        assert_eq!(script.to_hex(), format!("47{}01", der));
+        let script = Builder::new().push_signature(&sig, SigHashType::SinglePlusAnyoneCanPay).into_script();
+        assert_eq!(script.to_hex(), format!("47{}83", der));
+
+        let mut manual = sig.serialize_der().to_vec();
+        manual.push(SigHashType::None.as_u32() as u8);
+        assert_eq!(Builder::new().push_signature(&sig, SigHashType::None).into_script(),
+                   Builder::new().push_slice(&manual).into_script());
+    }
+
+    #[test]
+    fn script_used_opcodes() {
+        use blockdata::opcodes::all::*;
+
+        assert!(Script::new().used_opcodes().is_empty());
+        let multisig = Builder::new()
+            .push_opcode(OP_PUSHNUM_2)
+            .push_slice(&[2; 33])
+            .push_slice(&[3; 33])
+            .push_opcode(OP_PUSHNUM_2)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script();
+        assert_eq!(multisig.used_opcodes().into_iter().collect::<Vec<_>>(), vec![OP_CHECKMULTISIG]);
+
+        // Opcodes inside pushes are not used, repeated ones are listed once.
+        let script = hex_script!("76a9147e7676ac7e7e7e7e7e7e7e7e7e7e7e7e7e7e7e7e88ac7e63ac68");
+        assert_eq!(
+            script.used_opcodes().into_iter().collect::<Vec<_>>(),
+            vec![OP_IF, OP_ENDIF, OP_DUP, OP_CAT, OP_EQUALVERIFY, OP_HASH160, OP_CHECKSIG]
+        );
+        assert!(script.used_opcodes().iter().any(|op| op.is_disabled()));
+    }
+
+    #[test]
+    fn script_without_code_separators() {
+        let script = hex_script!("ab76a914000102030405060708090a0b0c0d0e0f10111213ab88acab");
+        assert_eq!(script.without_code_separators(), hex_script!("76a914000102030405060708090a0b0c0d0e0f1011121388ac"));
+        // Pushes of the opcode's byte are kept.
+        let script = hex_script!("01abab4c01abab");
+        assert_eq!(script.without_code_separators(), hex_script!("01ab4c01ab"));
+        let p2pkh = hex_script!("76a91416e1ae70ff0fa102905d4af297f6912bda6cce1988ac");
+        assert_eq!(p2pkh.without_code_separators(), p2pkh);
+        // A truncated push ends the search.
+        assert_eq!(hex_script!("ab4c05abab").without_code_separators(), hex_script!("4c05abab"));
+    }
+
+    #[test]
+    fn script_sigops() {
+        assert_eq!(Script::new().count_sigops(), 0);
+        let p2pkh = hex_script!("76a91416e1ae70ff0fa102905d4af297f6912bda6cce1988ac");
+        assert_eq!(p2pkh.count_sigops_legacy(), 1);
+        assert_eq!(p2pkh.count_sigops(), 1);
+
+        let multisig = Builder::new()
+            .push_opcode(opcodes::all::OP_PUSHNUM_2)
+            .push_slice(&[2; 33])
+            .push_slice(&[3; 33])
+            .push_slice(&[2; 33])
+            .push_opcode(opcodes::all::OP_PUSHNUM_3)
+            .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+            .into_script();
+        assert_eq!(multisig.count_sigops_legacy(), 20);
+        assert_eq!(multisig.count_sigops(), 3);
+
+        // Without a small number before it, the count of keys is not known.
+        assert_eq!(hex_script!("0103aeae").count_sigops(), 40);
+        assert_eq!(hex_script!("00ae").count_sigops(), 20);
+        assert_eq!(hex_script!("4fae").count_sigops(), 20);
+        assert_eq!(hex_script!("60ae51adac").count_sigops(), 18);
+        // Counting stops at a truncated push.
+        assert_eq!(hex_script!("ac4c05ac").count_sigops(), 1);
+    }
+
+    #[test]
+    fn script_push_only() {
+        use consensus::params::Params;
+        use network::constants::Network;
+
+        assert!(Script::new().is_push_only());
+        assert!(hex_script!("00514f60").is_push_only());
+        assert!(hex_script!("4730440220111a482aba6afba12a6f27de767dd4d06417def665bd100bc68c42845c752a8f02205e86f5e054b2c6cac5d663664e35779fb034387c07848bc7724442cacf65932401").is_push_only());
+        // OP_RESERVED is considered a push, like in Bitcoin Core.
+        assert!(hex_script!("50").is_push_only());
+        assert!(!hex_script!("61").is_push_only());
+        assert!(!hex_script!("0076").is_push_only());
+        // Truncated push
+        assert!(!hex_script!("4c05aa").is_push_only());
+
+        let params = Params::new(Network::Bitcoin);
+        let at_limit = Builder::new().push_slice(&[0u8; 1647]).into_script();
+        assert_eq!(at_limit.len(), 1650);
+        assert!(at_limit.scriptsig_size_standard(&params));
+        let over_limit = Builder::new().push_slice(&[0u8; 1648]).into_script();
+        assert_eq!(over_limit.len(), 1651);
+        assert!(!over_limit.scriptsig_size_standard(&params));
+    }
+
+    #[test]
+    fn script_opcodes() {
+        use blockdata::opcodes::all::*;
+
+        let p2pkh = hex_script!("76a914162c5ea71c0b23f5b9022ef047c4a86470a5b07088ac");
+        assert_eq!(
+            p2pkh.opcodes().collect::<Vec<_>>(),
+            vec![OP_DUP, OP_HASH160, OP_PUSHBYTES_20, OP_EQUALVERIFY, OP_CHECKSIG]
+        );
+
+        let script = Builder::new()
+            .push_int(0)
+            .push_slice(&[0xac; 80])
+            .push_slice(&[0x76; 300])
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        assert_eq!(
+            script.opcodes().collect::<Vec<_>>(),
+            vec![OP_PUSHBYTES_0, OP_PUSHDATA1, OP_PUSHDATA2, OP_CHECKSIG]
+        );
+
+        let truncated = hex_script!("4c05ac76ac");
+        assert_eq!(truncated.opcodes().collect::<Vec<_>>(), vec![OP_PUSHDATA1]);
+        assert_eq!(hex_script!("4e01").opcodes().collect::<Vec<_>>(), vec![OP_PUSHDATA4]);
+        assert_eq!(Script::new().opcodes().count(), 0);
+    }
+
+    #[test]
+    fn script_op_return_data() {
+        use consensus::params::Params;
+        use network::constants::Network;
+
+        let limit = Params::new(Network::Bitcoin).max_op_return_relay();
+        let data = [0xab; 80];
+        assert_eq!(Script::new_op_return(&data).op_return_data(limit), Some(&data[..]));
+        assert_eq!(Script::new_op_return(&data[..20]).op_return_data(limit), Some(&data[..20]));
+        assert_eq!(Script::new_op_return(&[]).op_return_data(limit), Some(&[][..]));
+        assert_eq!(Builder::new().push_opcode(opcodes::all::OP_RETURN).into_script().op_return_data(limit), Some(&[][..]));
+
+        // Over the limit
+        assert_eq!(Script::new_op_return(&[0xab; 81]).op_return_data(limit), None);
+        assert_eq!(Script::new_op_return(&data).op_return_data(79), None);
+
+        // Not a single push after OP_RETURN
+        let two_pushes = Builder::new().push_opcode(opcodes::all::OP_RETURN)
+            .push_slice(&data[..10]).push_slice(&data[..10]).into_script();
+        assert_eq!(two_pushes.op_return_data(limit), None);
+        let opcode = Builder::new().push_opcode(opcodes::all::OP_RETURN)
+            .push_opcode(opcodes::all::OP_DUP).into_script();
+        assert_eq!(opcode.op_return_data(limit), None);
+        assert_eq!(Script::from(vec![0x6a, 0x05, 0xab]).op_return_data(limit), None);
+        assert_eq!(Script::new_v0_wpkh(&WPubkeyHash::hash(&[])).op_return_data(limit), None);
+    }
+
+    #[test]
+    fn script_core_asm() {
+        let script = Builder::new()
+            .push_int(0)
+            .push_int(-1)
+            .push_int(16)
+            .push_int(1000)
+            .push_slice(&[0x81])
+            .push_slice(&[0x01, 0x00, 0x00, 0x80])
+            .push_opcode(opcodes::all::OP_CLTV)
+            .push_opcode(opcodes::all::OP_DROP)
+            .push_slice(&[0xab; 5])
+            .into_script();
+        assert_eq!(script.to_core_asm(false), "0 -1 16 1000 -1 -1 OP_CHECKLOCKTIMEVERIFY OP_DROP ababababab");
+
+        let sig = Vec::from_hex("3046022100a8ecd1e97e26586257e29c66fcd2cf7bb50e668c6df01e212a1a99a5e00000ce022100ac0ec3e3c7db1a60ada2fad7cf16daba74672b843a851f264b553830fca4778683").unwrap();
+        let script_sig = Builder::new().push_slice(&sig).into_script();
+        let der = sig[..sig.len() - 1].to_hex();
+        assert_eq!(script_sig.to_core_asm(true), format!("{}[SINGLE|ANYONECANPAY]", der));
+        assert_eq!(script_sig.to_core_asm(false), sig.to_hex());
+        // OP_RETURN data is never decoded as a signature.
+        let data = Builder::new().push_opcode(opcodes::all::OP_RETURN).push_slice(&sig).into_script();
+        assert_eq!(data.to_core_asm(true), format!("OP_RETURN {}", sig.to_hex()));
+
+        assert_eq!(Script::from(vec![0xba, 0xbb, 0xff, 0x4c]).to_core_asm(false), "OP_CHECKSIGADD OP_UNKNOWN OP_INVALIDOPCODE [error]");
+    }
+
+    #[test]
+    fn verify_flags_presets() {
+        use network::constants::Network;
+
+        let params = Params::new(Network::Bitcoin);
+        assert_eq!(VerifyFlags::consensus(&params, 0), VerifyFlags::P2SH | VerifyFlags::WITNESS);
+        let csv = VerifyFlags::consensus(&params, params.csv_height);
+        assert!(csv.has(VerifyFlags::DERSIG | VerifyFlags::CHECKLOCKTIMEVERIFY | VerifyFlags::CHECKSEQUENCEVERIFY));
+        assert!(!csv.has(VerifyFlags::NULLDUMMY));
+        let segwit = VerifyFlags::consensus(&params, params.segwit_height);
+        assert_eq!(segwit.as_u32(), 0x01 | 0x04 | 0x10 | 0x200 | 0x400 | 0x800);
+        // Policy rules are never consensus.
+        for flag in &[VerifyFlags::CLEANSTACK, VerifyFlags::MINIMALDATA, VerifyFlags::LOW_S, VerifyFlags::MINIMALIF] {
+            assert!(!segwit.has(*flag));
+            assert!(VerifyFlags::standard().has(*flag));
+        }
+
+        let regtest = Params::new(Network::Regtest);
+        let flags = VerifyFlags::consensus(&regtest, 1300);
+        assert!(flags.has(VerifyFlags::DERSIG | VerifyFlags::NULLDUMMY | VerifyFlags::CHECKSEQUENCEVERIFY));
+        assert!(!flags.has(VerifyFlags::CHECKLOCKTIMEVERIFY));
+
+        // Everything but SIGPUSHONLY, which is checked separately, is standard.
+        let standard = VerifyFlags::standard();
+        assert_eq!(standard.as_u32(), 0x1fffdf);
+        assert!(standard.has(VerifyFlags::consensus(&params, 700_000)));
+        assert!(!standard.has(VerifyFlags::SIGPUSHONLY));
+        let mut flags = standard;
+        assert!(!flags.remove(VerifyFlags::TAPROOT).has(VerifyFlags::TAPROOT));
+        assert!(!flags.remove(VerifyFlags::TAPROOT).has(VerifyFlags::TAPROOT));
+        assert_eq!(flags | VerifyFlags::TAPROOT, standard);
+    }
+
+    #[test]
+    fn script_check_static() {
+        assert_eq!(Script::new().check_static(), Ok(()));
+        // OP_IF OP_1 OP_ELSE OP_NOTIF OP_ENDIF OP_ENDIF
+        assert_eq!(hex_script!("635167646868").check_static(), Ok(()));
+        assert_eq!(hex_script!("6351").check_static(), Err(ScriptError::UnbalancedConditional));
+        assert_eq!(hex_script!("5168").check_static(), Err(ScriptError::UnbalancedConditional));
+        assert_eq!(hex_script!("5167").check_static(), Err(ScriptError::UnbalancedConditional));
+        assert_eq!(hex_script!("636867").check_static(), Err(ScriptError::UnbalancedConditional));
+
+        // Disabled and invalid opcodes fail even in unexecuted branches.
+        assert_eq!(hex_script!("00637e68").check_static(), Err(ScriptError::DisabledOpcode));
+        assert_eq!(hex_script!("0063656868").check_static(), Err(ScriptError::BadOpcode));
+        assert_eq!(hex_script!("4c05aa").check_static(), Err(ScriptError::BadOpcode));
+
+        assert_eq!(Builder::new().push_slice(&[0; 520]).into_script().check_static(), Ok(()));
+        assert_eq!(Builder::new().push_slice(&[0; 521]).into_script().check_static(), Err(ScriptError::PushSize));
+        // Pushes do not count towards the opcode limit.
+        let mut nops = vec![0x51; 300];
+        nops.extend(vec![0x61; 201]);
+        assert_eq!(Script::from(nops.clone()).check_static(), Ok(()));
+        nops.push(0x61);
+        assert_eq!(Script::from(nops).check_static(), Err(ScriptError::OpCount));
+        assert_eq!(Script::from(vec![0x51; 10_001]).check_static(), Err(ScriptError::ScriptSize));
+
+        assert_eq!(ScriptError::UnbalancedConditional.to_string(), "invalid OP_IF construction");
+    }
+
+    #[test]
+    fn script_push_int() {
+        use blockdata::opcodes::all::*;
+
+        let push = |n: i64| Builder::new().push_int(n).into_script().into_bytes();
+        assert_eq!(push(0), vec![OP_PUSHBYTES_0.into_u8()]);
+        assert_eq!(push(-1), vec![OP_PUSHNUM_NEG1.into_u8()]);
+        assert_eq!(push(1), vec![OP_PUSHNUM_1.into_u8()]);
+        assert_eq!(push(16), vec![OP_PUSHNUM_16.into_u8()]);
+        for n in 1..=16 {
+            let opcode = opcodes::All::from(push(n)[0]);
+            assert_eq!(opcode.push_value(), Some(n));
+        }
+
+        // Everything else is a minimal number push.
+        assert_eq!(push(17), vec![0x01, 0x11]);
+        assert_eq!(push(-2), vec![0x01, 0x82]);
+        assert_eq!(push(-16), vec![0x01, 0x90]);
+        assert_eq!(push(128), vec![0x02, 0x80, 0x00]);
+        assert_eq!(push(0x7fffffff), vec![0x04, 0xff, 0xff, 0xff, 0x7f]);
+        assert_eq!(push(i64::max_value()), vec![0x08, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]);
+        assert_eq!(push(i64::min_value()), vec![0x09, 0, 0, 0, 0, 0, 0, 0, 0x80, 0x80]);
+        for &n in &[17, -2, 1000, -1000, 1 << 40, i64::max_value()] {
+            assert_eq!(scriptnum::decode(&push(n)[1..], 8), Ok(n));
+        }
+    }
+
     #[test]
     fn script_builder() {
         // from txid 3bb5e6434c11fb93f64574af5d116736510717f2c595eb45b52c28e31622dfff which was in my mempool when I wrote the test
@@ -1038,18 +1888,18 @@ mod test {
 
     #[test]
     fn scriptint_round_trip() {
-        assert_eq!(build_scriptint(-1), vec![0x81]);
-        assert_eq!(build_scriptint(255), vec![255, 0]);
-        assert_eq!(build_scriptint(256), vec![0, 1]);
-        assert_eq!(build_scriptint(257), vec![1, 1]);
-        assert_eq!(build_scriptint(511), vec![255, 1]);
+        assert_eq!(scriptnum::encode(-1), vec![0x81]);
+        assert_eq!(scriptnum::encode(255), vec![255, 0]);
+        assert_eq!(scriptnum::encode(256), vec![0, 1]);
+        assert_eq!(scriptnum::encode(257), vec![1, 1]);
+        assert_eq!(scriptnum::encode(511), vec![255, 1]);
         for &i in [10, 100, 255, 256, 1000, 10000, 25000, 200000, 5000000, 1000000000,
                              (1 << 31) - 1, -((1 << 31) - 1)].iter() {
-            assert_eq!(Ok(i), read_scriptint(&build_scriptint(i)));
-            assert_eq!(Ok(-i), read_scriptint(&build_scriptint(-i)));
+            assert_eq!(Ok(i), read_scriptint(&scriptnum::encode(i)));
+            assert_eq!(Ok(-i), read_scriptint(&scriptnum::encode(-i)));
         }
-        assert!(read_scriptint(&build_scriptint(1 << 31)).is_err());
-        assert!(read_scriptint(&build_scriptint(-(1 << 31))).is_err());
+        assert!(read_scriptint(&scriptnum::encode(1 << 31)).is_err());
+        assert!(read_scriptint(&scriptnum::encode(-(1 << 31))).is_err());
     }
 
     #[test]
@@ -1067,6 +1917,15 @@ mod test {
         // p2pkhash
         assert_eq!(hex_script!("76a914ee61d57ab51b9d212335b1dba62794ac20d2bcf988ac").is_provably_unspendable(), false);
         assert_eq!(hex_script!("6aa9149eb21980dc9d413d8eac27314938b9da920ee53e87").is_provably_unspendable(), true);
+        // OP_RETURN data carrier
+        assert!(Script::new_op_return(&[0xde, 0xad, 0xbe, 0xef]).is_provably_unspendable());
+        assert!(!Script::new().is_provably_unspendable());
+
+        // oversized scripts can never be executed, regardless of their content
+        let max_size = Script::from(vec![opcodes::all::OP_NOP.into_u8(); MAX_SCRIPT_SIZE]);
+        assert!(!max_size.is_provably_unspendable());
+        let oversized = Script::from(vec![opcodes::all::OP_NOP.into_u8(); MAX_SCRIPT_SIZE + 1]);
+        assert!(oversized.is_provably_unspendable());
     }
 
     #[test]
@@ -1202,6 +2061,55 @@ mod test {
         assert_eq!(v_nonmin_alt.unwrap(), slop_v_nonmin_alt.unwrap());
     }
 
+    #[test]
+    fn script_witness_program() {
+        let p2wpkh = hex_script!("0014751e76e8199196d454941c45d1b3a323f1433bd6");
+        assert_eq!(p2wpkh.witness_program(),
+                   Some((0, &Vec::from_hex("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap()[..])));
+
+        let p2wsh = hex_script!("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262");
+        let (version, program) = p2wsh.witness_program().unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(program.len(), 32);
+        assert_eq!(program, &p2wsh[2..]);
+
+        let p2tr = hex_script!("5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c");
+        assert_eq!(p2tr.witness_program().map(|(v, p)| (v, p.len())), Some((1, 32)));
+        let v16 = hex_script!("6002751e");
+        assert_eq!(v16.witness_program(), Some((16, &[0x75, 0x1e][..])));
+
+        // Non-witness scripts
+        let p2pkh = hex_script!("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac");
+        assert_eq!(p2pkh.witness_program(), None);
+        assert_eq!(hex_script!("0014751e76e8199196d454941c45d1b3a323f1433b").witness_program(), None);
+        assert_eq!(Script::new().witness_program(), None);
+    }
+
+    #[test]
+    fn script_instructions_data() {
+        // P2PKH scriptSig: <sig> <pubkey>
+        let script_sig = hex_script!("493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52");
+        let data = script_sig.instructions_data().unwrap();
+        assert_eq!(data.len(), 2);
+        assert_eq!(data[0].to_vec(), Vec::from_hex("3046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c01").unwrap());
+        assert_eq!(data[1].to_vec(), Vec::from_hex("033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52").unwrap());
+        assert!(PublicKey::from_slice(data[1]).is_ok());
+
+        // opcodes are skipped, OP_0 and OP_PUSHDATA variants are data pushes
+        let script = Builder::new()
+            .push_opcode(opcodes::all::OP_PUSHBYTES_0)
+            .push_int(5)
+            .push_slice(&[0xab; 80])
+            .push_slice(&[0xcd; 300])
+            .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+            .into_script();
+        let data = script.instructions_data().unwrap();
+        assert_eq!(data, vec![&[][..], &[0xab; 80][..], &[0xcd; 300][..]]);
+
+        assert!(Script::new().instructions_data().unwrap().is_empty());
+        assert_eq!(hex_script!("4c05abcd").instructions_data(), Err(Error::EarlyEndOfScript));
+    }
+
 	#[test]
     fn script_ord() {
         let script_1 = Builder::new().push_slice(&[1,2,3,4]).into_script();
@@ -0,0 +1,164 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Script numbers
+//!
+//! Encoding and decoding of the numbers which script arithmetic operates on,
+//! as `CScriptNum` in Bitcoin Core: little-endian sign-magnitude byte vectors
+//! whose most significant bit is the sign, with zero encoded as the empty
+//! vector.
+//!
+
+use std::{error, fmt};
+
+/// Largest size in bytes of a number read by arithmetic opcodes.
+pub const DEFAULT_MAX_SIZE: usize = 4;
+
+/// An error decoding a script number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The encoding is longer than the allowed size, or than 8 bytes.
+    Overflow,
+    /// The encoding has unnecessary trailing zero bytes, or is negative zero.
+    NonMinimal,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Overflow => f.write_str("script number overflow"),
+            Error::NonMinimal => f.write_str("non-minimally encoded script number"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Encodes `n` minimally.
+pub fn encode(n: i64) -> Vec<u8> {
+    let mut v = vec![];
+    let mut abs = n.wrapping_abs() as u64;
+    while abs > 0 {
+        v.push(abs as u8);
+        abs >>= 8;
+    }
+    // If the most significant byte has its top bit set, an extra byte is
+    // needed to hold the sign.
+    match v.last().cloned() {
+        Some(last) if last & 0x80 != 0 => v.push(if n < 0 { 0x80 } else { 0 }),
+        Some(last) if n < 0 => {
+            let len = v.len();
+            v[len - 1] = last | 0x80;
+        }
+        _ => {}
+    }
+    v
+}
+
+/// Decodes a minimally encoded number of at most `max_size` bytes, usually
+/// [`DEFAULT_MAX_SIZE`]. At most 8 bytes can be decoded.
+///
+/// [`DEFAULT_MAX_SIZE`]: constant.DEFAULT_MAX_SIZE.html
+pub fn decode(v: &[u8], max_size: usize) -> Result<i64, Error> {
+    let n = decode_lax(v, max_size)?;
+    if let Some((last, rest)) = v.split_last() {
+        // The last byte may only be zero, apart from the sign bit, if the sign
+        // bit does not fit into the previous byte. This also rules out
+        // negative zero.
+        if *last & 0x7f == 0 && rest.last().map_or(true, |b| *b & 0x80 == 0) {
+            return Err(Error::NonMinimal);
+        }
+    }
+    Ok(n)
+}
+
+/// Decodes a number of at most `max_size` bytes like [`decode`], but also
+/// accepts non-minimal encodings, which decode to the same number as their
+/// minimal one. Negative zero decodes to zero.
+///
+/// [`decode`]: fn.decode.html
+pub fn decode_lax(v: &[u8], max_size: usize) -> Result<i64, Error> {
+    if v.len() > max_size || v.len() > 8 {
+        return Err(Error::Overflow);
+    }
+    let (last, rest) = match v.split_last() {
+        Some(split) => split,
+        None => return Ok(0),
+    };
+    let magnitude = rest.iter().rev().fold(i64::from(*last & 0x7f), |acc, b| acc << 8 | i64::from(*b));
+    if *last & 0x80 != 0 {
+        Ok(-magnitude)
+    } else {
+        Ok(magnitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, decode_lax, encode, Error, DEFAULT_MAX_SIZE};
+
+    #[test]
+    fn encode_test() {
+        assert!(encode(0).is_empty());
+        assert_eq!(encode(1), vec![0x01]);
+        assert_eq!(encode(-1), vec![0x81]);
+        assert_eq!(encode(127), vec![0x7f]);
+        assert_eq!(encode(128), vec![0x80, 0x00]);
+        assert_eq!(encode(-128), vec![0x80, 0x80]);
+        assert_eq!(encode(255), vec![0xff, 0x00]);
+        assert_eq!(encode(256), vec![0x00, 0x01]);
+        assert_eq!(encode(-32768), vec![0x00, 0x80, 0x80]);
+        assert_eq!(encode(i64::max_value()), vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]);
+        assert_eq!(encode(-i64::max_value()), vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(encode(i64::min_value()), vec![0, 0, 0, 0, 0, 0, 0, 0x80, 0x80]);
+    }
+
+    #[test]
+    fn decode_test() {
+        assert_eq!(decode(&[], DEFAULT_MAX_SIZE), Ok(0));
+        assert_eq!(decode(&[0x81], DEFAULT_MAX_SIZE), Ok(-1));
+        assert_eq!(decode(&[0x7f], DEFAULT_MAX_SIZE), Ok(127));
+        assert_eq!(decode(&[0x80, 0x00], DEFAULT_MAX_SIZE), Ok(128));
+        assert_eq!(decode(&[0x80, 0x80], DEFAULT_MAX_SIZE), Ok(-128));
+        assert_eq!(decode(&[0xff, 0xff, 0xff, 0x7f], DEFAULT_MAX_SIZE), Ok(0x7fffffff));
+
+        for &n in &[0, 1, -1, 127, 128, -255, 0x7fff, -0x8000, 0x7fffffff, -0x7fffffff] {
+            assert_eq!(decode(&encode(n), DEFAULT_MAX_SIZE), Ok(n));
+        }
+        for &n in &[0x80000000, 1 << 40, -i64::max_value(), i64::max_value()] {
+            assert_eq!(decode(&encode(n), DEFAULT_MAX_SIZE), Err(Error::Overflow));
+            assert_eq!(decode(&encode(n), 8), Ok(n));
+        }
+        assert_eq!(decode(&encode(i64::min_value()), 9), Err(Error::Overflow));
+
+        // Non-minimal encodings, including negative zero.
+        assert_eq!(decode(&[0x00], DEFAULT_MAX_SIZE), Err(Error::NonMinimal));
+        assert_eq!(decode(&[0x80], DEFAULT_MAX_SIZE), Err(Error::NonMinimal));
+        assert_eq!(decode(&[0x01, 0x00], DEFAULT_MAX_SIZE), Err(Error::NonMinimal));
+        assert_eq!(decode(&[0x7f, 0x80], DEFAULT_MAX_SIZE), Err(Error::NonMinimal));
+        assert_eq!(decode(&[0xff, 0x00, 0x00], DEFAULT_MAX_SIZE), Err(Error::NonMinimal));
+        assert_eq!(decode(&[0x01, 0x02, 0x03, 0x04, 0x05], DEFAULT_MAX_SIZE), Err(Error::Overflow));
+    }
+
+    #[test]
+    fn decode_lax_test() {
+        for &n in &[0, 1, -1, 127, 128, -255, 0x7fffffff, -0x7fffffff] {
+            assert_eq!(decode_lax(&encode(n), DEFAULT_MAX_SIZE), Ok(n));
+        }
+        assert_eq!(decode_lax(&[0x00], DEFAULT_MAX_SIZE), Ok(0));
+        assert_eq!(decode_lax(&[0x80], DEFAULT_MAX_SIZE), Ok(0));
+        assert_eq!(decode_lax(&[0x01, 0x00], DEFAULT_MAX_SIZE), Ok(1));
+        assert_eq!(decode_lax(&[0x7f, 0x80], DEFAULT_MAX_SIZE), Ok(-127));
+        assert_eq!(decode_lax(&[0xff, 0x00, 0x00, 0x00, 0x00], DEFAULT_MAX_SIZE), Err(Error::Overflow));
+    }
+}
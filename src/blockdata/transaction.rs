@@ -23,18 +23,26 @@
 //! This module provides the structures and functions needed to support transactions.
 //!
 
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
 use std::default::Default;
+use std::ops::Range;
 use std::{error, fmt, io, str};
 
 use hashes::{self, Hash, sha256d};
 use hashes::hex::FromHex;
 
+use util;
+use util::amount::Denomination;
 use util::endian;
-use blockdata::constants::WITNESS_SCALE_FACTOR;
+use blockdata::constants::{MAX_BLOCK_WEIGHT, MAX_COINBASE_SCRIPTSIG_SIZE, MIN_COINBASE_SCRIPTSIG_SIZE, WITNESS_SCALE_FACTOR};
 #[cfg(feature="bitcoinconsensus")] use blockdata::script;
-use blockdata::script::Script;
+use blockdata::script::{Instruction, Script};
 use consensus::{encode, Decodable, Encodable};
+use consensus::params::Params;
 use hash_types::{SigHash, Txid, Wtxid};
+#[cfg(feature = "serde_json")] use network::constants::Network;
+#[cfg(feature = "serde_json")] use util::amount::Amount;
 use VarInt;
 
 /// A reference to a transaction output
@@ -172,6 +180,137 @@ impl ::std::str::FromStr for OutPoint {
     }
 }
 
+/// The sequence number (`nSequence`) of a transaction input.
+///
+/// Besides marking an input as final, the value signals BIP125 replaceability
+/// and, in transactions of version 2 and above, encodes a BIP68 relative lock
+/// time.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Sequence(u32);
+
+impl Sequence {
+    /// The final sequence number: no relative lock time, no replaceability,
+    /// and the transaction lock time is ignored if all inputs use it.
+    pub const MAX: Sequence = Sequence(0xFFFFFFFF);
+    /// Zero, a relative lock time of zero blocks which also signals
+    /// replaceability.
+    pub const ZERO: Sequence = Sequence(0);
+    /// The largest sequence number which enables the transaction lock time
+    /// without signalling replaceability.
+    pub const ENABLE_LOCKTIME_NO_RBF: Sequence = Sequence(0xFFFFFFFE);
+    /// The largest sequence number which signals replaceability, and enables
+    /// the transaction lock time without a relative lock time.
+    pub const ENABLE_RBF_NO_LOCKTIME: Sequence = Sequence(0xFFFFFFFD);
+
+    /// If set, the value is not a relative lock time (BIP68).
+    const LOCK_TIME_DISABLE_FLAG: u32 = 1 << 31;
+    /// If set, a relative lock time is in units of 512 seconds, otherwise in
+    /// blocks (BIP68).
+    const LOCK_TYPE_FLAG: u32 = 1 << 22;
+
+    /// Wraps the `nSequence` value as found in a serialized transaction.
+    pub fn from_consensus(sequence: u32) -> Sequence {
+        Sequence(sequence)
+    }
+
+    /// Returns the `nSequence` value as found in a serialized transaction.
+    pub fn to_consensus(self) -> u32 {
+        self.0
+    }
+
+    /// Creates a relative lock time of `height` blocks.
+    pub fn from_height(height: u16) -> Sequence {
+        Sequence(height as u32)
+    }
+
+    /// Creates a relative lock time of `intervals` times 512 seconds.
+    pub fn from_512_second_intervals(intervals: u16) -> Sequence {
+        Sequence(Sequence::LOCK_TYPE_FLAG | intervals as u32)
+    }
+
+    /// Creates a relative lock time of at most `seconds`, rounded down to a
+    /// multiple of 512 seconds. Returns `None` if it does not fit.
+    pub fn from_seconds_floor(seconds: u32) -> Option<Sequence> {
+        let intervals = seconds / 512;
+        if intervals > u16::max_value() as u32 {
+            None
+        } else {
+            Some(Sequence::from_512_second_intervals(intervals as u16))
+        }
+    }
+
+    /// Creates a relative lock time of at least `seconds`, rounded up to a
+    /// multiple of 512 seconds. Returns `None` if it does not fit.
+    pub fn from_seconds_ceil(seconds: u32) -> Option<Sequence> {
+        let intervals = seconds / 512 + if seconds % 512 == 0 { 0 } else { 1 };
+        if intervals > u16::max_value() as u32 {
+            None
+        } else {
+            Some(Sequence::from_512_second_intervals(intervals as u16))
+        }
+    }
+
+    /// Checks whether this is the final sequence number, [`Sequence::MAX`].
+    ///
+    /// [`Sequence::MAX`]: #associatedconstant.MAX
+    pub fn is_final(self) -> bool {
+        self == Sequence::MAX
+    }
+
+    /// Checks whether the input signals replaceability (BIP125).
+    pub fn is_rbf(self) -> bool {
+        self < Sequence::ENABLE_LOCKTIME_NO_RBF
+    }
+
+    /// Checks whether the value encodes a relative lock time (BIP68). This
+    /// only has an effect in transactions of version 2 and above.
+    pub fn is_relative_lock_time(self) -> bool {
+        self.0 & Sequence::LOCK_TIME_DISABLE_FLAG == 0
+    }
+
+    /// Checks whether the value encodes a relative lock time in blocks.
+    pub fn is_height_locked(self) -> bool {
+        self.is_relative_lock_time() && self.0 & Sequence::LOCK_TYPE_FLAG == 0
+    }
+
+    /// Checks whether the value encodes a relative lock time in units of 512
+    /// seconds.
+    pub fn is_time_locked(self) -> bool {
+        self.is_relative_lock_time() && self.0 & Sequence::LOCK_TYPE_FLAG != 0
+    }
+}
+
+impl Default for Sequence {
+    fn default() -> Sequence {
+        Sequence::MAX
+    }
+}
+
+impl fmt::Display for Sequence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#010x}", self.0)
+    }
+}
+
+impl fmt::LowerHex for Sequence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl Encodable for Sequence {
+    fn consensus_encode<S: io::Write>(&self, s: S) -> Result<usize, io::Error> {
+        self.0.consensus_encode(s)
+    }
+}
+
+impl Decodable for Sequence {
+    fn consensus_decode<D: io::Read>(d: D) -> Result<Self, encode::Error> {
+        Ok(Sequence(Decodable::consensus_decode(d)?))
+    }
+}
+
 /// A transaction input, which defines old coins to be consumed
 #[derive(Clone, PartialEq, Eq, Debug, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -181,11 +320,11 @@ pub struct TxIn {
     /// The script which pushes values on the stack which will cause
     /// the referenced output's script to accept
     pub script_sig: Script,
-    /// The sequence number, which suggests to miners which of two
-    /// conflicting transactions should be preferred, or 0xFFFFFFFF
-    /// to ignore this feature. This is generally never used since
-    /// the miner behaviour cannot be enforced.
-    pub sequence: u32,
+    /// The sequence number, which signals replaceability and relative lock
+    /// times, or [`Sequence::MAX`] to ignore these features.
+    ///
+    /// [`Sequence::MAX`]: struct.Sequence.html#associatedconstant.MAX
+    pub sequence: Sequence,
     /// Witness data: an array of byte-arrays.
     /// Note that this field is *not* (de)serialized with the rest of the TxIn in
     /// Encodable/Decodable, as it is (de)serialized at the end of the full
@@ -199,7 +338,7 @@ impl Default for TxIn {
         TxIn {
             previous_output: OutPoint::default(),
             script_sig: Script::new(),
-            sequence: u32::max_value(),
+            sequence: Sequence::MAX,
             witness: Vec::new(),
         }
     }
@@ -215,6 +354,17 @@ pub struct TxOut {
     pub script_pubkey: Script
 }
 
+impl TxOut {
+    /// Returns the key outputs are ordered by in BIP69: the value, then the
+    /// bytes of the script pubkey. [`Transaction::sort_bip69`] sorts outputs
+    /// in this order.
+    ///
+    /// [`Transaction::sort_bip69`]: struct.Transaction.html#method.sort_bip69
+    pub fn bip69_sort_key(&self) -> (u64, Vec<u8>) {
+        (self.value, self.script_pubkey.to_bytes())
+    }
+}
+
 // This is used as a "null txout" in consensus signing code
 impl Default for TxOut {
     fn default() -> TxOut {
@@ -267,6 +417,13 @@ pub struct Transaction {
 }
 
 impl Transaction {
+    /// Turns the transaction into a [`TransactionBuilder`] for editing.
+    ///
+    /// [`TransactionBuilder`]: struct.TransactionBuilder.html
+    pub fn into_builder(self) -> TransactionBuilder {
+        TransactionBuilder { tx: self }
+    }
+
     /// Computes a "normalized TXID" which does not include any signatures.
     /// This gives a way to identify a transaction that is "the same" as
     /// another in the sense of having same inputs and outputs.
@@ -311,12 +468,16 @@ impl Transaction {
     /// because internally 4 bytes are being hashed, even though only lowest byte
     /// is appended to signature in a transaction.
     ///
-    /// *Warning* This does NOT attempt to support OP_CODESEPARATOR. In general this would require
-    /// evaluating `script_pubkey` to determine which separators get evaluated and which don't,
-    /// which we don't have the information to determine.
+    /// Like in Bitcoin Core, any OP_CODESEPARATOR is removed from `script_pubkey`, see
+    /// [`Script::without_code_separators`]. The script is not cut at the last executed
+    /// separator though: this would require evaluating `script_pubkey` to determine which
+    /// separators get evaluated and which don't, which we don't have the information to
+    /// determine, so callers have to pass the part of the script after it.
     ///
     /// # Panics Panics if `input_index` is greater than or equal to `self.input.len()`
     ///
+    /// [`Script::without_code_separators`]: ../script/struct.Script.html#method.without_code_separators
+    ///
     pub fn encode_signing_data_to<Write: io::Write, U: Into<u32>>(
         &self,
         mut writer: Write,
@@ -338,6 +499,7 @@ impl Transaction {
             return Ok(());
         }
 
+        let script_code = script_pubkey.without_code_separators();
         // Build tx to sign
         let mut tx = Transaction {
             version: self.version,
@@ -349,7 +511,7 @@ impl Transaction {
         if anyone_can_pay {
             tx.input = vec![TxIn {
                 previous_output: self.input[input_index].previous_output,
-                script_sig: script_pubkey.clone(),
+                script_sig: script_code,
                 sequence: self.input[input_index].sequence,
                 witness: vec![],
             }];
@@ -358,8 +520,8 @@ impl Transaction {
             for (n, input) in self.input.iter().enumerate() {
                 tx.input.push(TxIn {
                     previous_output: input.previous_output,
-                    script_sig: if n == input_index { script_pubkey.clone() } else { Script::new() },
-                    sequence: if n != input_index && (sighash == SigHashType::Single || sighash == SigHashType::None) { 0 } else { input.sequence },
+                    script_sig: if n == input_index { script_code.clone() } else { Script::new() },
+                    sequence: if n != input_index && (sighash == SigHashType::Single || sighash == SigHashType::None) { Sequence::ZERO } else { input.sequence },
                     witness: vec![],
                 });
             }
@@ -389,10 +551,10 @@ impl Transaction {
     /// ECDSA signer, the SigHashType appended to the resulting sig, and a
     /// script written around this, but this is the general (and hard) part.
     ///
-    /// *Warning* This does NOT attempt to support OP_CODESEPARATOR. In general
-    /// this would require evaluating `script_pubkey` to determine which separators
-    /// get evaluated and which don't, which we don't have the information to
-    /// determine.
+    /// OP_CODESEPARATORs are removed from `script_pubkey`, but it is not cut at
+    /// the last executed one, see [`encode_signing_data_to`].
+    ///
+    /// [`encode_signing_data_to`]: #method.encode_signing_data_to
     ///
     /// # Panics
     /// Panics if `input_index` is greater than or equal to `self.input.len()`
@@ -424,6 +586,48 @@ impl Transaction {
         self.get_scaled_size(1)
     }
 
+    /// Gets the "virtual size" of this transaction, as defined by BIP141: the weight divided by 4,
+    /// rounded up. This is the size used for fee rate calculations.
+    #[inline]
+    pub fn get_vsize(&self) -> usize {
+        (self.get_weight() + WITNESS_SCALE_FACTOR - 1) / WITNESS_SCALE_FACTOR
+    }
+
+    /// Computes the fee rate of this transaction per virtual byte, expressed
+    /// in `denom`, given the values in satoshis of the outputs its inputs
+    /// spend, in input order.
+    ///
+    /// The fee is computed exactly, however large the values are, and only
+    /// the final rate is rounded to a float. It is negative if the outputs
+    /// are worth more than the inputs.
+    ///
+    /// # Panics
+    ///
+    /// If `input_values` does not have one value per input.
+    pub fn fee_rate_in(&self, input_values: &[u64], denom: Denomination) -> f64 {
+        assert_eq!(input_values.len(), self.input.len(), "one value is needed per input");
+        let input_value: i128 = input_values.iter().map(|&v| v as i128).sum();
+        let output_value: i128 = self.output.iter().map(|o| o.value as i128).sum();
+        let fee = (input_value - output_value) as f64;
+        fee / self.get_vsize() as f64 * 10f64.powi(denom.precision())
+    }
+
+    /// Splits the weight of this transaction into its parts: the bytes which
+    /// are serialized without witnesses, and count four weight units each, and
+    /// the witness bytes, including the segwit marker and flag, which count
+    /// one each.
+    pub fn weight_breakdown(&self) -> WeightBreakdown {
+        let weight = self.get_weight();
+        let size = self.get_size();
+        // weight = 4 * base + witness and size = base + witness
+        let base_bytes = (weight - size) / (WITNESS_SCALE_FACTOR - 1);
+        WeightBreakdown {
+            base_bytes,
+            witness_bytes: size - base_bytes,
+            weight,
+        }
+    }
+
     /// Internal utility function for get_{size,weight}
     fn get_scaled_size(&self, scale_factor: usize) -> usize {
         let mut input_weight = 0;
@@ -483,14 +687,487 @@ impl Transaction {
         self.input.len() == 1 && self.input[0].previous_output.is_null()
     }
 
+    /// Returns the range of bytes in the scriptSig of a coinbase which follow
+    /// its BIP34 height push. Mining pools put their extranonce there, so it
+    /// can be replaced without parsing the script again.
+    ///
+    /// Returns `None` if this is not a coinbase, or its scriptSig does not
+    /// start with a minimal data push.
+    pub fn coinbase_extranonce_range(&self) -> Option<Range<usize>> {
+        if !self.is_coin_base() {
+            return None;
+        }
+        let script_sig = &self.input[0].script_sig;
+        let push_len = match script_sig.instructions_minimal().next() {
+            Some(Ok(Instruction::PushBytes(data))) => data.len(),
+            _ => return None,
+        };
+        let header_len = match script_sig[0] {
+            0x4c => 2,
+            0x4d => 3,
+            0x4e => 5,
+            _ => 1,
+        };
+        Some(header_len + push_len..script_sig.len())
+    }
+
     /// Returns `true` if the transaction itself opted in to be BIP-125-replaceable (RBF). This
     /// **does not** cover the case where a transaction becomes replaceable due to ancestors being
     /// RBF.
     pub fn is_explicitly_rbf(&self) -> bool {
-        self.input.iter().any(|input| input.sequence < (0xffffffff - 1))
+        self.input.iter().any(|input| input.sequence.is_rbf())
+    }
+
+    /// Checks the standardness rules for the transaction's scriptSigs: each has
+    /// to be push-only and no larger than [`Params::max_standard_scriptsig_size`].
+    ///
+    /// [`Params::max_standard_scriptsig_size`]: ../../consensus/params/struct.Params.html#structfield.max_standard_scriptsig_size
+    pub fn script_sigs_standard(&self, params: &Params) -> bool {
+        self.input.iter().all(|input| {
+            input.script_sig.is_push_only() && input.script_sig.scriptsig_size_standard(params)
+        })
+    }
+
+    /// Checks that the scriptSig of a coinbase is between
+    /// [`MIN_COINBASE_SCRIPTSIG_SIZE`] and [`MAX_COINBASE_SCRIPTSIG_SIZE`]
+    /// bytes long. Other transactions always pass.
+    ///
+    /// [`MIN_COINBASE_SCRIPTSIG_SIZE`]: ../constants/constant.MIN_COINBASE_SCRIPTSIG_SIZE.html
+    /// [`MAX_COINBASE_SCRIPTSIG_SIZE`]: ../constants/constant.MAX_COINBASE_SCRIPTSIG_SIZE.html
+    pub fn check_coinbase_scriptsig(&self) -> Result<(), util::Error> {
+        if !self.is_coin_base() {
+            return Ok(());
+        }
+        let len = self.input[0].script_sig.len();
+        if len < MIN_COINBASE_SCRIPTSIG_SIZE || len > MAX_COINBASE_SCRIPTSIG_SIZE {
+            return Err(util::Error::BadCoinbaseLength);
+        }
+        Ok(())
+    }
+
+    /// Checks whether two inputs spend the same output, which makes the
+    /// transaction invalid.
+    pub fn has_duplicate_inputs(&self) -> bool {
+        let mut seen = HashSet::with_capacity(self.input.len());
+        !self.input.iter().all(|input| seen.insert(input.previous_output))
+    }
+
+    /// Checks whether any input has witness data.
+    pub fn has_witness(&self) -> bool {
+        self.input.iter().any(|input| !input.witness.is_empty())
+    }
+
+    /// Returns a copy of the transaction with all witnesses removed, which
+    /// serializes in the legacy format understood by pre-segwit peers.
+    ///
+    /// The exception are transactions without inputs, which are always
+    /// serialized in the segwit format, since in the legacy format their empty
+    /// input count would be read as the segwit marker.
+    pub fn strip_witness(&self) -> Transaction {
+        let mut tx = self.clone();
+        for input in &mut tx.input {
+            input.witness.clear();
+        }
+        tx
+    }
+
+    /// Checks whether any input has a scriptSig which does more than push data.
+    ///
+    /// Such scriptSigs can be changed by anyone relaying the transaction
+    /// without invalidating it, which changes the txid.
+    pub fn has_non_push_only_scriptsig(&self) -> bool {
+        self.input.iter().any(|input| !input.script_sig.is_push_only())
+    }
+
+    /// Checks whether the txid can not be changed by third parties
+    /// malleating the inputs.
+    ///
+    /// This is the case if every input is a segwit spend: its signatures are
+    /// in the witness, and its scriptSig is empty or, for a P2SH-wrapped
+    /// program, a single push. Legacy inputs are never stable, since even a
+    /// push-only scriptSig contains signatures which can be re-encoded.
+    pub fn is_txid_stable(&self) -> bool {
+        self.input.iter().all(|input| {
+            let mut instructions = input.script_sig.instructions();
+            !input.witness.is_empty() && match (instructions.next(), instructions.next()) {
+                (None, _) => true,
+                (Some(Ok(Instruction::PushBytes(_))), None) => true,
+                _ => false,
+            }
+        })
+    }
+
+    /// Sorts the inputs and outputs into the canonical order of BIP69.
+    ///
+    /// Inputs are sorted by the txid they spend, compared as displayed in
+    /// hex, then by output index. Outputs are sorted by value, then by the
+    /// bytes of their script pubkey. Since reordering changes what signatures
+    /// commit to, this should be done before signing.
+    pub fn sort_bip69(&mut self) {
+        self.input.sort_by(|a, b| {
+            let (a, b) = (&a.previous_output, &b.previous_output);
+            a.txid[..].iter().rev().cmp(b.txid[..].iter().rev()).then(a.vout.cmp(&b.vout))
+        });
+        self.output.sort_by(|a, b| a.value.cmp(&b.value).then_with(|| a.script_pubkey.cmp(&b.script_pubkey)));
+    }
+
+    /// Pairs each input with the output it spends, looked up in `prevouts`.
+    ///
+    /// Inputs whose previous output is not in `prevouts` are paired with `None`.
+    pub fn inputs_with_prevouts<'a>(
+        &'a self,
+        prevouts: &'a HashMap<OutPoint, TxOut>,
+    ) -> impl Iterator<Item = (&'a TxIn, Option<&'a TxOut>)> + 'a {
+        self.input.iter().map(move |input| (input, prevouts.get(&input.previous_output)))
+    }
+
+    /// Returns a compact one-line description of the transaction, suitable for logging.
+    ///
+    /// The summary contains the txid, version, number of inputs and outputs, the total
+    /// output value in satoshis and the locktime.
+    pub fn summary(&self) -> String {
+        let total_out = self.output.iter().fold(0u64, |acc, out| acc.saturating_add(out.value));
+        format!(
+            "txid {} version {} inputs {} outputs {} value {} sat locktime {}",
+            self.txid(), self.version, self.input.len(), self.output.len(), total_out, self.lock_time,
+        )
+    }
+
+    /// Returns the transaction in the JSON format of Bitcoin Core's
+    /// `decoderawtransaction` RPC, with the `reqSigs` and `addresses` fields
+    /// of the output scripts as up to Bitcoin Core 0.21.
+    ///
+    /// Addresses are encoded for `network`. Like Bitcoin Core, no addresses
+    /// are listed for pay-to-pubkey outputs. Unlike Bitcoin Core, none are
+    /// listed for witness v1+ outputs either, as their bech32m (BIP-350)
+    /// encoding is not supported yet.
+    #[cfg(feature = "serde_json")]
+    pub fn to_core_json(&self, network: Network) -> ::serde_json::Value {
+        use hashes::hex::ToHex;
+
+        let vin = self.input.iter().map(|input| {
+            let mut json = if self.is_coin_base() {
+                json!({ "coinbase": input.script_sig.as_bytes().to_hex() })
+            } else {
+                json!({
+                    "txid": input.previous_output.txid.to_hex(),
+                    "vout": input.previous_output.vout,
+                    "scriptSig": {
+                        "asm": input.script_sig.to_core_asm(true),
+                        "hex": input.script_sig.as_bytes().to_hex(),
+                    },
+                })
+            };
+            if !input.witness.is_empty() {
+                json["txinwitness"] = input.witness.iter().map(|w| w.to_hex()).collect();
+            }
+            json["sequence"] = input.sequence.to_consensus().into();
+            json
+        }).collect::<Vec<_>>();
+        let vout = self.output.iter().enumerate().map(|(n, output)| json!({
+            "value": Amount::from_sat(output.value).as_btc(),
+            "n": n,
+            "scriptPubKey": script_pubkey_to_core_json(&output.script_pubkey, network),
+        })).collect::<Vec<_>>();
+
+        json!({
+            "txid": self.txid().to_hex(),
+            "hash": self.wtxid().to_hex(),
+            "version": self.version,
+            "size": self.get_size(),
+            "vsize": self.get_vsize(),
+            "weight": self.get_weight(),
+            "locktime": self.lock_time,
+            "vin": vin,
+            "vout": vout,
+        })
+    }
+}
+
+/// Describes an output script like `ScriptPubKeyToUniv` in Bitcoin Core 0.21,
+/// classifying it as Bitcoin Core's `Solver` does.
+#[cfg(feature = "serde_json")]
+fn script_pubkey_to_core_json(script: &Script, network: Network) -> ::serde_json::Value {
+    use hashes::hex::ToHex;
+    use blockdata::opcodes;
+    use util::address::{Address, Payload};
+    use util::key::PublicKey;
+
+    // A slice of the size of a compressed or uncompressed key, as `CPubKey::ValidSize`.
+    fn valid_key_size(key: &[u8]) -> bool {
+        match key.first() {
+            Some(&0x02) | Some(&0x03) => key.len() == 33,
+            Some(&0x04) | Some(&0x06) | Some(&0x07) => key.len() == 65,
+            _ => false,
+        }
+    }
+    fn small_int(instruction: &Instruction) -> Option<usize> {
+        match *instruction {
+            Instruction::Op(op) if op.into_u8() >= opcodes::all::OP_PUSHNUM_1.into_u8()
+                && op.into_u8() <= opcodes::all::OP_PUSHNUM_16.into_u8() => {
+                Some((op.into_u8() - opcodes::all::OP_PUSHNUM_1.into_u8() + 1) as usize)
+            }
+            _ => None,
+        }
+    }
+    fn multisig(script: &Script) -> Option<(usize, Vec<&[u8]>)> {
+        let instructions = script.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+        if instructions.len() < 3 || instructions[instructions.len() - 1] != Instruction::Op(opcodes::all::OP_CHECKMULTISIG) {
+            return None;
+        }
+        let required = small_int(&instructions[0])?;
+        let count = small_int(&instructions[instructions.len() - 2])?;
+        let keys = instructions[1..instructions.len() - 2].iter().map(|instruction| match *instruction {
+            Instruction::PushBytes(key) if valid_key_size(key) => Some(key),
+            _ => None,
+        }).collect::<Option<Vec<_>>>()?;
+        if keys.len() != count || count < required {
+            return None;
+        }
+        Some((required, keys))
+    }
+
+    let bytes = script.as_bytes();
+    let witness_version = if script.is_witness_program() { Some(bytes[0]) } else { None };
+    let (kind, req_sigs, addresses) = if script.is_p2sh() {
+        ("scripthash", 1, vec![])
+    } else if witness_version == Some(0) && bytes.len() == 22 {
+        ("witness_v0_keyhash", 1, vec![])
+    } else if witness_version == Some(0) && bytes.len() == 34 {
+        ("witness_v0_scripthash", 1, vec![])
+    } else if witness_version == Some(opcodes::all::OP_PUSHNUM_1.into_u8()) && bytes.len() == 34 {
+        ("witness_v1_taproot", 1, vec![])
+    } else if witness_version.map_or(false, |v| v != 0) {
+        ("witness_unknown", 1, vec![])
+    } else if script.is_op_return() && Script::from(bytes[1..].to_vec()).is_push_only() {
+        ("nulldata", 0, vec![])
+    } else if script.is_p2pk() && valid_key_size(&bytes[1..bytes.len() - 1]) {
+        ("pubkey", 0, vec![])
+    } else if script.is_p2pkh() {
+        ("pubkeyhash", 1, vec![])
+    } else if let Some((required, keys)) = multisig(script) {
+        let addresses = keys.iter()
+            .filter_map(|key| PublicKey::from_slice(key).ok())
+            .map(|key| Address::p2pkh(&key, network).to_string())
+            .collect();
+        ("multisig", required, addresses)
+    } else {
+        ("nonstandard", 0, vec![])
+    };
+
+    let mut json = json!({
+        "asm": script.to_core_asm(false),
+        "hex": bytes.to_hex(),
+        "type": kind,
+    });
+    let addresses = match kind {
+        "multisig" => addresses,
+        "scripthash" | "pubkeyhash" | "witness_v0_keyhash" | "witness_v0_scripthash" => {
+            let payload = Payload::from_script(script).expect("classified as an address type");
+            vec![Address { payload, network }.to_string()]
+        }
+        _ => vec![],
+    };
+    if !addresses.is_empty() {
+        json["reqSigs"] = req_sigs.into();
+        json["addresses"] = addresses.into();
+    }
+    json
+}
+
+/// Where the weight of a transaction comes from, see [`Transaction::weight_breakdown`].
+///
+/// [`Transaction::weight_breakdown`]: struct.Transaction.html#method.weight_breakdown
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct WeightBreakdown {
+    /// Size of the transaction serialized without witnesses.
+    pub base_bytes: usize,
+    /// Size of the witnesses, and the segwit marker and flag if present.
+    pub witness_bytes: usize,
+    /// The transaction weight, `base_bytes` times four plus `witness_bytes`.
+    pub weight: usize,
+}
+
+/// Builds a [`Transaction`] input by input and output by output, or edits an
+/// existing one, see [`Transaction::into_builder`].
+///
+/// [`Transaction::into_builder`]: struct.Transaction.html#method.into_builder
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TransactionBuilder {
+    tx: Transaction,
+}
+
+impl TransactionBuilder {
+    /// Starts a version 2 transaction without inputs or outputs and with a
+    /// zero lock time.
+    pub fn new() -> TransactionBuilder {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![],
+        }.into_builder()
+    }
+
+    /// Sets the version.
+    pub fn version(mut self, version: i32) -> TransactionBuilder {
+        self.tx.version = version;
+        self
+    }
+
+    /// Sets the lock time.
+    pub fn lock_time(mut self, lock_time: u32) -> TransactionBuilder {
+        self.tx.lock_time = lock_time;
+        self
+    }
+
+    /// Appends an input.
+    pub fn add_input(mut self, input: TxIn) -> TransactionBuilder {
+        self.tx.input.push(input);
+        self
+    }
+
+    /// Appends an output.
+    pub fn add_output(mut self, output: TxOut) -> TransactionBuilder {
+        self.tx.output.push(output);
+        self
+    }
+
+    /// Returns the input at `index` for editing.
+    pub fn input_mut(&mut self, index: usize) -> Option<&mut TxIn> {
+        self.tx.input.get_mut(index)
+    }
+
+    /// Returns the output at `index` for editing.
+    pub fn output_mut(&mut self, index: usize) -> Option<&mut TxOut> {
+        self.tx.output.get_mut(index)
+    }
+
+    /// Removes and returns the input at `index`, shifting later inputs down.
+    pub fn remove_input(&mut self, index: usize) -> Option<TxIn> {
+        if index < self.tx.input.len() {
+            Some(self.tx.input.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Removes and returns the output at `index`, shifting later outputs down.
+    pub fn remove_output(&mut self, index: usize) -> Option<TxOut> {
+        if index < self.tx.output.len() {
+            Some(self.tx.output.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the transaction built so far.
+    pub fn build(self) -> Transaction {
+        self.tx
+    }
+}
+
+impl Default for TransactionBuilder {
+    fn default() -> TransactionBuilder { TransactionBuilder::new() }
+}
+
+/// An immutable [`Transaction`] wrapper which computes the txid, wtxid and weight at most once.
+///
+/// Since the wrapped transaction can not be modified, the memoized values can never go stale.
+/// To make changes, take the transaction back out with [`CachedTransaction::into_inner`].
+#[derive(Clone, Debug)]
+pub struct CachedTransaction {
+    tx: Transaction,
+    txid: Cell<Option<Txid>>,
+    wtxid: Cell<Option<Wtxid>>,
+    weight: Cell<Option<usize>>,
+}
+
+impl CachedTransaction {
+    /// Wraps a transaction, nothing is computed until first requested.
+    pub fn new(tx: Transaction) -> CachedTransaction {
+        CachedTransaction {
+            tx,
+            txid: Cell::new(None),
+            wtxid: Cell::new(None),
+            weight: Cell::new(None),
+        }
+    }
+
+    /// Returns a reference to the wrapped transaction.
+    pub fn transaction(&self) -> &Transaction {
+        &self.tx
+    }
+
+    /// Unwraps the transaction, discarding the cached values.
+    pub fn into_inner(self) -> Transaction {
+        self.tx
+    }
+
+    /// Returns the txid of the wrapped transaction, see [`Transaction::txid`].
+    pub fn txid(&self) -> Txid {
+        memoize(&self.txid, || self.tx.txid())
+    }
+
+    /// Returns the wtxid of the wrapped transaction, see [`Transaction::wtxid`].
+    pub fn wtxid(&self) -> Wtxid {
+        memoize(&self.wtxid, || self.tx.wtxid())
+    }
+
+    /// Returns the weight of the wrapped transaction, see [`Transaction::get_weight`].
+    pub fn get_weight(&self) -> usize {
+        memoize(&self.weight, || self.tx.get_weight())
+    }
+}
+
+/// Returns the value of `cell`, filling it with the output of `f` if it is empty.
+fn memoize<T: Copy, F: FnOnce() -> T>(cell: &Cell<Option<T>>, f: F) -> T {
+    match cell.get() {
+        Some(value) => value,
+        None => {
+            let value = f();
+            cell.set(Some(value));
+            value
+        }
     }
 }
 
+impl From<Transaction> for CachedTransaction {
+    fn from(tx: Transaction) -> CachedTransaction {
+        CachedTransaction::new(tx)
+    }
+}
+
+impl AsRef<Transaction> for CachedTransaction {
+    fn as_ref(&self) -> &Transaction {
+        &self.tx
+    }
+}
+
+impl PartialEq for CachedTransaction {
+    fn eq(&self, other: &CachedTransaction) -> bool {
+        self.tx == other.tx
+    }
+}
+
+impl Eq for CachedTransaction {}
+
+/// Lists the offsets at which the serializations of two transactions differ,
+/// together with the byte of each, for debugging encoding mismatches.
+///
+/// If the serializations have different lengths, every offset past the end of
+/// the shorter one is listed, with `0` standing in for its missing byte.
+pub fn serialize_diff(a: &Transaction, b: &Transaction) -> Vec<(usize, u8, u8)> {
+    let a = encode::serialize(a);
+    let b = encode::serialize(b);
+    (0..a.len().max(b.len()))
+        .filter_map(|i| match (a.get(i), b.get(i)) {
+            (Some(x), Some(y)) if x == y => None,
+            (x, y) => Some((i, x.cloned().unwrap_or(0), y.cloned().unwrap_or(0))),
+        })
+        .collect()
+}
+
 impl_consensus_encoding!(TxOut, value, script_pubkey);
 
 impl Encodable for OutPoint {
@@ -534,6 +1211,8 @@ impl Decodable for TxIn {
     }
 }
 
+impl_consensus_hex!(Transaction);
+
 impl Encodable for Transaction {
     fn consensus_encode<S: io::Write>(
         &self,
@@ -563,20 +1242,68 @@ impl Encodable for Transaction {
         len += self.lock_time.consensus_encode(s)?;
         Ok(len)
     }
+
+    fn size_hint(&self) -> usize {
+        self.get_size()
+    }
+}
+
+/// Smallest consensus encoding of a `TxIn`: an outpoint, an empty script and
+/// a sequence number.
+const MIN_TXIN_SIZE: usize = 32 + 4 + 1 + 4;
+/// Smallest consensus encoding of a `TxOut`: a value and an empty script.
+const MIN_TXOUT_SIZE: usize = 8 + 1;
+
+/// Decodes a vector of transaction inputs or outputs, rejecting counts which
+/// could not fit into a block given the minimum encoded size of an element,
+/// before anything is allocated for them.
+fn decode_bounded_vec<T: Decodable, D: io::Read>(mut d: D, min_size: usize) -> Result<Vec<T>, encode::Error> {
+    let len = VarInt::consensus_decode(&mut d)?.0;
+    // Inputs and outputs are not witness data, so each byte costs four
+    // weight units.
+    let max = MAX_BLOCK_WEIGHT as usize / WITNESS_SCALE_FACTOR;
+    if len > (max / min_size) as u64 {
+        return Err(encode::Error::OversizedVectorAllocation {
+            requested: (len as usize).saturating_mul(min_size),
+            max,
+        });
+    }
+    let mut ret = Vec::with_capacity(len as usize);
+    for _ in 0..len {
+        ret.push(Decodable::consensus_decode(&mut d)?);
+    }
+    Ok(ret)
 }
 
+/// An empty input list can not be told apart from the segwit marker, so a
+/// zero input count is always read as the marker (BIP144), like Bitcoin Core
+/// does. The flag following it must be `1`, or `0` for the legacy encoding of
+/// a transaction without inputs and outputs. A transaction without inputs
+/// but with outputs therefore can only be decoded from the segwit format,
+/// which is how [`Encodable`] serializes it.
+///
+/// [`Encodable`]: ../../consensus/encode/trait.Encodable.html
 impl Decodable for Transaction {
     fn consensus_decode<D: io::Read>(mut d: D) -> Result<Self, encode::Error> {
         let version = i32::consensus_decode(&mut d)?;
-        let input = Vec::<TxIn>::consensus_decode(&mut d)?;
+        let input = decode_bounded_vec::<TxIn, _>(&mut d, MIN_TXIN_SIZE)?;
         // segwit
         if input.is_empty() {
             let segwit_flag = u8::consensus_decode(&mut d)?;
             match segwit_flag {
+                // No inputs and no outputs
+                0 => {
+                    Ok(Transaction {
+                        version,
+                        input: vec![],
+                        output: vec![],
+                        lock_time: Decodable::consensus_decode(d)?,
+                    })
+                }
                 // BIP144 input witnesses
                 1 => {
-                    let mut input = Vec::<TxIn>::consensus_decode(&mut d)?;
-                    let output = Vec::<TxOut>::consensus_decode(&mut d)?;
+                    let mut input = decode_bounded_vec::<TxIn, _>(&mut d, MIN_TXIN_SIZE)?;
+                    let output = decode_bounded_vec::<TxOut, _>(&mut d, MIN_TXOUT_SIZE)?;
                     for txin in input.iter_mut() {
                         txin.witness = Decodable::consensus_decode(&mut d)?;
                     }
@@ -601,7 +1328,7 @@ impl Decodable for Transaction {
             Ok(Transaction {
                 version: version,
                 input: input,
-                output: Decodable::consensus_decode(&mut d)?,
+                output: decode_bounded_vec(&mut d, MIN_TXOUT_SIZE)?,
                 lock_time: Decodable::consensus_decode(d)?,
             })
         }
@@ -743,18 +1470,22 @@ impl From<SigHashType> for u32 {
 
 #[cfg(test)]
 mod tests {
-    use super::{OutPoint, ParseOutPointError, Transaction, TxIn, NonStandardSigHashType};
+    use super::{OutPoint, ParseOutPointError, Transaction, TxIn, NonStandardSigHashType, CachedTransaction, Sequence};
+    use super::{serialize_diff, TransactionBuilder, TxOut};
+
+    use std::collections::HashMap;
 
     use std::str::FromStr;
-    use blockdata::constants::WITNESS_SCALE_FACTOR;
+    use blockdata::constants::{genesis_block, WITNESS_SCALE_FACTOR};
     use blockdata::script::Script;
-    use consensus::encode::serialize;
+    use consensus::encode::{self, serialize, serialize_hex};
     use consensus::encode::deserialize;
 
     use hashes::Hash;
     use hashes::hex::FromHex;
 
     use hash_types::*;
+    use network::constants::Network;
     use SigHashType;
 
     #[test]
@@ -801,11 +1532,76 @@ mod tests {
         let txin = TxIn::default();
         assert_eq!(txin.previous_output, OutPoint::default());
         assert_eq!(txin.script_sig, Script::new());
-        assert_eq!(txin.sequence, 0xFFFFFFFF);
+        assert_eq!(txin.sequence, Sequence::MAX);
         assert_eq!(txin.previous_output, OutPoint::default());
         assert_eq!(txin.witness.len(), 0 as usize);
     }
 
+    #[test]
+    fn test_txout_empty() {
+        let txout = TxOut { value: 0, script_pubkey: Script::new() };
+        let bytes = serialize(&txout);
+        assert_eq!(bytes, vec![0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(deserialize::<TxOut>(&bytes).unwrap(), txout);
+
+        // A zero-value OP_RETURN output
+        let txout = TxOut { value: 0, script_pubkey: Script::from(vec![0x6a]) };
+        let bytes = serialize(&txout);
+        assert_eq!(bytes, Vec::from_hex("0000000000000000016a").unwrap());
+        assert_eq!(deserialize::<TxOut>(&bytes).unwrap(), txout);
+
+        // Transactions with such outputs round-trip and are counted correctly.
+        let mut tx: Transaction = deserialize(&Vec::from_hex("0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000").unwrap()).unwrap();
+        tx.output.push(TxOut { value: 0, script_pubkey: Script::new() });
+        let bytes = serialize(&tx);
+        assert_eq!(bytes.len(), tx.get_size());
+        assert_eq!(deserialize::<Transaction>(&bytes).unwrap(), tx);
+    }
+
+    #[test]
+    fn test_sequence() {
+        assert_eq!(Sequence::MAX.to_consensus(), 0xffffffff);
+        assert_eq!(Sequence::ZERO.to_consensus(), 0);
+        assert_eq!(Sequence::ENABLE_LOCKTIME_NO_RBF.to_consensus(), 0xfffffffe);
+        assert_eq!(Sequence::ENABLE_RBF_NO_LOCKTIME.to_consensus(), 0xfffffffd);
+        assert_eq!(Sequence::default(), Sequence::MAX);
+        assert_eq!(Sequence::from_consensus(0x12345678).to_consensus(), 0x12345678);
+
+        assert_eq!(Sequence::from_height(0).to_consensus(), 0);
+        assert_eq!(Sequence::from_height(144).to_consensus(), 144);
+        assert_eq!(Sequence::from_height(0xffff).to_consensus(), 0xffff);
+        assert_eq!(Sequence::from_512_second_intervals(2).to_consensus(), 0x00400002);
+
+        assert_eq!(Sequence::from_seconds_floor(1023).unwrap().to_consensus(), 0x00400001);
+        assert_eq!(Sequence::from_seconds_ceil(1023).unwrap().to_consensus(), 0x00400002);
+        assert_eq!(Sequence::from_seconds_floor(1024).unwrap().to_consensus(), 0x00400002);
+        assert_eq!(Sequence::from_seconds_ceil(1024).unwrap().to_consensus(), 0x00400002);
+        assert_eq!(Sequence::from_seconds_floor(512 * 0xffff + 511).unwrap().to_consensus(), 0x0040ffff);
+        assert_eq!(Sequence::from_seconds_ceil(512 * 0xffff + 1), None);
+        assert_eq!(Sequence::from_seconds_floor(512 * 0x10000), None);
+
+        assert!(Sequence::MAX.is_final());
+        assert!(!Sequence::ENABLE_LOCKTIME_NO_RBF.is_final());
+
+        assert!(!Sequence::MAX.is_rbf());
+        assert!(!Sequence::ENABLE_LOCKTIME_NO_RBF.is_rbf());
+        assert!(Sequence::ENABLE_RBF_NO_LOCKTIME.is_rbf());
+        assert!(Sequence::from_height(10).is_rbf());
+
+        assert!(!Sequence::MAX.is_relative_lock_time());
+        assert!(!Sequence::ENABLE_RBF_NO_LOCKTIME.is_relative_lock_time());
+        assert!(Sequence::from_height(10).is_relative_lock_time());
+        assert!(Sequence::from_height(10).is_height_locked());
+        assert!(!Sequence::from_height(10).is_time_locked());
+        assert!(Sequence::from_512_second_intervals(10).is_time_locked());
+        assert!(!Sequence::from_512_second_intervals(10).is_height_locked());
+
+        assert_eq!(serialize(&Sequence::ENABLE_RBF_NO_LOCKTIME), vec![0xfd, 0xff, 0xff, 0xff]);
+        assert_eq!(deserialize::<Sequence>(&[0x02, 0x00, 0x40, 0x00]).unwrap(),
+                   Sequence::from_512_second_intervals(2));
+        assert_eq!(format!("{}", Sequence::from_height(144)), "0x00000090");
+    }
+
     #[test]
     fn test_is_coinbase () {
         use network::constants::Network;
@@ -842,6 +1638,74 @@ mod tests {
                    "a6eab3c14ab5272a58a5ba91505ba1a4b6d7a3a9fcbd187b6cd99a7b6d548cb7".to_string());
         assert_eq!(realtx.get_weight(), tx_bytes.len()*WITNESS_SCALE_FACTOR);
         assert_eq!(realtx.get_size(), tx_bytes.len());
+        assert_eq!(realtx.get_vsize(), tx_bytes.len());
+    }
+
+    #[test]
+    fn test_transaction_summary() {
+        let tx_bytes = Vec::from_hex("0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000").unwrap();
+        let tx: Transaction = deserialize(&tx_bytes).unwrap();
+        assert_eq!(
+            tx.summary(),
+            "txid a6eab3c14ab5272a58a5ba91505ba1a4b6d7a3a9fcbd187b6cd99a7b6d548cb7 version 1 \
+             inputs 1 outputs 1 value 100000000 sat locktime 0"
+        );
+
+        let genesis = genesis_block(Network::Bitcoin);
+        assert_eq!(
+            genesis.txdata[0].summary(),
+            "txid 4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b version 1 \
+             inputs 1 outputs 1 value 5000000000 sat locktime 0"
+        );
+    }
+
+    #[test]
+    fn test_transaction_hex() {
+        use consensus::encode::Error;
+        use hashes::hex::Error as HexError;
+
+        let hex_tx = "0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000";
+        let tx = Transaction::from_hex(hex_tx).unwrap();
+        assert_eq!(tx, deserialize(&Vec::from_hex(hex_tx).unwrap()).unwrap());
+        assert_eq!(tx.to_hex(), hex_tx);
+        assert_eq!(tx.to_hex(), serialize_hex(&tx));
+
+        match Transaction::from_hex(&hex_tx[1..]) {
+            Err(Error::Hex(HexError::OddLengthString(_))) => {},
+            r => panic!("expected odd length error, got {:?}", r),
+        }
+        match Transaction::from_hex(&hex_tx.replace("a15d", "a1zz")) {
+            Err(Error::Hex(HexError::InvalidChar(b'z'))) => {},
+            r => panic!("expected invalid char error, got {:?}", r),
+        }
+        match Transaction::from_hex(&format!("{}00", hex_tx)) {
+            Err(Error::ParseFailed(_)) => {},
+            r => panic!("expected trailing data error, got {:?}", r),
+        }
+
+        assert_eq!(Transaction::from_core_hex(&format!("{}\n", hex_tx)).unwrap(), tx);
+        assert_eq!(Transaction::from_core_hex(&format!("  {}\r\n", hex_tx)).unwrap(), tx);
+        assert!(Transaction::from_hex(&format!("{}\n", hex_tx)).is_err());
+        assert!(Transaction::from_core_hex(&format!("{}\n00", hex_tx)).is_err());
+    }
+
+    #[test]
+    fn test_script_sigs_standard() {
+        use blockdata::script::Builder;
+        use consensus::params::Params;
+
+        let params = Params::new(Network::Bitcoin);
+        let tx_bytes = Vec::from_hex("0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000").unwrap();
+        let mut tx: Transaction = deserialize(&tx_bytes).unwrap();
+        assert!(tx.script_sigs_standard(&params));
+
+        tx.input.push(tx.input[0].clone());
+        tx.input[1].script_sig = Builder::new().push_slice(&[0u8; 1647]).into_script();
+        assert!(tx.script_sigs_standard(&params));
+        tx.input[1].script_sig = Builder::new().push_slice(&[0u8; 1648]).into_script();
+        assert!(!tx.script_sigs_standard(&params));
+        tx.input[1].script_sig = Builder::new().push_int(1).push_opcode(::blockdata::opcodes::all::OP_DUP).into_script();
+        assert!(!tx.script_sigs_standard(&params));
     }
 
     #[test]
@@ -874,6 +1738,426 @@ mod tests {
                    "80b7d8a82d5d5bf92905b06f2014dd699e03837ca172e3a59d51426ebbe3e7f5".to_string());
         assert_eq!(realtx.get_weight(), 442);
         assert_eq!(realtx.get_size(), tx_bytes.len());
+        assert_eq!(realtx.get_vsize(), 111);
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn test_to_core_json() {
+        use serde_json;
+
+        let genesis = genesis_block(Network::Bitcoin);
+        let expected: serde_json::Value = serde_json::from_str(r#"{
+            "txid": "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+            "hash": "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+            "version": 1,
+            "size": 204,
+            "vsize": 204,
+            "weight": 816,
+            "locktime": 0,
+            "vin": [
+                {
+                    "coinbase": "04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73",
+                    "sequence": 4294967295
+                }
+            ],
+            "vout": [
+                {
+                    "value": 50.00000000,
+                    "n": 0,
+                    "scriptPubKey": {
+                        "asm": "04678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5f OP_CHECKSIG",
+                        "hex": "4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac",
+                        "type": "pubkey"
+                    }
+                }
+            ]
+        }"#).unwrap();
+        assert_eq!(genesis.txdata[0].to_core_json(Network::Bitcoin), expected);
+
+        let tx: Transaction = deserialize(&Vec::from_hex(
+            "02000000000101595895ea20179de87052b4046dfe6fd515860505d6511a9004cf12a1f93cac7c01000000\
+            00ffffffff01deb807000000000017a9140f3444e271620c736808aa7b33e370bd87cb5a078702483045022\
+            100fb60dad8df4af2841adc0346638c16d0b8035f5e3f3753b88db122e70c79f9370220756e6633b17fd271\
+            0e626347d28d60b0a2d6cbb41de51740644b9fb3ba7751040121028fa937ca8cba2197a37c007176ed89410\
+            55d3bcb8627d085e94553e62f057dcc00000000"
+        ).unwrap()).unwrap();
+        let expected: serde_json::Value = serde_json::from_str(r#"{
+            "txid": "f5864806e3565c34d1b41e716f72609d00b55ea5eac5b924c9719a842ef42206",
+            "hash": "80b7d8a82d5d5bf92905b06f2014dd699e03837ca172e3a59d51426ebbe3e7f5",
+            "version": 2,
+            "size": 193,
+            "vsize": 111,
+            "weight": 442,
+            "locktime": 0,
+            "vin": [
+                {
+                    "txid": "7cac3cf9a112cf04901a51d605058615d56ffe6d04b45270e89d1720ea955859",
+                    "vout": 1,
+                    "scriptSig": {
+                        "asm": "",
+                        "hex": ""
+                    },
+                    "txinwitness": [
+                        "3045022100fb60dad8df4af2841adc0346638c16d0b8035f5e3f3753b88db122e70c79f9370220756e6633b17fd2710e626347d28d60b0a2d6cbb41de51740644b9fb3ba77510401",
+                        "028fa937ca8cba2197a37c007176ed8941055d3bcb8627d085e94553e62f057dcc"
+                    ],
+                    "sequence": 4294967295
+                }
+            ],
+            "vout": [
+                {
+                    "value": 0.00506078,
+                    "n": 0,
+                    "scriptPubKey": {
+                        "asm": "OP_HASH160 0f3444e271620c736808aa7b33e370bd87cb5a07 OP_EQUAL",
+                        "hex": "a9140f3444e271620c736808aa7b33e370bd87cb5a0787",
+                        "reqSigs": 1,
+                        "type": "scripthash",
+                        "addresses": [
+                            "335Qg979J2ZDHzBzMuisgikxnfEi9ERNU7"
+                        ]
+                    }
+                }
+            ]
+        }"#).unwrap();
+        assert_eq!(tx.to_core_json(Network::Bitcoin), expected);
+        let testnet = tx.to_core_json(Network::Testnet);
+        assert_eq!(testnet["vout"][0]["scriptPubKey"]["addresses"][0], "2Mtdcjt3AuV4ZVmpY33LkJfkE11SsyqdAvj");
+
+        // Signatures in scriptSigs are shown with their sighash type.
+        let tx: Transaction = deserialize(&Vec::from_hex(
+            "0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c49\
+            3046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd\
+            7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f50\
+            6efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a92\
+            25b3839e2bbf32d826a1e222031fd888ac00000000"
+        ).unwrap()).unwrap();
+        let json = tx.to_core_json(Network::Bitcoin);
+        assert_eq!(
+            json["vin"][0]["scriptSig"]["asm"],
+            "3046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c[ALL] \
+            033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52"
+        );
+        assert!(json["vin"][0].get("txinwitness").is_none());
+        assert_eq!(json["vout"][0]["value"], 1.0);
+        assert_eq!(json["vout"][0]["scriptPubKey"]["type"], "pubkeyhash");
+        assert_eq!(json["vout"][0]["scriptPubKey"]["addresses"][0], "1KhAQw5BU3i4G8BDGpw8YqUKRLuW3mQdh");
+
+        // Witness v1+ addresses would need bech32m, so none are listed.
+        let mut taproot = tx.clone();
+        taproot.output[0].script_pubkey = Script::from(Vec::from_hex(
+            "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"
+        ).unwrap());
+        let json = taproot.to_core_json(Network::Bitcoin);
+        assert_eq!(json["vout"][0]["scriptPubKey"]["type"], "witness_v1_taproot");
+        assert!(json["vout"][0]["scriptPubKey"].get("addresses").is_none());
+        assert!(json["vout"][0]["scriptPubKey"].get("reqSigs").is_none());
+    }
+
+    #[test]
+    fn test_txid_stability() {
+        // A legacy P2PKH spend, whose scriptSig is push-only.
+        let legacy: Transaction = deserialize(&Vec::from_hex(
+            "0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c49\
+            3046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd\
+            7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f50\
+            6efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a92\
+            25b3839e2bbf32d826a1e222031fd888ac00000000"
+        ).unwrap()).unwrap();
+        assert!(!legacy.has_non_push_only_scriptsig());
+        assert!(!legacy.is_txid_stable());
+
+        // A native P2WPKH spend.
+        let segwit: Transaction = deserialize(&Vec::from_hex(
+            "02000000000101595895ea20179de87052b4046dfe6fd515860505d6511a9004cf12a1f93cac7c01000000\
+            00ffffffff01deb807000000000017a9140f3444e271620c736808aa7b33e370bd87cb5a078702483045022\
+            100fb60dad8df4af2841adc0346638c16d0b8035f5e3f3753b88db122e70c79f9370220756e6633b17fd271\
+            0e626347d28d60b0a2d6cbb41de51740644b9fb3ba7751040121028fa937ca8cba2197a37c007176ed89410\
+            55d3bcb8627d085e94553e62f057dcc00000000"
+        ).unwrap()).unwrap();
+        assert!(!segwit.has_non_push_only_scriptsig());
+        assert!(segwit.is_txid_stable());
+
+        // Nested in P2SH the redeem script is pushed, which is fine...
+        let mut nested = segwit.clone();
+        nested.input[0].script_sig = Script::from(Vec::from_hex("160014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap());
+        assert!(nested.is_txid_stable());
+        // ... but anything else in the scriptSig can be malleated.
+        let mut malleated = nested.clone();
+        malleated.input[0].script_sig = Script::from(Vec::from_hex("00160014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap());
+        assert!(!malleated.has_non_push_only_scriptsig());
+        assert!(!malleated.is_txid_stable());
+        malleated.input[0].script_sig = Script::from(Vec::from_hex("5175160014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap());
+        assert!(malleated.has_non_push_only_scriptsig());
+        assert!(!malleated.is_txid_stable());
+
+        // A single legacy input makes the whole transaction malleable.
+        let mut mixed = segwit.clone();
+        mixed.input.push(legacy.input[0].clone());
+        assert!(!mixed.is_txid_stable());
+    }
+
+    #[test]
+    fn test_check_coinbase_scriptsig() {
+        let genesis = genesis_block(Network::Bitcoin);
+        let mut coinbase = genesis.txdata[0].clone();
+        assert!(coinbase.check_coinbase_scriptsig().is_ok());
+
+        for &(len, ok) in &[(0, false), (1, false), (2, true), (100, true), (101, false)] {
+            coinbase.input[0].script_sig = Script::from(vec![0x51; len]);
+            match coinbase.check_coinbase_scriptsig() {
+                Ok(()) => assert!(ok, "length {}", len),
+                Err(::util::Error::BadCoinbaseLength) => assert!(!ok, "length {}", len),
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+
+        // Other transactions are not checked.
+        let mut spend = coinbase.clone();
+        spend.input[0].previous_output = OutPoint::new(genesis.txdata[0].txid(), 0);
+        assert!(spend.check_coinbase_scriptsig().is_ok());
+    }
+
+    #[test]
+    fn test_transaction_builder() {
+        let tx: Transaction = deserialize(&Vec::from_hex(
+            "0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000"
+        ).unwrap()).unwrap();
+        assert_eq!(tx.clone().into_builder().build(), tx);
+
+        let mut builder = tx.clone().into_builder();
+        builder.output_mut(0).unwrap().value -= 1000;
+        assert!(builder.output_mut(1).is_none());
+        let edited = builder.build();
+        assert_eq!(edited.output[0].value, tx.output[0].value - 1000);
+        assert_eq!(edited.output[0].script_pubkey, tx.output[0].script_pubkey);
+        assert_eq!(edited.input, tx.input);
+        assert_ne!(edited.txid(), tx.txid());
+
+        let mut builder = TransactionBuilder::new()
+            .version(1)
+            .add_input(tx.input[0].clone())
+            .add_output(tx.output[0].clone());
+        assert_eq!(builder.clone().build(), tx);
+        builder.input_mut(0).unwrap().sequence = Sequence::ZERO;
+        assert_eq!(builder.remove_input(0).unwrap().sequence, Sequence::ZERO);
+        assert!(builder.remove_input(0).is_none());
+        assert_eq!(builder.remove_output(0), Some(tx.output[0].clone()));
+        assert_eq!(builder.lock_time(10).build(), Transaction {
+            version: 1,
+            lock_time: 10,
+            input: vec![],
+            output: vec![],
+        });
+    }
+
+    #[test]
+    fn test_has_duplicate_inputs() {
+        let mut tx: Transaction = deserialize(&Vec::from_hex(
+            "0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000"
+        ).unwrap()).unwrap();
+        assert!(!tx.has_duplicate_inputs());
+
+        let mut other = tx.input[0].clone();
+        other.previous_output.vout += 1;
+        tx.input.push(other);
+        assert!(!tx.has_duplicate_inputs());
+
+        let mut duplicate = tx.input[0].clone();
+        duplicate.script_sig = Script::new();
+        tx.input.push(duplicate);
+        assert!(tx.has_duplicate_inputs());
+    }
+
+    #[test]
+    fn test_coinbase_extranonce_range() {
+        use blockdata::script::Builder;
+
+        let genesis = genesis_block(Network::Bitcoin);
+        let mut coinbase = genesis.txdata[0].clone();
+        coinbase.input[0].script_sig = Builder::new()
+            .push_int(700_000)
+            .push_slice(&[0xaa; 8])
+            .into_script();
+        let range = coinbase.coinbase_extranonce_range().unwrap();
+        assert_eq!(range, 4..13);
+        assert_eq!(&coinbase.input[0].script_sig[range], &[0x08, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa][..]);
+
+        // Only the height push, with a longer push opcode.
+        coinbase.input[0].script_sig = Builder::new().push_slice(&[0x01; 80]).into_script();
+        assert_eq!(coinbase.coinbase_extranonce_range(), Some(82..82));
+
+        // The push has to be minimal.
+        coinbase.input[0].script_sig = Script::from(vec![0x4c, 0x01, 0x01, 0xaa]);
+        assert_eq!(coinbase.coinbase_extranonce_range(), None);
+        coinbase.input[0].script_sig = Script::from(vec![0x51, 0xaa]);
+        assert_eq!(coinbase.coinbase_extranonce_range(), None);
+        coinbase.input[0].script_sig = Script::new();
+        assert_eq!(coinbase.coinbase_extranonce_range(), None);
+
+        let mut spend = genesis.txdata[0].clone();
+        spend.input[0].previous_output = OutPoint::new(genesis.txdata[0].txid(), 0);
+        assert_eq!(spend.coinbase_extranonce_range(), None);
+    }
+
+    #[test]
+    fn test_strip_witness() {
+        let tx: Transaction = deserialize(&Vec::from_hex(
+            "02000000000101595895ea20179de87052b4046dfe6fd515860505d6511a9004cf12a1f93cac7c01000000\
+            00ffffffff01deb807000000000017a9140f3444e271620c736808aa7b33e370bd87cb5a078702483045022\
+            100fb60dad8df4af2841adc0346638c16d0b8035f5e3f3753b88db122e70c79f9370220756e6633b17fd271\
+            0e626347d28d60b0a2d6cbb41de51740644b9fb3ba7751040121028fa937ca8cba2197a37c007176ed89410\
+            55d3bcb8627d085e94553e62f057dcc00000000"
+        ).unwrap()).unwrap();
+        assert!(tx.has_witness());
+
+        let stripped = tx.strip_witness();
+        assert!(!stripped.has_witness());
+        assert!(!stripped.strip_witness().has_witness());
+        assert_eq!(stripped.txid(), tx.txid());
+        assert_eq!(stripped.wtxid().as_hash(), tx.txid().as_hash());
+        // Version, inputs, outputs and locktime, without marker, flag and witness.
+        assert_eq!(
+            serialize(&stripped),
+            Vec::from_hex(
+                "0200000001595895ea20179de87052b4046dfe6fd515860505d6511a9004cf12a1f93cac7c0100000000\
+                ffffffff01deb807000000000017a9140f3444e271620c736808aa7b33e370bd87cb5a078700000000"
+            ).unwrap()
+        );
+        assert_eq!(stripped.get_weight(), 4 * stripped.get_size());
+    }
+
+    #[test]
+    fn test_sort_bip69() {
+        // Txids are displayed byte-reversed, so this one sorts last although
+        // its first byte is the smallest.
+        let mut high = [0u8; 32];
+        high[31] = 0xff;
+        let mut low = [0xffu8; 32];
+        low[31] = 0x00;
+        let high = Txid::from_slice(&high).unwrap();
+        let low = Txid::from_slice(&low).unwrap();
+        assert!(low.to_string() < high.to_string());
+
+        let input = |txid, vout| TxIn {
+            previous_output: OutPoint::new(txid, vout),
+            ..Default::default()
+        };
+        let output = |value, script: &str| TxOut {
+            value,
+            script_pubkey: Script::from(Vec::from_hex(script).unwrap()),
+        };
+        let mut tx = Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![input(high, 0), input(low, 2), input(high, 1), input(low, 0)],
+            output: vec![output(2000, "00"), output(1000, "76a9"), output(1000, "0014"), output(500, "ff")],
+        };
+        tx.sort_bip69();
+        assert_eq!(tx.input, vec![input(low, 0), input(low, 2), input(high, 0), input(high, 1)]);
+        assert_eq!(tx.output, vec![output(500, "ff"), output(1000, "0014"), output(1000, "76a9"), output(2000, "00")]);
+
+        // Sorting is idempotent.
+        let sorted = tx.clone();
+        tx.sort_bip69();
+        assert_eq!(tx, sorted);
+
+        // Outputs of equal value are ordered by their script bytes.
+        let (a, b) = (output(1000, "0014"), output(1000, "76a9"));
+        assert!(a.bip69_sort_key() < b.bip69_sort_key());
+        assert!(output(1000, "00").bip69_sort_key() < output(1000, "0000").bip69_sort_key());
+        assert!(output(999, "ff").bip69_sort_key() < a.bip69_sort_key());
+        let mut outputs = vec![output(2000, "00"), b, a, output(500, "ff")];
+        outputs.sort_by_key(TxOut::bip69_sort_key);
+        assert_eq!(outputs, sorted.output);
+    }
+
+    #[test]
+    fn test_inputs_with_prevouts() {
+        let tx: Transaction = deserialize(&Vec::from_hex(
+            "0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c49\
+            3046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd\
+            7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f50\
+            6efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a92\
+            25b3839e2bbf32d826a1e222031fd888ac00000000"
+        ).unwrap()).unwrap();
+        let mut two_inputs = tx.clone();
+        two_inputs.input.push(TxIn {
+            previous_output: OutPoint::new(tx.txid(), 0),
+            ..Default::default()
+        });
+
+        let mut prevouts = HashMap::new();
+        prevouts.insert(two_inputs.input[1].previous_output, tx.output[0].clone());
+        let pairs: Vec<_> = two_inputs.inputs_with_prevouts(&prevouts).collect();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0], (&two_inputs.input[0], None));
+        assert_eq!(pairs[1], (&two_inputs.input[1], Some(&tx.output[0])));
+
+        prevouts.insert(two_inputs.input[0].previous_output, tx.output[0].clone());
+        assert!(two_inputs.inputs_with_prevouts(&prevouts).all(|(_, prevout)| prevout.is_some()));
+    }
+
+    #[test]
+    fn test_serialize_diff() {
+        let tx: Transaction = deserialize(&Vec::from_hex(
+            "02000000000101595895ea20179de87052b4046dfe6fd515860505d6511a9004cf12a1f93cac7c01000000\
+            00ffffffff01deb807000000000017a9140f3444e271620c736808aa7b33e370bd87cb5a078702483045022\
+            100fb60dad8df4af2841adc0346638c16d0b8035f5e3f3753b88db122e70c79f9370220756e6633b17fd271\
+            0e626347d28d60b0a2d6cbb41de51740644b9fb3ba7751040121028fa937ca8cba2197a37c007176ed89410\
+            55d3bcb8627d085e94553e62f057dcc00000000"
+        ).unwrap()).unwrap();
+        assert!(serialize_diff(&tx, &tx).is_empty());
+
+        // Flip the sighash byte of the signature, which is only in the witness.
+        let mut malleated = tx.clone();
+        let sig_len = malleated.input[0].witness[0].len();
+        malleated.input[0].witness[0][sig_len - 1] = 0x81;
+        assert_eq!(malleated.txid(), tx.txid());
+        // version, marker and flag, inputs, outputs, witness count and length, signature
+        let offset = 4 + 2 + (1 + 41) + (1 + 32) + 2 + sig_len - 1;
+        assert_eq!(serialize_diff(&tx, &malleated), vec![(offset, 0x01, 0x81)]);
+
+        // Dropping the witness shifts everything after the version.
+        let mut stripped = tx.clone();
+        stripped.input[0].witness.clear();
+        let diff = serialize_diff(&tx, &stripped);
+        assert_eq!(diff[0], (4, 0x00, 0x01));
+        assert_eq!(diff.last(), Some(&(serialize(&tx).len() - 1, 0x00, 0x00)));
+        assert!(diff.iter().all(|&(i, ..)| i >= 4));
+    }
+
+    #[test]
+    fn test_cached_transaction() {
+        let tx_bytes = Vec::from_hex(
+            "02000000000101595895ea20179de87052b4046dfe6fd515860505d6511a9004cf12a1f93cac7c01000000\
+            00ffffffff01deb807000000000017a9140f3444e271620c736808aa7b33e370bd87cb5a078702483045022\
+            100fb60dad8df4af2841adc0346638c16d0b8035f5e3f3753b88db122e70c79f9370220756e6633b17fd271\
+            0e626347d28d60b0a2d6cbb41de51740644b9fb3ba7751040121028fa937ca8cba2197a37c007176ed89410\
+            55d3bcb8627d085e94553e62f057dcc00000000"
+        ).unwrap();
+        let tx: Transaction = deserialize(&tx_bytes).unwrap();
+        let cached = CachedTransaction::new(tx.clone());
+
+        assert!(cached.txid.get().is_none());
+        assert!(cached.wtxid.get().is_none());
+        assert!(cached.weight.get().is_none());
+
+        assert_eq!(cached.txid(), tx.txid());
+        assert_eq!(cached.wtxid(), tx.wtxid());
+        assert_eq!(cached.get_weight(), 442);
+
+        assert_eq!(cached.txid.get(), Some(tx.txid()));
+        assert_eq!(cached.wtxid.get(), Some(tx.wtxid()));
+        assert_eq!(cached.weight.get(), Some(442));
+
+        // repeated calls are served from the cache
+        assert_eq!(cached.txid(), tx.txid());
+        assert_eq!(cached.wtxid(), tx.wtxid());
+        assert_eq!(cached.get_weight(), 442);
+
+        assert_eq!(cached.into_inner(), tx);
     }
 
     #[test]
@@ -982,6 +2266,53 @@ mod tests {
         serde_round_trip!(tx);
     }
 
+    #[test]
+    fn test_txn_absurd_counts() {
+        let max = 1_000_000;
+        // Claims 2^64 - 1 inputs.
+        let bytes = Vec::from_hex("01000000ffffffffffffffffff").unwrap();
+        match deserialize::<Transaction>(&bytes) {
+            Err(encode::Error::OversizedVectorAllocation { max: m, .. }) => assert_eq!(m, max),
+            x => panic!("unexpected result: {:?}", x),
+        }
+        // The same after a segwit marker.
+        let bytes = Vec::from_hex("010000000001feffffff7f").unwrap();
+        match deserialize::<Transaction>(&bytes) {
+            Err(encode::Error::OversizedVectorAllocation { requested, max: m }) => {
+                assert_eq!(requested, 0x7fffffff * 41);
+                assert_eq!(m, max);
+            }
+            x => panic!("unexpected result: {:?}", x),
+        }
+        // One empty input followed by 111112 outputs, one more than fit.
+        let input = "010000000100000000000000000000000000000000000000000000000000000000000000000000000000ffffffff";
+        let bytes = Vec::from_hex(&format!("{}fe08b20100", input)).unwrap();
+        match deserialize::<Transaction>(&bytes) {
+            Err(encode::Error::OversizedVectorAllocation { requested, max: m }) => {
+                assert_eq!(requested, 111_112 * 9);
+                assert_eq!(m, max);
+            }
+            x => panic!("unexpected result: {:?}", x),
+        }
+        // 111111 outputs get as far as decoding, until the data runs out.
+        let bytes = Vec::from_hex(&format!("{}fe07b20100", input)).unwrap();
+        match deserialize::<Transaction>(&bytes) {
+            Err(encode::Error::Io(_)) => {}
+            x => panic!("unexpected result: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_sighash_code_separators() {
+        let tx: Transaction = deserialize(&Vec::from_hex("73107cbd025c22ebc8c3e0a47b2a760739216a528de8d4dab5d45cbeb3051cebae73b01ca10200000007ab6353656a636affffffffe26816dffc670841e6a6c8c61c586da401df1261a330a6c6b3dd9f9a0789bc9e000000000800ac6552ac6aac51ffffffff0174a8f0010000000004ac52515100000000").unwrap()).unwrap();
+        let script = Script::from(Vec::from_hex("5163ac63635151ac").unwrap());
+        let with_separators = Script::from(Vec::from_hex("ab5163acab63635151acab").unwrap());
+        for &hash_type in &[0x01u32, 0x02, 0x03, 0x81] {
+            assert_eq!(tx.signature_hash(1, &with_separators, hash_type), tx.signature_hash(1, &script, hash_type));
+            assert_eq!(tx.signature_hash(0, &with_separators, hash_type), tx.signature_hash(0, &script, hash_type));
+        }
+    }
+
     fn run_test_sighash(tx: &str, script: &str, input_index: usize, hash_type: i32, expected_result: &str) {
         let tx: Transaction = deserialize(&Vec::from_hex(tx).unwrap()[..]).unwrap();
         let script = Script::from(Vec::from_hex(script).unwrap());
@@ -1007,6 +2338,100 @@ mod tests {
         assert_eq!(consensus_encoded, tx_bytes);
     }
 
+    #[test]
+    fn test_fee_rate_in() {
+        use util::amount::Denomination;
+
+        let output = |value| TxOut { value, script_pubkey: Script::from(vec![0; 22]) };
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn::default(), TxIn::default()],
+            output: vec![output(140_000)],
+        };
+        // 4 + 1 + 2 * 41 + 1 + 31 + 4 bytes
+        assert_eq!(tx.get_vsize(), 123);
+        let values = [100_000, 52_300];
+        assert_eq!(tx.fee_rate_in(&values, Denomination::Satoshi), 100.0);
+        assert_eq!(tx.fee_rate_in(&values, Denomination::MilliSatoshi), 100_000.0);
+        assert!((tx.fee_rate_in(&values, Denomination::Bitcoin) - 0.000001).abs() < 1e-15);
+        assert_eq!(tx.fee_rate_in(&[70_000, 70_000], Denomination::Satoshi), 0.0);
+        assert_eq!(tx.fee_rate_in(&[70_000, 69_877], Denomination::Satoshi), -1.0);
+
+        // Values far above 21 million BTC, whose sums do not fit into a u64,
+        // as on chains with a larger money supply.
+        tx.output = vec![output(u64::max_value()), output(u64::max_value() - 12_300)];
+        let values = [u64::max_value(), u64::max_value()];
+        assert_eq!(tx.fee_rate_in(&values, Denomination::Satoshi), 12_300.0 / tx.get_vsize() as f64);
+    }
+
+    #[test]
+    fn test_weight_breakdown() {
+        // The segwit coinbase from `test_segwit_tx_decode`.
+        let tx_bytes = Vec::from_hex("010000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff3603da1b0e00045503bd5704c7dd8a0d0ced13bb5785010800000000000a636b706f6f6c122f4e696e6a61506f6f6c2f5345475749542fffffffff02b4e5a212000000001976a914876fbb82ec05caa6af7a3b5e5a983aae6c6cc6d688ac0000000000000000266a24aa21a9edf91c46b49eb8a29089980f02ee6b57e7d63d33b18b4fddac2bcd7db2a39837040120000000000000000000000000000000000000000000000000000000000000000000000000").unwrap();
+        let tx: Transaction = deserialize(&tx_bytes).unwrap();
+        let breakdown = tx.weight_breakdown();
+        // Marker and flag, one witness stack of a single 32-byte element.
+        assert_eq!(breakdown.witness_bytes, 2 + 1 + 1 + 32);
+        assert_eq!(breakdown.base_bytes + breakdown.witness_bytes, tx_bytes.len());
+        assert_eq!(breakdown.base_bytes * WITNESS_SCALE_FACTOR + breakdown.witness_bytes, breakdown.weight);
+        assert_eq!(breakdown.weight, 780);
+
+        let mut stripped = tx.clone();
+        stripped.input[0].witness.clear();
+        assert_eq!(breakdown.base_bytes, serialize(&stripped).len());
+        let breakdown = stripped.weight_breakdown();
+        assert_eq!(breakdown.witness_bytes, 0);
+        assert_eq!(breakdown.weight, breakdown.base_bytes * WITNESS_SCALE_FACTOR);
+    }
+
+    #[test]
+    fn test_zero_input_decode() {
+        // The segwit encoding of a transaction without inputs, as we produce it.
+        let no_inputs = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![TxOut { value: 50_000, script_pubkey: Script::from(vec![0x51]) }],
+        };
+        let segwit = serialize(&no_inputs);
+        assert_eq!(segwit, Vec::from_hex("020000000001000150c3000000000000015100000000").unwrap());
+        assert_eq!(deserialize::<Transaction>(&segwit).unwrap(), no_inputs);
+
+        // The legacy encoding reads the output count as the segwit flag, and
+        // the output as inputs, which fails.
+        let legacy = Vec::from_hex("02000000000150c300000000000001510000000000").unwrap();
+        assert!(deserialize::<Transaction>(&legacy).is_err());
+        let legacy = Vec::from_hex("02000000000250c30000000000000151102700000000000001510000000000").unwrap();
+        match deserialize::<Transaction>(&legacy) {
+            Err(encode::Error::UnsupportedSegwitFlag(2)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Without outputs either, the legacy encoding is unambiguous.
+        let empty = deserialize::<Transaction>(&Vec::from_hex("02000000000000000000").unwrap()).unwrap();
+        assert!(empty.input.is_empty() && empty.output.is_empty());
+        assert_eq!(empty.version, 2);
+
+        // Witnesses without a witness flag are trailing data.
+        let segwit_tx = Vec::from_hex(
+            "02000000000101595895ea20179de87052b4046dfe6fd515860505d6511a9004cf12a1f93cac7c01000000\
+            00ffffffff01deb807000000000017a9140f3444e271620c736808aa7b33e370bd87cb5a078702483045022\
+            100fb60dad8df4af2841adc0346638c16d0b8035f5e3f3753b88db122e70c79f9370220756e6633b17fd271\
+            0e626347d28d60b0a2d6cbb41de51740644b9fb3ba7751040121028fa937ca8cba2197a37c007176ed89410\
+            55d3bcb8627d085e94553e62f057dcc00000000"
+        ).unwrap();
+        let mut no_marker = segwit_tx[..4].to_vec();
+        no_marker.extend(&segwit_tx[6..]);
+        assert!(deserialize::<Transaction>(&no_marker).is_err());
+        let mut bad_flag = segwit_tx.clone();
+        bad_flag[5] = 0x03;
+        match deserialize::<Transaction>(&bad_flag) {
+            Err(encode::Error::UnsupportedSegwitFlag(3)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
     #[test]
     fn test_sighashtype_fromstr_display() {
         let sighashtypes = vec![("SIGHASH_ALL", SigHashType::All),
@@ -32,9 +32,9 @@
 use std::{fmt, error, io, mem, u32};
 use std::borrow::Cow;
 use std::io::{Cursor, Read, Write};
-use hashes::hex::ToHex;
+use hashes::hex::{self, FromHex, ToHex};
 
-use hashes::{sha256d, Hash};
+use hashes::{sha256d, Hash, HashEngine};
 use hash_types::{BlockHash, FilterHash, TxMerkleNode, FilterHeader};
 
 use util::endian;
@@ -80,6 +80,8 @@ pub enum Error {
     ParseFailed(&'static str),
     /// Unsupported Segwit flag
     UnsupportedSegwitFlag(u8),
+    /// Invalid hex encoding
+    Hex(hex::Error),
 }
 
 impl fmt::Display for Error {
@@ -98,6 +100,7 @@ impl fmt::Display for Error {
             Error::ParseFailed(ref e) => write!(f, "parse failed: {}", e),
             Error::UnsupportedSegwitFlag(ref swflag) => write!(f,
                 "unsupported segwit version: {}", swflag),
+            Error::Hex(ref e) => write!(f, "hex error: {}", e),
         }
     }
 }
@@ -107,6 +110,7 @@ impl error::Error for Error {
         match *self {
             Error::Io(ref e) => Some(e),
             Error::Psbt(ref e) => Some(e),
+            Error::Hex(ref e) => Some(e),
             Error::UnexpectedNetworkMagic { .. }
             | Error::OversizedVectorAllocation { .. }
             | Error::InvalidChecksum { .. }
@@ -134,14 +138,26 @@ impl From<psbt::Error> for Error {
     }
 }
 
+#[doc(hidden)]
+impl From<hex::Error> for Error {
+    fn from(e: hex::Error) -> Error {
+        Error::Hex(e)
+    }
+}
+
 /// Encode an object into a vector
 pub fn serialize<T: Encodable + ?Sized>(data: &T) -> Vec<u8> {
-    let mut encoder = Vec::new();
+    let mut encoder = Vec::with_capacity(data.size_hint());
     let len = data.consensus_encode(&mut encoder).unwrap();
     debug_assert_eq!(len, encoder.len());
     encoder
 }
 
+/// Compute the length of an object's consensus encoding without allocating a buffer
+pub fn serialized_size<T: Encodable + ?Sized>(data: &T) -> usize {
+    data.consensus_encode(io::sink()).expect("writing to a sink never fails")
+}
+
 /// Encode an object into a hex-encoded string
 pub fn serialize_hex<T: Encodable + ?Sized>(data: &T) -> String {
     serialize(data)[..].to_hex()
@@ -160,6 +176,27 @@ pub fn deserialize<T: Decodable>(data: &[u8]) -> Result<T, Error> {
     }
 }
 
+/// Deserialize an object from a hex-encoded string, will error if the string
+/// is not valid hex or if deserialization doesn't consume all of its bytes.
+pub fn deserialize_hex<T: Decodable>(hex: &str) -> Result<T, Error> {
+    deserialize(&Vec::<u8>::from_hex(hex)?)
+}
+
+/// Checks that `bytes` is the canonical encoding of a `T`: it has to decode
+/// without leftover data, re-encode to exactly the same bytes and decode again
+/// to an equal value.
+///
+/// Decoders which accept several encodings of the same value fail this check,
+/// which makes it useful as a fuzzing or test assertion.
+pub fn roundtrip_check<T: Encodable + Decodable + Eq>(bytes: &[u8]) -> bool {
+    let decoded: T = match deserialize(bytes) {
+        Ok(decoded) => decoded,
+        Err(_) => return false,
+    };
+    let encoded = serialize(&decoded);
+    encoded == bytes && deserialize::<T>(&encoded).ok().map_or(false, |again| again == decoded)
+}
+
 /// Deserialize an object from a vector, but will not report an error if said deserialization
 /// doesn't consume the entire vector.
 pub fn deserialize_partial<T: Decodable>(
@@ -304,6 +341,67 @@ impl<R: Read> ReadExt for R {
     }
 }
 
+macro_rules! define_read_fn {
+    ($(#[$attr:meta])* $name:ident, $val_type:ty, $convfn:ident, $byte_len:expr) => {
+        $(#[$attr])*
+        #[inline]
+        pub fn $name<R: Read>(mut reader: R) -> Result<$val_type, Error> {
+            let mut val = [0; $byte_len];
+            reader.read_exact(&mut val[..]).map_err(Error::Io)?;
+            Ok(endian::$convfn(&val))
+        }
+    }
+}
+
+macro_rules! define_write_fn {
+    ($(#[$attr:meta])* $name:ident, $val_type:ty, $convfn:ident, $byte_len:expr) => {
+        $(#[$attr])*
+        #[inline]
+        pub fn $name<W: Write>(mut writer: W, v: $val_type) -> Result<usize, io::Error> {
+            writer.write_all(&endian::$convfn(v))?;
+            Ok($byte_len)
+        }
+    }
+}
+
+define_read_fn!(
+    /// Read a little-endian 16-bit uint
+    read_u16_le, u16, slice_to_u16_le, 2);
+define_read_fn!(
+    /// Read a big-endian 16-bit uint
+    read_u16_be, u16, slice_to_u16_be, 2);
+define_read_fn!(
+    /// Read a little-endian 32-bit uint
+    read_u32_le, u32, slice_to_u32_le, 4);
+define_read_fn!(
+    /// Read a big-endian 32-bit uint
+    read_u32_be, u32, slice_to_u32_be, 4);
+define_read_fn!(
+    /// Read a little-endian 64-bit uint
+    read_u64_le, u64, slice_to_u64_le, 8);
+define_read_fn!(
+    /// Read a big-endian 64-bit uint
+    read_u64_be, u64, slice_to_u64_be, 8);
+
+define_write_fn!(
+    /// Output a little-endian 16-bit uint, returning the number of bytes written
+    write_u16_le, u16, u16_to_array_le, 2);
+define_write_fn!(
+    /// Output a big-endian 16-bit uint, returning the number of bytes written
+    write_u16_be, u16, u16_to_array_be, 2);
+define_write_fn!(
+    /// Output a little-endian 32-bit uint, returning the number of bytes written
+    write_u32_le, u32, u32_to_array_le, 4);
+define_write_fn!(
+    /// Output a big-endian 32-bit uint, returning the number of bytes written
+    write_u32_be, u32, u32_to_array_be, 4);
+define_write_fn!(
+    /// Output a little-endian 64-bit uint, returning the number of bytes written
+    write_u64_le, u64, u64_to_array_le, 8);
+define_write_fn!(
+    /// Output a big-endian 64-bit uint, returning the number of bytes written
+    write_u64_be, u64, u64_to_array_be, 8);
+
 /// Maximum size, in bytes, of a vector we are allowed to decode
 pub const MAX_VEC_SIZE: usize = 4_000_000;
 
@@ -314,6 +412,15 @@ pub trait Encodable {
     ///
     /// The only errors returned are errors propagated from the writer.
     fn consensus_encode<W: io::Write>(&self, writer: W) -> Result<usize, io::Error>;
+
+    /// Returns the expected length of the encoding in bytes, which
+    /// [`serialize`] reserves up front. It is only a hint and may be wrong;
+    /// the default implementation returns 0.
+    ///
+    /// [`serialize`]: fn.serialize.html
+    fn size_hint(&self) -> usize {
+        0
+    }
 }
 
 /// Data which can be encoded in a consensus-consistent way
@@ -644,6 +751,50 @@ fn sha2_checksum(data: &[u8]) -> [u8; 4] {
     [checksum[0], checksum[1], checksum[2], checksum[3]]
 }
 
+/// A writer which forwards everything to an inner writer and computes the
+/// checksum of [`CheckedData`] over the bytes written, so a payload can be
+/// checksummed while it is encoded rather than in a second pass.
+///
+/// [`CheckedData`]: struct.CheckedData.html
+pub struct ChecksummedWriter<W> {
+    writer: W,
+    engine: <sha256d::Hash as Hash>::Engine,
+}
+
+impl<W: io::Write> ChecksummedWriter<W> {
+    /// Wraps `writer`.
+    pub fn new(writer: W) -> ChecksummedWriter<W> {
+        ChecksummedWriter {
+            writer,
+            engine: sha256d::Hash::engine(),
+        }
+    }
+
+    /// Returns the checksum of the bytes written so far.
+    pub fn checksum(&self) -> [u8; 4] {
+        let checksum = sha256d::Hash::from_engine(self.engine.clone());
+        [checksum[0], checksum[1], checksum[2], checksum[3]]
+    }
+
+    /// Returns the inner writer and the checksum of the bytes written.
+    pub fn finish(self) -> (W, [u8; 4]) {
+        let checksum = self.checksum();
+        (self.writer, checksum)
+    }
+}
+
+impl<W: io::Write> io::Write for ChecksummedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.engine.input(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
 // Checked data
 impl Encodable for CheckedData {
     #[inline]
@@ -697,12 +848,20 @@ impl<T: Encodable> Encodable for ::std::rc::Rc<T> {
     fn consensus_encode<S: io::Write>(&self, s: S) -> Result<usize, io::Error> {
         (&**self).consensus_encode(s)
     }
+
+    fn size_hint(&self) -> usize {
+        (**self).size_hint()
+    }
 }
 
 impl<T: Encodable> Encodable for ::std::sync::Arc<T> {
     fn consensus_encode<S: io::Write>(&self, s: S) -> Result<usize, io::Error> {
         (&**self).consensus_encode(s)
     }
+
+    fn size_hint(&self) -> usize {
+        (**self).size_hint()
+    }
 }
 
 // Tuples
@@ -757,7 +916,8 @@ impl Decodable for sha256d::Hash {
 mod tests {
     use std::{io, mem, fmt};
     use std::mem::discriminant;
-    use super::{deserialize, serialize, Error, CheckedData, VarInt};
+    use super::{deserialize, roundtrip_check, serialize, serialized_size, Error, CheckedData, VarInt};
+    use super::{sha2_checksum, ChecksummedWriter};
     use super::{Transaction, BlockHash, FilterHash, TxMerkleNode, TxOut, TxIn};
     use consensus::{Encodable, deserialize_partial, Decodable};
     use util::endian::{u64_to_array_le, u32_to_array_le, u16_to_array_le};
@@ -814,6 +974,177 @@ mod tests {
         assert_eq!(serialize(&723401728380766730i64), vec![10u8, 10, 10, 10, 10, 10, 10, 10]);
     }
 
+    #[test]
+    fn consensus_encode_len_test() {
+        fn check<T: Encodable>(data: T, expected: usize) {
+            let mut buf = Vec::new();
+            assert_eq!(data.consensus_encode(&mut buf).unwrap(), expected);
+            assert_eq!(buf.len(), expected);
+        }
+
+        check(1u8, 1);
+        check(1u16, 2);
+        check(1u32, 4);
+        check(-1i64, 8);
+        check(true, 1);
+        check(VarInt(0xfc), 1);
+        check(VarInt(0xfd), 3);
+        check(VarInt(0x10000), 5);
+        check(VarInt(0x100000000), 9);
+        check(vec![0u8; 0xfd], 0xfd + 3);
+        check("hello".to_string(), 6);
+        check([0u8; 32], 32);
+        check(BlockHash::default(), 32);
+        check(TxOut::default(), 8 + 1);
+        check(vec![TxOut::default(), TxOut::default()], 1 + 2 * 9);
+
+        // Encoding into an offset keeps reporting only the newly written bytes.
+        let mut buf = vec![0xff; 10];
+        assert_eq!(VarInt(0xfd).consensus_encode(&mut buf).unwrap(), 3);
+        assert_eq!(buf.len(), 13);
+    }
+
+    #[test]
+    fn roundtrip_check_test() {
+        assert!(roundtrip_check::<VarInt>(&[0xfc]));
+        assert!(roundtrip_check::<VarInt>(&[0xfd, 0xfd, 0x00]));
+        assert!(roundtrip_check::<u32>(&[1, 2, 3, 4]));
+        assert!(roundtrip_check::<Vec<u8>>(&[2, 0xaa, 0xbb]));
+        // Non-minimal varints are already rejected while decoding...
+        assert!(!roundtrip_check::<VarInt>(&[0xfd, 0xfc, 0x00]));
+        assert!(!roundtrip_check::<Vec<u8>>(&[0xfd, 0x02, 0x00, 0xaa, 0xbb]));
+        // ... as are truncated and over-long inputs.
+        assert!(!roundtrip_check::<u32>(&[1, 2, 3]));
+        assert!(!roundtrip_check::<u32>(&[1, 2, 3, 4, 5]));
+
+        // Any non-zero byte decodes to `true`, but only 1 is canonical.
+        assert!(roundtrip_check::<bool>(&[1]));
+        assert!(!roundtrip_check::<bool>(&[2]));
+    }
+
+    #[test]
+    fn serialized_size_test() {
+        use blockdata::constants::genesis_block;
+        use hashes::hex::FromHex;
+        use network::constants::Network;
+
+        let genesis = genesis_block(Network::Bitcoin);
+        assert_eq!(serialized_size(&genesis.header), 80);
+        assert_eq!(serialized_size(&genesis), serialize(&genesis).len());
+        assert_eq!(serialized_size(&genesis), genesis.get_size());
+        assert_eq!(serialized_size(&genesis.txdata[0]), serialize(&genesis.txdata[0]).len());
+
+        let segwit_tx: Transaction = deserialize(&Vec::from_hex(
+            "02000000000101595895ea20179de87052b4046dfe6fd515860505d6511a9004cf12a1f93cac7c01000000\
+            00ffffffff01deb807000000000017a9140f3444e271620c736808aa7b33e370bd87cb5a078702483045022\
+            100fb60dad8df4af2841adc0346638c16d0b8035f5e3f3753b88db122e70c79f9370220756e6633b17fd271\
+            0e626347d28d60b0a2d6cbb41de51740644b9fb3ba7751040121028fa937ca8cba2197a37c007176ed89410\
+            55d3bcb8627d085e94553e62f057dcc00000000"
+        ).unwrap()).unwrap();
+        assert_eq!(serialized_size(&segwit_tx), serialize(&segwit_tx).len());
+        assert_eq!(serialized_size(&segwit_tx), segwit_tx.get_size());
+
+        assert_eq!(serialized_size(&VarInt(0x10000)), 5);
+        assert_eq!(serialized_size(&vec![1u8; 300]), 303);
+    }
+
+    #[test]
+    fn checksummed_writer_test() {
+        use blockdata::constants::genesis_block;
+        use network::constants::Network;
+
+        let genesis = genesis_block(Network::Bitcoin);
+        let payload = serialize(&genesis);
+
+        let mut writer = ChecksummedWriter::new(Vec::new());
+        assert_eq!(writer.checksum(), sha2_checksum(&[]));
+        genesis.header.consensus_encode(&mut writer).unwrap();
+        assert_eq!(writer.checksum(), sha2_checksum(&payload[..80]));
+        genesis.txdata.consensus_encode(&mut writer).unwrap();
+        let (written, checksum) = writer.finish();
+        assert_eq!(written, payload);
+        assert_eq!(checksum, sha2_checksum(&payload));
+
+        // Without keeping the payload.
+        let mut writer = ChecksummedWriter::new(io::sink());
+        let len = genesis.consensus_encode(&mut writer).unwrap();
+        assert_eq!(len, payload.len());
+        assert_eq!(writer.checksum(), sha2_checksum(&payload));
+
+        let framed = serialize(&CheckedData(payload));
+        assert_eq!(&framed[4..8], &checksum[..]);
+    }
+
+    #[test]
+    fn size_hint_test() {
+        use blockdata::block::Block;
+        use blockdata::constants::genesis_block;
+        use hashes::hex::FromHex;
+        use network::constants::Network;
+        use std::rc::Rc;
+
+        let genesis = genesis_block(Network::Bitcoin);
+        assert_eq!(genesis.size_hint(), serialized_size(&genesis));
+        assert_eq!(genesis.txdata[0].size_hint(), serialized_size(&genesis.txdata[0]));
+        assert_eq!(Rc::new(genesis.clone()).size_hint(), serialized_size(&genesis));
+        assert_eq!(VarInt(1).size_hint(), 0);
+
+        let tx_bytes = Vec::from_hex(
+            "02000000000101595895ea20179de87052b4046dfe6fd515860505d6511a9004cf12a1f93cac7c01000000\
+            00ffffffff01deb807000000000017a9140f3444e271620c736808aa7b33e370bd87cb5a078702483045022\
+            100fb60dad8df4af2841adc0346638c16d0b8035f5e3f3753b88db122e70c79f9370220756e6633b17fd271\
+            0e626347d28d60b0a2d6cbb41de51740644b9fb3ba7751040121028fa937ca8cba2197a37c007176ed89410\
+            55d3bcb8627d085e94553e62f057dcc00000000"
+        ).unwrap();
+        let segwit_tx: Transaction = deserialize(&tx_bytes).unwrap();
+        assert_eq!(segwit_tx.size_hint(), tx_bytes.len());
+        assert_eq!(serialize(&segwit_tx), tx_bytes);
+
+        let mut block = genesis.clone();
+        block.txdata.push(segwit_tx);
+        let block_bytes = serialize(&block);
+        assert_eq!(block.size_hint(), block_bytes.len());
+        assert_eq!(deserialize::<Block>(&block_bytes).unwrap(), block);
+    }
+
+    #[test]
+    fn endian_read_write_test() {
+        use super::{read_u16_le, read_u16_be, read_u32_le, read_u32_be, read_u64_le, read_u64_be};
+        use super::{write_u16_le, write_u16_be, write_u32_le, write_u32_be, write_u64_le, write_u64_be};
+
+        let data = [0xde, 0xad, 0xbe, 0xef, 0x1b, 0xad, 0xca, 0xfe];
+
+        assert_eq!(read_u16_le(&data[..]).unwrap(), 0xadde);
+        assert_eq!(read_u16_be(&data[..]).unwrap(), 0xdead);
+        assert_eq!(read_u32_le(&data[..]).unwrap(), 0xefbeadde);
+        assert_eq!(read_u32_be(&data[..]).unwrap(), 0xdeadbeef);
+        assert_eq!(read_u64_le(&data[..]).unwrap(), 0xfecaad1befbeadde);
+        assert_eq!(read_u64_be(&data[..]).unwrap(), 0xdeadbeef1badcafe);
+
+        // readers advance
+        let mut cursor = io::Cursor::new(&data[..]);
+        assert_eq!(read_u32_be(&mut cursor).unwrap(), 0xdeadbeef);
+        assert_eq!(read_u32_le(&mut cursor).unwrap(), 0xfecaad1b);
+        assert!(read_u16_le(&mut cursor).is_err());
+        assert!(read_u64_be(&data[..7]).is_err());
+
+        let mut out = vec![];
+        assert_eq!(write_u16_le(&mut out, 0xdead).unwrap(), 2);
+        assert_eq!(write_u16_be(&mut out, 0xdead).unwrap(), 2);
+        assert_eq!(out, vec![0xad, 0xde, 0xde, 0xad]);
+
+        let mut out = vec![];
+        assert_eq!(write_u32_le(&mut out, 0xdeadbeef).unwrap(), 4);
+        assert_eq!(write_u32_be(&mut out, 0xdeadbeef).unwrap(), 4);
+        assert_eq!(out, vec![0xef, 0xbe, 0xad, 0xde, 0xde, 0xad, 0xbe, 0xef]);
+
+        let mut out = vec![];
+        assert_eq!(write_u64_le(&mut out, 0xdeadbeef1badcafe).unwrap(), 8);
+        assert_eq!(write_u64_be(&mut out, 0xdeadbeef1badcafe).unwrap(), 8);
+        assert_eq!(out[..8], [0xfe, 0xca, 0xad, 0x1b, 0xef, 0xbe, 0xad, 0xde]);
+        assert_eq!(out[8..], data);
+    }
+
     #[test]
     fn serialize_varint_test() {
         assert_eq!(serialize(&VarInt(10)), vec![10u8]);
@@ -20,6 +20,8 @@
 
 pub mod encode;
 pub mod params;
+pub mod signet;
+pub mod validation;
 
 pub use self::encode::{Encodable, Decodable, WriteExt, ReadExt};
 pub use self::encode::{serialize, deserialize, deserialize_partial};
@@ -17,6 +17,18 @@
 //! This module provides predefined set of parameters for different chains.
 //!
 
+#[cfg(not(feature = "global-context"))]
+use secp256k1::Secp256k1;
+
+use blockdata::block::{Block, BlockHeader, CompactTarget};
+use blockdata::constants::{
+    genesis_block, max_money, COIN_VALUE, MAX_BLOCK_SIGOPS_COST, MAX_OP_RETURN_RELAY, MAX_SCRIPT_ELEMENT_SIZE,
+    MAX_STANDARD_TX_WEIGHT,
+};
+use blockdata::script::Script;
+use consensus::signet;
+use hash_types::BlockHash;
+use hashes::hex::FromHex;
 use network::constants::Network;
 use util::uint::Uint256;
 
@@ -49,11 +61,70 @@ const MAX_BITS_REGTEST: Uint256 = Uint256([
     0x7fffff0000000000u64,
 ]);
 
+/// Challenge of the default global signet, a 1-of-2 multisig.
+const SIGNET_CHALLENGE: &str = "512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d1e086be430210359ef5021964fe22d6f8e05b2463c9540ce96883fe3b278760f048f5189f2e6c452ae";
+
+/// Number of blocks used for the median time past calculation.
+const MEDIAN_TIME_SPAN: usize = 11;
+
+/// Mask of the top three version bits, which must be `001` for a version to signal BIP9 bits.
+const VERSIONBITS_TOP_MASK: i32 = 0xE0000000u32 as i32;
+/// Expected value of the top three bits of a BIP9 version.
+const VERSIONBITS_TOP_BITS: i32 = 0x20000000;
+
+/// A BIP9 soft-fork deployment signalled through block version bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deployment {
+    /// Name of the deployment.
+    pub name: &'static str,
+    /// Version bit used to signal readiness.
+    pub bit: u8,
+    /// Median time past from which signalling is counted.
+    pub start_time: u32,
+    /// Median time past at which the deployment fails if it did not lock in.
+    pub timeout: u32,
+    /// Lowest height at which the deployment may become active once locked in.
+    pub min_activation_height: u32,
+}
+
+/// The BIP9 state of a deployment for a given block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThresholdState {
+    /// The deployment's start time has not been reached.
+    Defined,
+    /// Blocks are counted for signalling.
+    Started,
+    /// The threshold was reached in the previous window; the rules activate after this one.
+    LockedIn,
+    /// The new rules are enforced.
+    Active,
+    /// The timeout passed without the deployment locking in.
+    Failed,
+}
+
+/// Description of a chain which is not one of the predefined networks, such
+/// as a private signet, for [`Params::from_custom`].
+///
+/// [`Params::from_custom`]: struct.Params.html#method.from_custom
+#[derive(Debug, Clone)]
+pub struct CustomNetworkConfig {
+    /// Predefined network whose parameters the chain starts from, and whose
+    /// address and message formats it uses.
+    pub base: Network,
+    /// Header of the first block of the chain.
+    pub genesis_header: BlockHeader,
+    /// Easiest target allowed on the chain.
+    pub pow_limit: Uint256,
+}
+
 #[derive(Debug, Clone)]
 /// Parameters that influence chain consensus.
 pub struct Params {
-    /// Network for which parameters are valid.
+    /// Network for which parameters are valid. For custom chains this is the
+    /// network whose address and message formats the chain uses.
     pub network: Network,
+    /// Header of the first block of the chain.
+    pub genesis_header: BlockHeader,
     /// Time when BIP16 becomes active.
     pub bip16_time: u32,
     /// Block height at which BIP34 becomes active.
@@ -62,6 +133,10 @@ pub struct Params {
     pub bip65_height: u32,
     /// Block height at which BIP66 becomes active.
     pub bip66_height: u32,
+    /// Block height at which CSV (BIP68, BIP112 and BIP113) becomes active.
+    pub csv_height: u32,
+    /// Block height at which segwit (BIP141, BIP143 and BIP147) becomes active.
+    pub segwit_height: u32,
     /// Minimum blocks including miner confirmation of the total of 2016 blocks in a retargeting period,
     /// (nPowTargetTimespan / nPowTargetSpacing) which is also used for BIP9 deployments.
     /// Examples: 1916 for 95%, 1512 for testchains.
@@ -85,6 +160,22 @@ pub struct Params {
     pub allow_min_difficulty_blocks: bool,
     /// Determines whether retargeting is disabled for this network or not.
     pub no_pow_retargeting: bool,
+    /// Number of blocks a coinbase output has to be buried under before it can be spent.
+    pub coinbase_maturity: u32,
+    /// Number of blocks after which the block subsidy is halved.
+    pub subsidy_halving_interval: u32,
+    /// Largest scriptSig size in bytes which is relayed by default (policy rule).
+    pub max_standard_scriptsig_size: usize,
+    /// How many seconds a block timestamp may be ahead of the local time.
+    pub max_future_block_time: u32,
+    /// BIP9 deployments which are tracked through version bits on this network.
+    pub deployments: Vec<Deployment>,
+    /// Script which the solutions of signet blocks (BIP325) must satisfy, or
+    /// `None` if the network is not a signet.
+    pub signet_challenge: Option<Script>,
+    /// Heights and hashes of blocks known to be in the best chain, ordered by
+    /// height, starting with the genesis block.
+    pub checkpoints: Vec<(u32, BlockHash)>,
 }
 
 impl Params {
@@ -93,10 +184,13 @@ impl Params {
         match network {
             Network::Bitcoin => Params {
                 network: Network::Bitcoin,
+                genesis_header: genesis_block(Network::Bitcoin).header,
                 bip16_time: 1333238400,                 // Apr 1 2012
                 bip34_height: 227931, // 000000000000024b89b42a942fe0d9fea3bb44ab7bd1b19115dd6a759c0808b8
                 bip65_height: 388381, // 000000000000000004c2b624ed5d7756c508d90fd0da2c7c679febfa6c4735f0
                 bip66_height: 363725, // 00000000000000000379eaa19dce8c9b722d46ae6a57c2f1a988119488b50931
+                csv_height: 419328, // 000000000000000004a1b34462cb8aeebd5799177f7a29cf28f2d1961716b5b5
+                segwit_height: 481824, // 0000000000000000001c8018d9cb3b742ef25114f27563e3fc4a1902167f9893
                 rule_change_activation_threshold: 1916, // 95%
                 miner_confirmation_window: 2016,
                 pow_limit: MAX_BITS_BITCOIN,
@@ -104,13 +198,60 @@ impl Params {
                 pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
                 allow_min_difficulty_blocks: false,
                 no_pow_retargeting: false,
+                coinbase_maturity: 100,
+                subsidy_halving_interval: 210000,
+                max_standard_scriptsig_size: 1650,
+                max_future_block_time: 2 * 60 * 60,     // 2 hours.
+                deployments: vec![
+                    Deployment {
+                        name: "csv",
+                        bit: 0,
+                        start_time: 1462060800, // May 1st, 2016
+                        timeout: 1493596800,    // May 1st, 2017
+                        min_activation_height: 0,
+                    },
+                    Deployment {
+                        name: "segwit",
+                        bit: 1,
+                        start_time: 1479168000, // November 15th, 2016
+                        timeout: 1510704000,    // November 15th, 2017
+                        min_activation_height: 0,
+                    },
+                    Deployment {
+                        name: "taproot",
+                        bit: 2,
+                        start_time: 1619222400, // April 24th, 2021
+                        timeout: 1628640000,    // August 11th, 2021
+                        min_activation_height: 709632,
+                    },
+                ],
+                signet_challenge: None,
+                checkpoints: checkpoints(&[
+                    (0, "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"),
+                    (11111, "0000000069e244f73d78e8fd29ba2fd2ed618bd6fa2ee92559f542fdb26e7c1d"),
+                    (33333, "000000002dd5588a74784eaa7ab0507a18ad16a236e7b1ce69f00d7ddfb5d0a6"),
+                    (74000, "0000000000573993a3c9e41ce34471c079dcf5f52a0e824a81e7f953b8661a20"),
+                    (105000, "00000000000291ce28027faea320c8d2b054b2e0fe44a773f3eefb151d6bdc97"),
+                    (134444, "00000000000005b12ffd4cd315cd34ffd4a594f430ac814c91184a0d42d2b0fe"),
+                    (168000, "000000000000099e61ea72015e79632f216fe6cb33d7899acb35b75c8303b763"),
+                    (193000, "000000000000059f452a5f7340de6682a977387c17010ff6e6c3bd83ca8b1317"),
+                    (210000, "000000000000048b95347e83192f69cf0366076336c639f9b7228e9ba171342e"),
+                    (216116, "00000000000001b4f4b433e81ee46494af945cf96014816a4e2370f11b23df4e"),
+                    (225430, "00000000000001c108384350f74090433e7fcf79a606b8e797f065b130575932"),
+                    (250000, "000000000000003887df1f29024b06fc2200b55f8af8f35453d7be294df2d214"),
+                    (279000, "0000000000000001ae8c72a0b0c301f67e3afca10e819efa9041e458e9bd7e40"),
+                    (295000, "00000000000000004d9b4ef50f0f9d686fd69db2e03af35a100370c64632a983"),
+                ]),
             },
             Network::Testnet => Params {
                 network: Network::Testnet,
+                genesis_header: genesis_block(Network::Testnet).header,
                 bip16_time: 1333238400,                 // Apr 1 2012
                 bip34_height: 21111, // 0000000023b3a96d3484e5abb3755c413e7d41500f8e2a5c3f0dd01299cd8ef8
                 bip65_height: 581885, // 00000000007f6655f22f98e72ed80d8b06dc761d5da09df0fa1dc4be4f861eb6
                 bip66_height: 330776, // 000000002104c8c45e99a8853285a3b592602a3ccde2b832481da85e9e4ba182
+                csv_height: 770112, // 00000000025e930139bac5c6c31a403776da130831ab85be56578f3fa75369bb
+                segwit_height: 834624, // 00000000002b980fcd729daaa248fd9316a5200e9b367f4ff2c42453e84201ca
                 rule_change_activation_threshold: 1512, // 75%
                 miner_confirmation_window: 2016,
                 pow_limit: MAX_BITS_TESTNET,
@@ -118,13 +259,48 @@ impl Params {
                 pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
                 allow_min_difficulty_blocks: true,
                 no_pow_retargeting: false,
+                coinbase_maturity: 100,
+                subsidy_halving_interval: 210000,
+                max_standard_scriptsig_size: 1650,
+                max_future_block_time: 2 * 60 * 60,     // 2 hours.
+                deployments: vec![
+                    Deployment {
+                        name: "csv",
+                        bit: 0,
+                        start_time: 1456790400, // March 1st, 2016
+                        timeout: 1493596800,    // May 1st, 2017
+                        min_activation_height: 0,
+                    },
+                    Deployment {
+                        name: "segwit",
+                        bit: 1,
+                        start_time: 1462060800, // May 1st, 2016
+                        timeout: 1493596800,    // May 1st, 2017
+                        min_activation_height: 0,
+                    },
+                    Deployment {
+                        name: "taproot",
+                        bit: 2,
+                        start_time: 1619222400, // April 24th, 2021
+                        timeout: 1628640000,    // August 11th, 2021
+                        min_activation_height: 0,
+                    },
+                ],
+                signet_challenge: None,
+                checkpoints: checkpoints(&[
+                    (0, "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943"),
+                    (546, "000000002a936ca763904c3c35fce2f3556c559c0214345d31b1bcebf76acb70"),
+                ]),
             },
             Network::Signet => Params {
                 network: Network::Signet,
+                genesis_header: genesis_block(Network::Signet).header,
                 bip16_time: 1333238400,                 // Apr 1 2012
                 bip34_height: 1,
                 bip65_height: 1,
                 bip66_height: 1,
+                csv_height: 1,
+                segwit_height: 1,
                 rule_change_activation_threshold: 1916, // 95%
                 miner_confirmation_window: 2016,
                 pow_limit: MAX_BITS_SIGNET,
@@ -132,13 +308,27 @@ impl Params {
                 pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
                 allow_min_difficulty_blocks: false,
                 no_pow_retargeting: false,
+                coinbase_maturity: 100,
+                subsidy_halving_interval: 210000,
+                max_standard_scriptsig_size: 1650,
+                max_future_block_time: 2 * 60 * 60,     // 2 hours.
+                deployments: vec![], // all deployments are always active
+                signet_challenge: Some(Script::from(
+                    Vec::from_hex(SIGNET_CHALLENGE).expect("valid hex")
+                )),
+                checkpoints: checkpoints(&[
+                    (0, "00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6"),
+                ]),
             },
             Network::Regtest => Params {
                 network: Network::Regtest,
+                genesis_header: genesis_block(Network::Regtest).header,
                 bip16_time: 1333238400,  // Apr 1 2012
                 bip34_height: 100000000, // not activated on regtest
                 bip65_height: 1351,
                 bip66_height: 1251,                    // used only in rpc tests
+                csv_height: 1,    // always active unless overridden
+                segwit_height: 0, // always active unless overridden
                 rule_change_activation_threshold: 108, // 75%
                 miner_confirmation_window: 144,
                 pow_limit: MAX_BITS_REGTEST,
@@ -146,12 +336,557 @@ impl Params {
                 pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
                 allow_min_difficulty_blocks: true,
                 no_pow_retargeting: true,
+                coinbase_maturity: 100,
+                subsidy_halving_interval: 150,
+                max_standard_scriptsig_size: 1650,
+                max_future_block_time: 2 * 60 * 60,     // 2 hours.
+                deployments: vec![], // all deployments are always active
+                signet_challenge: None,
+                checkpoints: checkpoints(&[
+                    (0, "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206"),
+                ]),
             },
         }
     }
 
+    /// Creates parameters for a custom chain.
+    ///
+    /// All parameters not given in `config` are those of `config.base`, and can
+    /// be adjusted on the returned value. The checkpoints of `config.base` do
+    /// not apply to the chain, so the only checkpoint is the genesis block.
+    pub fn from_custom(config: CustomNetworkConfig) -> Self {
+        Params {
+            genesis_header: config.genesis_header,
+            pow_limit: config.pow_limit,
+            checkpoints: vec![(0, config.genesis_header.block_hash())],
+            ..Params::new(config.base)
+        }
+    }
+
+    /// Checks the signet solution of `block` against [`Params::signet_challenge`].
+    ///
+    /// The genesis block needs no solution. Unless the `global-context`
+    /// feature is enabled, this creates a new secp256k1 context on every call,
+    /// use [`signet::validate_block`] to validate many blocks.
+    ///
+    /// [`Params::signet_challenge`]: #structfield.signet_challenge
+    /// [`signet::validate_block`]: ../signet/fn.validate_block.html
+    pub fn validate_signet_block(&self, block: &Block) -> Result<(), signet::Error> {
+        let challenge = self.signet_challenge.as_ref().ok_or(signet::Error::NotSignet)?;
+        if block.header.block_hash() == self.genesis_header.block_hash() {
+            return Ok(());
+        }
+        #[cfg(feature = "global-context")]
+        let secp = ::util::ecdsa::secp256k1_context();
+        #[cfg(not(feature = "global-context"))]
+        let secp = &Secp256k1::verification_only();
+        signet::validate_block(secp, block, challenge)
+    }
+
+    /// Checks whether BIP34 (block height in coinbase) is enforced at the given height.
+    pub fn bip34_active(&self, height: u32) -> bool {
+        height >= self.bip34_height
+    }
+
+    /// Checks whether segwit is enforced at the given height.
+    pub fn segwit_active(&self, height: u32) -> bool {
+        height >= self.segwit_height
+    }
+
+    /// Returns the compact encoding of [`Params::pow_limit`], as used in the
+    /// headers of minimum difficulty blocks.
+    ///
+    /// [`Params::pow_limit`]: #structfield.pow_limit
+    pub fn pow_limit_bits(&self) -> CompactTarget {
+        CompactTarget::from_target(&self.pow_limit)
+    }
+
+    /// Returns the maximum size in bytes of a single element pushed in a script.
+    ///
+    /// This is the same on all networks supported here, see
+    /// [`MAX_SCRIPT_ELEMENT_SIZE`].
+    ///
+    /// [`MAX_SCRIPT_ELEMENT_SIZE`]: ../../blockdata/constants/constant.MAX_SCRIPT_ELEMENT_SIZE.html
+    pub fn max_script_element_size(&self) -> usize {
+        MAX_SCRIPT_ELEMENT_SIZE
+    }
+
+    /// Returns the maximum weight of a transaction which is relayed by default.
+    ///
+    /// This is the same on all networks supported here, see
+    /// [`MAX_STANDARD_TX_WEIGHT`].
+    ///
+    /// [`MAX_STANDARD_TX_WEIGHT`]: ../../blockdata/constants/constant.MAX_STANDARD_TX_WEIGHT.html
+    pub fn max_standard_tx_weight(&self) -> u32 {
+        MAX_STANDARD_TX_WEIGHT
+    }
+
+    /// Returns the maximum signature operation cost of a block.
+    ///
+    /// This is the same on all networks supported here, see
+    /// [`MAX_BLOCK_SIGOPS_COST`].
+    ///
+    /// [`MAX_BLOCK_SIGOPS_COST`]: ../../blockdata/constants/constant.MAX_BLOCK_SIGOPS_COST.html
+    pub fn max_block_sigops(&self) -> u32 {
+        MAX_BLOCK_SIGOPS_COST
+    }
+
+    /// Returns the maximum size in bytes of the data carried by an `OP_RETURN`
+    /// output which is relayed by default.
+    ///
+    /// This is the same on all networks supported here, see
+    /// [`MAX_OP_RETURN_RELAY`].
+    ///
+    /// [`MAX_OP_RETURN_RELAY`]: ../../blockdata/constants/constant.MAX_OP_RETURN_RELAY.html
+    pub fn max_op_return_relay(&self) -> usize {
+        MAX_OP_RETURN_RELAY
+    }
+
+    /// Returns the largest amount in satoshis an output, or the outputs of a
+    /// transaction together, may have.
+    ///
+    /// This is the same on all networks supported here, see [`max_money`].
+    ///
+    /// [`max_money`]: ../../blockdata/constants/fn.max_money.html
+    pub fn max_money(&self) -> u64 {
+        max_money(self.network)
+    }
+
+    /// Calculates the subsidy in satoshis a coinbase at the given height may
+    /// claim in addition to the fees of the block's transactions.
+    pub fn block_subsidy(&self, height: u32) -> u64 {
+        let halvings = height / self.subsidy_halving_interval;
+        if halvings >= 64 {
+            0
+        } else {
+            (50 * COIN_VALUE) >> halvings
+        }
+    }
+
     /// Calculates the number of blocks between difficulty adjustments.
     pub fn difficulty_adjustment_interval(&self) -> u64 {
         self.pow_target_timespan / self.pow_target_spacing
     }
+
+    /// Computes the compact target a block following `headers` and timestamped
+    /// `time` has to commit to, following Bitcoin Core's `GetNextWorkRequired`.
+    ///
+    /// `headers` must be the chain from the genesis block up to the current tip, so that
+    /// `headers[n]` is the header at height `n`.
+    ///
+    /// The target only changes every [`difficulty_adjustment_interval`] blocks, and
+    /// never on networks with [`no_pow_retargeting`] set, where the bits of the tip
+    /// are returned regardless of how long the last period took.
+    ///
+    /// # Panics
+    ///
+    /// If `headers` is empty.
+    ///
+    /// [`difficulty_adjustment_interval`]: #method.difficulty_adjustment_interval
+    /// [`no_pow_retargeting`]: #structfield.no_pow_retargeting
+    pub fn next_work_required(&self, headers: &[BlockHeader], time: u32) -> CompactTarget {
+        let tip = *headers.last().expect("next_work_required called without a tip");
+        self.next_bits_with(headers.len() as u32, |height| {
+            if height as usize == headers.len() {
+                BlockHeader { time, ..tip }
+            } else {
+                headers[height as usize]
+            }
+        })
+    }
+
+    /// Computes the target the block at `height` has to meet, like
+    /// [`next_work_required`], fetching headers of the chain through `get_header`.
+    ///
+    /// `get_header(h)` must return the header at height `h` of the chain the new block
+    /// extends. It is only called for the heights the computation needs: the tip and the
+    /// first block of the period at adjustment boundaries, and the preceding headers of
+    /// the current period on networks allowing minimum difficulty blocks. On those
+    /// networks it is also called with `height` itself, as the decision depends on the
+    /// timestamp of the new block.
+    ///
+    /// # Panics
+    ///
+    /// If `height` is zero.
+    ///
+    /// [`next_work_required`]: #method.next_work_required
+    pub fn next_work_required_with<F>(&self, height: u32, get_header: F) -> Uint256
+    where
+        F: Fn(u32) -> BlockHeader,
+    {
+        self.next_bits_with(height, get_header).to_target()
+    }
+
+    pub(crate) fn next_bits_with<F>(&self, height: u32, get_header: F) -> CompactTarget
+    where
+        F: Fn(u32) -> BlockHeader,
+    {
+        assert!(height > 0, "the genesis block has no previous target");
+        let interval = self.difficulty_adjustment_interval() as u32;
+        let tip = get_header(height - 1);
+
+        if height % interval != 0 {
+            if self.allow_min_difficulty_blocks {
+                let pow_limit_bits = self.pow_limit_bits();
+                // A block more than twice the target spacing after its parent may be
+                // mined at minimum difficulty.
+                let time = get_header(height).time;
+                if time as u64 > tip.time as u64 + self.pow_target_spacing * 2 {
+                    return pow_limit_bits;
+                }
+                // Otherwise it must commit to the last target which was not the result
+                // of the rule above.
+                let mut last = tip;
+                let mut last_height = height - 1;
+                while last_height % interval != 0 && last.bits == pow_limit_bits {
+                    last_height -= 1;
+                    last = get_header(last_height);
+                }
+                return last.bits;
+            }
+            return tip.bits;
+        }
+
+        if self.no_pow_retargeting {
+            return tip.bits;
+        }
+
+        let first = get_header(height - interval);
+        let timespan = self.pow_target_timespan;
+        let actual = (tip.time as i64 - first.time as i64)
+            .max(timespan as i64 / 4)
+            .min(timespan as i64 * 4) as u32;
+        let target = tip.bits.to_target().mul_u32(actual) / Uint256::from_u64(timespan).unwrap();
+        if target > self.pow_limit {
+            self.pow_limit_bits()
+        } else {
+            CompactTarget::from_target(&target)
+        }
+    }
+
+    /// Looks up a tracked BIP9 deployment by name.
+    pub fn deployment(&self, name: &str) -> Option<&Deployment> {
+        self.deployments.iter().find(|d| d.name == name)
+    }
+
+    /// Computes the BIP9 state of `deployment` for the block following `headers`.
+    ///
+    /// `headers` must be the chain from the genesis block up to the current tip, so that
+    /// `headers[n]` is the header at height `n`. The state machine advances once per
+    /// `miner_confirmation_window` blocks, locking in when at least
+    /// `rule_change_activation_threshold` blocks of a window signal the deployment's bit.
+    pub fn bip9_state(&self, headers: &[BlockHeader], deployment: &Deployment) -> ThresholdState {
+        let window = self.miner_confirmation_window as usize;
+        let mut state = ThresholdState::Defined;
+
+        // The state only changes on window boundaries, where it is computed from the last
+        // block of the previous window.
+        let mut period_end = window;
+        while period_end <= headers.len() {
+            let period = &headers[period_end - window..period_end];
+            let mtp = median_time_past(&headers[..period_end]);
+            state = match state {
+                ThresholdState::Defined if mtp >= deployment.start_time => ThresholdState::Started,
+                ThresholdState::Started => {
+                    let count = period.iter().filter(|h| signals_bit(h.version, deployment.bit)).count();
+                    if count >= self.rule_change_activation_threshold as usize {
+                        ThresholdState::LockedIn
+                    } else if mtp >= deployment.timeout {
+                        ThresholdState::Failed
+                    } else {
+                        ThresholdState::Started
+                    }
+                }
+                ThresholdState::LockedIn if period_end as u32 >= deployment.min_activation_height => {
+                    ThresholdState::Active
+                }
+                other => other,
+            };
+            period_end += window;
+        }
+        state
+    }
+}
+
+/// Parses a list of checkpoints with hashes in hex.
+fn checkpoints(list: &[(u32, &str)]) -> Vec<(u32, BlockHash)> {
+    list.iter()
+        .map(|&(height, hash)| (height, BlockHash::from_hex(hash).expect("valid hex")))
+        .collect()
+}
+
+/// Median of the timestamps of the last (up to) 11 headers of `headers`.
+pub(crate) fn median_time_past(headers: &[BlockHeader]) -> u32 {
+    let start = headers.len().saturating_sub(MEDIAN_TIME_SPAN);
+    let mut times: Vec<u32> = headers[start..].iter().map(|h| h.time).collect();
+    times.sort();
+    times[times.len() / 2]
+}
+
+/// Whether a block version signals readiness for the given BIP9 bit. Only
+/// bits 0 to 28 can be signalled.
+pub(crate) fn signals_bit(version: i32, bit: u8) -> bool {
+    bit < 29 && version & VERSIONBITS_TOP_MASK == VERSIONBITS_TOP_BITS && version & (1 << bit) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CustomNetworkConfig, Deployment, Params, ThresholdState};
+
+    use blockdata::block::{BlockHeader, CompactTarget};
+    use blockdata::constants::{genesis_block, COIN_VALUE};
+    use network::constants::Network;
+
+    const DEPLOYMENT: Deployment = Deployment {
+        name: "test",
+        bit: 28,
+        start_time: 10_000,
+        timeout: 20_000,
+        min_activation_height: 0,
+    };
+
+    /// Builds a chain of `n` headers ten seconds apart, the ones at `signalling` heights
+    /// setting the deployment's bit.
+    fn chain(n: usize, signalling: &dyn Fn(usize) -> bool) -> Vec<BlockHeader> {
+        let genesis = genesis_block(Network::Regtest).header;
+        (0..n).map(|height| BlockHeader {
+            version: if signalling(height) { 0x20000000 | 1 << DEPLOYMENT.bit } else { 0x20000000 },
+            time: 9_000 + 10 * height as u32,
+            ..genesis
+        }).collect()
+    }
+
+    #[test]
+    fn bip9_state_machine() {
+        let params = Params::new(Network::Regtest);
+        assert_eq!(params.miner_confirmation_window, 144);
+        assert_eq!(params.rule_change_activation_threshold, 108);
+
+        // Window 0 ends with an MTP past the start time, so counting starts in window 1.
+        let headers = chain(144 * 4, &|h| h >= 144 && h < 144 + 108);
+        assert_eq!(params.bip9_state(&headers[..0], &DEPLOYMENT), ThresholdState::Defined);
+        assert_eq!(params.bip9_state(&headers[..143], &DEPLOYMENT), ThresholdState::Defined);
+        assert_eq!(params.bip9_state(&headers[..144], &DEPLOYMENT), ThresholdState::Started);
+        assert_eq!(params.bip9_state(&headers[..287], &DEPLOYMENT), ThresholdState::Started);
+        assert_eq!(params.bip9_state(&headers[..288], &DEPLOYMENT), ThresholdState::LockedIn);
+        assert_eq!(params.bip9_state(&headers[..431], &DEPLOYMENT), ThresholdState::LockedIn);
+        assert_eq!(params.bip9_state(&headers[..432], &DEPLOYMENT), ThresholdState::Active);
+        assert_eq!(params.bip9_state(&headers, &DEPLOYMENT), ThresholdState::Active);
+
+        // A signalling block short of the threshold, the wrong top bits, or the wrong bit.
+        let headers = chain(288, &|h| h >= 144 && h < 144 + 107);
+        assert_eq!(params.bip9_state(&headers, &DEPLOYMENT), ThresholdState::Started);
+        let mut headers = chain(288, &|h| h >= 144 && h < 144 + 108);
+        headers[150].version |= 0x40000000;
+        assert_eq!(params.bip9_state(&headers, &DEPLOYMENT), ThresholdState::Started);
+        let other = Deployment { bit: 27, ..DEPLOYMENT };
+        let headers = chain(288, &|h| h >= 144 && h < 144 + 108);
+        assert_eq!(params.bip9_state(&headers, &other), ThresholdState::Started);
+
+        // Activation is delayed until the minimum activation height.
+        let delayed = Deployment { min_activation_height: 576, ..DEPLOYMENT };
+        let headers = chain(144 * 5, &|h| h >= 144 && h < 144 + 108);
+        assert_eq!(params.bip9_state(&headers[..432], &delayed), ThresholdState::LockedIn);
+        assert_eq!(params.bip9_state(&headers[..576], &delayed), ThresholdState::Active);
+
+        // Without enough signalling the deployment fails once the MTP passes the timeout.
+        let headers = chain(144 * 8, &|_| false);
+        assert_eq!(params.bip9_state(&headers[..144 * 7], &DEPLOYMENT), ThresholdState::Started);
+        assert_eq!(params.bip9_state(&headers, &DEPLOYMENT), ThresholdState::Failed);
+    }
+
+    #[test]
+    fn activation_heights() {
+        let params = Params::new(Network::Bitcoin);
+        assert_eq!(params.csv_height, 419328);
+        assert!(!params.segwit_active(481823));
+        assert!(params.segwit_active(481824));
+
+        let params = Params::new(Network::Testnet);
+        assert!(!params.segwit_active(834623));
+        assert!(params.segwit_active(834624));
+
+        assert!(Params::new(Network::Signet).segwit_active(1));
+        assert!(Params::new(Network::Regtest).segwit_active(0));
+    }
+
+    #[test]
+    fn max_money() {
+        for &network in &[Network::Bitcoin, Network::Testnet, Network::Signet, Network::Regtest] {
+            assert_eq!(Params::new(network).max_money(), 21_000_000 * COIN_VALUE);
+        }
+    }
+
+    #[test]
+    fn next_work_required() {
+        // Retarget of mainnet block 32256, as in Bitcoin Core's `get_next_work` test.
+        let params = Params::new(Network::Bitcoin);
+        let mut headers = vec![genesis_block(Network::Bitcoin).header; 2016];
+        headers[0].time = 1261130161;
+        headers[2015].time = 1262152739;
+        assert_eq!(params.next_work_required(&headers, 1262153000).to_consensus(), 0x1d00d86a);
+        assert_eq!(params.next_work_required(&headers[..2015], 1262152739).to_consensus(), 0x1d00ffff);
+
+        // Blocks ten seconds apart quadruple the difficulty at most.
+        for (h, header) in headers.iter_mut().enumerate() {
+            header.time = 1261130161 + 10 * h as u32;
+        }
+        assert_eq!(params.next_work_required(&headers, 1261150311).to_consensus(), 0x1c3fffc0);
+
+        // Regtest keeps the difficulty constant across adjustment boundaries, however fast
+        // the blocks were mined.
+        let params = Params::new(Network::Regtest);
+        for &n in &[1, 2015, 2016, 2017] {
+            let headers = chain(n, &|_| false);
+            let time = headers[n - 1].time + 10;
+            assert_eq!(params.next_work_required(&headers, time), params.pow_limit_bits());
+        }
+    }
+
+    #[test]
+    fn next_work_required_with() {
+        use std::cell::RefCell;
+
+        // Testnet blocks every ten minutes, the last two at minimum difficulty.
+        let params = Params::new(Network::Testnet);
+        let genesis = genesis_block(Network::Testnet).header;
+        let header_at = |height: u32| BlockHeader {
+            time: genesis.time + 600 * height,
+            bits: if height >= 2018 { params.pow_limit_bits() } else { CompactTarget::from_consensus(0x1c0fffff) },
+            ..genesis
+        };
+        let fetched = RefCell::new(vec![]);
+        let get_header = |height| {
+            fetched.borrow_mut().push(height);
+            header_at(height)
+        };
+
+        // Walks back over the minimum difficulty blocks to the last real target.
+        let target = params.next_work_required_with(2020, get_header);
+        assert_eq!(target, CompactTarget::from_consensus(0x1c0fffff).to_target());
+        assert_eq!(*fetched.borrow(), vec![2019, 2020, 2018, 2017]);
+
+        // At the boundary only the tip and the first block of the period are needed.
+        fetched.borrow_mut().clear();
+        let retarget = params.next_work_required_with(2016, get_header);
+        assert_eq!(retarget, CompactTarget::from_consensus(0x1c0ffdf6).to_target());
+        assert_eq!(*fetched.borrow(), vec![2015, 0]);
+
+        // Agrees with the slice based version.
+        let headers: Vec<_> = (0..2020).map(header_at).collect();
+        assert_eq!(params.next_work_required(&headers, header_at(2020).time).to_target(), target);
+    }
+
+    #[test]
+    fn size_limits() {
+        for &network in &[Network::Bitcoin, Network::Testnet, Network::Signet, Network::Regtest] {
+            let params = Params::new(network);
+            assert_eq!(params.max_script_element_size(), 520);
+            assert_eq!(params.max_standard_tx_weight(), 400_000);
+            assert_eq!(params.max_block_sigops(), 80_000);
+            assert_eq!(params.max_op_return_relay(), 80);
+        }
+    }
+
+    #[test]
+    fn pow_limit_bits() {
+        for &network in &[Network::Bitcoin, Network::Testnet, Network::Signet, Network::Regtest] {
+            let params = Params::new(network);
+            assert_eq!(params.pow_limit_bits(), genesis_block(network).header.bits);
+            assert_eq!(params.pow_limit_bits().to_target(), params.pow_limit);
+        }
+    }
+
+    #[test]
+    fn block_subsidy() {
+        let params = Params::new(Network::Bitcoin);
+        assert_eq!(params.block_subsidy(0), 50_0000_0000);
+        assert_eq!(params.block_subsidy(209999), 50_0000_0000);
+        assert_eq!(params.block_subsidy(210000), 25_0000_0000);
+        assert_eq!(params.block_subsidy(630000), 6_2500_0000);
+        assert_eq!(params.block_subsidy(33 * 210000 - 1), 1);
+        assert_eq!(params.block_subsidy(33 * 210000), 0);
+        assert_eq!(params.block_subsidy(u32::max_value()), 0);
+
+        let params = Params::new(Network::Regtest);
+        assert_eq!(params.block_subsidy(149), 50_0000_0000);
+        assert_eq!(params.block_subsidy(150), 25_0000_0000);
+        assert_eq!(params.block_subsidy(150 * 64), 0);
+    }
+
+    #[test]
+    fn deployments() {
+        let params = Params::new(Network::Bitcoin);
+        let segwit = params.deployment("segwit").unwrap();
+        assert_eq!(segwit.bit, 1);
+        assert_eq!(params.deployment("taproot").unwrap().min_activation_height, 709632);
+        assert!(params.deployment("unknown").is_none());
+        assert!(Params::new(Network::Regtest).deployments.is_empty());
+    }
+
+    #[test]
+    fn max_future_block_time() {
+        for &network in &[Network::Bitcoin, Network::Testnet, Network::Signet, Network::Regtest] {
+            assert_eq!(Params::new(network).max_future_block_time, 7200);
+        }
+
+        // The limit is taken from the parameters.
+        let mut params = Params::new(Network::Regtest);
+        params.max_future_block_time = 60;
+        let header = genesis_block(Network::Regtest).header;
+        let now = header.time - 60;
+        assert!(header.check_timestamp(0, now, &params).is_ok());
+        assert!(header.check_timestamp(0, now - 1, &params).is_err());
+    }
+
+    #[test]
+    fn checkpoints() {
+        for &network in &[Network::Bitcoin, Network::Testnet, Network::Signet, Network::Regtest] {
+            let params = Params::new(network);
+            assert_eq!(params.checkpoints[0], (0, genesis_block(network).block_hash()));
+            assert!(params.checkpoints.windows(2).all(|w| w[0].0 < w[1].0));
+        }
+
+        let params = Params::new(Network::Bitcoin);
+        assert_eq!(params.checkpoints.len(), 14);
+        assert_eq!(
+            params.checkpoints.last().unwrap().1.to_string(),
+            "00000000000000004d9b4ef50f0f9d686fd69db2e03af35a100370c64632a983"
+        );
+        // Checkpointed hashes meet the target of their era.
+        assert!(params.checkpoints.iter().all(|&(_, hash)| hash[28..] == [0; 4]));
+    }
+
+    #[test]
+    fn custom_network() {
+        use blockdata::block::CompactTarget;
+        use util::headerchain::HeaderChain;
+        use util::uint::Uint256;
+
+        // A fictional signet with its own genesis block and an easier target.
+        let mut genesis_header = genesis_block(Network::Signet).header;
+        genesis_header.time = 1_700_000_000;
+        genesis_header.bits = CompactTarget::from_consensus(0x1f00ffff);
+        let pow_limit = genesis_header.target().to_uint256();
+        let mut params = Params::from_custom(CustomNetworkConfig {
+            base: Network::Signet,
+            genesis_header,
+            pow_limit,
+        });
+        params.bip34_height = 100;
+
+        assert_eq!(params.network, Network::Signet);
+        assert_eq!(params.pow_limit, Uint256::from_u64(0xffff).unwrap() << 224);
+        assert_eq!(params.pow_limit_bits(), genesis_header.bits);
+        assert_eq!(params.subsidy_halving_interval, Params::new(Network::Signet).subsidy_halving_interval);
+        assert!(!params.bip34_active(99));
+        assert_eq!(params.checkpoints, vec![(0, genesis_header.block_hash())]);
+
+        let chain = HeaderChain::new(params);
+        assert_eq!(chain.tip(), genesis_header.block_hash());
+        assert_ne!(chain.tip(), genesis_block(Network::Signet).block_hash());
+
+        let params = Params::new(Network::Testnet);
+        assert_eq!(params.genesis_header, genesis_block(Network::Testnet).header);
+    }
 }
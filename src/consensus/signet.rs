@@ -0,0 +1,518 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Signet block solutions
+//!
+//! Blocks of a signet (BIP325) must carry a solution to the network's
+//! challenge script in their coinbase witness commitment. The solution is
+//! checked as the spend of a virtual transaction which commits to the block.
+//!
+//! This library has no script interpreter, so only the challenges used in
+//! practice are supported: bare `k`-of-`n` multisig, as on the default global
+//! signet, and the trivial `OP_TRUE`.
+//!
+
+use std::{error, fmt};
+
+use secp256k1::{Message, Secp256k1, Signature, Verification};
+
+use blockdata::block::Block;
+use blockdata::opcodes;
+use blockdata::script::{Builder, Instruction, Script, ScriptError};
+use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut, Sequence};
+use consensus::encode::{deserialize, deserialize_partial, Encodable};
+use hash_types::TxMerkleNode;
+use util::ecdsa::is_strict_der;
+use util::hash::bitcoin_merkle_root;
+use util::key::PublicKey;
+
+/// Header of the push in the witness commitment output which holds the solution.
+pub const SIGNET_HEADER: [u8; 4] = [0xec, 0xc7, 0xda, 0xa2];
+
+/// Number of elements allowed on the stack, as `MAX_STACK_SIZE` in Bitcoin Core.
+const MAX_STACK_SIZE: usize = 1000;
+
+/// An error validating a signet block solution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The parameters have no signet challenge.
+    NotSignet,
+    /// The block has no transactions.
+    NoCoinbase,
+    /// The coinbase has no witness commitment output.
+    MissingCommitment,
+    /// The solution could not be decoded, or has trailing data.
+    InvalidSolution,
+    /// The challenge or solution uses script features which can not be evaluated.
+    Unsupported,
+    /// The solution does not satisfy the challenge.
+    Script(ScriptError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::NotSignet => f.write_str("network has no signet challenge"),
+            Error::NoCoinbase => f.write_str("block has no coinbase"),
+            Error::MissingCommitment => f.write_str("block has no witness commitment"),
+            Error::InvalidSolution => f.write_str("signet block solution parse failure"),
+            Error::Unsupported => f.write_str("unsupported signet challenge or solution"),
+            Error::Script(ref e) => write!(f, "invalid signet block solution: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::Script(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<ScriptError> for Error {
+    fn from(e: ScriptError) -> Error {
+        Error::Script(e)
+    }
+}
+
+/// The virtual transactions whose validity decides that of a signet block,
+/// as `SignetTxs` in Bitcoin Core.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignetTxs {
+    /// Transaction paying to the challenge, whose scriptSig commits to the block.
+    pub to_spend: Transaction,
+    /// Transaction spending `to_spend` with the solution from the block.
+    pub to_sign: Transaction,
+}
+
+impl SignetTxs {
+    /// Builds the virtual transactions of `block` for the given challenge.
+    ///
+    /// If the block carries no solution the scriptSig and witness of
+    /// `to_sign` are empty, so this can also be used to compute the signature
+    /// hash of a block which is yet to be signed.
+    pub fn new(block: &Block, challenge: &Script) -> Result<SignetTxs, Error> {
+        let coinbase = block.txdata.first().ok_or(Error::NoCoinbase)?;
+        let commitment_idx = coinbase.output.iter()
+            .rposition(|o| {
+                o.script_pubkey.len() >= 38 &&
+                o.script_pubkey[0..6] == [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed] })
+            .ok_or(Error::MissingCommitment)?;
+
+        let mut modified_cb = coinbase.clone();
+        let mut script_sig = Script::new();
+        let mut witness = vec![];
+        let commitment = &coinbase.output[commitment_idx].script_pubkey;
+        if let Some((cleared, solution)) = fetch_and_clear_solution(commitment) {
+            modified_cb.output[commitment_idx].script_pubkey = cleared;
+            let (sig, consumed) = deserialize_partial::<Script>(&solution)
+                .map_err(|_| Error::InvalidSolution)?;
+            script_sig = sig;
+            witness = deserialize(&solution[consumed..]).map_err(|_| Error::InvalidSolution)?;
+        }
+
+        let hashes = Some(modified_cb.txid().as_hash()).into_iter()
+            .chain(block.txdata.iter().skip(1).map(|tx| tx.txid().as_hash()))
+            .collect::<Vec<_>>();
+        let signet_merkle: TxMerkleNode = bitcoin_merkle_root(hashes.into_iter()).into();
+
+        let mut block_data = Vec::with_capacity(72);
+        block.header.version.consensus_encode(&mut block_data).expect("vecs don't error");
+        block.header.prev_blockhash.consensus_encode(&mut block_data).expect("vecs don't error");
+        signet_merkle.consensus_encode(&mut block_data).expect("vecs don't error");
+        block.header.time.consensus_encode(&mut block_data).expect("vecs don't error");
+
+        let to_spend = Transaction {
+            version: 0,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Builder::new()
+                    .push_opcode(opcodes::OP_FALSE)
+                    .push_slice(&block_data)
+                    .into_script(),
+                sequence: Sequence::ZERO,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 0,
+                script_pubkey: challenge.clone(),
+            }],
+        };
+        let to_sign = Transaction {
+            version: 0,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(to_spend.txid(), 0),
+                script_sig,
+                sequence: Sequence::ZERO,
+                witness,
+            }],
+            output: vec![TxOut {
+                value: 0,
+                script_pubkey: Builder::new().push_opcode(opcodes::all::OP_RETURN).into_script(),
+            }],
+        };
+        Ok(SignetTxs { to_spend, to_sign })
+    }
+
+    /// Checks that the solution in `to_sign` satisfies the challenge of `to_spend`.
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>) -> Result<(), Error> {
+        let challenge = &self.to_spend.output[0].script_pubkey;
+        let input = &self.to_sign.input[0];
+
+        input.script_sig.check_static()?;
+        let mut stack = vec![];
+        for instruction in input.script_sig.instructions() {
+            match instruction {
+                Ok(Instruction::PushBytes(data)) => stack.push(data.to_vec()),
+                Ok(Instruction::Op(_)) => return Err(Error::Unsupported),
+                Err(_) => return Err(Error::Script(ScriptError::BadOpcode)),
+            }
+        }
+        if stack.len() > MAX_STACK_SIZE {
+            return Err(Error::Script(ScriptError::StackSize));
+        }
+
+        challenge.check_static()?;
+        let success = if challenge.as_bytes() == [opcodes::OP_TRUE.into_u8()] {
+            true
+        } else {
+            let (required, keys) = parse_multisig(challenge).ok_or(Error::Unsupported)?;
+            self.check_multisig(secp, &stack, required, &keys)?
+        };
+        if !success {
+            return Err(Error::Script(ScriptError::EvalFalse));
+        }
+        // The challenge is not a witness program, so there must be no witness.
+        if !input.witness.is_empty() {
+            return Err(Error::Script(ScriptError::WitnessUnexpected));
+        }
+        Ok(())
+    }
+
+    /// Runs `OP_CHECKMULTISIG` on the stack left by the scriptSig, matching
+    /// signatures to keys from the top of the stack down like Bitcoin Core.
+    fn check_multisig<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        stack: &[Vec<u8>],
+        required: usize,
+        keys: &[&[u8]],
+    ) -> Result<bool, Error> {
+        if stack.len() < required + 1 {
+            return Err(Error::Script(ScriptError::InvalidStackOperation));
+        }
+        let dummy = &stack[stack.len() - required - 1];
+        let mut sigs = stack[stack.len() - required..].iter().rev().peekable();
+        let mut keys_left = keys.iter().rev();
+        let mut sigs_left = required;
+        let mut success = true;
+        while success && sigs_left > 0 {
+            let sig = *sigs.peek().expect("sigs_left counts the remaining signatures");
+            let key = keys_left.next().expect("at least sigs_left keys are left");
+            if !sig.is_empty() && !is_strict_der(sig) {
+                return Err(Error::Script(ScriptError::SigDer));
+            }
+            if self.check_sig(secp, sig, key) {
+                sigs.next();
+                sigs_left -= 1;
+            }
+            if sigs_left > keys_left.len() {
+                success = false;
+            }
+        }
+        if !dummy.is_empty() {
+            return Err(Error::Script(ScriptError::SigNullDummy));
+        }
+        Ok(success)
+    }
+
+    /// Checks a single ECDSA signature of `to_sign` against the challenge.
+    fn check_sig<C: Verification>(&self, secp: &Secp256k1<C>, sig: &[u8], key: &[u8]) -> bool {
+        let (hash_ty, der) = match sig.split_last() {
+            Some(split) => split,
+            None => return false,
+        };
+        let mut sig = match Signature::from_der(der) {
+            Ok(sig) => sig,
+            Err(_) => return false,
+        };
+        // libsecp256k1 only verifies low-S signatures, which is not a consensus rule.
+        sig.normalize_s();
+        let key = match PublicKey::from_slice(key) {
+            Ok(key) => key,
+            Err(_) => return false,
+        };
+        let challenge = &self.to_spend.output[0].script_pubkey;
+        let sighash = self.to_sign.signature_hash(0, challenge, u32::from(*hash_ty));
+        let msg = Message::from_slice(&sighash[..]).expect("sighashes are 32 bytes");
+        secp.verify(&msg, &sig, &key.key).is_ok()
+    }
+}
+
+/// Validates the solution of `block` against `challenge`.
+///
+/// Note that this does not exempt the genesis block, which has no solution,
+/// see [`Params::validate_signet_block`].
+///
+/// [`Params::validate_signet_block`]: ../params/struct.Params.html#method.validate_signet_block
+pub fn validate_block<C: Verification>(
+    secp: &Secp256k1<C>,
+    block: &Block,
+    challenge: &Script,
+) -> Result<(), Error> {
+    SignetTxs::new(block, challenge)?.verify(secp)
+}
+
+/// Finds the first push in `commitment` which starts with [`SIGNET_HEADER`]
+/// and is followed by data, as `FetchAndClearCommitmentSection` in Bitcoin Core.
+///
+/// Returns the script with that push cut down to the header, and the data
+/// following the header.
+///
+/// [`SIGNET_HEADER`]: constant.SIGNET_HEADER.html
+fn fetch_and_clear_solution(commitment: &Script) -> Option<(Script, Vec<u8>)> {
+    let mut replacement = Builder::new();
+    let mut solution = None;
+    for instruction in commitment.instructions() {
+        replacement = match instruction {
+            Ok(Instruction::PushBytes(data)) => {
+                if solution.is_none() && data.len() > SIGNET_HEADER.len() && data.starts_with(&SIGNET_HEADER) {
+                    solution = Some(data[SIGNET_HEADER.len()..].to_vec());
+                    replacement.push_slice(&SIGNET_HEADER)
+                } else {
+                    replacement.push_slice(data)
+                }
+            }
+            Ok(Instruction::Op(op)) => replacement.push_opcode(op),
+            // Like Bitcoin Core, stop at the first opcode which can not be parsed.
+            Err(_) => break,
+        };
+    }
+    solution.map(|solution| (replacement.into_script(), solution))
+}
+
+/// Splits a bare multisig script `OP_k <pubkey>... OP_n OP_CHECKMULTISIG`
+/// into the number of required signatures and the keys.
+fn parse_multisig(script: &Script) -> Option<(usize, Vec<&[u8]>)> {
+    let instructions = script.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+    let (first, rest) = instructions.split_first()?;
+    let (last, rest) = rest.split_last()?;
+    let (count, keys) = rest.split_last()?;
+
+    let small_int = |instruction: &Instruction| match *instruction {
+        Instruction::Op(op) => {
+            let op = op.into_u8();
+            if op >= opcodes::all::OP_PUSHNUM_1.into_u8() && op <= opcodes::all::OP_PUSHNUM_16.into_u8() {
+                Some((op - opcodes::all::OP_PUSHNUM_1.into_u8() + 1) as usize)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+    let required = small_int(first)?;
+    let count = small_int(count)?;
+    if *last != Instruction::Op(opcodes::all::OP_CHECKMULTISIG) || count != keys.len() || required > count {
+        return None;
+    }
+    let keys = keys.iter()
+        .map(|instruction| match *instruction {
+            Instruction::PushBytes(data) => Some(data),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some((required, keys))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, SignetTxs, SIGNET_HEADER};
+
+    use secp256k1::{Message, Secp256k1, SecretKey};
+
+    use blockdata::block::{Block, BlockHeader};
+    use blockdata::constants::genesis_block;
+    use blockdata::opcodes;
+    use blockdata::script::{Builder, Script, ScriptError};
+    use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut, Sequence};
+    use consensus::encode::serialize;
+    use consensus::params::Params;
+    use hashes::hex::FromHex;
+    use network::constants::Network;
+    use util::key::PublicKey;
+
+    /// Builds a block on the signet genesis whose coinbase commitment output
+    /// is followed by `extra`.
+    fn signet_block(extra: &[u8]) -> Block {
+        let genesis = genesis_block(Network::Signet);
+        let mut commitment = Vec::from_hex("6a24aa21a9ed").unwrap();
+        commitment.extend_from_slice(&[0x42; 32]);
+        commitment.extend_from_slice(extra);
+        let coinbase = Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Builder::new().push_int(1).push_int(0).into_script(),
+                sequence: Sequence::MAX,
+                witness: vec![vec![0; 32]],
+            }],
+            output: vec![
+                TxOut { value: 50 * 100_000_000, script_pubkey: Builder::new().push_opcode(opcodes::OP_TRUE).into_script() },
+                TxOut { value: 0, script_pubkey: Script::from(commitment) },
+            ],
+        };
+        let mut block = Block {
+            header: BlockHeader {
+                version: 0x20000000,
+                prev_blockhash: genesis.block_hash(),
+                merkle_root: Default::default(),
+                time: genesis.header.time + 600,
+                bits: genesis.header.bits,
+                nonce: 0,
+            },
+            txdata: vec![coinbase],
+        };
+        block.header.merkle_root = block.merkle_root();
+        block
+    }
+
+    /// Replaces the solution of `block` with the given scriptSig and witness.
+    fn set_solution(block: &mut Block, script_sig: &Script, witness: &[Vec<u8>]) {
+        let mut solution = SIGNET_HEADER.to_vec();
+        solution.extend(serialize(script_sig));
+        solution.extend(serialize(&witness.to_vec()));
+        let commitment = &mut block.txdata[0].output[1].script_pubkey;
+        *commitment = Script::from(commitment[..38].to_vec());
+        let push = Builder::new().push_slice(&solution).into_script();
+        *commitment = Script::from([commitment.as_bytes(), push.as_bytes()].concat());
+        block.header.merkle_root = block.merkle_root();
+    }
+
+    #[test]
+    fn global_signet() {
+        let params = Params::new(Network::Signet);
+        assert_eq!(
+            params.signet_challenge,
+            Some(Script::from(Vec::from_hex(
+                "512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d1e086be430210359ef5021964fe22d6f8e05b2463c9540ce96883fe3b278760f048f5189f2e6c452ae"
+            ).unwrap()))
+        );
+        // The genesis block has no solution but is always valid.
+        assert_eq!(params.validate_signet_block(&genesis_block(Network::Signet)), Ok(()));
+
+        assert_eq!(Params::new(Network::Bitcoin).validate_signet_block(&signet_block(&[])), Err(Error::NotSignet));
+        let mut empty = signet_block(&[]);
+        empty.txdata.clear();
+        assert_eq!(params.validate_signet_block(&empty), Err(Error::NoCoinbase));
+        let mut no_commitment = signet_block(&[]);
+        no_commitment.txdata[0].output.pop();
+        assert_eq!(params.validate_signet_block(&no_commitment), Err(Error::MissingCommitment));
+
+        // Without a solution the 1-of-2 multisig lacks its signature.
+        assert_eq!(
+            params.validate_signet_block(&signet_block(&[])),
+            Err(Error::Script(ScriptError::InvalidStackOperation)),
+        );
+        // A valid signature by somebody else does not do either.
+        let secp = Secp256k1::new();
+        let mut block = signet_block(&[]);
+        let challenge = params.signet_challenge.clone().unwrap();
+        let sighash = SignetTxs::new(&block, &challenge).unwrap().to_sign.signature_hash(0, &challenge, 0x01);
+        let sk = SecretKey::from_slice(&[0xab; 32]).unwrap();
+        let mut sig = secp.sign(&Message::from_slice(&sighash[..]).unwrap(), &sk).serialize_der().to_vec();
+        sig.push(0x01);
+        let script_sig = Builder::new().push_opcode(opcodes::OP_FALSE).push_slice(&sig).into_script();
+        set_solution(&mut block, &script_sig, &[]);
+        assert_eq!(params.validate_signet_block(&block), Err(Error::Script(ScriptError::EvalFalse)));
+    }
+
+    #[test]
+    fn custom_signet() {
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let pk = PublicKey { compressed: true, key: secp256k1::PublicKey::from_secret_key(&secp, &sk) };
+        let challenge = Builder::new()
+            .push_int(1)
+            .push_key(&pk)
+            .push_int(1)
+            .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+            .into_script();
+        let mut params = Params::new(Network::Signet);
+        params.signet_challenge = Some(challenge.clone());
+
+        // Sign a block with an empty solution push, which the solution replaces.
+        let mut block = signet_block(&[0x04, 0xec, 0xc7, 0xda, 0xa2]);
+        let txs = SignetTxs::new(&block, &challenge).unwrap();
+        assert!(txs.to_sign.input[0].script_sig.is_empty());
+        assert_eq!(txs.to_spend.input[0].script_sig.len(), 1 + 1 + 72);
+        assert_eq!(txs.to_sign.input[0].previous_output, OutPoint::new(txs.to_spend.txid(), 0));
+        let sighash = txs.to_sign.signature_hash(0, &challenge, 0x01);
+        let sig = secp.sign(&Message::from_slice(&sighash[..]).unwrap(), &sk);
+        let mut sig = sig.serialize_der().to_vec();
+        sig.push(0x01);
+        let script_sig = Builder::new().push_opcode(opcodes::OP_FALSE).push_slice(&sig).into_script();
+        set_solution(&mut block, &script_sig, &[]);
+        // Adding the solution changes neither of the committed transactions.
+        let signed = SignetTxs::new(&block, &challenge).unwrap();
+        assert_eq!(signed.to_spend, txs.to_spend);
+        assert_eq!(signed.to_sign.input[0].script_sig, script_sig);
+        assert_eq!(params.validate_signet_block(&block), Ok(()));
+
+        // The solution commits to the header...
+        let mut tampered = block.clone();
+        tampered.header.time += 1;
+        assert_eq!(params.validate_signet_block(&tampered), Err(Error::Script(ScriptError::EvalFalse)));
+        // ... and to the transactions.
+        let mut tampered = block.clone();
+        tampered.txdata[0].output[0].value -= 1;
+        assert_eq!(params.validate_signet_block(&tampered), Err(Error::Script(ScriptError::EvalFalse)));
+
+        let mut bad = block.clone();
+        let script_sig = Builder::new().push_int(1).push_slice(&sig).into_script();
+        set_solution(&mut bad, &script_sig, &[]);
+        assert_eq!(params.validate_signet_block(&bad), Err(Error::Unsupported));
+        let script_sig = Builder::new().push_slice(&[1]).push_slice(&sig).into_script();
+        set_solution(&mut bad, &script_sig, &[]);
+        assert_eq!(params.validate_signet_block(&bad), Err(Error::Script(ScriptError::SigNullDummy)));
+        let script_sig = Builder::new().push_opcode(opcodes::OP_FALSE).push_slice(&sig[1..]).into_script();
+        set_solution(&mut bad, &script_sig, &[]);
+        assert_eq!(params.validate_signet_block(&bad), Err(Error::Script(ScriptError::SigDer)));
+        let script_sig = Builder::new().push_opcode(opcodes::OP_FALSE).push_slice(&sig).into_script();
+        set_solution(&mut bad, &script_sig, &[vec![1]]);
+        assert_eq!(params.validate_signet_block(&bad), Err(Error::Script(ScriptError::WitnessUnexpected)));
+
+        // Trailing data after the witness is rejected.
+        let commitment = &mut bad.txdata[0].output[1].script_pubkey;
+        let mut solution = SIGNET_HEADER.to_vec();
+        solution.extend(serialize(&script_sig));
+        solution.extend(&[0x00, 0x00]);
+        *commitment = Script::from([&commitment[..38], Builder::new().push_slice(&solution).into_script().as_bytes()].concat());
+        assert_eq!(params.validate_signet_block(&bad), Err(Error::InvalidSolution));
+    }
+
+    #[test]
+    fn trivial_challenge() {
+        let mut params = Params::new(Network::Signet);
+        params.signet_challenge = Some(Builder::new().push_opcode(opcodes::OP_TRUE).into_script());
+        assert_eq!(params.validate_signet_block(&signet_block(&[])), Ok(()));
+        // Challenges that can not be evaluated are reported as such.
+        params.signet_challenge = Some(Builder::new().push_opcode(opcodes::all::OP_DEPTH).into_script());
+        assert_eq!(params.validate_signet_block(&signet_block(&[])), Err(Error::Unsupported));
+    }
+}
@@ -0,0 +1,576 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Block validation
+//!
+//! Checks of a block against the rules which can be verified from the block
+//! itself and its immediate context in the chain, following `CheckBlock`,
+//! `ContextualCheckBlockHeader` and `ContextualCheckBlock` in Bitcoin Core.
+//! Transactions are not checked against the UTXO set and scripts are not
+//! executed.
+//!
+
+use std::{error, fmt};
+
+use blockdata::block::{Block, BlockHeader};
+use blockdata::constants::MAX_BLOCK_WEIGHT;
+use blockdata::script::Builder;
+use consensus::params::Params;
+use consensus::signet;
+use hash_types::BlockHash;
+use util;
+
+/// A consensus rule violated by a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The block does not build on the given previous header.
+    PrevBlockMismatch,
+    /// The header's target is easier than the network's proof-of-work limit.
+    TargetAbovePowLimit,
+    /// The header's target is not the one required at its height.
+    BadTarget,
+    /// The header's hash does not meet its target.
+    BadProofOfWork,
+    /// The block timestamp is not after the median time past of its ancestors.
+    TimeTooOld,
+    /// The block timestamp is too far ahead of the local time.
+    TimeTooNew,
+    /// The merkle root in the header does not match the transactions.
+    BadMerkleRoot,
+    /// The block has no transactions, or the first one is not a coinbase.
+    NoCoinbase,
+    /// A transaction other than the first one is a coinbase.
+    MultipleCoinbases,
+    /// A transaction spends the same output more than once.
+    DuplicateInputs,
+    /// The coinbase scriptSig is shorter or longer than allowed.
+    BadCoinbaseLength,
+    /// The coinbase scriptSig does not start with the block height (BIP34).
+    BadCoinbaseHeight,
+    /// The witness commitment does not match the transactions' witnesses.
+    BadWitnessCommitment,
+    /// The block has witness data before segwit is active.
+    UnexpectedWitness,
+    /// An output value, or the sum of a transaction's output values, is above
+    /// [`Params::max_money`].
+    ///
+    /// [`Params::max_money`]: ../params/struct.Params.html#method.max_money
+    BadOutputValue,
+    /// The block weight exceeds [`MAX_BLOCK_WEIGHT`].
+    ///
+    /// [`MAX_BLOCK_WEIGHT`]: ../../blockdata/constants/constant.MAX_BLOCK_WEIGHT.html
+    BadWeight,
+    /// The signet block solution is invalid.
+    Signet(signet::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::PrevBlockMismatch => f.write_str("block does not build on the previous header"),
+            Error::TargetAbovePowLimit => f.write_str("block target above the proof-of-work limit"),
+            Error::BadTarget => f.write_str("block target incorrect"),
+            Error::BadProofOfWork => f.write_str("block target correct but not attained"),
+            Error::TimeTooOld => f.write_str("block timestamp not after median time past"),
+            Error::TimeTooNew => f.write_str("block timestamp too far in the future"),
+            Error::BadMerkleRoot => f.write_str("merkle root mismatch"),
+            Error::NoCoinbase => f.write_str("first transaction is not a coinbase"),
+            Error::MultipleCoinbases => f.write_str("more than one coinbase"),
+            Error::DuplicateInputs => f.write_str("transaction spends an output twice"),
+            Error::BadCoinbaseLength => f.write_str("coinbase scriptSig length out of range"),
+            Error::BadCoinbaseHeight => f.write_str("block height mismatch in coinbase"),
+            Error::BadWitnessCommitment => f.write_str("witness commitment mismatch"),
+            Error::UnexpectedWitness => f.write_str("unexpected witness data"),
+            Error::BadOutputValue => f.write_str("output value out of range"),
+            Error::BadWeight => f.write_str("block weight limit exceeded"),
+            Error::Signet(ref e) => write!(f, "invalid signet block: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::Signet(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<signet::Error> for Error {
+    fn from(e: signet::Error) -> Error {
+        Error::Signet(e)
+    }
+}
+
+/// Checks whether `value` is a valid output amount, or sum of output amounts,
+/// in satoshis, following `MoneyRange` in Bitcoin Core.
+pub fn check_money_range(value: u64, params: &Params) -> bool {
+    value <= params.max_money()
+}
+
+/// Checks that `headers` form a chain, each one building on the previous one
+/// and meeting the target required at its height, returning the index of the
+/// first invalid header and the rule it violates.
+///
+/// `headers[n]` must be the header at height `n`, so the chain has to start
+/// with the genesis block. Targets are checked exactly, following
+/// [`Params::next_work_required`]; timestamps are not checked.
+///
+/// [`Params::next_work_required`]: ../params/struct.Params.html#method.next_work_required
+pub fn verify_chain(headers: &[BlockHeader], params: &Params) -> Result<(), (usize, Error)> {
+    for (i, header) in headers.iter().enumerate() {
+        let target = header.target();
+        if target.to_uint256() > params.pow_limit {
+            return Err((i, Error::TargetAbovePowLimit));
+        }
+        if i > 0 {
+            if header.prev_blockhash != headers[i - 1].block_hash() {
+                return Err((i, Error::PrevBlockMismatch));
+            }
+            if header.bits != params.next_work_required(&headers[..i], header.time) {
+                return Err((i, Error::BadTarget));
+            }
+        }
+        if !target.is_met_by(header.block_hash()) {
+            return Err((i, Error::BadProofOfWork));
+        }
+    }
+    Ok(())
+}
+
+/// A block which failed validation, and the rule it violated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockValidationError {
+    /// Hash of the invalid block.
+    pub block_hash: BlockHash,
+    /// The rule the block violated.
+    pub reason: Error,
+}
+
+impl fmt::Display for BlockValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "block {} is invalid: {}", self.block_hash, self.reason)
+    }
+}
+
+impl error::Error for BlockValidationError {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        Some(&self.reason)
+    }
+}
+
+/// Validates blocks connected to the tip of a chain.
+///
+/// The validator holds the time context of the chain, which can not be
+/// derived from the previous header alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockValidator {
+    /// Median time past of the blocks up to and including the previous block.
+    pub median_time_past: u32,
+    /// The current local time.
+    pub now: u32,
+}
+
+impl BlockValidator {
+    /// Creates a validator for blocks connected to a chain with the given time
+    /// context.
+    pub fn new(median_time_past: u32, now: u32) -> BlockValidator {
+        BlockValidator { median_time_past, now }
+    }
+
+    /// Checks that `block`, connected on top of `prev_header` at `height`,
+    /// follows the consensus rules of `params`, returning the first rule it
+    /// violates.
+    ///
+    /// The target is checked with [`BlockHeader::check_difficulty_transition`],
+    /// except at difficulty adjustment heights, where the first header of the
+    /// retarget period would be needed; it is then only checked against the
+    /// proof-of-work limit. Use [`Params::next_work_required`] to check it
+    /// exactly.
+    ///
+    /// [`BlockHeader::check_difficulty_transition`]: ../../blockdata/block/struct.BlockHeader.html#method.check_difficulty_transition
+    /// [`Params::next_work_required`]: ../params/struct.Params.html#method.next_work_required
+    pub fn check_block(&self, block: &Block, prev_header: &BlockHeader, height: u32, params: &Params) -> Result<(), BlockValidationError> {
+        self.check_block_rules(block, prev_header, height, params)
+            .map_err(|reason| BlockValidationError { block_hash: block.block_hash(), reason })
+    }
+
+    /// Checks only the header of a block, connected on top of `prev_header` at
+    /// `height`. See [`check_block`] for the limits of the target check.
+    ///
+    /// [`check_block`]: #method.check_block
+    pub fn check_header(&self, header: &BlockHeader, prev_header: &BlockHeader, height: u32, params: &Params) -> Result<(), BlockValidationError> {
+        self.check_header_rules(header, prev_header, height, params)
+            .map_err(|reason| BlockValidationError { block_hash: header.block_hash(), reason })
+    }
+
+    fn check_block_rules(&self, block: &Block, prev_header: &BlockHeader, height: u32, params: &Params) -> Result<(), Error> {
+        self.check_header_rules(&block.header, prev_header, height, params)?;
+
+        if !block.check_merkle_root() {
+            return Err(Error::BadMerkleRoot);
+        }
+        let coinbase = match block.txdata.first() {
+            Some(tx) if tx.is_coin_base() => tx,
+            _ => return Err(Error::NoCoinbase),
+        };
+        if block.txdata[1..].iter().any(|tx| tx.is_coin_base()) {
+            return Err(Error::MultipleCoinbases);
+        }
+        for tx in &block.txdata {
+            if tx.has_duplicate_inputs() {
+                return Err(Error::DuplicateInputs);
+            }
+            let mut total = 0u64;
+            for output in &tx.output {
+                // Both values are at most 21e14, so the sum can not overflow.
+                if !check_money_range(output.value, params) || !check_money_range(total + output.value, params) {
+                    return Err(Error::BadOutputValue);
+                }
+                total += output.value;
+            }
+        }
+        coinbase.check_coinbase_scriptsig().map_err(|_| Error::BadCoinbaseLength)?;
+        if params.bip34_active(height) {
+            let expected = Builder::new().push_int(height as i64).into_script();
+            if !coinbase.input[0].script_sig.as_bytes().starts_with(expected.as_bytes()) {
+                return Err(Error::BadCoinbaseHeight);
+            }
+        }
+        if params.segwit_active(height) {
+            if !block.check_witness_commitment() {
+                return Err(Error::BadWitnessCommitment);
+            }
+        } else if block.txdata.iter().any(|tx| tx.has_witness()) {
+            return Err(Error::UnexpectedWitness);
+        }
+        if block.get_weight() > MAX_BLOCK_WEIGHT as usize {
+            return Err(Error::BadWeight);
+        }
+        if params.signet_challenge.is_some() {
+            params.validate_signet_block(block)?;
+        }
+        Ok(())
+    }
+
+    fn check_header_rules(&self, header: &BlockHeader, prev_header: &BlockHeader, height: u32, params: &Params) -> Result<(), Error> {
+        if header.prev_blockhash != prev_header.block_hash() {
+            return Err(Error::PrevBlockMismatch);
+        }
+
+        let target = header.target();
+        if target.to_uint256() > params.pow_limit {
+            return Err(Error::TargetAbovePowLimit);
+        }
+        // The first header of the period is only needed at adjustment
+        // boundaries, where it is not known.
+        let retarget = u64::from(height) % params.difficulty_adjustment_interval() == 0;
+        if !retarget || params.no_pow_retargeting {
+            header.check_difficulty_transition(prev_header, prev_header, height, params)
+                .map_err(|_| Error::BadTarget)?;
+        }
+        if !target.is_met_by(header.block_hash()) {
+            return Err(Error::BadProofOfWork);
+        }
+
+        match header.check_timestamp(self.median_time_past, self.now, params) {
+            Err(util::Error::BlockTimeTooOld) => Err(Error::TimeTooOld),
+            Err(util::Error::BlockTimeTooNew) => Err(Error::TimeTooNew),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_money_range, verify_chain, BlockValidationError, BlockValidator, Error};
+
+    use blockdata::block::{Block, BlockHeader, CompactTarget};
+    use blockdata::constants::genesis_block;
+    use blockdata::opcodes;
+    use blockdata::script::{Builder, Script};
+    use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut, Sequence};
+    use consensus::params::Params;
+    use consensus::signet;
+    use network::constants::Network;
+
+    fn coinbase(script_sig: Script) -> Transaction {
+        Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig,
+                sequence: Sequence::MAX,
+                witness: vec![],
+            }],
+            output: vec![TxOut { value: 50_0000_0000, script_pubkey: Script::new() }],
+        }
+    }
+
+    fn spend(script_pubkey: Script) -> Transaction {
+        Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Default::default(), 0),
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                witness: vec![],
+            }],
+            output: vec![TxOut { value: 1000, script_pubkey }],
+        }
+    }
+
+    /// Updates the merkle root and grinds the nonce until the block meets its target.
+    fn finish(mut block: Block) -> Block {
+        block.header.merkle_root = block.merkle_root();
+        while !block.header.target().is_met_by(block.block_hash()) {
+            block.header.nonce += 1;
+        }
+        block
+    }
+
+    fn setup() -> (Params, BlockHeader, Block, BlockValidator) {
+        let mut params = Params::new(Network::Regtest);
+        params.bip34_height = 1;
+        let genesis = genesis_block(Network::Regtest).header;
+        let script_sig = Builder::new().push_int(1).push_int(0).into_script();
+        let block = finish(Block {
+            header: BlockHeader {
+                version: 4,
+                prev_blockhash: genesis.block_hash(),
+                merkle_root: Default::default(),
+                time: genesis.time + 600,
+                bits: genesis.bits,
+                nonce: 0,
+            },
+            txdata: vec![coinbase(script_sig)],
+        });
+        let validator = BlockValidator::new(genesis.time, genesis.time + 600);
+        (params, genesis, block, validator)
+    }
+
+    #[test]
+    fn valid_block() {
+        let (params, genesis, block, validator) = setup();
+        assert_eq!(validator.check_block(&block, &genesis, 1, &params), Ok(()));
+    }
+
+    #[test]
+    fn money_range() {
+        for &network in &[Network::Bitcoin, Network::Testnet, Network::Signet, Network::Regtest] {
+            let params = Params::new(network);
+            assert!(check_money_range(0, &params));
+            assert!(check_money_range(params.max_money(), &params));
+            assert!(!check_money_range(params.max_money() + 1, &params));
+            assert!(!check_money_range(u64::max_value(), &params));
+        }
+    }
+
+    #[test]
+    fn chain_verification() {
+        use util::test_utils::build_regtest_chain;
+
+        let params = Params::new(Network::Regtest);
+        let mut headers = vec![genesis_block(Network::Regtest).header];
+        headers.extend(build_regtest_chain(10).iter().map(|block| block.header));
+        assert_eq!(verify_chain(&headers, &params), Ok(()));
+        assert_eq!(verify_chain(&headers[..1], &params), Ok(()));
+        assert_eq!(verify_chain(&[], &params), Ok(()));
+
+        // A header which does not build on its predecessor.
+        let mut broken = headers.clone();
+        broken.remove(5);
+        assert_eq!(verify_chain(&broken, &params), Err((5, Error::PrevBlockMismatch)));
+
+        let mut bad = headers.clone();
+        bad[7].nonce += 1;
+        while bad[7].target().is_met_by(bad[7].block_hash()) {
+            bad[7].nonce += 1;
+        }
+        assert_eq!(verify_chain(&bad, &params), Err((7, Error::BadProofOfWork)));
+
+        // The target has to stay the same on regtest.
+        let mut bad = headers.clone();
+        bad[3].bits = CompactTarget::from_consensus(0x207ffffe);
+        while !bad[3].target().is_met_by(bad[3].block_hash()) {
+            bad[3].nonce += 1;
+        }
+        assert_eq!(verify_chain(&bad, &params), Err((3, Error::BadTarget)));
+
+        // Mainnet headers with a correct target, but far from meeting it.
+        let params = Params::new(Network::Bitcoin);
+        let genesis = genesis_block(Network::Bitcoin).header;
+        let next = BlockHeader { prev_blockhash: genesis.block_hash(), ..genesis };
+        assert_eq!(verify_chain(&[genesis, next], &params), Err((1, Error::BadProofOfWork)));
+    }
+
+    #[test]
+    fn error_has_block_hash() {
+        let (params, genesis, block, validator) = setup();
+
+        let mut bad = block.clone();
+        bad.header.time = genesis.time;
+        let bad = finish(bad);
+        let err = validator.check_block(&bad, &genesis, 1, &params).unwrap_err();
+        assert_eq!(err, BlockValidationError { block_hash: bad.block_hash(), reason: Error::TimeTooOld });
+        assert_eq!(validator.check_header(&bad.header, &genesis, 1, &params), Err(err.clone()));
+        assert_eq!(err.to_string(), format!("block {} is invalid: block timestamp not after median time past", bad.block_hash()));
+
+        let mut bad = block.clone();
+        bad.txdata[0].output[0].value += 1;
+        let err = validator.check_block(&bad, &genesis, 1, &params).unwrap_err();
+        assert_eq!(err.block_hash, block.block_hash());
+        assert_eq!(err.reason, Error::BadMerkleRoot);
+        assert_eq!(validator.check_header(&bad.header, &genesis, 1, &params), Ok(()));
+    }
+
+    #[test]
+    fn header_failures() {
+        let (params, genesis, block, validator) = setup();
+        let check = |block: &Block| validator.check_block(block, &genesis, 1, &params).map_err(|e| e.reason);
+
+        let mut bad = block.clone();
+        bad.header.prev_blockhash = Default::default();
+        assert_eq!(check(&finish(bad)), Err(Error::PrevBlockMismatch));
+
+        let mut bad = block.clone();
+        bad.header.bits = CompactTarget::from_consensus(0x2100ffff);
+        assert_eq!(check(&bad), Err(Error::TargetAbovePowLimit));
+
+        let mut bad = block.clone();
+        bad.header.bits = CompactTarget::from_consensus(0x207ffffe);
+        assert_eq!(check(&finish(bad)), Err(Error::BadTarget));
+
+        let mut bad = block.clone();
+        while bad.header.target().is_met_by(bad.block_hash()) {
+            bad.header.nonce += 1;
+        }
+        assert_eq!(check(&bad), Err(Error::BadProofOfWork));
+
+        let mut bad = block.clone();
+        bad.header.time = genesis.time;
+        assert_eq!(check(&finish(bad)), Err(Error::TimeTooOld));
+
+        let mut bad = block.clone();
+        bad.header.time = validator.now + params.max_future_block_time + 1;
+        assert_eq!(check(&finish(bad)), Err(Error::TimeTooNew));
+    }
+
+    #[test]
+    fn target_rules() {
+        let (_, genesis, block, validator) = setup();
+
+        // Without minimum difficulty blocks the target must not change
+        // between difficulty adjustments.
+        let mut params = Params::new(Network::Regtest);
+        params.no_pow_retargeting = false;
+        params.allow_min_difficulty_blocks = false;
+        let mut harder = genesis;
+        harder.bits = CompactTarget::from_consensus(0x207ffffe);
+        let mut child = block.clone();
+        child.header.prev_blockhash = harder.block_hash();
+        let child = finish(child);
+        assert_eq!(validator.check_block(&child, &harder, 1, &params).map_err(|e| e.reason), Err(Error::BadTarget));
+        // At a difficulty adjustment only the proof-of-work limit is checked.
+        assert_eq!(validator.check_block(&child, &harder, 144 * 14, &params), Ok(()));
+
+        // Minimum difficulty blocks must use the limit once the previous block
+        // is more than twenty minutes old, otherwise the previous target.
+        params.allow_min_difficulty_blocks = true;
+        assert_eq!(validator.check_block(&child, &harder, 1, &params).map_err(|e| e.reason), Err(Error::BadTarget));
+        let mut same = child.clone();
+        same.header.bits = harder.bits;
+        assert_eq!(validator.check_block(&finish(same), &harder, 1, &params), Ok(()));
+        let mut late = child.clone();
+        late.header.bits = harder.bits;
+        late.header.time = harder.time + 1201;
+        let late = finish(late);
+        let late_validator = BlockValidator::new(genesis.time, late.header.time);
+        assert_eq!(late_validator.check_block(&late, &harder, 1, &params).map_err(|e| e.reason), Err(Error::BadTarget));
+    }
+
+    #[test]
+    fn block_failures() {
+        let (params, genesis, block, validator) = setup();
+        let check = |block: &Block| validator.check_block(block, &genesis, 1, &params).map_err(|e| e.reason);
+
+        let mut bad = block.clone();
+        bad.txdata[0].output[0].value += 1;
+        assert_eq!(check(&bad), Err(Error::BadMerkleRoot));
+
+        let mut bad = block.clone();
+        bad.txdata.clear();
+        assert_eq!(check(&finish(bad)), Err(Error::NoCoinbase));
+
+        let mut bad = block.clone();
+        bad.txdata[0] = spend(Script::new());
+        assert_eq!(check(&finish(bad)), Err(Error::NoCoinbase));
+
+        let mut bad = block.clone();
+        bad.txdata.push(block.txdata[0].clone());
+        assert_eq!(check(&finish(bad)), Err(Error::MultipleCoinbases));
+
+        let mut bad = block.clone();
+        let mut tx = spend(Script::new());
+        tx.input.push(tx.input[0].clone());
+        bad.txdata.push(tx);
+        assert_eq!(check(&finish(bad)), Err(Error::DuplicateInputs));
+
+        let mut bad = block.clone();
+        bad.txdata[0].output[0].value = params.max_money() + 1;
+        assert_eq!(check(&finish(bad)), Err(Error::BadOutputValue));
+        let mut bad = block.clone();
+        let mut tx = spend(Script::new());
+        tx.output[0].value = params.max_money();
+        tx.output.push(TxOut { value: 1, script_pubkey: Script::new() });
+        bad.txdata.push(tx);
+        assert_eq!(check(&finish(bad)), Err(Error::BadOutputValue));
+
+        let mut bad = block.clone();
+        bad.txdata[0] = coinbase(Builder::new().push_int(1).into_script());
+        assert_eq!(check(&finish(bad)), Err(Error::BadCoinbaseLength));
+
+        let mut bad = block.clone();
+        bad.txdata[0] = coinbase(Builder::new().push_int(2).push_int(0).into_script());
+        let bad = finish(bad);
+        assert_eq!(check(&bad), Err(Error::BadCoinbaseHeight));
+        let mut inactive = params.clone();
+        inactive.bip34_height = 2;
+        assert_eq!(validator.check_block(&bad, &genesis, 1, &inactive), Ok(()));
+
+        let mut witness = spend(Script::new());
+        witness.input[0].witness = vec![vec![1]];
+        let mut bad = block.clone();
+        bad.txdata.push(witness);
+        let bad = finish(bad);
+        assert_eq!(check(&bad), Err(Error::BadWitnessCommitment));
+        let mut pre_segwit = params.clone();
+        pre_segwit.segwit_height = 2;
+        assert_eq!(validator.check_block(&bad, &genesis, 1, &pre_segwit).map_err(|e| e.reason), Err(Error::UnexpectedWitness));
+
+        let mut bad = block.clone();
+        bad.txdata.push(spend(Script::from(vec![opcodes::all::OP_NOP.into_u8(); 1_000_000])));
+        assert_eq!(check(&finish(bad)), Err(Error::BadWeight));
+
+        let mut signet_params = params.clone();
+        signet_params.signet_challenge = Some(Builder::new().push_opcode(opcodes::OP_TRUE).into_script());
+        assert_eq!(
+            validator.check_block(&block, &genesis, 1, &signet_params).map_err(|e| e.reason),
+            Err(Error::Signet(signet::Error::MissingCommitment))
+        );
+    }
+}
@@ -35,30 +35,153 @@ macro_rules! impl_hashencode {
     }
 }
 
-hash_newtype!(Txid, sha256d::Hash, 32, doc="A bitcoin transaction hash/transaction ID.");
-hash_newtype!(Wtxid, sha256d::Hash, 32, doc="A bitcoin witness transaction ID.");
-hash_newtype!(BlockHash, sha256d::Hash, 32, doc="A bitcoin block hash.");
-hash_newtype!(SigHash, sha256d::Hash, 32, doc="Hash of the transaction according to the signature algorithm");
+macro_rules! impl_byte_order {
+    ($hashtype:ident) => {
+        impl $hashtype {
+            /// Constructs the hash from bytes in internal (little-endian) order, as they
+            /// appear in serialized transactions and blocks.
+            pub fn from_byte_array(bytes: [u8; 32]) -> $hashtype {
+                <$hashtype as Hash>::from_inner(bytes)
+            }
+
+            /// Returns the hash as bytes in internal (little-endian) order.
+            pub fn to_byte_array(&self) -> [u8; 32] {
+                self.into_inner()
+            }
+
+            /// Constructs the hash from bytes in display (big-endian) order, as shown by
+            /// block explorers and returned by Bitcoin Core's JSON-RPC interface.
+            ///
+            /// This is the byte order used by `from_str` and `from_hex`.
+            pub fn from_display_byte_array(mut bytes: [u8; 32]) -> $hashtype {
+                bytes.reverse();
+                $hashtype::from_byte_array(bytes)
+            }
+
+            /// Returns the hash as bytes in display (big-endian) order.
+            pub fn to_display_byte_array(&self) -> [u8; 32] {
+                let mut bytes = self.to_byte_array();
+                bytes.reverse();
+                bytes
+            }
+
+            /// Wraps a raw SHA256d hash without changing its byte order.
+            pub fn from_raw_hash(hash: sha256d::Hash) -> $hashtype {
+                $hashtype::from_hash(hash)
+            }
+
+            /// Returns the underlying raw SHA256d hash.
+            pub fn to_raw_hash(&self) -> sha256d::Hash {
+                self.as_hash()
+            }
+        }
+    }
+}
+
+/// Defines a hash newtype around `sha256d::Hash` with consensus encoding and
+/// conversions between internal and display byte order.
+macro_rules! sha256d_hash_newtype {
+    ($hashtype:ident, $docs:meta) => {
+        hash_newtype!($hashtype, sha256d::Hash, 32, $docs);
+        impl_hashencode!($hashtype);
+        impl_byte_order!($hashtype);
+    }
+}
+
+sha256d_hash_newtype!(Txid, doc="A bitcoin transaction hash/transaction ID.");
+sha256d_hash_newtype!(Wtxid, doc="A bitcoin witness transaction ID.");
+sha256d_hash_newtype!(BlockHash, doc="A bitcoin block hash.");
+sha256d_hash_newtype!(SigHash, doc="Hash of the transaction according to the signature algorithm");
 
 hash_newtype!(PubkeyHash, hash160::Hash, 20, doc="A hash of a public key.");
 hash_newtype!(ScriptHash, hash160::Hash, 20, doc="A hash of Bitcoin Script bytecode.");
 hash_newtype!(WPubkeyHash, hash160::Hash, 20, doc="SegWit version of a public key hash.");
 hash_newtype!(WScriptHash, sha256::Hash, 32, doc="SegWit version of a Bitcoin Script bytecode hash.");
 
-hash_newtype!(TxMerkleNode, sha256d::Hash, 32, doc="A hash of the Merkle tree branch or root for transactions");
-hash_newtype!(WitnessMerkleNode, sha256d::Hash, 32, doc="A hash corresponding to the Merkle tree root for witness data");
-hash_newtype!(WitnessCommitment, sha256d::Hash, 32, doc="A hash corresponding to the witness structure commitment in the coinbase transaction");
+sha256d_hash_newtype!(TxMerkleNode, doc="A hash of the Merkle tree branch or root for transactions");
+sha256d_hash_newtype!(WitnessMerkleNode, doc="A hash corresponding to the Merkle tree root for witness data");
+sha256d_hash_newtype!(WitnessCommitment, doc="A hash corresponding to the witness structure commitment in the coinbase transaction");
 hash_newtype!(XpubIdentifier, hash160::Hash, 20, doc="XpubIdentifier as defined in BIP-32.");
 
-hash_newtype!(FilterHash, sha256d::Hash, 32, doc="Filter hash, as defined in BIP-157");
-hash_newtype!(FilterHeader, sha256d::Hash, 32, doc="Filter header, as defined in BIP-157");
+sha256d_hash_newtype!(FilterHash, doc="Filter hash, as defined in BIP-157");
+sha256d_hash_newtype!(FilterHeader, doc="Filter header, as defined in BIP-157");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::str::FromStr;
+
+    use hashes::hex::{FromHex, ToHex};
+    use consensus::encode::{deserialize, serialize};
 
+    #[test]
+    fn byte_order() {
+        // Mainnet genesis block hash and coinbase txid
+        let display = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+        let hash = BlockHash::from_str(display).unwrap();
+        assert_eq!(BlockHash::from_hex(display).unwrap(), hash);
+
+        let internal = hash.to_byte_array();
+        let big_endian = hash.to_display_byte_array();
+        assert_ne!(internal, big_endian);
+        assert_eq!(big_endian.to_hex(), display);
+        assert_eq!(internal[31], 0);
+        assert_eq!(big_endian[0], 0);
+
+        assert_eq!(BlockHash::from_byte_array(internal), hash);
+        assert_eq!(BlockHash::from_display_byte_array(big_endian), hash);
+        assert_ne!(BlockHash::from_byte_array(big_endian), hash);
+        assert_eq!(BlockHash::from_byte_array(internal).to_string(), display);
+
+        let txid = Txid::from_str("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b").unwrap();
+        let raw = sha256d::Hash::from_inner(txid.to_byte_array());
+        assert_eq!(Txid::from_raw_hash(raw), txid);
+        assert_eq!(txid.to_raw_hash(), raw);
+        assert_eq!(Txid::from_display_byte_array(txid.to_display_byte_array()), txid);
+        assert_eq!(txid.to_display_byte_array().to_hex(), txid.to_string());
+    }
 
-impl_hashencode!(Txid);
-impl_hashencode!(Wtxid);
-impl_hashencode!(SigHash);
-impl_hashencode!(BlockHash);
-impl_hashencode!(TxMerkleNode);
-impl_hashencode!(WitnessMerkleNode);
-impl_hashencode!(FilterHash);
-impl_hashencode!(FilterHeader);
\ No newline at end of file
+    #[test]
+    fn sha256d_types_roundtrip() {
+        macro_rules! roundtrip {
+            ($hashtype:ident) => {
+                let hash = $hashtype::hash(stringify!($hashtype).as_bytes());
+                assert_eq!($hashtype::from_str(&hash.to_string()).unwrap(), hash);
+                assert_eq!(deserialize::<$hashtype>(&serialize(&hash)).unwrap(), hash);
+                assert_eq!(serialize(&hash), hash.to_byte_array().to_vec());
+                assert_eq!(hash.to_display_byte_array().to_hex(), hash.to_string());
+                assert_eq!($hashtype::from_display_byte_array(hash.to_display_byte_array()), hash);
+                assert_eq!($hashtype::from_raw_hash(hash.to_raw_hash()), hash);
+            }
+        }
+        roundtrip!(Txid);
+        roundtrip!(Wtxid);
+        roundtrip!(BlockHash);
+        roundtrip!(SigHash);
+        roundtrip!(TxMerkleNode);
+        roundtrip!(WitnessMerkleNode);
+        roundtrip!(WitnessCommitment);
+        roundtrip!(FilterHash);
+        roundtrip!(FilterHeader);
+    }
+
+    #[test]
+    fn hash160_types_roundtrip() {
+        macro_rules! roundtrip {
+            ($hashtype:ident) => {
+                let hash = $hashtype::hash(stringify!($hashtype).as_bytes());
+                assert_eq!($hashtype::from_str(&hash.to_string()).unwrap(), hash);
+                // Unlike the double SHA256 types, these display in internal order.
+                assert_eq!(hash.to_string(), hash.into_inner().to_hex());
+            }
+        }
+        roundtrip!(PubkeyHash);
+        roundtrip!(ScriptHash);
+        roundtrip!(WPubkeyHash);
+        roundtrip!(XpubIdentifier);
+        let hash = WScriptHash::hash(b"WScriptHash");
+        assert_eq!(WScriptHash::from_str(&hash.to_string()).unwrap(), hash);
+        assert_eq!(hash.to_string(), hash.into_inner().to_hex());
+    }
+}
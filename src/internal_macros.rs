@@ -43,6 +43,31 @@ macro_rules! impl_consensus_encoding {
     );
 }
 
+/// Implements `to_hex` and `from_hex` methods going through the consensus encoding
+macro_rules! impl_consensus_hex {
+    ($thing:ident) => (
+        impl $thing {
+            /// Returns the hex-encoded consensus serialization
+            pub fn to_hex(&self) -> String {
+                $crate::consensus::encode::serialize_hex(self)
+            }
+
+            /// Parses the hex-encoded consensus serialization, rejecting invalid
+            /// hex and trailing data
+            pub fn from_hex(s: &str) -> Result<$thing, $crate::consensus::encode::Error> {
+                $crate::consensus::encode::deserialize_hex(s)
+            }
+
+            /// Parses a hex-encoded consensus serialization as returned by Bitcoin
+            /// Core's REST and RPC interfaces, ignoring surrounding whitespace such
+            /// as a trailing newline
+            pub fn from_core_hex(s: &str) -> Result<$thing, $crate::consensus::encode::Error> {
+                $thing::from_hex(s.trim())
+            }
+        }
+    );
+}
+
 /// Implements standard array methods for a given wrapper type
 macro_rules! impl_array_newtype {
     ($thing:ident, $ty:ty, $len:expr) => {
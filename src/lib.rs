@@ -45,7 +45,8 @@ pub extern crate bech32;
 
 #[cfg(feature="bitcoinconsensus")] extern crate bitcoinconsensus;
 #[cfg(feature = "serde")] #[macro_use] extern crate serde;
-#[cfg(all(test, feature = "serde"))] extern crate serde_json;
+#[cfg(feature = "serde_json")] #[macro_use] extern crate serde_json;
+#[cfg(all(test, feature = "serde", not(feature = "serde_json")))] extern crate serde_json;
 #[cfg(all(test, feature = "serde"))] extern crate serde_test;
 #[cfg(all(test, feature = "serde"))] extern crate bincode;
 #[cfg(all(test, feature = "unstable"))] extern crate test;
@@ -71,6 +72,8 @@ pub mod hash_types;
 pub use hash_types::*;
 pub use blockdata::block::Block;
 pub use blockdata::block::BlockHeader;
+pub use blockdata::block::CompactTarget;
+pub use blockdata::block::Target;
 pub use blockdata::script::Script;
 pub use blockdata::transaction::Transaction;
 pub use blockdata::transaction::TxIn;
@@ -84,6 +87,7 @@ pub use util::address::Address;
 pub use util::address::AddressType;
 pub use util::amount::Amount;
 pub use util::amount::Denomination;
+pub use util::amount::FeeRate;
 pub use util::amount::SignedAmount;
 pub use util::key::PrivateKey;
 pub use util::key::PublicKey;
@@ -39,7 +39,10 @@
 
 use std::{fmt, io, ops};
 
+use hashes::hex::FromHex;
+
 use consensus::encode::{self, Encodable, Decodable};
+use hash_types::BlockHash;
 
 /// Version of the protocol as appearing in network message headers
 /// This constant is used to signal to other peers which features you support.
@@ -60,6 +63,10 @@ pub const PROTOCOL_VERSION: u32 = 70001;
 
 user_enum! {
     /// The cryptocurrency to act on
+    ///
+    /// Networks compare, sort and hash by their position in this declaration:
+    /// Bitcoin, Testnet, Signet, Regtest. New networks are only ever appended,
+    /// so this order is stable.
     #[derive(Copy, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Debug)]
     pub enum Network {
         /// Classic Bitcoin
@@ -115,6 +122,54 @@ impl Network {
             Network::Regtest => 0xDAB5BFFA,
         }
     }
+
+    /// Returns the hash of the genesis block, without building the block as
+    /// [`genesis_block`] does.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use bitcoin::network::constants::Network;
+    ///
+    /// assert_eq!(
+    ///     Network::Bitcoin.genesis_hash().to_string(),
+    ///     "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+    /// );
+    /// ```
+    ///
+    /// [`genesis_block`]: ../../blockdata/constants/fn.genesis_block.html
+    pub fn genesis_hash(self) -> BlockHash {
+        let hex = match self {
+            Network::Bitcoin => "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            Network::Testnet => "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943",
+            Network::Signet  => "00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6",
+            Network::Regtest => "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+        };
+        BlockHash::from_hex(hex).expect("valid hex")
+    }
+
+    /// Returns the subdirectory of the Bitcoin Core data directory holding
+    /// the data of this network. Mainnet data is stored in the data directory
+    /// itself, so the name is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::path::Path;
+    /// use bitcoin::network::constants::Network;
+    ///
+    /// let datadir = Path::new("/home/satoshi/.bitcoin");
+    /// assert_eq!(datadir.join(Network::Testnet.datadir_subdir()), Path::new("/home/satoshi/.bitcoin/testnet3"));
+    /// assert_eq!(datadir.join(Network::Bitcoin.datadir_subdir()), Path::new("/home/satoshi/.bitcoin/"));
+    /// ```
+    pub fn datadir_subdir(self) -> &'static str {
+        match self {
+            Network::Bitcoin => "",
+            Network::Testnet => "testnet3",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        }
+    }
 }
 
 /// Flags to indicate which network services a node supports.
@@ -291,8 +346,24 @@ impl Decodable for ServiceFlags {
 #[cfg(test)]
 mod tests {
     use super::{Network, ServiceFlags};
+    use blockdata::constants::genesis_block;
     use consensus::encode::{deserialize, serialize};
 
+    #[test]
+    fn genesis_hash_test() {
+        for &network in &[Network::Bitcoin, Network::Testnet, Network::Signet, Network::Regtest] {
+            assert_eq!(network.genesis_hash(), genesis_block(network).block_hash());
+        }
+    }
+
+    #[test]
+    fn datadir_subdir_test() {
+        assert_eq!(Network::Bitcoin.datadir_subdir(), "");
+        assert_eq!(Network::Testnet.datadir_subdir(), "testnet3");
+        assert_eq!(Network::Signet.datadir_subdir(), "signet");
+        assert_eq!(Network::Regtest.datadir_subdir(), "regtest");
+    }
+
     #[test]
     fn serialize_test() {
         assert_eq!(
@@ -344,6 +415,22 @@ mod tests {
         assert!("fakenet".parse::<Network>().is_err());
     }
 
+    #[test]
+    fn ordering_test() {
+        use std::collections::{BTreeSet, HashSet};
+
+        let mut networks = vec![Network::Regtest, Network::Signet, Network::Bitcoin, Network::Testnet];
+        networks.sort();
+        assert_eq!(networks, [Network::Bitcoin, Network::Testnet, Network::Signet, Network::Regtest]);
+        assert!(Network::Bitcoin < Network::Testnet);
+
+        let sorted: Vec<Network> = [Network::Signet, Network::Regtest, Network::Testnet, Network::Bitcoin, Network::Signet]
+            .iter().cloned().collect::<BTreeSet<_>>().into_iter().collect();
+        assert_eq!(sorted, networks);
+        let hashed: HashSet<Network> = networks.iter().cloned().collect();
+        assert_eq!(hashed.len(), 4);
+    }
+
     #[test]
     fn service_flags_test() {
         let all = [
@@ -26,6 +26,7 @@ use std::io::Cursor;
 use blockdata::block;
 use blockdata::transaction;
 use network::address::{Address, AddrV2Message};
+use network::constants::Network;
 use network::message_network;
 use network::message_blockdata;
 use network::message_filter;
@@ -246,6 +247,31 @@ impl RawNetworkMessage {
     pub fn command(&self) -> CommandString {
         self.payload.command()
     }
+
+    /// Decodes a message frame, failing with
+    /// [`encode::Error::UnexpectedNetworkMagic`] if it is not meant for
+    /// `network`.
+    ///
+    /// [`encode::Error::UnexpectedNetworkMagic`]: ../../consensus/encode/enum.Error.html#variant.UnexpectedNetworkMagic
+    pub fn decode(bytes: &[u8], network: Network) -> Result<RawNetworkMessage, encode::Error> {
+        let (msg, magic) = RawNetworkMessage::decode_any_magic(bytes)?;
+        if magic != network.magic() {
+            return Err(encode::Error::UnexpectedNetworkMagic {
+                expected: network.magic(),
+                actual: magic,
+            });
+        }
+        Ok(msg)
+    }
+
+    /// Decodes a message frame whatever network its magic belongs to, and
+    /// returns it together with the magic, such as when replaying frames
+    /// captured from another chain.
+    pub fn decode_any_magic(bytes: &[u8]) -> Result<(RawNetworkMessage, u32), encode::Error> {
+        let msg: RawNetworkMessage = encode::deserialize(bytes)?;
+        let magic = msg.magic;
+        Ok((msg, magic))
+    }
 }
 
 struct HeaderSerializationWrapper<'a>(&'a Vec<block::BlockHeader>);
@@ -476,6 +502,33 @@ mod test {
                                   0x00, 0x00, 0x00, 0x00, 0x5d, 0xf6, 0xe0, 0xe2]);
     }
 
+    #[test]
+    fn decode_any_magic_test() {
+        use consensus::encode::Error;
+        use network::constants::Network;
+
+        // A `verack` frame carrying the Dogecoin mainnet magic.
+        let frame = [0xc0, 0xc0, 0xc0, 0xc0, 0x76, 0x65, 0x72, 0x61,
+                     0x63, 0x6B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                     0x00, 0x00, 0x00, 0x00, 0x5d, 0xf6, 0xe0, 0xe2];
+        assert_eq!(Network::from_magic(0xc0c0c0c0), None);
+        match RawNetworkMessage::decode(&frame, Network::Bitcoin) {
+            Err(Error::UnexpectedNetworkMagic { expected: 0xd9b4bef9, actual: 0xc0c0c0c0 }) => {},
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let (msg, magic) = RawNetworkMessage::decode_any_magic(&frame).unwrap();
+        assert_eq!(magic, 0xc0c0c0c0);
+        assert_eq!(msg.payload, NetworkMessage::Verack);
+
+        let mut bitcoin = frame;
+        bitcoin[..4].copy_from_slice(&[0xf9, 0xbe, 0xb4, 0xd9]);
+        assert_eq!(RawNetworkMessage::decode(&bitcoin, Network::Bitcoin).unwrap().payload, NetworkMessage::Verack);
+
+        // Other decoding errors are still reported.
+        assert!(RawNetworkMessage::decode_any_magic(&frame[..23]).is_err());
+    }
+
     #[test]
     fn serialize_ping_test() {
         assert_eq!(serialize(&RawNetworkMessage { magic: 0xd9b4bef9, payload: NetworkMessage::Ping(100) }),
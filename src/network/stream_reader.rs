@@ -338,7 +338,7 @@ mod test {
         assert_eq!(serialize(&block.header.prev_blockhash), prevhash);
         assert_eq!(serialize(&block.header.merkle_root), merkle);
         assert_eq!(block.header.time, 1231965655);
-        assert_eq!(block.header.bits, 486604799);
+        assert_eq!(block.header.bits.to_consensus(), 486604799);
         assert_eq!(block.header.nonce, 2067413810);
 
         // should be also ok for a non-witness block as commitment is optional in that case
@@ -36,6 +36,7 @@
 //! let address = Address::p2pkh(&public_key, Network::Bitcoin);
 //! ```
 
+use std::collections::HashSet;
 use std::fmt::{self, Display, Formatter};
 use std::str::FromStr;
 use std::error;
@@ -43,6 +44,7 @@ use std::error;
 use bech32;
 use hashes::Hash;
 use hash_types::{PubkeyHash, WPubkeyHash, ScriptHash, WScriptHash};
+use blockdata::block::Block;
 use blockdata::script;
 use network::constants::Network;
 use util::base58;
@@ -122,6 +124,8 @@ pub enum AddressType {
     P2wpkh,
     /// pay-to-witness-script-hash
     P2wsh,
+    /// pay-to-taproot
+    P2tr,
 }
 
 impl fmt::Display for AddressType {
@@ -131,6 +135,7 @@ impl fmt::Display for AddressType {
             AddressType::P2sh => "p2sh",
             AddressType::P2wpkh => "p2wpkh",
             AddressType::P2wsh => "p2wsh",
+            AddressType::P2tr => "p2tr",
         })
     }
 }
@@ -143,6 +148,7 @@ impl FromStr for AddressType {
             "p2sh" => Ok(AddressType::P2sh),
             "p2wpkh" => Ok(AddressType::P2wpkh),
             "p2wsh" => Ok(AddressType::P2wsh),
+            "p2tr" => Ok(AddressType::P2tr),
             _ => Err(()),
         }
     }
@@ -240,6 +246,26 @@ impl Address {
         }
     }
 
+    /// Creates a pay to public key hash address from the hash of a public key,
+    /// for when the public key itself is not known.
+    #[inline]
+    pub fn from_pubkey_hash(hash: PubkeyHash, network: Network) -> Address {
+        Address {
+            network,
+            payload: Payload::PubkeyHash(hash),
+        }
+    }
+
+    /// Creates a pay to script hash address from the hash of a script, for
+    /// when the script itself is not known.
+    #[inline]
+    pub fn from_script_hash(hash: ScriptHash, network: Network) -> Address {
+        Address {
+            network,
+            payload: Payload::ScriptHash(hash),
+        }
+    }
+
     /// Create a witness pay to public key address from a public key
     /// This is the native segwit address type for an output redeemable with a single signature
     ///
@@ -318,13 +344,14 @@ impl Address {
                 version: ver,
                 program: ref prog,
             } => {
-                // BIP-141 p2wpkh or p2wsh addresses.
+                // BIP-141 p2wpkh or p2wsh addresses, BIP-341 p2tr addresses.
                 match ver.to_u8() {
                     0 => match prog.len() {
                         20 => Some(AddressType::P2wpkh),
                         32 => Some(AddressType::P2wsh),
                         _ => None,
                     },
+                    1 if prog.len() == 32 => Some(AddressType::P2tr),
                     _ => None,
                 }
             }
@@ -337,7 +364,42 @@ impl Address {
     /// Segwit addresses with unassigned witness versions or non-standard
     /// program sizes are considered non-standard.
     pub fn is_standard(&self) -> bool {
-        self.address_type().is_some()
+        self.is_spend_standard()
+    }
+
+    /// Check whether or not the address is following Bitcoin standardness
+    /// rules when *spending* from this address, i.e. whether wallets can
+    /// expect outputs paying to it to be relayed and mined.
+    ///
+    /// Segwit addresses with unassigned witness versions or non-standard
+    /// program sizes are considered non-standard. So are P2tr addresses for
+    /// now, as their bech32m (BIP-350) encoding is not supported yet and the
+    /// string produced for them is not a valid Taproot address.
+    pub fn is_spend_standard(&self) -> bool {
+        match self.address_type() {
+            Some(AddressType::P2tr) | None => false,
+            Some(_) => true,
+        }
+    }
+
+    /// Check whether the address can be used on the given network.
+    ///
+    /// Testnet, signet and regtest share their base58 prefixes, so legacy
+    /// addresses parsed as one of them are valid for all three. Segwit
+    /// addresses are also shared between testnet and signet, but regtest
+    /// has its own bech32 prefix.
+    pub fn is_valid_for_network(&self, network: Network) -> bool {
+        let is_legacy = match self.address_type() {
+            Some(AddressType::P2pkh) | Some(AddressType::P2sh) => true,
+            _ => false,
+        };
+
+        match (self.network, network) {
+            (a, b) if a == b => true,
+            (Network::Bitcoin, _) | (_, Network::Bitcoin) => false,
+            (Network::Regtest, _) | (_, Network::Regtest) if !is_legacy => false,
+            (Network::Testnet, _) | (Network::Regtest, _) | (Network::Signet, _) => true,
+        }
     }
 
     /// Get an [Address] from an output script (scriptPubkey).
@@ -491,6 +553,25 @@ impl ::std::fmt::Debug for Address {
     }
 }
 
+/// Compares the addresses paid by the outputs of two blocks, such as the old
+/// and new block at a height during a reorg. Returns the addresses only paid
+/// in `old`, followed by those only paid in `new`.
+///
+/// Outputs whose script has no address form are ignored.
+pub fn address_delta(old: &Block, new: &Block, network: Network) -> (HashSet<Address>, HashSet<Address>) {
+    let addresses = |block: &Block| -> HashSet<Address> {
+        block.txdata.iter()
+            .flat_map(|tx| tx.output.iter())
+            .filter_map(|output| Address::from_script(&output.script_pubkey, network))
+            .collect()
+    };
+    let old = addresses(old);
+    let new = addresses(new);
+    let removed = old.difference(&new).cloned().collect();
+    let added = new.difference(&old).cloned().collect();
+    (removed, added)
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -555,6 +636,28 @@ mod tests {
         roundtrips(&addr);
     }
 
+    #[test]
+    fn test_address_from_hash() {
+        let hash = hex_pubkeyhash!("162c5ea71c0b23f5b9022ef047c4a86470a5b070");
+        let addr = Address::from_pubkey_hash(hash, Bitcoin);
+        assert_eq!(&addr.to_string(), "132F25rTsvBdp9JzLLBHP5mvGY66i1xdiM");
+        assert_eq!(addr.payload, Payload::PubkeyHash(hash));
+        let addr = Address::from_pubkey_hash(hash, Testnet);
+        assert_eq!(&addr.to_string(), "mhYCK8wSgwctbFnc3u9fCzzF8XgodR9KHX");
+        roundtrips(&addr);
+
+        let hash = hex_scripthash!("162c5ea71c0b23f5b9022ef047c4a86470a5b070");
+        let addr = Address::from_script_hash(hash, Bitcoin);
+        assert_eq!(&addr.to_string(), "33iFwdLuRpW1uK1RTRqsoi8rR4NpDzk66k");
+        assert_eq!(addr.address_type(), Some(AddressType::P2sh));
+        let addr = Address::from_script_hash(hash, Testnet);
+        assert_eq!(&addr.to_string(), "2MuGU1NGw3H1N76dy8ZTkRf87dQaz5oKCau");
+        roundtrips(&addr);
+
+        let script = hex_script!("51");
+        assert_eq!(Address::from_script_hash(ScriptHash::hash(&script[..]), Bitcoin), Address::p2sh(&script, Bitcoin));
+    }
+
     #[test]
     fn test_p2sh_address_58() {
         let addr = Address {
@@ -616,10 +719,15 @@ mod tests {
         assert_eq!(&addr.to_string(), "3QBRmWNqqBGme9er7fMkGqtZtp4gjMFxhE");
         assert_eq!(addr.address_type(), Some(AddressType::P2sh));
         roundtrips(&addr);
+        let script = Script::new_p2sh_p2wpkh(&key.wpubkey_hash().unwrap());
+        assert_eq!(script, addr.script_pubkey());
+        assert_eq!(script.to_hex(), "a914f6b2517ca82f1b0ed43830d075069d6aa0b695ca87");
+        assert_eq!(Address::from_script(&script, Bitcoin), Some(addr));
 
         // Test uncompressed pubkey
         key.compressed = false;
         assert_eq!(Address::p2wpkh(&key, Bitcoin), Err(Error::UncompressedPubkey));
+        assert_eq!(Address::p2shwpkh(&key, Bitcoin), Err(Error::UncompressedPubkey));
     }
 
     #[test]
@@ -649,6 +757,82 @@ mod tests {
         roundtrips(&addr);
     }
 
+    #[test]
+    fn test_address_type() {
+        let addresses = [
+            ("1QJVDzdqb1VpbDK7uDeyVXy9mR27CJiyhY", Some(AddressType::P2pkh)),
+            ("33iFwdLuRpW1uK1RTRqsoi8rR4NpDzk66k", Some(AddressType::P2sh)),
+            ("bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw", Some(AddressType::P2wpkh)),
+            ("bc1qwqdg6squsna38e46795at95yu9atm8azzmyvckulcc7kytlcckxswvvzej", Some(AddressType::P2wsh)),
+        ];
+        for &(addr, expected) in addresses.iter() {
+            let addr = Address::from_str(addr).unwrap();
+            assert_eq!(addr.address_type(), expected, "address {}", addr);
+            assert!(addr.is_spend_standard());
+        }
+
+        // Taproot output key, witness v1 with a 32-byte program.
+        let p2tr = Address {
+            payload: Payload::WitnessProgram {
+                version: bech32::u5::try_from_u8(1).expect("0<32"),
+                program: hex!("a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"),
+            },
+            network: Network::Bitcoin,
+        };
+        assert_eq!(p2tr.address_type(), Some(AddressType::P2tr));
+        assert!(!p2tr.is_spend_standard());
+        assert_eq!(AddressType::from_str("p2tr"), Ok(AddressType::P2tr));
+        assert_eq!(AddressType::P2tr.to_string(), "p2tr");
+
+        // Witness v1 with a non-32-byte program and unassigned versions are unknown.
+        let unknown = [
+            (1, hex!("751e76e8199196d454941c45d1b3a323f1433bd6")),
+            (13, hex!("751e76e8199196d454941c45d1b3a323f1433bd6")),
+        ];
+        for &(version, ref program) in unknown.iter() {
+            let addr = Address {
+                payload: Payload::WitnessProgram {
+                    version: bech32::u5::try_from_u8(version).expect("0<32"),
+                    program: program.clone(),
+                },
+                network: Network::Bitcoin,
+            };
+            assert_eq!(addr.address_type(), None);
+            assert!(!addr.is_spend_standard());
+        }
+    }
+
+    #[test]
+    fn test_is_valid_for_network() {
+        use network::constants::Network::{Regtest, Signet};
+
+        let p2pkh = Address::from_str("1QJVDzdqb1VpbDK7uDeyVXy9mR27CJiyhY").unwrap();
+        assert!(p2pkh.is_valid_for_network(Bitcoin));
+        assert!(!p2pkh.is_valid_for_network(Testnet));
+        assert!(!p2pkh.is_valid_for_network(Signet));
+        assert!(!p2pkh.is_valid_for_network(Regtest));
+
+        // Legacy testnet addresses are shared by all test networks.
+        let p2pkh = Address::from_str("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn").unwrap();
+        assert_eq!(p2pkh.network, Testnet);
+        assert!(!p2pkh.is_valid_for_network(Bitcoin));
+        assert!(p2pkh.is_valid_for_network(Testnet));
+        assert!(p2pkh.is_valid_for_network(Signet));
+        assert!(p2pkh.is_valid_for_network(Regtest));
+
+        // Segwit testnet addresses are not valid on regtest.
+        let p2wpkh = Address::from_str("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx").unwrap();
+        assert!(!p2wpkh.is_valid_for_network(Bitcoin));
+        assert!(p2wpkh.is_valid_for_network(Testnet));
+        assert!(p2wpkh.is_valid_for_network(Signet));
+        assert!(!p2wpkh.is_valid_for_network(Regtest));
+
+        let p2wpkh = Address::from_str("bcrt1q2nfxmhd4n3c8834pj72xagvyr9gl57n5r94fsl").unwrap();
+        assert!(p2wpkh.is_valid_for_network(Regtest));
+        assert!(!p2wpkh.is_valid_for_network(Testnet));
+        assert!(!p2wpkh.is_valid_for_network(Bitcoin));
+    }
+
     #[test]
     fn test_bip173_vectors() {
         let valid_vectors = [
@@ -743,4 +927,33 @@ mod tests {
             hex_script!("001454d26dddb59c7073c6a197946ea1841951fa7a74")
         );
     }
+
+    #[test]
+    fn test_address_delta() {
+        use blockdata::constants::genesis_block;
+        use blockdata::transaction::TxOut;
+
+        let pay = |addr: &Address| TxOut { value: 1, script_pubkey: addr.script_pubkey() };
+        let shared = Address::from_str("1QJVDzdqb1VpbDK7uDeyVXy9mR27CJiyhY").unwrap();
+        let old_only = Address::from_str("33iFwdLuRpW1uK1RTRqsoi8rR4NpDzk66k").unwrap();
+        let new_only = Address::from_str("bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw").unwrap();
+
+        let mut old = genesis_block(Bitcoin);
+        old.txdata[0].output = vec![pay(&shared), pay(&old_only)];
+        let mut new = genesis_block(Bitcoin);
+        new.txdata[0].output = vec![pay(&new_only), pay(&shared)];
+        // Outputs without an address are ignored.
+        new.txdata[0].output.push(TxOut { value: 0, script_pubkey: hex_script!("6a") });
+        // Paying the same address twice is no different from once.
+        let mut tx = new.txdata[0].clone();
+        tx.output = vec![pay(&new_only)];
+        new.txdata.push(tx);
+
+        let (removed, added) = address_delta(&old, &new, Bitcoin);
+        assert_eq!(removed, vec![old_only.clone()].into_iter().collect());
+        assert_eq!(added, vec![new_only].into_iter().collect());
+
+        let (removed, added) = address_delta(&old, &old, Bitcoin);
+        assert!(removed.is_empty() && added.is_empty());
+    }
 }
@@ -40,7 +40,7 @@ pub enum Denomination {
 
 impl Denomination {
     /// The number of decimal places more than a satoshi.
-    fn precision(self) -> i32 {
+    pub(crate) fn precision(self) -> i32 {
         match self {
             Denomination::Bitcoin => -8,
             Denomination::MilliBitcoin => -5,
@@ -292,6 +292,10 @@ impl Amount {
     }
 
     /// Convert from a value expressing bitcoins to an [Amount].
+    ///
+    /// Fails with [ParseAmountError::TooPrecise] if the shortest decimal
+    /// representation of `btc` has more than eight decimal places, such as
+    /// `0.1 + 0.2`, instead of rounding to whole satoshis.
     pub fn from_btc(btc: f64) -> Result<Amount, ParseAmountError> {
         Amount::from_float_in(btc, Denomination::Bitcoin)
     }
@@ -666,8 +670,13 @@ impl SignedAmount {
     // Some arithmetic that doesn't fit in `std::ops` traits.
 
     /// Get the absolute value of this [SignedAmount].
+    ///
+    /// The absolute value of [SignedAmount::min_value] does not fit, so it
+    /// saturates to [SignedAmount::max_value]. Use [checked_abs] to detect this.
+    ///
+    /// [checked_abs]: #method.checked_abs
     pub fn abs(self) -> SignedAmount {
-        SignedAmount(self.0.abs())
+        self.checked_abs().unwrap_or_else(SignedAmount::max_value)
     }
 
     /// Returns a number representing sign of this [SignedAmount].
@@ -849,6 +858,54 @@ impl FromStr for SignedAmount {
     }
 }
 
+/// A fee rate, expressed in satoshis per 1000 virtual bytes like Bitcoin Core's `CFeeRate`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FeeRate(u64);
+
+impl FeeRate {
+    /// Create a [FeeRate] from a number of satoshis per 1000 virtual bytes.
+    pub fn from_sat_per_kvb(sat_per_kvb: u64) -> FeeRate {
+        FeeRate(sat_per_kvb)
+    }
+
+    /// Create a [FeeRate] from a number of satoshis per virtual byte.
+    pub fn from_sat_per_vb(sat_per_vb: u64) -> FeeRate {
+        FeeRate(sat_per_vb.saturating_mul(1000))
+    }
+
+    /// Create the [FeeRate] paid by `fee` satoshis over `vsize` virtual bytes.
+    ///
+    /// The rate is rounded down to the satoshi per kvB. A zero `vsize` gives a zero rate.
+    pub fn from_fee_and_vsize(fee: u64, vsize: usize) -> FeeRate {
+        if vsize == 0 {
+            return FeeRate(0);
+        }
+        FeeRate(fee.saturating_mul(1000) / vsize as u64)
+    }
+
+    /// Get the number of satoshis per 1000 virtual bytes.
+    pub fn as_sat_per_kvb(self) -> u64 {
+        self.0
+    }
+
+    /// Get the number of satoshis per virtual byte.
+    pub fn as_sat_per_vb(self) -> f64 {
+        self.0 as f64 / 1000.0
+    }
+
+    /// Calculate the fee in satoshis for `vsize` virtual bytes at this rate, rounding up.
+    pub fn fee_for_vsize(self, vsize: usize) -> u64 {
+        let fee = self.0.saturating_mul(vsize as u64);
+        fee / 1000 + if fee % 1000 == 0 { 0 } else { 1 }
+    }
+}
+
+impl fmt::Display for FeeRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} sat/kvB", self.0)
+    }
+}
+
 #[cfg(feature = "serde")]
 pub mod serde {
     // methods are implementation of a standardized serde-specific signature
@@ -1192,6 +1249,20 @@ mod tests {
         assert_eq!(&btc(0.0012).to_float_in(D::Bitcoin).to_string(), "0.0012")
     }
 
+    #[test]
+    fn from_btc_precision() {
+        assert_eq!(Amount::from_btc(0.1), Ok(Amount::from_sat(10_000_000)));
+        assert_eq!(Amount::from_btc(0.00000001), Ok(Amount::from_sat(1)));
+        assert_eq!(Amount::from_btc(20_999_999.976_9), Ok(Amount::from_sat(2_099_999_997_690_000)));
+        assert_eq!(SignedAmount::from_btc(-0.1), Ok(SignedAmount::from_sat(-10_000_000)));
+
+        // Sub-satoshi values are rejected rather than rounded.
+        assert_eq!(Amount::from_btc(0.000000015), Err(ParseAmountError::TooPrecise));
+        assert_eq!(Amount::from_btc(1e-9), Err(ParseAmountError::TooPrecise));
+        assert_eq!(Amount::from_btc(0.1 + 0.2), Err(ParseAmountError::TooPrecise));
+        assert_eq!(SignedAmount::from_btc(-0.123456789), Err(ParseAmountError::TooPrecise));
+    }
+
     #[test]
     fn parsing() {
         use super::ParseAmountError as E;
@@ -1257,6 +1328,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn signed_abs_and_display() {
+        use super::Denomination as D;
+
+        let min = SignedAmount::min_value();
+        assert_eq!(SignedAmount::from_sat(-42).abs(), SignedAmount::from_sat(42));
+        assert_eq!(SignedAmount::from_sat(42).abs(), SignedAmount::from_sat(42));
+        assert_eq!(min.abs(), SignedAmount::max_value());
+        assert_eq!(min.checked_abs(), None);
+        assert_eq!((min + SignedAmount::ONE_SAT).checked_abs(), Some(SignedAmount::max_value()));
+        assert_eq!(SignedAmount::from_sat(-42).checked_abs(), Some(SignedAmount::from_sat(42)));
+
+        assert_eq!(SignedAmount::from_sat(-1_5000_0000).to_string_with_denomination(D::Bitcoin), "-1.50000000 BTC");
+        assert_eq!(SignedAmount::from_sat(-1).to_string_with_denomination(D::MilliSatoshi), "-1000 msat");
+        assert_eq!(min.to_string_in(D::Satoshi), "-9223372036854775808");
+        assert_eq!(min.to_string_with_denomination(D::Satoshi), "-9223372036854775808 satoshi");
+        assert_eq!(min.to_string_with_denomination(D::Bitcoin), "-92233720368.54775808 BTC");
+        assert_eq!(min.to_string(), "-92233720368.54775808 BTC");
+    }
+
     #[test]
     fn test_unsigned_signed_conversion() {
         use super::ParseAmountError as E;
@@ -1346,6 +1437,37 @@ mod tests {
 
     }
 
+    #[test]
+    fn from_str_with_unit_suffix() {
+        use super::Denomination as D;
+        use super::ParseAmountError as E;
+
+        let parse = Amount::from_str_with_denomination;
+        assert_eq!(parse("1.5 BTC"), Ok(Amount::from_sat(1_5000_0000)));
+        assert_eq!(parse("2.5 mBTC"), Ok(Amount::from_sat(250_000)));
+        assert_eq!(parse("1.23 uBTC"), Ok(Amount::from_sat(123)));
+        assert_eq!(parse("1.23 bits"), Ok(Amount::from_sat(123)));
+        assert_eq!(parse("50000 sat"), Ok(Amount::from_sat(50_000)));
+        assert_eq!(parse("50000 satoshi"), Ok(Amount::from_sat(50_000)));
+        assert_eq!(parse("2000 msat"), Ok(Amount::from_sat(2)));
+        assert_eq!("1.5 BTC".parse::<Amount>(), Ok(Amount::from_sat(1_5000_0000)));
+
+        // Decimal places are checked against the unit.
+        assert_eq!(parse("0.000000001 BTC"), Err(E::TooPrecise));
+        assert_eq!(parse("0.000001 mBTC"), Err(E::TooPrecise));
+        assert_eq!(parse("0.001 uBTC"), Err(E::TooPrecise));
+        assert_eq!(parse("0.5 sat"), Err(E::TooPrecise));
+        assert_eq!(parse("1001 msat"), Err(E::TooPrecise));
+
+        // A missing or unknown unit is rejected rather than guessed.
+        assert_eq!(parse("1.5"), Err(E::InvalidFormat));
+        assert_eq!("1.5".parse::<Amount>(), Err(E::InvalidFormat));
+        assert_eq!(parse("1.5 DOGE"), Err(E::UnknownDenomination("DOGE".to_owned())));
+        assert_eq!(parse("1.5 BTC extra"), Err(E::InvalidFormat));
+        // Callers which assume bitcoin parse the number with an explicit denomination.
+        assert_eq!(Amount::from_str_in("1.5", D::Bitcoin), Ok(Amount::from_sat(1_5000_0000)));
+    }
+
     #[test]
     fn to_string_with_denomination_from_str_roundtrip() {
         use super::Denomination as D;
@@ -1393,6 +1515,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fee_rate() {
+        assert_eq!(FeeRate::from_sat_per_vb(5), FeeRate::from_sat_per_kvb(5000));
+        assert_eq!(FeeRate::from_fee_and_vsize(1000, 141), FeeRate::from_sat_per_kvb(7092));
+        assert_eq!(FeeRate::from_fee_and_vsize(1000, 0), FeeRate::default());
+        assert_eq!(FeeRate::from_sat_per_kvb(7092).as_sat_per_vb(), 7.092);
+
+        assert_eq!(FeeRate::from_sat_per_kvb(1000).fee_for_vsize(141), 141);
+        assert_eq!(FeeRate::from_sat_per_kvb(1001).fee_for_vsize(141), 142);
+        assert_eq!(FeeRate::from_sat_per_kvb(7092).fee_for_vsize(0), 0);
+
+        assert!(FeeRate::from_sat_per_vb(2) > FeeRate::from_sat_per_kvb(1999));
+        assert_eq!(FeeRate::from_sat_per_kvb(1234).to_string(), "1234 sat/kvB");
+    }
+
     #[cfg(feature = "serde")]
     #[test]
     fn serde_as_btc() {
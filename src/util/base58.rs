@@ -160,6 +160,17 @@ pub fn from_check(data: &str) -> Result<Vec<u8>, Error> {
     Ok(ret)
 }
 
+/// Decode a base58check-encoded string, splitting off the leading version byte
+/// from the payload
+pub fn from_check_with_version(data: &str) -> Result<(u8, Vec<u8>), Error> {
+    let mut ret = from_check(data)?;
+    if ret.is_empty() {
+        return Err(Error::InvalidLength(0));
+    }
+    let payload = ret.split_off(1);
+    Ok((ret[0], payload))
+}
+
 fn format_iter<I, W>(writer: &mut W, data: I) -> Result<(), fmt::Error>
 where
     I: Iterator<Item = u8> + Clone,
@@ -300,5 +311,33 @@ mod tests {
         assert_eq!(from_check(&encode_slice(&[1,2,3])), Err(Error::TooShort(3)));
 
     }
+
+    #[test]
+    fn test_base58_check_errors() {
+        let addr = "1PfJpZsjreyVrqeoAfabrRwwjQyoSQMmHH";
+        assert_eq!(from_check_with_version(addr),
+                   Ok((0, Vec::from_hex("f8917303bfa8ef24f292e8fa1419b20460ba064d").unwrap())));
+        assert_eq!(from_check_with_version("3CMNFxN1oHBc4R1EpboAL5yzHGgE611Xou").unwrap().0, 5);
+
+        // Corrupted checksum
+        let mut data = from_check(addr).unwrap();
+        let checksum = sha256d::Hash::hash(&data);
+        data.extend_from_slice(&checksum[..4]);
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        match from_check(&encode_slice(&data)) {
+            Err(Error::BadChecksum(..)) => {},
+            r => panic!("expected bad checksum, got {:?}", r),
+        }
+        assert!(from_check_with_version("1PfJpZsjreyVrqeoAfabrRwwjQyoSQMmHJ").is_err());
+
+        // Non-base58 characters
+        assert_eq!(from_check("1PfJpZsjreyVrqeoAfabrRwwjQyoSQMmH0"), Err(Error::BadByte(b'0')));
+        assert_eq!(from_check_with_version("1PfJpZsjreyVrqeoAfabrRwwjQyoSQMmHl"), Err(Error::BadByte(b'l')));
+
+        // Too short for either checksum or version byte
+        assert_eq!(from_check_with_version(&encode_slice(&[1, 2])), Err(Error::TooShort(2)));
+        assert_eq!(from_check_with_version(&check_encode_slice(&[])), Err(Error::InvalidLength(0)));
+    }
 }
 
@@ -105,6 +105,10 @@ impl SighashComponents {
 }
 
 /// A replacement for SigHashComponents which supports all sighash modes
+///
+/// The midstate hashes shared by all inputs (`hashPrevouts`, `hashSequence` and `hashOutputs`)
+/// are computed once and reused for every input signed through the same cache, mirroring
+/// Bitcoin Core's `PrecomputedTransactionData`.
 pub struct SigHashCache<R: Deref<Target=Transaction>> {
     /// Access to transaction required for various introspection
     tx: R,
@@ -335,6 +339,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn sighash_cache_reuse() {
+        let tx = deserialize::<Transaction>(
+            &Vec::from_hex(
+                "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f000000\
+                0000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a01000000\
+                00ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093\
+                510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000",
+            ).unwrap()[..],
+        ).unwrap();
+        let script_code = p2pkh_hex("025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357");
+        let value = 600_000_000;
+
+        let comp = SighashComponents::new(&tx);
+        let mut cache = SigHashCache::new(&tx);
+        assert_eq!(cache.hash_prevouts(), comp.hash_prevouts.as_hash());
+        assert_eq!(cache.hash_sequence(), comp.hash_sequence.as_hash());
+        assert_eq!(cache.hash_outputs(), comp.hash_outputs.as_hash());
+
+        let sighash_types = [
+            SigHashType::All, SigHashType::None, SigHashType::Single,
+            SigHashType::AllPlusAnyoneCanPay, SigHashType::NonePlusAnyoneCanPay,
+            SigHashType::SinglePlusAnyoneCanPay,
+        ];
+        for input_index in 0..tx.input.len() {
+            for sighash_type in sighash_types.iter() {
+                let cached = cache.signature_hash(input_index, &script_code, value, *sighash_type);
+                let naive = SigHashCache::new(&tx).signature_hash(input_index, &script_code, value, *sighash_type);
+                assert_eq!(cached, naive);
+            }
+            assert_eq!(
+                cache.signature_hash(input_index, &script_code, value, SigHashType::All),
+                comp.sighash_all(&tx.input[input_index], &script_code, value)
+            );
+        }
+        assert_eq!(
+            cache.signature_hash(1, &script_code, value, SigHashType::All),
+            hex_hash!(SigHash, "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670")
+        );
+    }
+
     #[test]
     fn bip143_p2wpkh_nested_in_p2sh() {
         let tx = deserialize::<Transaction>(
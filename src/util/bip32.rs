@@ -17,7 +17,7 @@
 //! at https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki
 
 use std::default::Default;
-use std::{error, fmt};
+use std::{error, fmt, io, slice};
 use std::str::FromStr;
 #[cfg(feature = "serde")] use serde;
 
@@ -25,6 +25,7 @@ use hash_types::XpubIdentifier;
 use hashes::{sha512, Hash, HashEngine, Hmac, HmacEngine};
 use secp256k1::{self, Secp256k1};
 
+use consensus::encode::{self, Decodable, Encodable};
 use network::constants::Network;
 use util::{base58, endian};
 use util::key::{self, PublicKey, PrivateKey};
@@ -41,6 +42,18 @@ pub struct Fingerprint([u8; 4]);
 impl_array_newtype!(Fingerprint, u8, 4);
 impl_bytes_newtype!(Fingerprint, 4);
 
+impl Encodable for Fingerprint {
+    fn consensus_encode<W: io::Write>(&self, w: W) -> Result<usize, io::Error> {
+        self.0.consensus_encode(w)
+    }
+}
+
+impl Decodable for Fingerprint {
+    fn consensus_decode<D: io::Read>(d: D) -> Result<Self, encode::Error> {
+        Ok(Fingerprint(Decodable::consensus_decode(d)?))
+    }
+}
+
 impl Default for Fingerprint {
     fn default() -> Fingerprint { Fingerprint([0; 4]) }
 }
@@ -138,11 +151,16 @@ impl ChildNumber {
         }
     }
 
-    /// Returns the child number that is a single increment from this one.
+    /// Returns the child number that is a single increment from this one, or
+    /// an error if the index would leave [0, 2^31 - 1].
     pub fn increment(self) -> Result<ChildNumber, Error> {
         match self {
-            ChildNumber::Normal{ index: idx } => ChildNumber::from_normal_idx(idx+1),
-            ChildNumber::Hardened{ index: idx } => ChildNumber::from_hardened_idx(idx+1),
+            ChildNumber::Normal{ index: idx } => {
+                ChildNumber::from_normal_idx(idx.checked_add(1).ok_or(Error::InvalidChildNumber(idx))?)
+            }
+            ChildNumber::Hardened{ index: idx } => {
+                ChildNumber::from_hardened_idx(idx.checked_add(1).ok_or(Error::InvalidChildNumber(idx))?)
+            }
         }
     }
 }
@@ -399,6 +417,27 @@ impl DerivationPath {
         new_path.0.extend_from_slice(path.as_ref());
         new_path
     }
+
+    /// Serializes the path as concatenated little-endian child numbers, the
+    /// format used by the key origins of PSBT.
+    pub fn to_consensus_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::with_capacity(4 * self.0.len());
+        for cn in &self.0 {
+            ret.extend_from_slice(&endian::u32_to_array_le(u32::from(*cn)));
+        }
+        ret
+    }
+
+    /// Parses a path from concatenated little-endian child numbers, see
+    /// [`DerivationPath::to_consensus_bytes`].
+    ///
+    /// [`DerivationPath::to_consensus_bytes`]: #method.to_consensus_bytes
+    pub fn from_consensus_bytes(bytes: &[u8]) -> Result<DerivationPath, Error> {
+        if bytes.len() % 4 != 0 {
+            return Err(Error::InvalidDerivationPathFormat);
+        }
+        Ok(bytes.chunks(4).map(|cn| ChildNumber::from(endian::slice_to_u32_le(cn))).collect())
+    }
 }
 
 impl fmt::Display for DerivationPath {
@@ -422,6 +461,19 @@ impl fmt::Debug for DerivationPath {
 /// master extended public key and a derivation path from it.
 pub type KeySource = (Fingerprint, DerivationPath);
 
+/// Checks that a key for `found` can be used on `expected`.
+///
+/// All test networks share their version bytes, so keys decoded from a string
+/// are always for `Network::Testnet`; only mainnet and test networks are told
+/// apart.
+fn check_network(expected: Network, found: Network) -> Result<(), Error> {
+    if (expected == Network::Bitcoin) == (found == Network::Bitcoin) {
+        Ok(())
+    } else {
+        Err(Error::NetworkMismatch { expected, found })
+    }
+}
+
 /// A BIP32 error
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Error {
@@ -442,7 +494,14 @@ pub enum Error {
     /// Encoded extended key data has wrong length
     WrongExtendedKeyLength(usize),
     /// Base58 encoding error
-    Base58(base58::Error)
+    Base58(base58::Error),
+    /// A key was used with a network other than its own
+    NetworkMismatch {
+        /// The network the key was expected to be for
+        expected: Network,
+        /// The network of the key
+        found: Network,
+    },
 }
 
 impl fmt::Display for Error {
@@ -457,6 +516,7 @@ impl fmt::Display for Error {
             Error::UnknownVersion(ref bytes) => write!(f, "unknown version magic bytes: {:?}", bytes),
             Error::WrongExtendedKeyLength(ref len) => write!(f, "encoded extended key data has wrong length {}", len),
             Error::Base58(ref err) => write!(f, "base58 encoding error: {}", err),
+            Error::NetworkMismatch { expected, found } => write!(f, "key is for network {}, expected {}", found, expected),
         }
     }
 }
@@ -528,6 +588,37 @@ impl ExtendedPrivKey {
         Ok(sk)
     }
 
+    /// Like [`derive_priv`], but first checks that this key is for `network`,
+    /// returning [`Error::NetworkMismatch`] otherwise.
+    ///
+    /// [`derive_priv`]: #method.derive_priv
+    /// [`Error::NetworkMismatch`]: enum.Error.html#variant.NetworkMismatch
+    pub fn derive_priv_for_network<C: secp256k1::Signing, P: AsRef<[ChildNumber]>>(
+        &self,
+        secp: &Secp256k1<C>,
+        network: Network,
+        path: &P,
+    ) -> Result<ExtendedPrivKey, Error> {
+        check_network(network, self.network)?;
+        self.derive_priv(secp, path)
+    }
+
+    /// Derives the keys along `path` one step at a time, yielding each child
+    /// number together with the key derived with it.
+    ///
+    /// The iteration stops after the first derivation error.
+    pub fn derive_path_iter<'s, 'p, C: secp256k1::Signing>(
+        &self,
+        secp: &'s Secp256k1<C>,
+        path: &'p [ChildNumber],
+    ) -> DerivePrivIter<'s, 'p, C> {
+        DerivePrivIter {
+            secp,
+            key: Some(*self),
+            path: path.iter(),
+        }
+    }
+
     /// Private->Private child key derivation
     pub fn ckd_priv<C: secp256k1::Signing>(&self, secp: &Secp256k1<C>, i: ChildNumber) -> Result<ExtendedPrivKey, Error> {
         let mut hmac_engine: HmacEngine<sha512::Hash> = HmacEngine::new(&self.chain_code[..]);
@@ -619,6 +710,49 @@ impl ExtendedPrivKey {
     pub fn fingerprint<C: secp256k1::Signing>(&self, secp: &Secp256k1<C>) -> Fingerprint {
         Fingerprint::from(&self.identifier(secp)[0..4])
     }
+
+    /// Returns the fingerprint of the master key this key was derived from,
+    /// as used in the key origins of PSBTs and descriptors. Like the key's
+    /// own fingerprint, it does not depend on the network.
+    ///
+    /// This is only known for the master key itself and its direct children,
+    /// other keys return `None`.
+    pub fn master_fingerprint<C: secp256k1::Signing>(&self, secp: &Secp256k1<C>) -> Option<Fingerprint> {
+        match self.depth {
+            0 => Some(self.fingerprint(secp)),
+            1 => Some(self.parent_fingerprint),
+            _ => None,
+        }
+    }
+}
+
+/// An iterator over the intermediate keys of a derivation, returned by
+/// [`ExtendedPrivKey::derive_path_iter`].
+///
+/// [`ExtendedPrivKey::derive_path_iter`]: struct.ExtendedPrivKey.html#method.derive_path_iter
+pub struct DerivePrivIter<'s, 'p, C: 's + secp256k1::Signing> {
+    secp: &'s Secp256k1<C>,
+    key: Option<ExtendedPrivKey>,
+    path: slice::Iter<'p, ChildNumber>,
+}
+
+impl<'s, 'p, C: secp256k1::Signing> Iterator for DerivePrivIter<'s, 'p, C> {
+    type Item = Result<(ChildNumber, ExtendedPrivKey), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = self.key?;
+        let cnum = *self.path.next()?;
+        match key.ckd_priv(self.secp, cnum) {
+            Ok(child) => {
+                self.key = Some(child);
+                Some(Ok((cnum, child)))
+            }
+            Err(e) => {
+                self.key = None;
+                Some(Err(e))
+            }
+        }
+    }
 }
 
 impl ExtendedPubKey {
@@ -649,6 +783,21 @@ impl ExtendedPubKey {
         Ok(pk)
     }
 
+    /// Like [`derive_pub`], but first checks that this key is for `network`,
+    /// returning [`Error::NetworkMismatch`] otherwise.
+    ///
+    /// [`derive_pub`]: #method.derive_pub
+    /// [`Error::NetworkMismatch`]: enum.Error.html#variant.NetworkMismatch
+    pub fn derive_pub_for_network<C: secp256k1::Verification, P: AsRef<[ChildNumber]>>(
+        &self,
+        secp: &Secp256k1<C>,
+        network: Network,
+        path: &P,
+    ) -> Result<ExtendedPubKey, Error> {
+        check_network(network, self.network)?;
+        self.derive_pub(secp, path)
+    }
+
     /// Compute the scalar tweak added to this key to get a child key
     pub fn ckd_pub_tweak(&self, i: ChildNumber) -> Result<(PrivateKey, ChainCode), Error> {
         match i {
@@ -700,15 +849,7 @@ impl ExtendedPubKey {
         }
 
         Ok(ExtendedPubKey {
-            network: if data[0..4] == [0x04u8, 0x88, 0xB2, 0x1E] {
-                Network::Bitcoin
-            } else if data[0..4] == [0x04u8, 0x35, 0x87, 0xCF] {
-                Network::Testnet
-            } else {
-                let mut ver = [0u8; 4];
-                ver.copy_from_slice(&data[0..4]);
-                return Err(Error::UnknownVersion(ver));
-            },
+            network: ExtendedPubKey::network_from_version(&data[0..4])?,
             depth: data[4],
             parent_fingerprint: Fingerprint::from(&data[5..9]),
             child_number: endian::slice_to_u32_be(&data[9..13]).into(),
@@ -717,6 +858,33 @@ impl ExtendedPubKey {
         })
     }
 
+    /// Returns the network of a base58-encoded extended public key from its
+    /// version bytes, without decoding the key itself.
+    ///
+    /// Like [`ExtendedPubKey::decode`], this returns `Network::Testnet` for
+    /// all test networks, which share their version bytes.
+    ///
+    /// [`ExtendedPubKey::decode`]: #method.decode
+    pub fn network_from_str(inp: &str) -> Result<Network, Error> {
+        let data = base58::from_check(inp)?;
+        if data.len() != 78 {
+            return Err(base58::Error::InvalidLength(data.len()).into());
+        }
+        ExtendedPubKey::network_from_version(&data[0..4])
+    }
+
+    fn network_from_version(version: &[u8]) -> Result<Network, Error> {
+        if version == [0x04u8, 0x88, 0xB2, 0x1E] {
+            Ok(Network::Bitcoin)
+        } else if version == [0x04u8, 0x35, 0x87, 0xCF] {
+            Ok(Network::Testnet)
+        } else {
+            let mut ver = [0u8; 4];
+            ver.copy_from_slice(version);
+            Err(Error::UnknownVersion(ver))
+        }
+    }
+
     /// Extended public key binary encoding according to BIP 32
     pub fn encode(&self) -> [u8; 78] {
         let mut ret = [0; 78];
@@ -862,6 +1030,26 @@ mod tests {
         assert_eq!(indexed.child(ChildNumber::from_hardened_idx(2).unwrap()), path);
     }
 
+    #[test]
+    fn test_derivation_path_consensus_bytes() {
+        use consensus::encode::{deserialize, serialize};
+        use hashes::hex::ToHex;
+
+        let path = DerivationPath::from_str("m/44'/3'/0'/0/5").unwrap();
+        let bytes = path.to_consensus_bytes();
+        assert_eq!(bytes.to_hex(), "2c000080030000800000008000000000\
+                                    05000000");
+        assert_eq!(DerivationPath::from_consensus_bytes(&bytes).unwrap(), path);
+        assert_eq!(DerivationPath::from_consensus_bytes(&[]).unwrap(), DerivationPath::master());
+        assert_eq!(DerivationPath::from_consensus_bytes(&bytes[..19]),
+                   Err(Error::InvalidDerivationPathFormat));
+
+        let fingerprint = Fingerprint::from(&[0xd3, 0x4d, 0xb3, 0x3f][..]);
+        assert_eq!(serialize(&fingerprint), vec![0xd3, 0x4d, 0xb3, 0x3f]);
+        assert_eq!(deserialize::<Fingerprint>(&[0xd3, 0x4d, 0xb3, 0x3f]).unwrap(), fingerprint);
+        assert!(deserialize::<Fingerprint>(&[0xd3, 0x4d, 0xb3]).is_err());
+    }
+
     fn test_path<C: secp256k1::Signing + secp256k1::Verification>(secp: &Secp256k1<C>,
                  network: Network,
                  seed: &[u8],
@@ -921,6 +1109,44 @@ mod tests {
         assert_eq!(Ok(pk), decoded_pk);
     }
 
+    #[test]
+    fn test_child_number_bounds() {
+        let max = (1 << 31) - 1;
+        assert_eq!(ChildNumber::from_normal_idx(max), Ok(Normal { index: max }));
+        assert_eq!(ChildNumber::from_hardened_idx(max), Ok(Hardened { index: max }));
+        assert_eq!(ChildNumber::from_normal_idx(1 << 31), Err(Error::InvalidChildNumber(1 << 31)));
+        assert_eq!(ChildNumber::from_hardened_idx(1 << 31), Err(Error::InvalidChildNumber(1 << 31)));
+        assert_eq!(ChildNumber::from_hardened_idx(u32::max_value()),
+                   Err(Error::InvalidChildNumber(u32::max_value())));
+
+        assert!(ChildNumber::from_hardened_idx(0).unwrap().is_hardened());
+        assert!(ChildNumber::from_normal_idx(max).unwrap().is_normal());
+        assert_eq!(u32::from(ChildNumber::from_hardened_idx(max).unwrap()), u32::max_value());
+        assert_eq!(ChildNumber::from(1 << 31), Hardened { index: 0 });
+    }
+
+    #[test]
+    fn test_derive_path_iter() {
+        let secp = Secp256k1::new();
+        let seed = Vec::from_hex("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = ExtendedPrivKey::new_master(Bitcoin, &seed).unwrap();
+        let path = DerivationPath::from_str("m/0'/1/2'").unwrap();
+
+        let steps: Vec<(ChildNumber, ExtendedPrivKey)> = master.derive_path_iter(&secp, path.as_ref())
+            .collect::<Result<_, _>>().unwrap();
+        assert_eq!(steps.len(), 3);
+        for (i, &(cnum, key)) in steps.iter().enumerate() {
+            assert_eq!(cnum, path[i]);
+            assert_eq!(key.depth as usize, i + 1);
+            assert_eq!(key, master.derive_priv(&secp, &&path[..i + 1]).unwrap());
+        }
+        // BIP32 test vector 1
+        assert_eq!(ExtendedPubKey::from_private(&secp, &steps[2].1).to_string(),
+                   "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5");
+
+        assert_eq!(master.derive_path_iter(&secp, &[]).count(), 0);
+    }
+
     #[test]
     fn test_increment() {
         let idx = 9345497; // randomly generated, I promise
@@ -934,6 +1160,11 @@ mod tests {
         assert_eq!(cn.increment().err(), Some(Error::InvalidChildNumber(1<<31)));
         let cn = ChildNumber::from_hardened_idx(max).unwrap();
         assert_eq!(cn.increment().err(), Some(Error::InvalidChildNumber(1<<31)));
+        // Out of range indices built directly do not overflow.
+        let cn = ChildNumber::Normal { index: u32::max_value() };
+        assert_eq!(cn.increment().err(), Some(Error::InvalidChildNumber(u32::max_value())));
+        let cn = ChildNumber::Hardened { index: u32::max_value() };
+        assert_eq!(cn.increment().err(), Some(Error::InvalidChildNumber(u32::max_value())));
 
         let cn = ChildNumber::from_normal_idx(350).unwrap();
         let path = DerivationPath::from_str("m/42'").unwrap();
@@ -1053,6 +1284,133 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_network_from_str() {
+        let xpub = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+        // The same key with testnet version bytes.
+        let tpub = "tpubD6NzVbkrYhZ4XgiXtGrdW5XDAPFCL9h7we1vwNCpn8tGbBcgfVYjXyhWo4E1xkh56hjod1RhGjxbaTLV3X4FyWuejifB9jusQ46QzG87VKp";
+
+        assert_eq!(ExtendedPubKey::network_from_str(xpub), Ok(Bitcoin));
+        assert_eq!(ExtendedPubKey::network_from_str(tpub), Ok(Network::Testnet));
+        assert_eq!(ExtendedPubKey::from_str(xpub).unwrap().network, Bitcoin);
+        assert_eq!(ExtendedPubKey::from_str(tpub).unwrap().network, Network::Testnet);
+        assert_eq!(ExtendedPubKey::from_str(tpub).unwrap().public_key, ExtendedPubKey::from_str(xpub).unwrap().public_key);
+
+        // Private keys have different version bytes.
+        let xprv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+        assert_eq!(ExtendedPubKey::network_from_str(xprv), Err(Error::UnknownVersion([0x04, 0x88, 0xAD, 0xE4])));
+        assert!(ExtendedPubKey::network_from_str(&xpub[..100]).is_err());
+    }
+
+    #[test]
+    fn test_fingerprint_identifier() {
+        use hashes::hex::ToHex;
+
+        let secp = Secp256k1::new();
+        let seed = Vec::from_hex("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = ExtendedPrivKey::new_master(Bitcoin, &seed).unwrap();
+        let master_pub = ExtendedPubKey::from_private(&secp, &master);
+
+        // BIP32 test vector 1
+        assert_eq!(master_pub.identifier().to_hex(), "3442193e1bb70916e914552172cd4e2dbc9df811");
+        assert_eq!(master_pub.fingerprint().to_hex(), "3442193e");
+        assert_eq!(master.identifier(&secp), master_pub.identifier());
+        assert_eq!(master.fingerprint(&secp), master_pub.fingerprint());
+        assert_eq!(master_pub.identifier().as_hash(), ::util::hash::hash160(&master_pub.public_key.to_bytes()));
+
+        let child = master.derive_priv(&secp, &DerivationPath::from_str("m/0h").unwrap()).unwrap();
+        assert_eq!(child.parent_fingerprint, master.fingerprint(&secp));
+        assert_eq!(child.fingerprint(&secp).to_hex(), "5c1bd648");
+        assert_eq!(ExtendedPubKey::from_private(&secp, &child).identifier().to_hex(),
+                   "5c1bd648ed23aa5fd50ba52b2457c11e9e80a6a7");
+    }
+
+    #[test]
+    fn test_derive_bip44_path() {
+        use util::address::Address;
+
+        let secp = Secp256k1::new();
+        // BIP39 seed of "abandon abandon ... about" without passphrase
+        let seed = Vec::from_hex("5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc19a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4").unwrap();
+        let path = DerivationPath::from_str("m/44'/0'/0'/0/0").unwrap();
+
+        let master = ExtendedPrivKey::new_master(Bitcoin, &seed).unwrap();
+        let sk = master.derive_priv(&secp, &path).unwrap();
+        assert_eq!(sk.network, Bitcoin);
+        assert_eq!(sk.depth, 5);
+        assert_eq!(sk.child_number, Normal { index: 0 });
+        let pk = ExtendedPubKey::from_private(&secp, &sk);
+        assert_eq!(Address::p2pkh(&pk.public_key, pk.network).to_string(), "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA");
+
+        // The network is kept across derivation.
+        let master = ExtendedPrivKey::new_master(Network::Testnet, &seed).unwrap();
+        let sk = master.derive_priv(&secp, &path).unwrap();
+        assert_eq!(sk.network, Network::Testnet);
+        assert_eq!(ExtendedPubKey::from_private(&secp, &sk).network, Network::Testnet);
+
+        // Public derivation works for normal steps only.
+        let account = master.derive_priv(&secp, &DerivationPath::from_str("m/44'/0'/0'").unwrap()).unwrap();
+        let account_pub = ExtendedPubKey::from_private(&secp, &account);
+        let normal = DerivationPath::from_str("m/0/0").unwrap();
+        assert_eq!(
+            account_pub.derive_pub(&secp, &normal).unwrap(),
+            ExtendedPubKey::from_private(&secp, &account.derive_priv(&secp, &normal).unwrap())
+        );
+        let master_pub = ExtendedPubKey::from_private(&secp, &master);
+        assert_eq!(master_pub.derive_pub(&secp, &path), Err(Error::CannotDeriveFromHardenedKey));
+    }
+
+    #[test]
+    fn test_master_fingerprint() {
+        let secp = Secp256k1::new();
+        let seed = Vec::from_hex("000102030405060708090a0b0c0d0e0f").unwrap();
+        let expected = Fingerprint::from(&[0x34, 0x42, 0x19, 0x3e][..]);
+        for &network in &[Bitcoin, Network::Testnet, Network::Regtest] {
+            let master = ExtendedPrivKey::new_master(network, &seed).unwrap();
+            assert_eq!(master.master_fingerprint(&secp), Some(expected));
+            let child = master.derive_priv(&secp, &[ChildNumber::from_hardened_idx(0).unwrap()]).unwrap();
+            assert_eq!(child.master_fingerprint(&secp), Some(expected));
+            let grandchild = child.derive_priv(&secp, &[ChildNumber::from_normal_idx(1).unwrap()]).unwrap();
+            assert_eq!(grandchild.master_fingerprint(&secp), None);
+        }
+    }
+
+    #[test]
+    fn test_derive_network_mismatch() {
+        let secp = Secp256k1::new();
+        let seed = [0x42; 32];
+        let path = DerivationPath::from_str("m/0/1").unwrap();
+
+        let master = ExtendedPrivKey::new_master(Bitcoin, &seed).unwrap();
+        assert_eq!(master.derive_priv_for_network(&secp, Bitcoin, &path), master.derive_priv(&secp, &path));
+        assert_eq!(
+            master.derive_priv_for_network(&secp, Network::Testnet, &path),
+            Err(Error::NetworkMismatch { expected: Network::Testnet, found: Bitcoin })
+        );
+        let master_pub = ExtendedPubKey::from_private(&secp, &master);
+        assert_eq!(
+            master_pub.derive_pub_for_network(&secp, Network::Regtest, &path),
+            Err(Error::NetworkMismatch { expected: Network::Regtest, found: Bitcoin })
+        );
+
+        // Test networks cannot be told apart.
+        let master = ExtendedPrivKey::new_master(Network::Testnet, &seed).unwrap();
+        let master_pub = ExtendedPubKey::from_private(&secp, &master);
+        assert!(master.derive_priv_for_network(&secp, Network::Signet, &path).is_ok());
+        assert_eq!(
+            master_pub.derive_pub_for_network(&secp, Network::Regtest, &path),
+            master_pub.derive_pub(&secp, &path)
+        );
+        assert_eq!(
+            master_pub.derive_pub_for_network(&secp, Bitcoin, &path),
+            Err(Error::NetworkMismatch { expected: Bitcoin, found: Network::Testnet })
+        );
+        assert_eq!(
+            Error::NetworkMismatch { expected: Bitcoin, found: Network::Testnet }.to_string(),
+            "key is for network testnet, expected bitcoin"
+        );
+    }
+
     #[test]
     #[cfg(feature = "serde")]
     pub fn encode_decode_childnumber() {
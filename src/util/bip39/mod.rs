@@ -0,0 +1,370 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! BIP39 mnemonics
+//!
+//! Implementation of BIP39 mnemonic codes for generating deterministic keys,
+//! as defined at https://github.com/bitcoin/bips/blob/master/bip-0039.mediawiki
+//! Only the English wordlist is supported. Phrases and passphrases are used as
+//! given, without Unicode NFKD normalization, which only matters for non-ASCII
+//! passphrases.
+
+use std::{error, fmt};
+use std::str::FromStr;
+
+use hashes::{sha256, sha512, Hash, HashEngine, Hmac, HmacEngine};
+
+mod english;
+
+/// Number of PBKDF2 rounds used to derive a seed.
+const PBKDF2_ROUNDS: usize = 2048;
+
+/// A BIP39 error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The phrase does not have 12, 15, 18, 21 or 24 words.
+    BadWordCount(usize),
+    /// A word is not in the English wordlist.
+    UnknownWord(String),
+    /// The entropy is not 16, 20, 24, 28 or 32 bytes long.
+    BadEntropyLength(usize),
+    /// The checksum encoded in the last word does not match.
+    InvalidChecksum,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::BadWordCount(n) => write!(f, "mnemonic has {} words, expected 12, 15, 18, 21 or 24", n),
+            Error::UnknownWord(ref w) => write!(f, "unknown mnemonic word: {}", w),
+            Error::BadEntropyLength(n) => write!(f, "entropy of {} bytes, expected 16, 20, 24, 28 or 32", n),
+            Error::InvalidChecksum => f.write_str("mnemonic checksum mismatch"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// A BIP39 mnemonic phrase.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Mnemonic {
+    /// The encoded entropy.
+    entropy: Vec<u8>,
+    /// The words, separated by single spaces.
+    phrase: String,
+}
+
+impl Mnemonic {
+    /// Creates the mnemonic encoding `entropy`, which must be 16, 20, 24, 28 or 32 bytes
+    /// of random data for a 12, 15, 18, 21 or 24-word phrase respectively.
+    pub fn from_entropy(entropy: &[u8]) -> Result<Mnemonic, Error> {
+        if entropy.len() < 16 || entropy.len() > 32 || entropy.len() % 4 != 0 {
+            return Err(Error::BadEntropyLength(entropy.len()));
+        }
+
+        let checksum = sha256::Hash::hash(entropy)[0];
+        let n_words = entropy.len() * 3 / 4;
+        let bit = |i: usize| {
+            let byte = if i < entropy.len() * 8 { entropy[i / 8] } else { checksum };
+            (byte >> (7 - i % 8)) & 1 == 1
+        };
+
+        let words: Vec<&str> = (0..n_words).map(|w| {
+            let index = (0..11).fold(0, |acc, i| (acc << 1) | bit(w * 11 + i) as usize);
+            english::WORDS[index]
+        }).collect();
+
+        Ok(Mnemonic {
+            entropy: entropy.to_vec(),
+            phrase: words.join(" "),
+        })
+    }
+
+    /// Parses a mnemonic phrase, checking the word count, the words and the checksum.
+    ///
+    /// Words may be separated by any whitespace.
+    pub fn from_phrase(phrase: &str) -> Result<Mnemonic, Error> {
+        let words: Vec<&str> = phrase.split_whitespace().collect();
+        if words.len() < 12 || words.len() > 24 || words.len() % 3 != 0 {
+            return Err(Error::BadWordCount(words.len()));
+        }
+
+        let mut bits = Vec::with_capacity(words.len() * 11);
+        for word in &words {
+            let index = match english::WORDS.binary_search(word) {
+                Ok(index) => index,
+                Err(_) => return Err(Error::UnknownWord(word.to_string())),
+            };
+            bits.extend((0..11).rev().map(|i| (index >> i) & 1 == 1));
+        }
+
+        let entropy_bits = bits.len() * 32 / 33;
+        let entropy: Vec<u8> = bits[..entropy_bits].chunks(8).map(|byte| {
+            byte.iter().fold(0u8, |acc, &b| (acc << 1) | b as u8)
+        }).collect();
+
+        let mnemonic = Mnemonic::from_entropy(&entropy)?;
+        if mnemonic.phrase != words.join(" ") {
+            return Err(Error::InvalidChecksum);
+        }
+        Ok(mnemonic)
+    }
+
+    /// Returns the mnemonic phrase, with words separated by single spaces.
+    pub fn phrase(&self) -> &str {
+        &self.phrase
+    }
+
+    /// Returns the entropy encoded by the mnemonic.
+    pub fn entropy(&self) -> &[u8] {
+        &self.entropy
+    }
+
+    /// Returns the number of words in the phrase.
+    pub fn word_count(&self) -> usize {
+        self.entropy.len() * 3 / 4
+    }
+
+    /// Derives the 512-bit seed for BIP32 from the mnemonic and an optional passphrase
+    /// (use `""` for none).
+    pub fn to_seed(&self, passphrase: &str) -> [u8; 64] {
+        let mut salt = String::with_capacity(8 + passphrase.len());
+        salt.push_str("mnemonic");
+        salt.push_str(passphrase);
+        pbkdf2_hmac_sha512(self.phrase.as_bytes(), salt.as_bytes(), PBKDF2_ROUNDS)
+    }
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.phrase)
+    }
+}
+
+impl FromStr for Mnemonic {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Mnemonic, Error> {
+        Mnemonic::from_phrase(s)
+    }
+}
+
+/// PBKDF2 with HMAC-SHA512, producing a single 64-byte block.
+fn pbkdf2_hmac_sha512(password: &[u8], salt: &[u8], rounds: usize) -> [u8; 64] {
+    let keyed: HmacEngine<sha512::Hash> = HmacEngine::new(password);
+
+    let mut engine = keyed.clone();
+    engine.input(salt);
+    engine.input(&[0, 0, 0, 1]);
+    let mut u = Hmac::from_engine(engine).into_inner();
+    let mut result = u;
+
+    for _ in 1..rounds {
+        let mut engine = keyed.clone();
+        engine.input(&u[..]);
+        u = Hmac::from_engine(engine).into_inner();
+        for (r, b) in result.iter_mut().zip(u.iter()) {
+            *r ^= *b;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{english, Error, Mnemonic};
+
+    use hashes::hex::{FromHex, ToHex};
+    use network::constants::Network;
+    use util::bip32::ExtendedPrivKey;
+
+    #[test]
+    fn wordlist() {
+        assert_eq!(english::WORDS.len(), 2048);
+        assert!(english::WORDS.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(english::WORDS[0], "abandon");
+        assert_eq!(english::WORDS[1337], "poet");
+        assert_eq!(english::WORDS[2047], "zoo");
+    }
+
+    #[test]
+    fn english_vectors() {
+        // (entropy, mnemonic, seed with passphrase "TREZOR") from the official BIP39 vectors
+        let vectors = [
+            (
+                "00000000000000000000000000000000",
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+                "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+            ),
+            (
+                "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+                "legal winner thank year wave sausage worth useful legal winner thank yellow",
+                "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607",
+            ),
+            (
+                "ffffffffffffffffffffffffffffffffffffffffffffffff",
+                "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo when",
+                "0cd6e5d827bb62eb8fc1e262254223817fd068a74b5b449cc2f667c3f1f985a76379b43348d952e2265b4cd129090758b3e3c2c49103b5051aac2eaeb890a528",
+            ),
+            (
+                "9e885d952ad362caeb4efe34a8e91bd2",
+                "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic",
+                "274ddc525802f7c828d8ef7ddbcdc5304e87ac3535913611fbbfa986d0c9e5476c91689f9c8a54fd55bd38606aa6a8595ad213d4c9c9f9aca3fb217069a41028",
+            ),
+            (
+                "f585c11aec520db57dd353c69554b21a89b20fb0650966fa0a9d6f74fd989d8f",
+                "void come effort suffer camp survey warrior heavy shoot primary clutch crush open amazing screen patrol group space point ten exist slush involve unfold",
+                "01f5bced59dec48e362f2c45b5de68b9fd6c92c6634f44d6d40aab69056506f0e35524a518034ddc1192e1dacd32c1ed3eaa3c3b131c88ed8e7e54c49a5d0998",
+            ),
+        ];
+
+        for &(entropy, phrase, seed) in vectors.iter() {
+            let entropy = Vec::from_hex(entropy).unwrap();
+            let mnemonic = Mnemonic::from_entropy(&entropy).unwrap();
+            assert_eq!(mnemonic.phrase(), phrase);
+            assert_eq!(mnemonic.word_count(), phrase.split(' ').count());
+
+            let parsed = Mnemonic::from_phrase(phrase).unwrap();
+            assert_eq!(parsed, mnemonic);
+            assert_eq!(parsed.entropy(), &entropy[..]);
+            assert_eq!(parsed.to_seed("TREZOR").to_hex(), seed);
+        }
+    }
+
+    #[test]
+    fn english_vectors_entropy() {
+        // The remaining (entropy, mnemonic) pairs of the official BIP39 vectors
+        let vectors = [
+            (
+                "80808080808080808080808080808080",
+                "letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+            ),
+            (
+                "ffffffffffffffffffffffffffffffff",
+                "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong",
+            ),
+            (
+                "000000000000000000000000000000000000000000000000",
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon agent",
+            ),
+            (
+                "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+                "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal will",
+            ),
+            (
+                "808080808080808080808080808080808080808080808080",
+                "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter always",
+            ),
+            (
+                "0000000000000000000000000000000000000000000000000000000000000000",
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art",
+            ),
+            (
+                "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+                "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth title",
+            ),
+            (
+                "8080808080808080808080808080808080808080808080808080808080808080",
+                "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic bless",
+            ),
+            (
+                "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+            ),
+            (
+                "6610b25967cdcca9d59875f5cb50b0ea75433311869e930b",
+                "gravity machine north sort system female filter attitude volume fold club stay feature office ecology stable narrow fog",
+            ),
+            (
+                "68a79eaca2324873eacc50cb9c6eca8cc68ea5d936f98787c60c7ebc74e6ce7c",
+                "hamster diagram private dutch cause delay private meat slide toddler razor book happy fancy gospel tennis maple dilemma loan word shrug inflict delay length",
+            ),
+            (
+                "c0ba5a8e914111210f2bd131f3d5e08d",
+                "scheme spot photo card baby mountain device kick cradle pact join borrow",
+            ),
+            (
+                "6d9be1ee6ebd27a258115aad99b7317b9c8d28b6d76431c3",
+                "horn tenant knee talent sponsor spell gate clip pulse soap slush warm silver nephew swap uncle crack brave",
+            ),
+            (
+                "9f6a2878b2520799a44ef18bc7df394e7061a224d2c33cd015b157d746869863",
+                "panda eyebrow bullet gorilla call smoke muffin taste mesh discover soft ostrich alcohol speed nation flash devote level hobby quick inner drive ghost inside",
+            ),
+            (
+                "8197a4a47f0425faeaa69deebc05ca29c0a5b5cc76ceacc0",
+                "light rule cinnamon wrap drastic word pride squirrel upgrade then income fatal apart sustain crack supply proud access",
+            ),
+            (
+                "066dca1a2bb7e8a1db2832148ce9933eea0f3ac9548d793112d9a95c9407efad",
+                "all hour make first leader extend hole alien behind guard gospel lava path output census museum junior mass reopen famous sing advance salt reform",
+            ),
+            (
+                "f30f8c1da665478f49b001d94c5fc452",
+                "vessel ladder alter error federal sibling chat ability sun glass valve picture",
+            ),
+            (
+                "c10ec20dc3cd9f652c7fac2f1230f7a3c828389a14392f05",
+                "scissors invite lock maple supreme raw rapid void congress muscle digital elegant little brisk hair mango congress clump",
+            ),
+        ];
+
+        for &(entropy, phrase) in vectors.iter() {
+            let entropy = Vec::from_hex(entropy).unwrap();
+            assert_eq!(Mnemonic::from_entropy(&entropy).unwrap().phrase(), phrase);
+            assert_eq!(Mnemonic::from_phrase(phrase).unwrap().entropy(), &entropy[..]);
+        }
+    }
+
+    #[test]
+    fn seed_to_master_key() {
+        let mnemonic: Mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about".parse().unwrap();
+        let seed = mnemonic.to_seed("TREZOR");
+        let master = ExtendedPrivKey::new_master(Network::Bitcoin, &seed).unwrap();
+        assert_eq!(
+            master.to_string(),
+            "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF"
+        );
+    }
+
+    #[test]
+    fn invalid_phrases() {
+        let valid = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        // Extra whitespace is accepted and normalized.
+        let spaced = Mnemonic::from_phrase("  legal winner thank year wave sausage\tworth useful legal winner thank  yellow\n").unwrap();
+        assert_eq!(spaced.phrase(), valid);
+        assert_eq!(spaced.to_string(), valid);
+
+        assert_eq!(
+            Mnemonic::from_phrase("legal winner thank year wave sausage worth useful legal winner thank year"),
+            Err(Error::InvalidChecksum)
+        );
+        assert_eq!(
+            Mnemonic::from_phrase("legal winner thank year wave sausage worth useful legal winner thank"),
+            Err(Error::BadWordCount(11))
+        );
+        assert_eq!(Mnemonic::from_phrase(""), Err(Error::BadWordCount(0)));
+        assert_eq!(
+            Mnemonic::from_phrase("legal winner thank year wave sausage worth useful legal winner thank yelow"),
+            Err(Error::UnknownWord("yelow".to_string()))
+        );
+        assert_eq!(
+            Mnemonic::from_phrase("Legal winner thank year wave sausage worth useful legal winner thank yellow"),
+            Err(Error::UnknownWord("Legal".to_string()))
+        );
+
+        assert_eq!(Mnemonic::from_entropy(&[0; 15]), Err(Error::BadEntropyLength(15)));
+        assert_eq!(Mnemonic::from_entropy(&[0; 17]), Err(Error::BadEntropyLength(17)));
+        assert_eq!(Mnemonic::from_entropy(&[0; 36]), Err(Error::BadEntropyLength(36)));
+    }
+}
@@ -0,0 +1,255 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Output descriptors
+//!
+//! A parser for a small subset of output script descriptors, as documented
+//! at https://github.com/bitcoin/bitcoin/blob/master/doc/descriptors.md
+//! It is not a replacement for a full descriptor or miniscript implementation.
+//!
+//! The supported grammar is:
+//!
+//! ```text
+//! DESC  := pkh(KEY) | wpkh(KEY) | multi(K,KEY,...) | sh(INNER)
+//! INNER := pkh(KEY) | wpkh(KEY) | multi(K,KEY,...)
+//! KEY   := hex-encoded public key (compressed only inside wpkh)
+//! ```
+//!
+//! Key origins, extended keys, `wsh`, other script functions and descriptor
+//! checksums are not supported.
+//!
+
+use std::{error, fmt};
+use std::str::FromStr;
+
+use blockdata::constants::MAX_SCRIPT_ELEMENT_SIZE;
+use blockdata::opcodes;
+use blockdata::script::{Builder, Script};
+use util::key::{self, PublicKey};
+
+/// Maximum number of keys in a `multi` descriptor.
+const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+
+/// An error parsing a descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The descriptor is not of the form `function(arguments)`.
+    InvalidSyntax,
+    /// The script function is not supported, or not allowed in this position.
+    UnsupportedFunction(String),
+    /// A function was given the wrong number of arguments.
+    WrongArgumentCount(String),
+    /// A public key could not be parsed.
+    InvalidKey(key::Error),
+    /// An uncompressed public key was used inside `wpkh`.
+    UncompressedKey,
+    /// The `multi` threshold is not a number between 1 and the number of keys,
+    /// or there are more than 20 keys.
+    InvalidThreshold,
+    /// The script inside `sh` is larger than 520 bytes.
+    RedeemScriptTooLarge(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidSyntax => f.write_str("invalid descriptor syntax"),
+            Error::UnsupportedFunction(ref name) => write!(f, "unsupported descriptor function: {}", name),
+            Error::WrongArgumentCount(ref name) => write!(f, "wrong number of arguments to {}", name),
+            Error::InvalidKey(ref e) => write!(f, "invalid public key: {}", e),
+            Error::UncompressedKey => f.write_str("uncompressed key in wpkh"),
+            Error::InvalidThreshold => f.write_str("invalid multi threshold or key count"),
+            Error::RedeemScriptTooLarge(len) => write!(f, "redeem script of {} bytes exceeds 520 bytes", len),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::InvalidKey(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<key::Error> for Error {
+    fn from(e: key::Error) -> Error {
+        Error::InvalidKey(e)
+    }
+}
+
+/// Parses a descriptor of the supported subset into the output script it describes.
+pub fn parse_descriptor(desc: &str) -> Result<Script, Error> {
+    parse_script(desc, true)
+}
+
+fn parse_script(desc: &str, top_level: bool) -> Result<Script, Error> {
+    let (name, args) = split_function(desc)?;
+    match name {
+        "pkh" => {
+            let key = single_key(name, &args)?;
+            Ok(Script::new_p2pkh(&key.pubkey_hash()))
+        }
+        "wpkh" => {
+            let key = single_key(name, &args)?;
+            let hash = key.wpubkey_hash().ok_or(Error::UncompressedKey)?;
+            Ok(Script::new_v0_wpkh(&hash))
+        }
+        "multi" => multi(&args),
+        "sh" if top_level => {
+            if args.len() != 1 {
+                return Err(Error::WrongArgumentCount(name.to_owned()));
+            }
+            let redeem_script = parse_script(args[0], false)?;
+            if redeem_script.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                return Err(Error::RedeemScriptTooLarge(redeem_script.len()));
+            }
+            Ok(Script::new_p2sh(&redeem_script.script_hash()))
+        }
+        _ => Err(Error::UnsupportedFunction(name.to_owned())),
+    }
+}
+
+/// Splits `name(a,b,...)` into its name and top-level arguments.
+fn split_function(desc: &str) -> Result<(&str, Vec<&str>), Error> {
+    let open = desc.find('(').ok_or(Error::InvalidSyntax)?;
+    if !desc.ends_with(')') {
+        return Err(Error::InvalidSyntax);
+    }
+    let name = &desc[..open];
+    let inner = &desc[open + 1..desc.len() - 1];
+
+    let mut args = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Err(Error::InvalidSyntax),
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(Error::InvalidSyntax);
+    }
+    args.push(&inner[start..]);
+    Ok((name, args))
+}
+
+fn single_key(name: &str, args: &[&str]) -> Result<PublicKey, Error> {
+    if args.len() != 1 {
+        return Err(Error::WrongArgumentCount(name.to_owned()));
+    }
+    Ok(PublicKey::from_str(args[0])?)
+}
+
+fn multi(args: &[&str]) -> Result<Script, Error> {
+    if args.len() < 2 {
+        return Err(Error::WrongArgumentCount("multi".to_owned()));
+    }
+    let threshold = usize::from_str(args[0]).map_err(|_| Error::InvalidThreshold)?;
+    let keys = &args[1..];
+    if threshold == 0 || threshold > keys.len() || keys.len() > MAX_PUBKEYS_PER_MULTISIG {
+        return Err(Error::InvalidThreshold);
+    }
+
+    let mut builder = Builder::new().push_int(threshold as i64);
+    for key in keys {
+        builder = builder.push_key(&PublicKey::from_str(key)?);
+    }
+    Ok(builder
+        .push_int(keys.len() as i64)
+        .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+        .into_script())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_descriptor, Error};
+
+    use hashes::hex::ToHex;
+
+    const KEY1: &str = "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd";
+    const KEY2: &str = "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+    const UNCOMPRESSED: &str = "04a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd5b8dec5235a0fa8722476c7709c02559e3aa73aa03918ba2d492eea75abea235";
+
+    #[test]
+    fn parse_pkh_wpkh() {
+        let pkh = parse_descriptor(&format!("pkh({})", KEY2)).unwrap();
+        assert!(pkh.is_p2pkh());
+        assert_eq!(pkh.to_hex(), "76a9147dd65592d0ab2fe0d0257d571abf032cd9db93dc88ac");
+
+        let wpkh = parse_descriptor(&format!("wpkh({})", KEY2)).unwrap();
+        assert!(wpkh.is_v0_p2wpkh());
+        assert_eq!(wpkh.to_hex(), "00147dd65592d0ab2fe0d0257d571abf032cd9db93dc");
+
+        // Uncompressed keys are fine for pkh but not for wpkh.
+        assert!(parse_descriptor(&format!("pkh({})", UNCOMPRESSED)).unwrap().is_p2pkh());
+        assert_eq!(parse_descriptor(&format!("wpkh({})", UNCOMPRESSED)), Err(Error::UncompressedKey));
+    }
+
+    #[test]
+    fn parse_sh_multi() {
+        let multi = parse_descriptor(&format!("multi(1,{},{})", KEY1, KEY2)).unwrap();
+        assert_eq!(multi.to_hex(), format!("5121{}21{}52ae", KEY1, KEY2));
+
+        let sh_multi = parse_descriptor(&format!("sh(multi(1,{},{}))", KEY1, KEY2)).unwrap();
+        assert!(sh_multi.is_p2sh());
+        assert_eq!(sh_multi.to_hex(), format!("a914{}87", multi.script_hash().to_hex()));
+
+        let sh_wpkh = parse_descriptor(&format!("sh(wpkh({}))", KEY2)).unwrap();
+        assert!(sh_wpkh.is_p2sh());
+        assert_eq!(sh_wpkh.to_hex(), "a91469ea5ff598a286f418ae77503ce85d83da4ae88e87");
+
+        let sh_pkh = parse_descriptor(&format!("sh(pkh({}))", KEY2)).unwrap();
+        assert!(sh_pkh.is_p2sh());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse_descriptor(&format!("wsh(multi(1,{}))", KEY1)),
+                   Err(Error::UnsupportedFunction("wsh".to_owned())));
+        assert_eq!(parse_descriptor(&format!("combo({})", KEY1)),
+                   Err(Error::UnsupportedFunction("combo".to_owned())));
+        assert_eq!(parse_descriptor(&format!("sh(sh(pkh({})))", KEY1)),
+                   Err(Error::UnsupportedFunction("sh".to_owned())));
+
+        assert_eq!(parse_descriptor(KEY1), Err(Error::InvalidSyntax));
+        assert_eq!(parse_descriptor(&format!("pkh({}", KEY1)), Err(Error::InvalidSyntax));
+        assert_eq!(parse_descriptor(&format!("sh(pkh({})", KEY1)), Err(Error::InvalidSyntax));
+        assert_eq!(parse_descriptor(&format!("pkh({}))(", KEY1)), Err(Error::InvalidSyntax));
+        assert_eq!(parse_descriptor(&format!("pkh({},{})", KEY1, KEY2)),
+                   Err(Error::WrongArgumentCount("pkh".to_owned())));
+        match parse_descriptor("pkh(02aa)") {
+            Err(Error::InvalidKey(_)) => {},
+            r => panic!("expected invalid key, got {:?}", r),
+        }
+
+        assert_eq!(parse_descriptor(&format!("multi(0,{})", KEY1)), Err(Error::InvalidThreshold));
+        assert_eq!(parse_descriptor(&format!("multi(2,{})", KEY1)), Err(Error::InvalidThreshold));
+        assert_eq!(parse_descriptor(&format!("multi(x,{})", KEY1)), Err(Error::InvalidThreshold));
+        assert_eq!(parse_descriptor("multi(1)"), Err(Error::WrongArgumentCount("multi".to_owned())));
+
+        // 16 compressed keys push the redeem script past 520 bytes.
+        let keys = vec![KEY1; 16].join(",");
+        assert!(parse_descriptor(&format!("multi(1,{})", keys)).is_ok());
+        assert_eq!(parse_descriptor(&format!("sh(multi(1,{}))", keys)), Err(Error::RedeemScriptTooLarge(547)));
+    }
+}
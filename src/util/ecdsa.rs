@@ -0,0 +1,315 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! ECDSA signatures
+//!
+//! ECDSA signatures as they appear in scriptSigs and witnesses: a DER-encoded
+//! signature followed by a sighash type byte.
+//!
+
+use std::{error, fmt};
+
+use secp256k1::{self, Message, PublicKey, Secp256k1, Signature, Verification};
+#[cfg(feature = "global-context")]
+use secp256k1::{All, SecretKey};
+
+use blockdata::transaction::SigHashType;
+
+/// An error decoding an [`EcdsaSig`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The signature is empty, so it has no sighash type byte.
+    EmptySignature,
+    /// The signature is not strictly DER-encoded according to BIP66.
+    NonStrictDer,
+    /// The sighash type byte is not one of the standard types.
+    NonStandardSigHashType(u8),
+    /// secp256k1 rejected the signature.
+    Secp256k1(secp256k1::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::EmptySignature => f.write_str("empty signature"),
+            Error::NonStrictDer => f.write_str("signature is not strict DER"),
+            Error::NonStandardSigHashType(ty) => write!(f, "non-standard sighash type {:#04x}", ty),
+            Error::Secp256k1(ref e) => write!(f, "invalid signature: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::Secp256k1(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<secp256k1::Error> for Error {
+    fn from(e: secp256k1::Error) -> Error {
+        Error::Secp256k1(e)
+    }
+}
+
+/// Returns a process-wide context for signing and verification, which is
+/// created and randomized on first use.
+///
+/// The signing and verification methods of [`EcdsaSig`] use this context.
+///
+/// [`EcdsaSig`]: struct.EcdsaSig.html
+#[cfg(feature = "global-context")]
+pub fn secp256k1_context() -> &'static Secp256k1<All> {
+    secp256k1::SECP256K1
+}
+
+/// An ECDSA signature together with its sighash type.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EcdsaSig {
+    /// The signature.
+    pub sig: Signature,
+    /// The sighash type the signature commits to.
+    pub hash_ty: SigHashType,
+}
+
+impl EcdsaSig {
+    /// Decodes a DER-encoded signature followed by a sighash type byte.
+    ///
+    /// Like Bitcoin Core with `SCRIPT_VERIFY_DERSIG` and `SCRIPT_VERIFY_STRICTENC`,
+    /// this only accepts strict DER (BIP66) and standard sighash types. High-S
+    /// signatures are accepted, see [`EcdsaSig::is_low_s`].
+    ///
+    /// [`EcdsaSig::is_low_s`]: #method.is_low_s
+    pub fn from_slice(sl: &[u8]) -> Result<EcdsaSig, Error> {
+        let (hash_ty, der) = sl.split_last().ok_or(Error::EmptySignature)?;
+        if !is_strict_der(sl) {
+            return Err(Error::NonStrictDer);
+        }
+        let hash_ty = SigHashType::from_u32_standard(u32::from(*hash_ty))
+            .map_err(|_| Error::NonStandardSigHashType(*hash_ty))?;
+        Ok(EcdsaSig {
+            sig: Signature::from_der(der)?,
+            hash_ty,
+        })
+    }
+
+    /// Signs `msg`, usually a sighash, with `sk` using the shared
+    /// [`secp256k1_context`].
+    ///
+    /// [`secp256k1_context`]: fn.secp256k1_context.html
+    #[cfg(feature = "global-context")]
+    pub fn sign(msg: &Message, sk: &SecretKey, hash_ty: SigHashType) -> EcdsaSig {
+        EcdsaSig {
+            sig: secp256k1_context().sign(msg, sk),
+            hash_ty,
+        }
+    }
+
+    /// Verifies the signature of `msg` by `pk` using the shared
+    /// [`secp256k1_context`].
+    ///
+    /// [`secp256k1_context`]: fn.secp256k1_context.html
+    #[cfg(feature = "global-context")]
+    pub fn verify(&self, msg: &Message, pk: &PublicKey) -> Result<(), Error> {
+        secp256k1_context().verify(msg, &self.sig, pk)?;
+        Ok(())
+    }
+
+    /// Serializes the signature in DER followed by the sighash type byte.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut ser = self.sig.serialize_der().to_vec();
+        ser.push(self.hash_ty.as_u32() as u8);
+        ser
+    }
+
+    /// Checks whether the S value is in the lower half of the curve order, as
+    /// required for standard transactions (BIP146 `LOW_S`).
+    pub fn is_low_s(&self) -> bool {
+        let mut normalized = self.sig;
+        normalized.normalize_s();
+        normalized == self.sig
+    }
+
+    /// Replaces a high S value with its negation, which yields an equally valid
+    /// signature that is low-S.
+    pub fn normalize_s(&mut self) {
+        self.sig.normalize_s();
+    }
+}
+
+/// Verifies a batch of signatures, returning whether all of them are valid.
+///
+/// libsecp256k1 has no batch verification for ECDSA, so the signatures are
+/// checked one after another, stopping at the first invalid one.
+pub fn verify_batch<C: Verification>(secp: &Secp256k1<C>, items: &[(Message, Signature, PublicKey)]) -> bool {
+    items.iter().all(|(msg, sig, pk)| secp.verify(msg, sig, pk).is_ok())
+}
+
+/// Checks the strict DER encoding of a signature including its trailing sighash
+/// byte, following `IsValidSignatureEncoding` in Bitcoin Core.
+pub(crate) fn is_strict_der(sig: &[u8]) -> bool {
+    // Format: 0x30 [total-length] 0x02 [R-length] [R] 0x02 [S-length] [S] [sighash]
+    if sig.len() < 9 || sig.len() > 73 {
+        return false;
+    }
+    if sig[0] != 0x30 || sig[1] as usize != sig.len() - 3 {
+        return false;
+    }
+    let len_r = sig[3] as usize;
+    if 5 + len_r >= sig.len() {
+        return false;
+    }
+    let len_s = sig[5 + len_r] as usize;
+    if len_r + len_s + 7 != sig.len() {
+        return false;
+    }
+
+    // R must be a positive integer without excess padding.
+    if sig[2] != 0x02 || len_r == 0 || sig[4] & 0x80 != 0 {
+        return false;
+    }
+    if len_r > 1 && sig[4] == 0x00 && sig[5] & 0x80 == 0 {
+        return false;
+    }
+
+    // Same for S.
+    if sig[len_r + 4] != 0x02 || len_s == 0 || sig[len_r + 6] & 0x80 != 0 {
+        return false;
+    }
+    if len_s > 1 && sig[len_r + 6] == 0x00 && sig[len_r + 7] & 0x80 == 0 {
+        return false;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_batch, EcdsaSig, Error};
+    use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+
+    use blockdata::transaction::SigHashType;
+    use hashes::hex::{FromHex, ToHex};
+
+    // A SIGHASH_ALL signature with a high S value
+    const HIGH_S: &str = "3046022100a8ecd1e97e26586257e29c66fcd2cf7bb50e668c6df01e212a1a99a5e00000ce022100ac0ec3e3c7db1a60ada2fad7cf16daba74672b843a851f264b553830fca4778601";
+
+    #[test]
+    fn ecdsa_sig_roundtrip() {
+        let bytes = Vec::from_hex(HIGH_S).unwrap();
+        let sig = EcdsaSig::from_slice(&bytes).unwrap();
+        assert_eq!(sig.hash_ty, SigHashType::All);
+        assert_eq!(sig.to_vec(), bytes);
+    }
+
+    #[test]
+    fn ecdsa_sig_low_s() {
+        let mut sig = EcdsaSig::from_slice(&Vec::from_hex(HIGH_S).unwrap()).unwrap();
+        // The S value above is in the upper half of the curve order...
+        assert!(!sig.is_low_s());
+        let high_s = sig;
+        sig.normalize_s();
+        // ... and normalizes to n - S, which needs no padding byte.
+        assert!(sig.is_low_s());
+        assert_ne!(sig, high_s);
+        assert_eq!(sig.hash_ty, SigHashType::All);
+        let normalized = sig.to_vec();
+        assert_eq!(normalized.len(), 72);
+        assert_eq!(
+            normalized[39..71].to_hex(),
+            "53f13c1c3824e59f525d052830e925444647b16274c38115747d265bd391c9bb"
+        );
+        assert_eq!(EcdsaSig::from_slice(&normalized).unwrap(), sig);
+
+        sig.normalize_s();
+        assert_eq!(sig.to_vec(), normalized);
+    }
+
+    #[test]
+    fn ecdsa_sig_strict_der() {
+        let valid = Vec::from_hex(HIGH_S).unwrap();
+        assert_eq!(EcdsaSig::from_slice(&[]), Err(Error::EmptySignature));
+
+        let mut bad = valid.clone();
+        bad[0] = 0x31;
+        assert_eq!(EcdsaSig::from_slice(&bad), Err(Error::NonStrictDer));
+        // Wrong total length
+        let mut bad = valid.clone();
+        bad[1] += 1;
+        assert_eq!(EcdsaSig::from_slice(&bad), Err(Error::NonStrictDer));
+        // Trailing garbage before the sighash byte
+        let mut bad = valid.clone();
+        bad.insert(valid.len() - 1, 0);
+        assert_eq!(EcdsaSig::from_slice(&bad), Err(Error::NonStrictDer));
+        // Negative R, by dropping its padding byte
+        let mut bad = valid.clone();
+        bad.remove(4);
+        bad[1] -= 1;
+        bad[3] -= 1;
+        assert_eq!(EcdsaSig::from_slice(&bad), Err(Error::NonStrictDer));
+        // Excess padding of R
+        let mut bad = valid.clone();
+        bad.insert(4, 0);
+        bad[1] += 1;
+        bad[3] += 1;
+        assert_eq!(EcdsaSig::from_slice(&bad), Err(Error::NonStrictDer));
+        // Truncated
+        assert_eq!(EcdsaSig::from_slice(&valid[..8]), Err(Error::NonStrictDer));
+
+        let mut bad = valid.clone();
+        *bad.last_mut().unwrap() = 0x04;
+        assert_eq!(EcdsaSig::from_slice(&bad), Err(Error::NonStandardSigHashType(0x04)));
+        *bad.last_mut().unwrap() = 0x81;
+        assert_eq!(EcdsaSig::from_slice(&bad).unwrap().hash_ty, SigHashType::AllPlusAnyoneCanPay);
+    }
+
+    #[test]
+    fn batch_verification() {
+        let secp = Secp256k1::new();
+        let items: Vec<_> = (1..5u8).map(|i| {
+            let sk = SecretKey::from_slice(&[i; 32]).unwrap();
+            let msg = Message::from_slice(&[i + 100; 32]).unwrap();
+            (msg, secp.sign(&msg, &sk), PublicKey::from_secret_key(&secp, &sk))
+        }).collect();
+        assert!(verify_batch(&secp, &[]));
+        assert!(verify_batch(&secp, &items));
+
+        // The signature of the second message by the third key.
+        let mut bad = items.clone();
+        bad[2].0 = items[1].0;
+        assert!(!verify_batch(&secp, &bad));
+        assert!(verify_batch(&secp, &bad[..2]));
+    }
+
+    #[test]
+    #[cfg(feature = "global-context")]
+    fn ecdsa_sig_global_context() {
+        use super::secp256k1_context;
+
+        let sk = SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = PublicKey::from_secret_key(secp256k1_context(), &sk);
+        let msg = Message::from_slice(&[2; 32]).unwrap();
+
+        // Every call hands out the same context.
+        assert!(::std::ptr::eq(secp256k1_context(), secp256k1_context()));
+        let first = EcdsaSig::sign(&msg, &sk, SigHashType::All);
+        let second = EcdsaSig::sign(&msg, &sk, SigHashType::All);
+        assert_eq!(first, second);
+        assert!(first.verify(&msg, &pk).is_ok());
+
+        let other = Message::from_slice(&[3; 32]).unwrap();
+        assert!(first.verify(&other, &pk).is_err());
+    }
+}
@@ -51,8 +51,10 @@ macro_rules! define_le_to_array {
     }
 }
 
+define_slice_to_be!(slice_to_u16_be, u16);
 define_slice_to_be!(slice_to_u32_be, u32);
 define_slice_to_be!(slice_to_u64_be, u64);
+define_be_to_array!(u16_to_array_be, u16, 2);
 define_be_to_array!(u32_to_array_be, u32, 4);
 define_be_to_array!(u64_to_array_be, u64, 8);
 define_slice_to_le!(slice_to_u16_le, u16);
@@ -106,9 +108,12 @@ mod tests {
 
     #[test]
     fn endianness_test() {
+        assert_eq!(slice_to_u16_be(&[0xde, 0xad]), 0xdead);
         assert_eq!(slice_to_u32_be(&[0xde, 0xad, 0xbe, 0xef]), 0xdeadbeef);
         assert_eq!(slice_to_u64_be(&[0xde, 0xad, 0xbe, 0xef, 0x1b, 0xad, 0xca, 0xfe]), 0xdeadbeef1badcafe);
+        assert_eq!(u16_to_array_be(0xdead), [0xde, 0xad]);
         assert_eq!(u32_to_array_be(0xdeadbeef), [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(u64_to_array_be(0xdeadbeef1badcafe), [0xde, 0xad, 0xbe, 0xef, 0x1b, 0xad, 0xca, 0xfe]);
 
         assert_eq!(slice_to_u16_le(&[0xad, 0xde]), 0xdead);
         assert_eq!(slice_to_u32_le(&[0xef, 0xbe, 0xad, 0xde]), 0xdeadbeef);
@@ -18,9 +18,15 @@
 use std::cmp::min;
 use std::io;
 
-use hashes::Hash;
+use hashes::{hash160, Hash};
 use consensus::encode::Encodable;
 
+/// Computes the HASH160 (RIPEMD160 of SHA256) of `data`, as used for public key and script
+/// hashes.
+pub fn hash160(data: &[u8]) -> hash160::Hash {
+    hash160::Hash::hash(data)
+}
+
 /// Calculates the merkle root of a list of hashes inline
 /// into the allocated slice.
 ///
@@ -75,3 +81,17 @@ pub fn bitcoin_merkle_root<T, I>(mut iter: I) -> T
     }
     bitcoin_merkle_root_inline(&mut alloc)
 }
+
+#[cfg(test)]
+mod tests {
+    use hashes::hex::{FromHex, ToHex};
+    use super::hash160;
+
+    #[test]
+    fn test_hash160() {
+        assert_eq!(hash160(&[]).to_hex(), "b472a266d0bd89c13706a4132ccfb16f7c3b9fcb");
+        // Compressed secp256k1 generator point
+        let pk = Vec::from_hex("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap();
+        assert_eq!(hash160(&pk).to_hex(), "751e76e8199196d454941c45d1b3a323f1433bd6");
+    }
+}
@@ -0,0 +1,636 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Header chain
+//!
+//! A minimal in-memory store of block headers which follows the chain with
+//! the most cumulative work, as needed by SPV clients. Headers are checked
+//! to meet their own target and the network's proof-of-work limit and to
+//! have a sane timestamp, but difficulty adjustments are not validated.
+//! Headers which arrive before their parent are kept aside until the parent
+//! is added.
+//!
+
+use std::collections::{HashMap, VecDeque};
+use std::{error, fmt};
+
+use blockdata::block::BlockHeader;
+use consensus::params::{self, Params};
+use hash_types::BlockHash;
+use util;
+use util::uint::Uint256;
+
+/// Maximum number of headers waiting for their parent in a [`HeaderChain`],
+/// which is the number of headers in a full `headers` message.
+pub const MAX_ORPHAN_HEADERS: usize = 2000;
+
+/// An error that might occur when adding a header to a [`HeaderChain`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The header's target is easier than the network's proof-of-work limit.
+    TargetAbovePowLimit(BlockHash),
+    /// The header's hash does not meet its target.
+    BadProofOfWork(BlockHash),
+    /// The header's timestamp is not after the median time past of its parent.
+    TimeTooOld(BlockHash),
+    /// The header's timestamp is too far in the future.
+    TimeTooNew(BlockHash),
+    /// The best chain has a block other than a checkpoint at its height.
+    CheckpointMismatch {
+        /// Height of the checkpoint.
+        height: u32,
+        /// Hash of the checkpoint.
+        expected: BlockHash,
+        /// Hash of the best chain block at that height.
+        found: BlockHash,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::TargetAbovePowLimit(ref hash) => write!(f, "target of block {} is above the proof-of-work limit", hash),
+            Error::BadProofOfWork(ref hash) => write!(f, "block {} does not meet its target", hash),
+            Error::TimeTooOld(ref hash) => write!(f, "timestamp of block {} is not after median time past", hash),
+            Error::TimeTooNew(ref hash) => write!(f, "timestamp of block {} is too far in the future", hash),
+            Error::CheckpointMismatch { height, ref expected, ref found } => {
+                write!(f, "block {} at height {} contradicts checkpoint {}", found, height, expected)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// The changes to the best chain caused by adding a header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainUpdate {
+    /// Blocks which became part of the best chain, from the fork point towards
+    /// the new tip.
+    pub connected: Vec<BlockHash>,
+    /// Blocks which left the best chain, from the old tip towards the fork
+    /// point.
+    pub disconnected: Vec<BlockHash>,
+    /// The tip of the best chain after the update.
+    pub new_tip: BlockHash,
+}
+
+impl ChainUpdate {
+    /// Whether the best chain changed.
+    pub fn is_empty(&self) -> bool {
+        self.connected.is_empty() && self.disconnected.is_empty()
+    }
+}
+
+/// The outcome of adding a header to a [`HeaderChain`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderStatus {
+    /// The header, and any waiting descendants, were added to the chain.
+    Accepted(ChainUpdate),
+    /// The header's parent is unknown; the header is kept until the parent is
+    /// added.
+    Parked,
+}
+
+#[derive(Debug, Clone)]
+struct HeaderEntry {
+    header: BlockHeader,
+    height: u32,
+    chain_work: Uint256,
+}
+
+/// In-memory chain of block headers, including stale branches.
+#[derive(Debug, Clone)]
+pub struct HeaderChain {
+    params: Params,
+    headers: HashMap<BlockHash, HeaderEntry>,
+    /// Hashes of the best chain, indexed by height.
+    best: Vec<BlockHash>,
+    /// Headers with an unknown parent, by parent hash.
+    orphans: HashMap<BlockHash, Vec<BlockHeader>>,
+    /// Parent and own hash of every parked header, oldest first.
+    orphan_order: VecDeque<(BlockHash, BlockHash)>,
+}
+
+impl HeaderChain {
+    /// Creates a chain containing only the genesis header of the chain
+    /// described by `params`.
+    pub fn new(params: Params) -> HeaderChain {
+        let genesis = params.genesis_header;
+        let hash = genesis.block_hash();
+        let mut headers = HashMap::new();
+        headers.insert(hash, HeaderEntry {
+            header: genesis,
+            height: 0,
+            chain_work: genesis.work(),
+        });
+        HeaderChain {
+            params,
+            headers,
+            best: vec![hash],
+            orphans: HashMap::new(),
+            orphan_order: VecDeque::new(),
+        }
+    }
+
+    /// Returns the hash of the tip of the best chain.
+    pub fn tip(&self) -> BlockHash {
+        *self.best.last().expect("best chain contains genesis")
+    }
+
+    /// Returns the height of the tip of the best chain.
+    pub fn height(&self) -> u32 {
+        self.best.len() as u32 - 1
+    }
+
+    /// Returns the total work of the best chain.
+    pub fn chain_work(&self) -> Uint256 {
+        self.headers[&self.tip()].chain_work
+    }
+
+    /// Returns the header with the given hash, which may be on a stale branch.
+    pub fn get_header(&self, hash: &BlockHash) -> Option<&BlockHeader> {
+        self.headers.get(hash).map(|entry| &entry.header)
+    }
+
+    /// Returns the hash of the best chain block at `height`.
+    pub fn block_hash_at(&self, height: u32) -> Option<BlockHash> {
+        self.best.get(height as usize).cloned()
+    }
+
+    /// Whether the block with the given hash is part of the best chain.
+    pub fn is_in_best_chain(&self, hash: &BlockHash) -> bool {
+        match self.headers.get(hash) {
+            Some(entry) => self.best.get(entry.height as usize) == Some(hash),
+            None => false,
+        }
+    }
+
+    /// Returns the median timestamp of the block with the given hash and its
+    /// ten predecessors.
+    pub fn median_time_past(&self, hash: &BlockHash) -> Option<u32> {
+        let mut headers = Vec::with_capacity(11);
+        let mut cursor = self.headers.get(hash);
+        while let Some(entry) = cursor {
+            headers.push(entry.header);
+            if headers.len() == 11 || entry.height == 0 {
+                break;
+            }
+            cursor = self.headers.get(&entry.header.prev_blockhash);
+        }
+        if headers.is_empty() {
+            None
+        } else {
+            Some(params::median_time_past(&headers))
+        }
+    }
+
+    /// Checks that the best chain agrees with all `checkpoints` up to its
+    /// height, such as [`Params::checkpoints`]. Checkpoints above the tip are
+    /// ignored.
+    ///
+    /// [`Params::checkpoints`]: ../../consensus/params/struct.Params.html#structfield.checkpoints
+    pub fn validate_against_checkpoints(&self, checkpoints: &[(u32, BlockHash)]) -> Result<(), Error> {
+        for &(height, expected) in checkpoints {
+            if let Some(found) = self.block_hash_at(height) {
+                if found != expected {
+                    return Err(Error::CheckpointMismatch { height, expected, found });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks whether adding `new_tip` would disconnect more than
+    /// `finality_depth` blocks from the best chain.
+    ///
+    /// A header whose parent is unknown, or which does not give its branch
+    /// more work than the best chain, disconnects nothing. The header itself
+    /// is not validated.
+    pub fn would_violate_finality(&self, new_tip: &BlockHeader, finality_depth: u32) -> bool {
+        let parent = match self.headers.get(&new_tip.prev_blockhash) {
+            Some(parent) => parent,
+            None => return false,
+        };
+        if parent.chain_work + new_tip.work() <= self.chain_work() {
+            return false;
+        }
+        let mut cursor = new_tip.prev_blockhash;
+        while !self.is_in_best_chain(&cursor) {
+            cursor = self.headers[&cursor].header.prev_blockhash;
+        }
+        self.height() - self.headers[&cursor].height > finality_depth
+    }
+
+    /// Returns the number of headers waiting for their parent.
+    pub fn orphan_count(&self) -> usize {
+        self.orphan_order.len()
+    }
+
+    /// Adds a header to the chain.
+    ///
+    /// If the header's parent is unknown, the header is parked until the
+    /// parent is added, at which point both are connected. At most
+    /// [`MAX_ORPHAN_HEADERS`] headers are parked at a time, parking another
+    /// one evicts the oldest. Parked headers which turn out to be invalid are
+    /// dropped together with their descendants.
+    ///
+    /// `now` is the local time, see [`BlockHeader::check_timestamp`].
+    ///
+    /// If the header gives a branch more work than the current best chain,
+    /// that branch becomes the best chain, even if the header extends a branch
+    /// which was stale until now. The returned [`ChainUpdate`] lists the
+    /// blocks to undo and to apply, in order. A header which is already known
+    /// or which ends up on a stale branch results in an empty update.
+    ///
+    /// [`MAX_ORPHAN_HEADERS`]: constant.MAX_ORPHAN_HEADERS.html
+    /// [`BlockHeader::check_timestamp`]: ../../blockdata/block/struct.BlockHeader.html#method.check_timestamp
+    pub fn add_header(&mut self, header: BlockHeader, now: u32) -> Result<HeaderStatus, Error> {
+        let hash = header.block_hash();
+        if self.headers.contains_key(&hash) {
+            return Ok(HeaderStatus::Accepted(self.no_update()));
+        }
+        let target = header.target();
+        if target.to_uint256() > self.params.pow_limit {
+            return Err(Error::TargetAbovePowLimit(hash));
+        }
+        if !target.is_met_by(hash) {
+            return Err(Error::BadProofOfWork(hash));
+        }
+
+        if !self.headers.contains_key(&header.prev_blockhash) {
+            return Ok(self.park(header, hash));
+        }
+
+        let old_tip = self.tip();
+        self.connect(header, hash, now)?;
+        let mut pending = self.take_orphans(&hash);
+        while let Some(orphan) = pending.pop() {
+            let orphan_hash = orphan.block_hash();
+            let children = self.take_orphans(&orphan_hash);
+            if self.connect(orphan, orphan_hash, now).is_ok() {
+                pending.extend(children);
+            } else {
+                let mut dropped = children;
+                while let Some(descendant) = dropped.pop() {
+                    dropped.extend(self.take_orphans(&descendant.block_hash()));
+                }
+            }
+        }
+        Ok(HeaderStatus::Accepted(self.update_since(old_tip)))
+    }
+
+    fn take_orphans(&mut self, parent: &BlockHash) -> Vec<BlockHeader> {
+        let children = self.orphans.remove(parent).unwrap_or_default();
+        if !children.is_empty() {
+            self.orphan_order.retain(|&(p, _)| p != *parent);
+        }
+        children
+    }
+
+    /// Checks the timestamp of a header with a known parent and inserts it.
+    fn connect(&mut self, header: BlockHeader, hash: BlockHash, now: u32) -> Result<(), Error> {
+        let mtp = self.median_time_past(&header.prev_blockhash).expect("parent is known");
+        header.check_timestamp(mtp, now, &self.params).map_err(|e| match e {
+            util::Error::BlockTimeTooOld => Error::TimeTooOld(hash),
+            _ => Error::TimeTooNew(hash),
+        })?;
+        self.insert(header, hash);
+        Ok(())
+    }
+
+    fn park(&mut self, header: BlockHeader, hash: BlockHash) -> HeaderStatus {
+        let parent = header.prev_blockhash;
+        let known = self.orphans.get(&parent).map_or(false, |siblings| {
+            siblings.iter().any(|o| o.block_hash() == hash)
+        });
+        if known {
+            return HeaderStatus::Parked;
+        }
+        if self.orphan_order.len() >= MAX_ORPHAN_HEADERS {
+            let (oldest_parent, oldest) = self.orphan_order.pop_front().expect("pool is full");
+            let now_empty = {
+                let siblings = self.orphans.get_mut(&oldest_parent).expect("parked headers are indexed");
+                siblings.retain(|o| o.block_hash() != oldest);
+                siblings.is_empty()
+            };
+            if now_empty {
+                self.orphans.remove(&oldest_parent);
+            }
+        }
+        self.orphans.entry(parent).or_default().push(header);
+        self.orphan_order.push_back((parent, hash));
+        HeaderStatus::Parked
+    }
+
+    /// Inserts a header with a known parent, switching the best chain to it if
+    /// it has the most work.
+    fn insert(&mut self, header: BlockHeader, hash: BlockHash) {
+        let (height, chain_work) = {
+            let parent = &self.headers[&header.prev_blockhash];
+            (parent.height + 1, parent.chain_work + header.work())
+        };
+        self.headers.insert(hash, HeaderEntry { header, height, chain_work });
+        if chain_work <= self.chain_work() {
+            return;
+        }
+
+        // Walk back from the new tip until we reach the best chain.
+        let mut branch = vec![hash];
+        let mut cursor = header.prev_blockhash;
+        while !self.is_in_best_chain(&cursor) {
+            branch.push(cursor);
+            cursor = self.headers[&cursor].header.prev_blockhash;
+        }
+        let fork_height = self.headers[&cursor].height as usize;
+        self.best.truncate(fork_height + 1);
+        self.best.extend(branch.into_iter().rev());
+    }
+
+    /// Describes how the best chain changed since `old_tip` was its tip.
+    fn update_since(&self, old_tip: BlockHash) -> ChainUpdate {
+        let mut disconnected = vec![];
+        let mut cursor = old_tip;
+        while !self.is_in_best_chain(&cursor) {
+            disconnected.push(cursor);
+            cursor = self.headers[&cursor].header.prev_blockhash;
+        }
+        let fork_height = self.headers[&cursor].height as usize;
+        ChainUpdate {
+            connected: self.best[fork_height + 1..].to_vec(),
+            disconnected,
+            new_tip: self.tip(),
+        }
+    }
+
+    fn no_update(&self) -> ChainUpdate {
+        ChainUpdate {
+            connected: vec![],
+            disconnected: vec![],
+            new_tip: self.tip(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChainUpdate, Error, HeaderChain, HeaderStatus, MAX_ORPHAN_HEADERS};
+
+    use blockdata::block::BlockHeader;
+    use consensus::params::Params;
+    use hash_types::TxMerkleNode;
+    use hashes::Hash;
+    use network::constants::Network;
+
+    /// Local time used for all headers, long after the regtest genesis block.
+    const NOW: u32 = 1_600_000_000;
+
+    /// Mines a regtest header on top of `prev`, ten minutes after it, using
+    /// `salt` to tell apart headers of competing branches.
+    fn mine(prev: &BlockHeader, salt: u8) -> BlockHeader {
+        mine_at(prev, prev.time + 600, salt)
+    }
+
+    fn mine_at(prev: &BlockHeader, time: u32, salt: u8) -> BlockHeader {
+        let mut header = BlockHeader {
+            version: 0x20000000,
+            prev_blockhash: prev.block_hash(),
+            merkle_root: TxMerkleNode::hash(&[salt]),
+            time,
+            bits: Params::new(Network::Regtest).pow_limit_bits(),
+            nonce: 0,
+        };
+        while !header.target().is_met_by(header.block_hash()) {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    /// A header which is never added to the chain, to make orphans.
+    fn unknown(chain: &HeaderChain, n: u32) -> BlockHeader {
+        let mut header = *chain.get_header(&chain.tip()).unwrap();
+        header.nonce = n.wrapping_add(1 << 31);
+        header
+    }
+
+    fn accepted(status: Result<HeaderStatus, Error>) -> ChainUpdate {
+        match status {
+            Ok(HeaderStatus::Accepted(update)) => update,
+            s => panic!("header not accepted: {:?}", s),
+        }
+    }
+
+    #[test]
+    fn extend_and_reject() {
+        let mut chain = HeaderChain::new(Params::new(Network::Regtest));
+        let genesis = *chain.get_header(&chain.tip()).unwrap();
+        assert_eq!(chain.height(), 0);
+
+        let a1 = mine(&genesis, 0);
+        let update = accepted(chain.add_header(a1, NOW));
+        assert_eq!(update, ChainUpdate {
+            connected: vec![a1.block_hash()],
+            disconnected: vec![],
+            new_tip: a1.block_hash(),
+        });
+        assert_eq!(chain.height(), 1);
+        assert_eq!(chain.block_hash_at(1), Some(a1.block_hash()));
+        // Adding a header twice is a no-op.
+        assert!(accepted(chain.add_header(a1, NOW)).is_empty());
+
+        let mut bad = mine(&a1, 0);
+        while bad.target().is_met_by(bad.block_hash()) {
+            bad.nonce += 1;
+        }
+        assert_eq!(chain.add_header(bad, NOW), Err(Error::BadProofOfWork(bad.block_hash())));
+        let mut easy = mine(&a1, 0);
+        easy.bits = ::blockdata::block::CompactTarget::from_consensus(0x2100ffff);
+        assert_eq!(chain.add_header(easy, NOW), Err(Error::TargetAbovePowLimit(easy.block_hash())));
+        assert_eq!(chain.tip(), a1.block_hash());
+    }
+
+    #[test]
+    fn timestamps() {
+        let mut chain = HeaderChain::new(Params::new(Network::Regtest));
+        let mut tip = *chain.get_header(&chain.tip()).unwrap();
+        for _ in 0..11 {
+            tip = mine(&tip, 0);
+            accepted(chain.add_header(tip, NOW));
+        }
+        // The median of the last 11 timestamps is the sixth newest.
+        let mtp = tip.time - 5 * 600;
+        assert_eq!(chain.median_time_past(&tip.block_hash()), Some(mtp));
+
+        let too_old = mine_at(&tip, mtp, 0);
+        assert_eq!(chain.add_header(too_old, NOW), Err(Error::TimeTooOld(too_old.block_hash())));
+        assert!(chain.add_header(mine_at(&tip, mtp + 1, 0), NOW).is_ok());
+
+        let too_new = mine_at(&tip, NOW + 2 * 60 * 60 + 1, 1);
+        assert_eq!(chain.add_header(too_new, NOW), Err(Error::TimeTooNew(too_new.block_hash())));
+        assert!(chain.add_header(mine_at(&tip, NOW + 2 * 60 * 60, 1), NOW).is_ok());
+    }
+
+    #[test]
+    fn two_block_reorg() {
+        let mut chain = HeaderChain::new(Params::new(Network::Regtest));
+        let genesis = *chain.get_header(&chain.tip()).unwrap();
+        let a1 = mine(&genesis, 0);
+        let a2 = mine(&a1, 0);
+        accepted(chain.add_header(a1, NOW));
+        accepted(chain.add_header(a2, NOW));
+
+        // A competing branch with as much work does not replace the best chain...
+        let b1 = mine(&genesis, 1);
+        let b2 = mine(&b1, 1);
+        assert_eq!(accepted(chain.add_header(b1, NOW)).new_tip, a2.block_hash());
+        assert!(accepted(chain.add_header(b2, NOW)).is_empty());
+        assert!(!chain.is_in_best_chain(&b2.block_hash()));
+        assert!(chain.get_header(&b2.block_hash()).is_some());
+
+        // ... until it is extended beyond it.
+        let b3 = mine(&b2, 1);
+        let update = accepted(chain.add_header(b3, NOW));
+        assert_eq!(update, ChainUpdate {
+            connected: vec![b1.block_hash(), b2.block_hash(), b3.block_hash()],
+            disconnected: vec![a2.block_hash(), a1.block_hash()],
+            new_tip: b3.block_hash(),
+        });
+        assert_eq!(chain.height(), 3);
+        assert_eq!(chain.block_hash_at(1), Some(b1.block_hash()));
+        assert!(!chain.is_in_best_chain(&a1.block_hash()));
+
+        // The stale branch can become best again.
+        let a3 = mine(&a2, 0);
+        let a4 = mine(&a3, 0);
+        assert!(accepted(chain.add_header(a3, NOW)).is_empty());
+        let update = accepted(chain.add_header(a4, NOW));
+        assert_eq!(update.connected, vec![a1.block_hash(), a2.block_hash(), a3.block_hash(), a4.block_hash()]);
+        assert_eq!(update.disconnected, vec![b3.block_hash(), b2.block_hash(), b1.block_hash()]);
+        assert_eq!(chain.tip(), a4.block_hash());
+    }
+
+    #[test]
+    fn finality_depth() {
+        let mut chain = HeaderChain::new(Params::new(Network::Regtest));
+        let genesis = *chain.get_header(&chain.tip()).unwrap();
+        let mut a = genesis;
+        let mut b = genesis;
+        for _ in 0..5 {
+            a = mine(&a, 0);
+            b = mine(&b, 1);
+            accepted(chain.add_header(a, NOW));
+            accepted(chain.add_header(b, NOW));
+        }
+        assert_eq!(chain.tip(), a.block_hash());
+
+        // Extending the best chain never reorgs.
+        assert!(!chain.would_violate_finality(&mine(&a, 0), 0));
+        // The competing branch replaces all five blocks once it has more work.
+        let b6 = mine(&b, 1);
+        assert!(chain.would_violate_finality(&b6, 4));
+        assert!(!chain.would_violate_finality(&b6, 5));
+        // A shorter fork has too little work.
+        let a1 = *chain.get_header(&chain.block_hash_at(1).unwrap()).unwrap();
+        assert!(!chain.would_violate_finality(&mine(&a1, 2), 0));
+        // Headers with an unknown parent are only parked.
+        assert!(!chain.would_violate_finality(&mine(&b6, 1), 0));
+
+        accepted(chain.add_header(b6, NOW));
+        assert_eq!(chain.tip(), b6.block_hash());
+    }
+
+    #[test]
+    fn checkpoints() {
+        let params = Params::new(Network::Regtest);
+        let mut chain = HeaderChain::new(params.clone());
+        let genesis = *chain.get_header(&chain.tip()).unwrap();
+        let a1 = mine(&genesis, 0);
+        let a2 = mine(&a1, 0);
+        let b1 = mine(&genesis, 1);
+        accepted(chain.add_header(a1, NOW));
+        accepted(chain.add_header(a2, NOW));
+        accepted(chain.add_header(b1, NOW));
+
+        assert_eq!(chain.validate_against_checkpoints(&params.checkpoints), Ok(()));
+        // Checkpoints above the tip are not checked yet.
+        let checkpoints = [(1, a1.block_hash()), (3, b1.block_hash())];
+        assert_eq!(chain.validate_against_checkpoints(&checkpoints), Ok(()));
+
+        // The stale branch contradicts the checkpoint.
+        let checkpoints = [(0, genesis.block_hash()), (1, b1.block_hash())];
+        assert_eq!(chain.validate_against_checkpoints(&checkpoints), Err(Error::CheckpointMismatch {
+            height: 1,
+            expected: b1.block_hash(),
+            found: a1.block_hash(),
+        }));
+    }
+
+    #[test]
+    fn orphan_headers() {
+        let mut chain = HeaderChain::new(Params::new(Network::Regtest));
+        let genesis = *chain.get_header(&chain.tip()).unwrap();
+        let a1 = mine(&genesis, 0);
+        let a2 = mine(&a1, 0);
+        let a3 = mine(&a2, 0);
+        let b3 = mine(&a2, 1);
+        // Invalid once connected, so it is dropped with its descendant.
+        let c3 = mine_at(&a2, genesis.time, 2);
+        let c4 = mine(&c3, 2);
+
+        assert_eq!(chain.add_header(a3, NOW), Ok(HeaderStatus::Parked));
+        assert_eq!(chain.add_header(a3, NOW), Ok(HeaderStatus::Parked));
+        assert_eq!(chain.add_header(b3, NOW), Ok(HeaderStatus::Parked));
+        assert_eq!(chain.add_header(c3, NOW), Ok(HeaderStatus::Parked));
+        assert_eq!(chain.add_header(c4, NOW), Ok(HeaderStatus::Parked));
+        assert_eq!(chain.add_header(a2, NOW), Ok(HeaderStatus::Parked));
+        assert_eq!(chain.orphan_count(), 5);
+        assert_eq!(chain.tip(), genesis.block_hash());
+
+        // The missing parent connects all valid waiting descendants at once.
+        let update = accepted(chain.add_header(a1, NOW));
+        assert_eq!(chain.orphan_count(), 0);
+        assert_eq!(chain.height(), 3);
+        assert_eq!(&update.connected[..2], &[a1.block_hash(), a2.block_hash()]);
+        assert_eq!(update.connected.len(), 3);
+        assert_eq!(update.new_tip, chain.tip());
+        assert!(chain.get_header(&a3.block_hash()).is_some());
+        assert!(chain.get_header(&b3.block_hash()).is_some());
+        assert!(chain.get_header(&c3.block_hash()).is_none());
+        assert!(chain.get_header(&c4.block_hash()).is_none());
+
+        // The pool is capped by evicting the oldest header; invalid headers
+        // are never parked.
+        let mut bad = mine(&unknown(&chain, 0), 0);
+        while bad.target().is_met_by(bad.block_hash()) {
+            bad.nonce += 1;
+        }
+        assert_eq!(chain.add_header(bad, NOW), Err(Error::BadProofOfWork(bad.block_hash())));
+        let tip = *chain.get_header(&chain.tip()).unwrap();
+        let parent = mine(&tip, 3);
+        let oldest = mine(&parent, 3);
+        assert_eq!(chain.add_header(oldest, NOW), Ok(HeaderStatus::Parked));
+        for i in 1..MAX_ORPHAN_HEADERS {
+            let orphan = mine(&unknown(&chain, i as u32), 0);
+            assert_eq!(chain.add_header(orphan, NOW), Ok(HeaderStatus::Parked));
+        }
+        let orphan = mine(&unknown(&chain, MAX_ORPHAN_HEADERS as u32), 0);
+        assert_eq!(chain.add_header(orphan, NOW), Ok(HeaderStatus::Parked));
+        assert_eq!(chain.orphan_count(), MAX_ORPHAN_HEADERS);
+
+        // The evicted header is not connected once its parent arrives.
+        let update = accepted(chain.add_header(parent, NOW));
+        assert_eq!(update.connected, vec![parent.block_hash()]);
+        assert!(chain.get_header(&oldest.block_hash()).is_none());
+        assert_eq!(chain.orphan_count(), MAX_ORPHAN_HEADERS);
+    }
+}
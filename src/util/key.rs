@@ -192,6 +192,17 @@ pub struct PrivateKey {
 }
 
 impl PrivateKey {
+    /// Generates a new random compressed private key for the given network, using the
+    /// thread-local CSPRNG.
+    #[cfg(feature = "rand")]
+    pub fn generate(network: Network) -> PrivateKey {
+        PrivateKey {
+            compressed: true,
+            network,
+            key: secp256k1::SecretKey::new(&mut secp256k1::rand::thread_rng()),
+        }
+    }
+
     /// Creates a public key from this private key
     pub fn public_key<C: secp256k1::Signing>(&self, secp: &Secp256k1<C>) -> PublicKey {
         PublicKey {
@@ -439,12 +450,35 @@ mod tests {
         assert_eq!(pk, PublicKey::from_str("032e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af").unwrap());
     }
 
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_generate() {
+        let secp = Secp256k1::new();
+        for &network in [Bitcoin, Testnet].iter() {
+            let sk = PrivateKey::generate(network);
+            assert!(sk.compressed);
+            assert_eq!(sk.network, network);
+
+            let wif = sk.to_wif();
+            assert_eq!(PrivateKey::from_wif(&wif).unwrap(), sk);
+            let pk = sk.public_key(&secp);
+            assert!(pk.compressed);
+            assert_eq!(PublicKey::from_slice(&pk.to_bytes()).unwrap(), pk);
+        }
+        assert_ne!(PrivateKey::generate(Bitcoin), PrivateKey::generate(Bitcoin));
+    }
+
     #[test]
     fn test_pubkey_hash() {
         let pk = PublicKey::from_str("032e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af").unwrap();
         let upk = PublicKey::from_str("042e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af191923a2964c177f5b5923ae500fca49e99492d534aa3759d6b25a8bc971b133").unwrap();
         assert_eq!(pk.pubkey_hash().to_hex(), "9511aa27ef39bbfa4e4f3dd15f4d66ea57f475b4");
         assert_eq!(upk.pubkey_hash().to_hex(), "ac2e7daf42d2c97418fd9f78af2de552bb9c6a7a");
+        assert_eq!(pk.pubkey_hash().as_hash(), ::util::hash::hash160(&pk.to_bytes()));
+
+        let generator = PublicKey::from_str("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap();
+        assert_eq!(generator.pubkey_hash().to_hex(), "751e76e8199196d454941c45d1b3a323f1433bd6");
+        assert_eq!(generator.wpubkey_hash().unwrap().to_hex(), "751e76e8199196d454941c45d1b3a323f1433bd6");
     }
 
     #[test]
@@ -0,0 +1,512 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Mempool helpers
+//!
+//! Utilities for working with sets of unconfirmed transactions, such as
+//! ordering them for inclusion in a block and assembling block templates.
+//!
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::{error, fmt};
+
+use blockdata::block::{Block, BlockHeader};
+use blockdata::constants::{MAX_BLOCK_SIGOPS_COST, MAX_BLOCK_WEIGHT, WITNESS_SCALE_FACTOR};
+use blockdata::opcodes;
+use blockdata::script::{Builder, Script};
+use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut, Sequence};
+use consensus::params::Params;
+use util::amount::FeeRate;
+
+/// Weight reserved for the header, transaction count and coinbase of a block
+/// template, as in Bitcoin Core.
+const COINBASE_RESERVED_WEIGHT: usize = 4000;
+/// Sigop cost reserved for the coinbase of a block template, as in Bitcoin Core.
+const COINBASE_RESERVED_SIGOPS: usize = 400;
+
+/// An error returned by [`topological_sort`] when the transactions' dependencies
+/// contain a cycle.
+///
+/// Such a set can not appear in valid data, as a transaction commits to the txids
+/// of its parents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    /// Indices of the transactions which could not be ordered, in ascending order.
+    pub unsorted: Vec<usize>,
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "dependency cycle between {} transactions", self.unsorted.len())
+    }
+}
+
+impl error::Error for CycleError {}
+
+/// The reason a transaction can not replace another under the BIP125 rules checked
+/// by [`check_replacement`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplacementError {
+    /// The original transaction does not signal replaceability.
+    NotSignaling,
+    /// The replacement does not pay a higher absolute fee than the original.
+    InsufficientFee,
+    /// The replacement does not pay a higher fee rate than the original.
+    InsufficientFeeRate,
+}
+
+impl fmt::Display for ReplacementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ReplacementError::NotSignaling => "original transaction does not signal replaceability",
+            ReplacementError::InsufficientFee => "replacement does not pay a higher fee",
+            ReplacementError::InsufficientFeeRate => "replacement does not pay a higher fee rate",
+        })
+    }
+}
+
+impl error::Error for ReplacementError {}
+
+/// Orders `txs` so that every transaction comes after all of its parents within
+/// the set, as required for block template assembly.
+///
+/// Returns the indices into `txs` in dependency order. Among transactions whose
+/// parents are all placed, the one appearing first in `txs` is placed first, so
+/// an already sorted set keeps its order. Inputs spending outputs not in `txs`
+/// are ignored.
+pub fn topological_sort(txs: &[Transaction]) -> Result<Vec<usize>, CycleError> {
+    let index: HashMap<_, _> = txs.iter().enumerate().map(|(i, tx)| (tx.txid(), i)).collect();
+    let parents: Vec<Vec<usize>> = txs.iter().map(|tx| {
+        let mut parents: Vec<usize> = tx.input.iter()
+            .filter_map(|input| index.get(&input.previous_output.txid).cloned())
+            .collect();
+        parents.sort();
+        parents.dedup();
+        parents
+    }).collect();
+    sort_graph(&parents)
+}
+
+/// Computes the combined fee rate of a package of transactions, given as pairs of
+/// a transaction and the fee in satoshis it pays.
+///
+/// This is the sum of the fees divided by the sum of the virtual sizes, which is
+/// how a child paying for its parents (CPFP) is evaluated.
+pub fn package_fee_rate(txs: &[(Transaction, u64)]) -> FeeRate {
+    let (fee, vsize) = txs.iter().fold((0u64, 0usize), |(fee, vsize), &(ref tx, tx_fee)| {
+        (fee.saturating_add(tx_fee), vsize + tx.get_vsize())
+    });
+    FeeRate::from_fee_and_vsize(fee, vsize)
+}
+
+/// Checks whether `replacement` may replace `original` according to BIP125, given
+/// the fees in satoshis each of them pays.
+///
+/// The original must explicitly signal replaceability (see
+/// [`Transaction::is_explicitly_rbf`]), and the replacement must pay both a higher
+/// absolute fee and a higher fee rate. Inherited signaling from unconfirmed
+/// ancestors, conflicts with further transactions and the incremental relay fee
+/// are not considered.
+///
+/// [`Transaction::is_explicitly_rbf`]: ../../blockdata/transaction/struct.Transaction.html#method.is_explicitly_rbf
+pub fn check_replacement(
+    original: &Transaction,
+    original_fee: u64,
+    replacement: &Transaction,
+    replacement_fee: u64,
+) -> Result<(), ReplacementError> {
+    if !original.is_explicitly_rbf() {
+        return Err(ReplacementError::NotSignaling);
+    }
+    if replacement_fee <= original_fee {
+        return Err(ReplacementError::InsufficientFee);
+    }
+    let original_rate = FeeRate::from_fee_and_vsize(original_fee, original.get_vsize());
+    let replacement_rate = FeeRate::from_fee_and_vsize(replacement_fee, replacement.get_vsize());
+    if replacement_rate <= original_rate {
+        return Err(ReplacementError::InsufficientFeeRate);
+    }
+    Ok(())
+}
+
+/// Assembles a block template on top of `header_base` at `height` from `mempool`,
+/// given as pairs of a transaction and the fee in satoshis it pays.
+///
+/// Transactions are picked greedily by fee rate, skipping those which would push
+/// the block over [`MAX_BLOCK_WEIGHT`] or [`MAX_BLOCK_SIGOPS_COST`]. A transaction
+/// is only picked once all of its parents in `mempool` are, so it never precedes
+/// them in the block; ancestor package fee rates are not taken into account.
+/// Coinbase transactions, transactions conflicting with an already picked one and,
+/// before segwit activation, transactions with witness data are skipped. The
+/// transactions are otherwise assumed to be valid.
+///
+/// Sigops are counted the legacy way, on input and output scripts only: without
+/// the spent outputs, the sigops of P2SH redeem scripts and witness scripts are
+/// not known.
+///
+/// The coinbase commits to `height` as required by BIP34 and pays the block
+/// subsidy plus all fees to `coinbase_script_pubkey`. A witness commitment is
+/// added when a picked transaction has witness data. The merkle root of the
+/// returned block is set, all other header fields are those of `header_base`.
+///
+/// [`MAX_BLOCK_WEIGHT`]: ../../blockdata/constants/constant.MAX_BLOCK_WEIGHT.html
+/// [`MAX_BLOCK_SIGOPS_COST`]: ../../blockdata/constants/constant.MAX_BLOCK_SIGOPS_COST.html
+pub fn assemble_block_template(
+    header_base: BlockHeader,
+    height: u32,
+    coinbase_script_pubkey: Script,
+    mempool: &[(Transaction, u64)],
+    params: &Params,
+) -> Block {
+    let segwit = params.segwit_active(height);
+    let index: HashMap<_, _> = mempool.iter().enumerate().map(|(i, (tx, _))| (tx.txid(), i)).collect();
+    let vsizes: Vec<usize> = mempool.iter().map(|(tx, _)| tx.get_vsize()).collect();
+
+    let mut candidates: Vec<usize> = (0..mempool.len()).filter(|&i| {
+        let tx = &mempool[i].0;
+        !tx.is_coin_base() && (segwit || tx.input.iter().all(|input| input.witness.is_empty()))
+    }).collect();
+    // Highest fee rate first, comparing cross products to avoid rounding. The
+    // sort is stable, so transactions with equal fee rates keep their order.
+    candidates.sort_by(|&a, &b| {
+        let rate_a = mempool[a].1 as u128 * vsizes[b] as u128;
+        let rate_b = mempool[b].1 as u128 * vsizes[a] as u128;
+        rate_b.cmp(&rate_a)
+    });
+
+    let mut picked = vec![false; mempool.len()];
+    let mut spent = HashSet::new();
+    let mut txdata = Vec::new();
+    let mut weight = COINBASE_RESERVED_WEIGHT;
+    let mut sigops = COINBASE_RESERVED_SIGOPS;
+    let mut fees = 0u64;
+    // Picking a parent can make children which were skipped earlier eligible,
+    // so iterate until a pass picks nothing.
+    loop {
+        let mut progress = false;
+        for &i in &candidates {
+            let (ref tx, fee) = mempool[i];
+            if picked[i] {
+                continue;
+            }
+            let parents_picked = tx.input.iter().all(|input| {
+                index.get(&input.previous_output.txid).map_or(true, |&parent| picked[parent])
+            });
+            if !parents_picked || tx.input.iter().any(|input| spent.contains(&input.previous_output)) {
+                continue;
+            }
+            let tx_weight = tx.get_weight();
+            let tx_sigops = sigop_cost(tx);
+            if weight + tx_weight > MAX_BLOCK_WEIGHT as usize || sigops + tx_sigops > MAX_BLOCK_SIGOPS_COST as usize {
+                continue;
+            }
+
+            picked[i] = true;
+            progress = true;
+            spent.extend(tx.input.iter().map(|input| input.previous_output));
+            weight += tx_weight;
+            sigops += tx_sigops;
+            fees = fees.saturating_add(fee);
+            txdata.push(tx.clone());
+        }
+        if !progress {
+            break;
+        }
+    }
+
+    let coinbase = Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Builder::new()
+                .push_int(height as i64)
+                .push_opcode(opcodes::OP_FALSE)
+                .into_script(),
+            sequence: Sequence::MAX,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: params.block_subsidy(height).saturating_add(fees),
+            script_pubkey: coinbase_script_pubkey,
+        }],
+    };
+    txdata.insert(0, coinbase);
+    let mut block = Block {
+        header: header_base,
+        txdata,
+    };
+
+    if block.txdata.iter().any(|tx| tx.input.iter().any(|input| !input.witness.is_empty())) {
+        let witness_reserved_value = vec![0u8; 32];
+        let commitment = Block::compute_witness_commitment(
+            &block.witness_root().expect("block has a coinbase"),
+            &witness_reserved_value,
+        );
+        let mut data = vec![0xaa, 0x21, 0xa9, 0xed];
+        data.extend_from_slice(&commitment[..]);
+        let coinbase = &mut block.txdata[0];
+        coinbase.input[0].witness = vec![witness_reserved_value];
+        coinbase.output.push(TxOut {
+            value: 0,
+            script_pubkey: Script::new_op_return(&data),
+        });
+    }
+    block.header.merkle_root = block.merkle_root();
+    block
+}
+
+/// Computes the legacy sigop cost of a transaction's input and output scripts.
+fn sigop_cost(tx: &Transaction) -> usize {
+    let scripts = tx.input.iter().map(|input| &input.script_sig)
+        .chain(tx.output.iter().map(|output| &output.script_pubkey));
+    scripts.map(Script::count_sigops_legacy).sum::<usize>() * WITNESS_SCALE_FACTOR
+}
+
+/// Kahn's algorithm over a graph given as the list of parents of each node.
+fn sort_graph(parents: &[Vec<usize>]) -> Result<Vec<usize>, CycleError> {
+    let mut children = vec![Vec::new(); parents.len()];
+    let mut pending: Vec<usize> = parents.iter().map(|p| p.len()).collect();
+    for (child, parents) in parents.iter().enumerate() {
+        for &parent in parents {
+            children[parent].push(child);
+        }
+    }
+
+    let mut ready: BinaryHeap<_> = pending.iter().enumerate()
+        .filter(|&(_, &n)| n == 0)
+        .map(|(i, _)| Reverse(i))
+        .collect();
+    let mut order = Vec::with_capacity(parents.len());
+    while let Some(Reverse(node)) = ready.pop() {
+        order.push(node);
+        for &child in &children[node] {
+            pending[child] -= 1;
+            if pending[child] == 0 {
+                ready.push(Reverse(child));
+            }
+        }
+    }
+
+    if order.len() == parents.len() {
+        Ok(order)
+    } else {
+        Err(CycleError {
+            unsorted: (0..parents.len()).filter(|&i| pending[i] > 0).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assemble_block_template, check_replacement, package_fee_rate, sort_graph, topological_sort};
+    use super::{CycleError, ReplacementError};
+
+    use blockdata::constants::{genesis_block, MAX_BLOCK_SIGOPS_COST, MAX_BLOCK_WEIGHT};
+    use blockdata::opcodes;
+    use blockdata::script::{Builder, Script};
+    use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut, Sequence};
+    use consensus::encode::deserialize;
+    use consensus::params::Params;
+    use hash_types::Txid;
+    use hashes::hex::FromHex;
+    use hashes::Hash;
+    use network::constants::Network;
+    use util::amount::FeeRate;
+
+    fn spend(prevouts: &[OutPoint]) -> Transaction {
+        Transaction {
+            version: 1,
+            lock_time: 0,
+            input: prevouts.iter().map(|prevout| TxIn {
+                previous_output: *prevout,
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                witness: vec![],
+            }).collect(),
+            output: vec![
+                TxOut { value: 1000, script_pubkey: Script::new() },
+                TxOut { value: 2000, script_pubkey: Script::new() },
+            ],
+        }
+    }
+
+    #[test]
+    fn sort_chain_out_of_order() {
+        let confirmed = OutPoint::new(Txid::hash(b"confirmed"), 0);
+        let parent = spend(&[confirmed]);
+        let child = spend(&[OutPoint::new(parent.txid(), 0)]);
+        let grandchild = spend(&[
+            OutPoint::new(child.txid(), 0),
+            OutPoint::new(child.txid(), 1),
+            OutPoint::new(parent.txid(), 1),
+        ]);
+        let unrelated = spend(&[OutPoint::new(confirmed.txid, 1)]);
+
+        let txs = [grandchild.clone(), unrelated.clone(), child.clone(), parent.clone()];
+        assert_eq!(topological_sort(&txs), Ok(vec![1, 3, 2, 0]));
+
+        // Already sorted input is kept as is.
+        let txs = [parent, child, grandchild, unrelated];
+        assert_eq!(topological_sort(&txs), Ok(vec![0, 1, 2, 3]));
+        assert_eq!(topological_sort(&[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn sort_detects_cycle() {
+        // 0 <- 1 <- 2 <- 1, 3 independent, 4 depends on the cycle.
+        let parents = vec![vec![], vec![0, 2], vec![1], vec![], vec![2]];
+        assert_eq!(sort_graph(&parents), Err(CycleError { unsorted: vec![1, 2, 4] }));
+    }
+
+    #[test]
+    fn cpfp_package_fee_rate() {
+        // A 111 vbyte segwit parent paying 111 sat (1 sat/vB) ...
+        let parent: Transaction = deserialize(&Vec::from_hex(
+            "02000000000101595895ea20179de87052b4046dfe6fd515860505d6511a9004cf12a1f93cac7c01000000\
+            00ffffffff01deb807000000000017a9140f3444e271620c736808aa7b33e370bd87cb5a078702483045022\
+            100fb60dad8df4af2841adc0346638c16d0b8035f5e3f3753b88db122e70c79f9370220756e6633b17fd271\
+            0e626347d28d60b0a2d6cbb41de51740644b9fb3ba7751040121028fa937ca8cba2197a37c007176ed89410\
+            55d3bcb8627d085e94553e62f057dcc00000000"
+        ).unwrap()).unwrap();
+        assert_eq!(parent.get_vsize(), 111);
+        // ... bumped by a child spending its output.
+        let child = spend(&[OutPoint::new(parent.txid(), 0)]);
+        assert_eq!(child.get_vsize(), 69);
+
+        assert_eq!(package_fee_rate(&[(parent.clone(), 111)]), FeeRate::from_sat_per_vb(1));
+        assert_eq!(package_fee_rate(&[(child.clone(), 69 * 20)]), FeeRate::from_sat_per_vb(20));
+        let package = [(parent, 111), (child, 69 * 20)];
+        assert_eq!(package_fee_rate(&package), FeeRate::from_fee_and_vsize(111 + 69 * 20, 111 + 69));
+        assert_eq!(package_fee_rate(&package).as_sat_per_kvb(), 8283);
+
+        assert_eq!(package_fee_rate(&[]), FeeRate::default());
+    }
+
+    #[test]
+    fn bip125_replacement() {
+        let confirmed = OutPoint::new(Txid::hash(b"confirmed"), 0);
+        let final_tx = spend(&[confirmed]);
+        assert!(!final_tx.is_explicitly_rbf());
+
+        // 0xfffffffe still opts out, anything lower signals.
+        let mut non_signaling = final_tx.clone();
+        non_signaling.input[0].sequence = Sequence::ENABLE_LOCKTIME_NO_RBF;
+        assert!(!non_signaling.is_explicitly_rbf());
+        let mut signaling = final_tx.clone();
+        signaling.input[0].sequence = Sequence::ENABLE_RBF_NO_LOCKTIME;
+        assert!(signaling.is_explicitly_rbf());
+        let mut one_of_many = spend(&[confirmed, OutPoint::new(confirmed.txid, 1)]);
+        one_of_many.input[1].sequence = Sequence::ZERO;
+        assert!(one_of_many.is_explicitly_rbf());
+
+        // The replacement adds an input, so it is larger than the original.
+        let mut replacement = one_of_many.clone();
+        replacement.input[1].sequence = Sequence::MAX;
+        replacement.input.push(replacement.input[0].clone());
+        replacement.input[2].previous_output.vout = 2;
+        assert!(replacement.get_vsize() > signaling.get_vsize());
+
+        assert_eq!(check_replacement(&final_tx, 1000, &replacement, 5000), Err(ReplacementError::NotSignaling));
+        assert_eq!(check_replacement(&non_signaling, 1000, &replacement, 5000), Err(ReplacementError::NotSignaling));
+        assert_eq!(check_replacement(&signaling, 1000, &replacement, 1000), Err(ReplacementError::InsufficientFee));
+        assert_eq!(check_replacement(&signaling, 1000, &replacement, 1001), Err(ReplacementError::InsufficientFeeRate));
+        assert_eq!(check_replacement(&signaling, 1000, &replacement, 5000), Ok(()));
+        // The replacement itself does not need to signal.
+        assert!(!replacement.is_explicitly_rbf());
+    }
+
+    #[test]
+    fn block_template_limits() {
+        let params = Params::new(Network::Regtest);
+        let mut header = genesis_block(Network::Regtest).header;
+        header.version = 0x20000000;
+        let payout = Script::new_p2pkh(&Hash::hash(b"miner"));
+
+        // 30 transactions of about 200k weight each, only 19 of which fit.
+        let mut mempool = Vec::new();
+        for i in 0..30u32 {
+            let mut tx = spend(&[OutPoint::new(Txid::hash(b"confirmed"), i)]);
+            tx.output[0].script_pubkey = Script::from(vec![0x61; 50_000]);
+            mempool.push((tx, 1000 + u64::from(i)));
+        }
+        let block = assemble_block_template(header, 1000, payout.clone(), &mempool, &params);
+        assert!(block.get_weight() <= MAX_BLOCK_WEIGHT as usize);
+        assert_eq!(block.txdata.len(), 1 + 19);
+        assert!(block.check_merkle_root());
+        assert_eq!(block.bip34_block_height(), Ok(1000));
+        // The highest fee rates were picked, in descending order.
+        for (k, tx) in block.txdata[1..].iter().enumerate() {
+            assert_eq!(*tx, mempool[29 - k].0);
+        }
+        let fees: u64 = (11..30).map(|i| 1000 + i).sum();
+        assert_eq!(block.txdata[0].output[0].value, params.block_subsidy(1000) + fees);
+        assert_eq!(block.txdata[0].output[0].script_pubkey, payout);
+
+        // 12 transactions costing 8000 sigops each, only 9 of which fit.
+        let mut mempool = Vec::new();
+        for i in 0..12u32 {
+            let mut tx = spend(&[OutPoint::new(Txid::hash(b"confirmed"), i)]);
+            tx.output[0].script_pubkey = Builder::new()
+                .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+                .push_opcode(opcodes::all::OP_CHECKSIG)
+                .into_script();
+            for _ in 0..99 {
+                tx.output.push(tx.output[0].clone());
+            }
+            mempool.push((tx, 1000));
+        }
+        let block = assemble_block_template(header, 1000, payout, &mempool, &params);
+        assert_eq!(block.txdata.len(), 1 + 9);
+        assert!(9 * 8000 <= MAX_BLOCK_SIGOPS_COST && 10 * 8000 > MAX_BLOCK_SIGOPS_COST - 400);
+    }
+
+    #[test]
+    fn block_template_dependencies() {
+        let params = Params::new(Network::Regtest);
+        let header = genesis_block(Network::Regtest).header;
+        let confirmed = OutPoint::new(Txid::hash(b"confirmed"), 0);
+
+        let parent = spend(&[confirmed]);
+        let mut child = spend(&[OutPoint::new(parent.txid(), 0)]);
+        child.input[0].witness = vec![vec![1; 72], vec![2; 33]];
+        let conflict = spend(&[confirmed, OutPoint::new(confirmed.txid, 1)]);
+        let orphan_child = spend(&[OutPoint::new(conflict.txid(), 0)]);
+        let unrelated = spend(&[OutPoint::new(confirmed.txid, 2)]);
+        let mempool = vec![
+            (child.clone(), 100_000),
+            (orphan_child, 100_000),
+            (unrelated.clone(), 500),
+            (conflict, 1000),
+            (parent.clone(), 1000),
+        ];
+
+        // The child has the highest fee rate but must wait for its parent, which
+        // beats the conflicting transaction on fee rate as it is smaller.
+        let block = assemble_block_template(header, 1000, Script::new(), &mempool, &params);
+        assert_eq!(block.txdata[1..].to_vec(), vec![parent.clone(), unrelated.clone(), child]);
+        assert!(block.check_merkle_root());
+        assert_eq!(block.txdata[0].output.len(), 2);
+        assert!(block.check_witness_commitment());
+        assert_eq!(block.txdata[0].output[0].value, params.block_subsidy(1000) + 101_500);
+
+        // Before segwit activation transactions with witness data are left out.
+        let mut params = params;
+        params.segwit_height = 2000;
+        let block = assemble_block_template(header, 1000, Script::new(), &mempool, &params);
+        assert_eq!(block.txdata[1..].to_vec(), vec![parent, unrelated]);
+        assert_eq!(block.txdata[0].output.len(), 1);
+        assert!(block.txdata[0].input[0].witness.is_empty());
+    }
+}
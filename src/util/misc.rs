@@ -23,6 +23,8 @@ use consensus::{encode, Encodable};
 
 #[cfg(feature = "secp-recovery")]
 pub use self::message_signing::{MessageSignature, MessageSignatureError};
+#[cfg(all(feature = "secp-recovery", feature = "base64"))]
+pub use self::message_signing::{sign_message, verify_message};
 
 /// The prefix for signed messages using Bitcoin's message signing protocol.
 pub const BITCOIN_SIGNED_MSG_PREFIX: &[u8] = b"\x18Bitcoin Signed Message:\n";
@@ -35,7 +37,7 @@ mod message_signing {
     use secp256k1;
     use secp256k1::recovery::{RecoveryId, RecoverableSignature};
 
-    use util::key::PublicKey;
+    use util::key::{PrivateKey, PublicKey};
     use util::address::{Address, AddressType};
 
     /// An error used for dealing with Bitcoin Signed Messages.
@@ -97,6 +99,20 @@ mod message_signing {
             }
         }
 
+        /// Sign a message hash with the given private key.
+        ///
+        /// The resulting signature records whether the key is compressed, so that the
+        /// signing address can be recovered. To get the message hash from a message, use
+        /// [signed_msg_hash].
+        pub fn sign<C: secp256k1::Signing>(
+            secp_ctx: &secp256k1::Secp256k1<C>,
+            privkey: &PrivateKey,
+            msg_hash: sha256d::Hash
+        ) -> MessageSignature {
+            let msg = secp256k1::Message::from_slice(&msg_hash[..]).expect("sha256d hashes are 32 bytes");
+            MessageSignature::new(secp_ctx.sign_recoverable(&msg, &privkey.key), privkey.compressed)
+        }
+
         /// Serialize to bytes.
         pub fn serialize(&self) -> [u8; 65] {
             let (recid, raw) = self.signature.serialize_compact();
@@ -159,6 +175,7 @@ mod message_signing {
                 Some(AddressType::P2sh) => false,
                 Some(AddressType::P2wpkh) => false,
                 Some(AddressType::P2wsh) => false,
+                Some(AddressType::P2tr) => false,
                 None => false,
             })
         }
@@ -177,6 +194,30 @@ mod message_signing {
         }
     }
 
+    /// Sign a message using Bitcoin's message signing format, returning the base64-encoded
+    /// signature as accepted by Bitcoin Core's `verifymessage`.
+    #[cfg(feature = "base64")]
+    pub fn sign_message<C: secp256k1::Signing>(
+        secp_ctx: &secp256k1::Secp256k1<C>,
+        privkey: &PrivateKey,
+        msg: &str
+    ) -> String {
+        MessageSignature::sign(secp_ctx, privkey, super::signed_msg_hash(msg)).to_base64()
+    }
+
+    /// Verify a base64-encoded signature over a message in Bitcoin's message signing format,
+    /// checking that it was produced by the key behind the given P2PKH address.
+    #[cfg(feature = "base64")]
+    pub fn verify_message<C: secp256k1::Verification>(
+        secp_ctx: &secp256k1::Secp256k1<C>,
+        address: &Address,
+        signature: &str,
+        msg: &str
+    ) -> Result<bool, MessageSignatureError> {
+        let signature = MessageSignature::from_base64(signature)?;
+        Ok(signature.is_signed_by_address(secp_ctx, address, super::signed_msg_hash(msg))?)
+    }
+
     #[cfg(feature = "base64")]
     impl fmt::Display for MessageSignature {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -323,5 +364,31 @@ mod tests {
         let p2shwpkh = ::Address::p2shwpkh(&pubkey, ::Network::Bitcoin).unwrap();
         assert_eq!(signature2.is_signed_by_address(&secp, &p2shwpkh, msg_hash), Ok(false));
     }
-}
 
+    #[test]
+    #[cfg(all(feature = "secp-recovery", feature = "base64"))]
+    fn test_sign_verify_message() {
+        use std::str::FromStr;
+        use secp256k1;
+        use util::key::PrivateKey;
+
+        let secp = secp256k1::Secp256k1::new();
+        let message = "rust-bitcoin sign_message test";
+        let privkey = PrivateKey::from_wif("L1HKVVLHXiUhecWnwFYF6L3shkf1E12HUmuZTESvBXUdx3yqVP1D").unwrap();
+        let address = ::Address::p2pkh(&privkey.public_key(&secp), ::Network::Bitcoin);
+
+        let signature = super::sign_message(&secp, &privkey, message);
+        let parsed = super::MessageSignature::from_str(&signature).unwrap();
+        assert!(parsed.compressed);
+        assert_eq!(parsed, super::MessageSignature::sign(&secp, &privkey, signed_msg_hash(message)));
+        assert_eq!(super::verify_message(&secp, &address, &signature, message), Ok(true));
+        assert_eq!(super::verify_message(&secp, &address, &signature, "another message"), Ok(false));
+
+        let other = PrivateKey::from_wif("KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617").unwrap();
+        let other_address = ::Address::p2pkh(&other.public_key(&secp), ::Network::Bitcoin);
+        assert_eq!(super::verify_message(&secp, &other_address, &signature, message), Ok(false));
+
+        assert_eq!(super::verify_message(&secp, &address, "not base64!", message),
+                   Err(super::MessageSignatureError::InvalidBase64));
+    }
+}
@@ -22,19 +22,30 @@ pub mod amount;
 pub mod base58;
 pub mod bip32;
 pub mod bip143;
+#[cfg(feature = "bip39")]
+pub mod bip39;
 pub mod contracthash;
+pub mod descriptor;
+pub mod ecdsa;
 pub mod hash;
+pub mod headerchain;
+pub mod mempool;
 pub mod merkleblock;
 pub mod misc;
 pub mod psbt;
+pub mod spv;
 pub mod taproot;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 pub mod uint;
 pub mod bip158;
+pub mod utxo;
 
 pub(crate) mod endian;
 
 use std::{error, fmt};
 
+use blockdata::transaction::OutPoint;
 use network;
 use consensus::encode;
 
@@ -71,6 +82,16 @@ pub enum Error {
     BlockBadProofOfWork,
     /// The `target` field of a block header did not match the expected difficulty
     BlockBadTarget,
+    /// The block timestamp is not after the median time past of its ancestors
+    BlockTimeTooOld,
+    /// The block timestamp is too far ahead of the local time
+    BlockTimeTooNew,
+    /// The coinbase scriptSig is shorter or longer than allowed
+    BadCoinbaseLength,
+    /// The signature operations of a block cost more than allowed
+    BlockTooManySigops,
+    /// The output spent by a transaction input is not known
+    UnknownSpentOutput(OutPoint),
 }
 
 impl fmt::Display for Error {
@@ -80,6 +101,11 @@ impl fmt::Display for Error {
             Error::Network(ref e) => fmt::Display::fmt(e, f),
             Error::BlockBadProofOfWork => f.write_str("block target correct but not attained"),
             Error::BlockBadTarget => f.write_str("block target incorrect"),
+            Error::BlockTimeTooOld => f.write_str("block timestamp not after median time past"),
+            Error::BlockTimeTooNew => f.write_str("block timestamp too far in the future"),
+            Error::BadCoinbaseLength => f.write_str("coinbase scriptSig length out of range"),
+            Error::BlockTooManySigops => f.write_str("block sigop cost limit exceeded"),
+            Error::UnknownSpentOutput(ref point) => write!(f, "unknown spent output: {}", point),
         }
     }
 }
@@ -89,7 +115,10 @@ impl error::Error for Error {
         match *self {
             Error::Encode(ref e) => Some(e),
             Error::Network(ref e) => Some(e),
-            Error::BlockBadProofOfWork | Error::BlockBadTarget => None
+            Error::BlockBadProofOfWork | Error::BlockBadTarget
+            | Error::BlockTimeTooOld | Error::BlockTimeTooNew
+            | Error::BadCoinbaseLength | Error::BlockTooManySigops
+            | Error::UnknownSpentOutput(_) => None
         }
     }
 }
@@ -16,6 +16,7 @@ use std::error;
 use std::fmt;
 
 use blockdata::transaction::Transaction;
+use hash_types::Txid;
 use consensus::encode;
 use util::psbt::raw;
 
@@ -76,6 +77,38 @@ pub enum Error {
     MergeConflict(String),
     /// Serialization error in bitcoin consensus-encoded structures
     ConsensusEncoding,
+    /// The unsigned transaction has no input with the given index.
+    InputIndexOutOfBounds(usize),
+    /// Neither a witness nor a non-witness UTXO is known for the given input.
+    MissingUtxo(usize),
+    /// The given segwit input has no witness UTXO, which is needed for the
+    /// amount committed to by the signature.
+    MissingWitnessUtxo(usize),
+    /// The non-witness UTXO of an input is not the transaction its previous
+    /// output refers to.
+    NonWitnessUtxoMismatch {
+        /// Input index
+        index: usize,
+        /// Txid of the previous output in the unsigned transaction
+        expected: Txid,
+        /// Txid of the non-witness UTXO
+        actual: Txid,
+    },
+    /// The witness UTXO of the given input is not the output of its
+    /// non-witness UTXO which the previous output refers to.
+    WitnessUtxoMismatch(usize),
+    /// Setting the named field would contradict data already in the map,
+    /// such as a witness UTXO which is not an output of the non-witness UTXO.
+    ConflictingField(&'static str),
+    /// The size of the final scriptSig and witness of the given input can not
+    /// be estimated from the scripts known for it.
+    UnknownInputType(usize),
+    /// The outputs of the transaction are worth more than the outputs it
+    /// spends.
+    NegativeFee,
+    /// The input with the given index has neither a final scriptSig nor a
+    /// final witness.
+    NotFinalized(usize),
 }
 
 impl fmt::Display for Error {
@@ -101,6 +134,19 @@ impl fmt::Display for Error {
             }
             Error::MergeConflict(ref s) => { write!(f, "Merge conflict: {}", s) }
             Error::ConsensusEncoding => f.write_str("bitcoin consensus or BIP-174 encoding error"),
+            Error::InputIndexOutOfBounds(index) => write!(f, "no input with index {}", index),
+            Error::MissingUtxo(index) => write!(f, "no UTXO for input {}", index),
+            Error::MissingWitnessUtxo(index) => write!(f, "no witness UTXO for segwit input {}", index),
+            Error::NonWitnessUtxoMismatch { index, ref expected, ref actual } => {
+                write!(f, "non-witness UTXO of input {} has txid {}, expected {}", index, actual, expected)
+            }
+            Error::WitnessUtxoMismatch(index) => {
+                write!(f, "witness UTXO of input {} does not match its non-witness UTXO", index)
+            }
+            Error::ConflictingField(field) => write!(f, "{} conflicts with data already in the map", field),
+            Error::UnknownInputType(index) => write!(f, "cannot estimate the final size of input {}", index),
+            Error::NegativeFee => f.write_str("outputs are worth more than the inputs"),
+            Error::NotFinalized(index) => write!(f, "input {} is not finalized", index),
         }
     }
 }
@@ -12,7 +12,7 @@
 // If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
 //
 
-use std::io;
+use std::{io, mem};
 use std::collections::btree_map::{BTreeMap, Entry};
 
 use blockdata::script::Script;
@@ -111,6 +111,97 @@ pub struct Input {
     pub unknown: BTreeMap<raw::Key, Vec<u8>>,
 }
 
+impl Input {
+    /// Returns the transaction containing the output spent by this input.
+    pub fn non_witness_utxo(&self) -> Option<&Transaction> {
+        self.non_witness_utxo.as_ref()
+    }
+
+    /// Returns the output spent by this input, as given for segwit inputs.
+    pub fn witness_utxo(&self) -> Option<&TxOut> {
+        self.witness_utxo.as_ref()
+    }
+
+    /// Returns the redeem script of a P2SH input.
+    pub fn redeem_script(&self) -> Option<&Script> {
+        self.redeem_script.as_ref()
+    }
+
+    /// Returns the witness script of a P2WSH input.
+    pub fn witness_script(&self) -> Option<&Script> {
+        self.witness_script.as_ref()
+    }
+
+    /// Returns the key origins of the public keys needed to spend this input.
+    pub fn bip32_derivation(&self) -> &BTreeMap<PublicKey, KeySource> {
+        &self.bip32_derivation
+    }
+
+    /// Sets the non-witness UTXO, failing if a witness UTXO is known which is
+    /// not one of the outputs of `tx`.
+    pub fn set_non_witness_utxo(&mut self, tx: Transaction) -> Result<(), Error> {
+        let old = mem::replace(&mut self.non_witness_utxo, Some(tx));
+        self.check_field("non_witness_utxo", move |input| input.non_witness_utxo = old)
+    }
+
+    /// Sets the witness UTXO, failing if it is not one of the outputs of the
+    /// non-witness UTXO, or if a known redeem or witness script does not hash
+    /// to its script pubkey.
+    pub fn set_witness_utxo(&mut self, utxo: TxOut) -> Result<(), Error> {
+        let old = mem::replace(&mut self.witness_utxo, Some(utxo));
+        self.check_field("witness_utxo", move |input| input.witness_utxo = old)
+    }
+
+    /// Sets the redeem script, failing if the witness UTXO is P2SH for a
+    /// different script, or the script is P2WSH for a different witness script.
+    pub fn set_redeem_script(&mut self, script: Script) -> Result<(), Error> {
+        let old = mem::replace(&mut self.redeem_script, Some(script));
+        self.check_field("redeem_script", move |input| input.redeem_script = old)
+    }
+
+    /// Sets the witness script, failing if the redeem script or the witness
+    /// UTXO is P2WSH for a different script.
+    pub fn set_witness_script(&mut self, script: Script) -> Result<(), Error> {
+        let old = mem::replace(&mut self.witness_script, Some(script));
+        self.check_field("witness_script", move |input| input.witness_script = old)
+    }
+
+    /// Checks the UTXOs and scripts against each other, calling `restore` to
+    /// roll back the change to `field` if they are inconsistent.
+    fn check_field<F: FnOnce(&mut Input)>(&mut self, field: &'static str, restore: F) -> Result<(), Error> {
+        if self.is_consistent() {
+            Ok(())
+        } else {
+            restore(self);
+            Err(Error::ConflictingField(field))
+        }
+    }
+
+    fn is_consistent(&self) -> bool {
+        if let (Some(tx), Some(utxo)) = (&self.non_witness_utxo, &self.witness_utxo) {
+            if !tx.output.contains(utxo) {
+                return false;
+            }
+        }
+        let spk = self.witness_utxo.as_ref().map(|utxo| &utxo.script_pubkey);
+        if let (Some(spk), Some(redeem)) = (spk, &self.redeem_script) {
+            if spk.is_p2sh() && *spk != redeem.to_p2sh() {
+                return false;
+            }
+        }
+        // The witness program is the redeem script of P2SH-P2WSH inputs and
+        // the script pubkey of native P2WSH ones.
+        if let Some(ref witness) = self.witness_script {
+            let p2wsh = witness.to_v0_p2wsh();
+            let mut programs = self.redeem_script.iter().chain(spk);
+            if programs.any(|program| program.is_v0_p2wsh() && *program != p2wsh) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
 impl Map for Input {
     fn insert_pair(&mut self, pair: raw::Pair) -> Result<(), encode::Error> {
         let raw::Pair {
@@ -12,7 +12,7 @@
 // If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
 //
 
-use std::io;
+use std::{io, mem};
 use std::collections::BTreeMap;
 use std::collections::btree_map::Entry;
 
@@ -55,6 +55,54 @@ pub struct Output {
     pub unknown: BTreeMap<raw::Key, Vec<u8>>,
 }
 
+impl Output {
+    /// Returns the redeem script of a P2SH output.
+    pub fn redeem_script(&self) -> Option<&Script> {
+        self.redeem_script.as_ref()
+    }
+
+    /// Returns the witness script of a P2WSH output.
+    pub fn witness_script(&self) -> Option<&Script> {
+        self.witness_script.as_ref()
+    }
+
+    /// Returns the key origins of the public keys needed to spend this output.
+    pub fn bip32_derivation(&self) -> &BTreeMap<PublicKey, KeySource> {
+        &self.bip32_derivation
+    }
+
+    /// Sets the redeem script, failing if it is P2WSH for a different
+    /// witness script.
+    pub fn set_redeem_script(&mut self, script: Script) -> Result<(), Error> {
+        let old = mem::replace(&mut self.redeem_script, Some(script));
+        if self.is_consistent() {
+            Ok(())
+        } else {
+            self.redeem_script = old;
+            Err(Error::ConflictingField("redeem_script"))
+        }
+    }
+
+    /// Sets the witness script, failing if the redeem script is P2WSH for a
+    /// different script.
+    pub fn set_witness_script(&mut self, script: Script) -> Result<(), Error> {
+        let old = mem::replace(&mut self.witness_script, Some(script));
+        if self.is_consistent() {
+            Ok(())
+        } else {
+            self.witness_script = old;
+            Err(Error::ConflictingField("witness_script"))
+        }
+    }
+
+    fn is_consistent(&self) -> bool {
+        match (&self.redeem_script, &self.witness_script) {
+            (Some(redeem), Some(witness)) if redeem.is_v0_p2wsh() => *redeem == witness.to_v0_p2wsh(),
+            _ => true,
+        }
+    }
+}
+
 impl Map for Output {
     fn insert_pair(&mut self, pair: raw::Pair) -> Result<(), encode::Error> {
         let raw::Pair {
@@ -18,9 +18,14 @@
 //! defined at https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki
 //! except we define PSBTs containing non-standard SigHash types as invalid.
 
-use blockdata::script::Script;
-use blockdata::transaction::Transaction;
+use blockdata::opcodes;
+use blockdata::script::{Builder, Script};
+use blockdata::transaction::{SigHashType, Transaction, TxIn, TxOut};
 use consensus::{encode, Encodable, Decodable};
+use hash_types::SigHash;
+use hashes::hex::FromHex;
+use util::amount::FeeRate;
+use util::bip143::SigHashCache;
 
 use std::io;
 
@@ -62,9 +67,36 @@ impl PartiallySignedTransaction {
         })
     }
 
+    /// Encodes the PSBT as a hex string.
+    pub fn to_hex(&self) -> String {
+        encode::serialize_hex(self)
+    }
+
+    /// Decodes a hex encoded PSBT. Fails with [`Error::InvalidMagic`], wrapped
+    /// in `encode::Error::Psbt`, if the data does not start with `psbt\xff`.
+    ///
+    /// [`Error::InvalidMagic`]: enum.Error.html#variant.InvalidMagic
+    pub fn from_hex(s: &str) -> Result<Self, encode::Error> {
+        encode::deserialize(&Vec::from_hex(s)?)
+    }
+
+    /// Checks whether every input has a final scriptSig or witness, so that
+    /// the transaction can be extracted.
+    pub fn is_finalized(&self) -> bool {
+        self.first_unfinalized_input().is_none()
+    }
+
     /// Extract the Transaction from a PartiallySignedTransaction by filling in
-    /// the available signature information in place.
-    pub fn extract_tx(self) -> Transaction {
+    /// the final scriptSigs and witnesses of its inputs.
+    ///
+    /// Fails with [`Error::NotFinalized`] unless [`is_finalized`].
+    ///
+    /// [`Error::NotFinalized`]: enum.Error.html#variant.NotFinalized
+    /// [`is_finalized`]: #method.is_finalized
+    pub fn extract_tx(self) -> Result<Transaction, self::Error> {
+        if let Some(index) = self.first_unfinalized_input() {
+            return Err(Error::NotFinalized(index));
+        }
         let mut tx: Transaction = self.global.unsigned_tx;
 
         for (vin, psbtin) in tx.input.iter_mut().zip(self.inputs.into_iter()) {
@@ -72,7 +104,165 @@ impl PartiallySignedTransaction {
             vin.witness = psbtin.final_script_witness.unwrap_or_else(Vec::new);
         }
 
-        tx
+        Ok(tx)
+    }
+
+    fn first_unfinalized_input(&self) -> Option<usize> {
+        (0..self.global.unsigned_tx.input.len()).find(|&index| match self.inputs.get(index) {
+            Some(input) => input.final_script_sig.is_none() && input.final_script_witness.is_none(),
+            None => true,
+        })
+    }
+
+    /// Returns the output spent by the input at `index`.
+    ///
+    /// The witness UTXO is preferred. A non-witness UTXO, if present, is
+    /// checked to be the transaction referred to by the input's previous
+    /// output, and to contain the witness UTXO if both are known.
+    pub fn spent_output(&self, index: usize) -> Result<&TxOut, self::Error> {
+        let (txin, input) = self.input_pair(index)?;
+        let non_witness_txout = match input.non_witness_utxo {
+            Some(ref tx) => {
+                let txid = tx.txid();
+                if txid != txin.previous_output.txid {
+                    return Err(Error::NonWitnessUtxoMismatch {
+                        index,
+                        expected: txin.previous_output.txid,
+                        actual: txid,
+                    });
+                }
+                tx.output.get(txin.previous_output.vout as usize)
+            }
+            None => None,
+        };
+        match (input.witness_utxo.as_ref(), non_witness_txout) {
+            (Some(txout), Some(other)) if txout != other => Err(Error::WitnessUtxoMismatch(index)),
+            (Some(txout), _) | (None, Some(txout)) => Ok(txout),
+            (None, None) => Err(Error::MissingUtxo(index)),
+        }
+    }
+
+    /// Computes the sighash to sign for the input at `index`, using the
+    /// input's sighash type (`SIGHASH_ALL` if unset).
+    ///
+    /// `script_code` is the scriptPubKey, redeem script or witness script
+    /// being satisfied, or the P2PKH script for P2WPKH inputs. Segwit inputs,
+    /// that is those with a witness script, a witness program as redeem script
+    /// or a witness program as spent output, use BIP143 and require a witness
+    /// UTXO for the amount. Other inputs use the legacy sighash even if a
+    /// witness UTXO is set.
+    pub fn signature_hash(&self, index: usize, script_code: &Script) -> Result<SigHash, self::Error> {
+        let spent = self.spent_output(index)?;
+        let input = &self.inputs[index];
+        let sighash_type = input.sighash_type.unwrap_or(SigHashType::All);
+        let is_segwit = spent.script_pubkey.is_witness_program()
+            || input.witness_script.is_some()
+            || (spent.script_pubkey.is_p2sh()
+                && input.redeem_script.as_ref().map_or(false, |s| s.is_witness_program()));
+
+        let tx = &self.global.unsigned_tx;
+        if is_segwit {
+            let value = input.witness_utxo.as_ref().ok_or(Error::MissingWitnessUtxo(index))?.value;
+            Ok(SigHashCache::new(tx).signature_hash(index, script_code, value, sighash_type))
+        } else {
+            Ok(tx.signature_hash(index, script_code, sighash_type.as_u32()))
+        }
+    }
+
+    /// Estimates the fee rate of the transaction once all inputs are signed.
+    ///
+    /// Inputs which are already finalized count with their final scriptSig
+    /// and witness. For the others the size is estimated from the spent
+    /// output, redeem script and witness script, assuming worst-case 72-byte
+    /// signatures and compressed public keys. P2PK, P2PKH, P2WPKH and bare
+    /// multisig witness or redeem scripts, nested in P2SH or not, are
+    /// supported.
+    pub fn estimated_fee_rate(&self) -> Result<FeeRate, self::Error> {
+        let mut tx = self.global.unsigned_tx.clone();
+        let mut input_value = 0u64;
+        for index in 0..tx.input.len() {
+            input_value = input_value.saturating_add(self.spent_output(index)?.value);
+            let (script_sig, witness) = self.estimated_satisfaction(index)?;
+            tx.input[index].script_sig = script_sig;
+            tx.input[index].witness = witness;
+        }
+        let output_value = tx.output.iter().fold(0u64, |sum, o| sum.saturating_add(o.value));
+        let fee = input_value.checked_sub(output_value).ok_or(Error::NegativeFee)?;
+        Ok(FeeRate::from_fee_and_vsize(fee, tx.get_vsize()))
+    }
+
+    /// Returns a scriptSig and witness of the size the input at `index` is
+    /// expected to have once finalized.
+    fn estimated_satisfaction(&self, index: usize) -> Result<(Script, Vec<Vec<u8>>), self::Error> {
+        // A high-R, low-S DER signature followed by the sighash type, the largest a
+        // standard signature can be, and a compressed key.
+        const SIG: [u8; 72] = [0; 72];
+        const KEY: [u8; 33] = [0; 33];
+
+        let spent = &self.spent_output(index)?.script_pubkey;
+        let input = &self.inputs[index];
+        if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+            return Ok((
+                input.final_script_sig.clone().unwrap_or_default(),
+                input.final_script_witness.clone().unwrap_or_default(),
+            ));
+        }
+
+        // The number of signatures needed by a bare multisig script.
+        let multisig_sigs = |script: &Script| -> Option<usize> {
+            let bytes = script.as_bytes();
+            if bytes.last() != Some(&opcodes::all::OP_CHECKMULTISIG.into_u8()) {
+                return None;
+            }
+            match opcodes::All::from(bytes[0]).push_value() {
+                Some(m) if m > 0 => Some(m as usize),
+                _ => None,
+            }
+        };
+        let witness_satisfaction = |script: &Script| -> Option<Vec<Vec<u8>>> {
+            if script.is_v0_p2wpkh() {
+                Some(vec![SIG.to_vec(), KEY.to_vec()])
+            } else if script.is_v0_p2wsh() {
+                let witness_script = input.witness_script.as_ref()?;
+                let mut witness = vec![vec![]];
+                witness.extend((0..multisig_sigs(witness_script)?).map(|_| SIG.to_vec()));
+                witness.push(witness_script.to_bytes());
+                Some(witness)
+            } else {
+                None
+            }
+        };
+
+        let satisfaction = if spent.is_witness_program() {
+            witness_satisfaction(spent).map(|witness| (Script::new(), witness))
+        } else if spent.is_p2sh() {
+            input.redeem_script.as_ref().and_then(|redeem| {
+                let push_redeem = Builder::new().push_slice(redeem.as_bytes());
+                if redeem.is_witness_program() {
+                    witness_satisfaction(redeem).map(|witness| (push_redeem.into_script(), witness))
+                } else {
+                    let mut builder = Builder::new().push_opcode(opcodes::all::OP_PUSHBYTES_0);
+                    for _ in 0..multisig_sigs(redeem)? {
+                        builder = builder.push_slice(&SIG);
+                    }
+                    Some((builder.push_slice(redeem.as_bytes()).into_script(), vec![]))
+                }
+            })
+        } else if spent.is_p2pkh() {
+            Some((Builder::new().push_slice(&SIG).push_slice(&KEY).into_script(), vec![]))
+        } else if spent.is_p2pk() {
+            Some((Builder::new().push_slice(&SIG).into_script(), vec![]))
+        } else {
+            None
+        };
+        satisfaction.ok_or(Error::UnknownInputType(index))
+    }
+
+    fn input_pair(&self, index: usize) -> Result<(&TxIn, &Input), self::Error> {
+        match (self.global.unsigned_tx.input.get(index), self.inputs.get(index)) {
+            (Some(txin), Some(input)) => Ok((txin, input)),
+            _ => Err(Error::InputIndexOutOfBounds(index)),
+        }
     }
 
     /// Attempt to merge with another `PartiallySignedTransaction`.
@@ -119,6 +309,20 @@ mod display_from_str {
 
     impl ::std::error::Error for PsbtParseError { }
 
+    impl PartiallySignedTransaction {
+        /// Encodes the PSBT in base64, the format BIP174 recommends for
+        /// exchanging PSBTs as text. Same as the `Display` output.
+        pub fn to_base64(&self) -> String {
+            ::base64::encode(&encode::serialize(self))
+        }
+
+        /// Decodes a base64 encoded PSBT. Same as `FromStr`.
+        pub fn from_base64(s: &str) -> Result<Self, PsbtParseError> {
+            let data = ::base64::decode(s).map_err(PsbtParseError::Base64Encoding)?;
+            encode::deserialize(&data).map_err(PsbtParseError::PsbtEncoding)
+        }
+    }
+
     impl Display for PartiallySignedTransaction {
         fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
             write!(f, "{}", Base64Display::with_config(&encode::serialize(self), ::base64::STANDARD))
@@ -129,8 +333,7 @@ mod display_from_str {
         type Err = PsbtParseError;
 
         fn from_str(s: &str) -> Result<Self, Self::Err> {
-            let data = ::base64::decode(s).map_err(PsbtParseError::Base64Encoding)?;
-            Ok(encode::deserialize(&data).map_err(PsbtParseError::PsbtEncoding)?)
+            PartiallySignedTransaction::from_base64(s)
         }
     }
 }
@@ -211,14 +414,14 @@ impl Decodable for PartiallySignedTransaction {
 mod tests {
     use hashes::hex::FromHex;
     use hashes::{sha256, hash160, Hash, ripemd160};
-    use hash_types::Txid;
+    use hash_types::{PubkeyHash, Txid, WPubkeyHash, WScriptHash};
 
     use std::collections::BTreeMap;
 
     use secp256k1::Secp256k1;
 
     use blockdata::script::Script;
-    use blockdata::transaction::{Transaction, TxIn, TxOut, OutPoint};
+    use blockdata::transaction::{Transaction, TxIn, TxOut, OutPoint, Sequence};
     use network::constants::Network::Bitcoin;
     use consensus::encode::{deserialize, serialize, serialize_hex};
     use util::bip32::{ChildNumber, ExtendedPrivKey, ExtendedPubKey, Fingerprint, KeySource};
@@ -226,7 +429,7 @@ mod tests {
     use util::psbt::map::{Global, Output, Input};
     use util::psbt::raw;
 
-    use super::PartiallySignedTransaction;
+    use super::{Error, PartiallySignedTransaction};
     use util::psbt::raw::ProprietaryKey;
 
     #[test]
@@ -253,6 +456,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn unsigned_tx_constraints() {
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                witness: vec![],
+            }],
+            output: vec![],
+        };
+        let psbt = PartiallySignedTransaction::from_unsigned_tx(tx.clone()).unwrap();
+        assert_eq!(psbt.inputs.len(), 1);
+        let decoded: PartiallySignedTransaction = deserialize(&serialize(&psbt)).unwrap();
+        assert_eq!(decoded, psbt);
+
+        tx.input[0].witness = vec![vec![]];
+        assert_eq!(
+            PartiallySignedTransaction::from_unsigned_tx(tx.clone()),
+            Err(Error::UnsignedTxHasScriptWitnesses)
+        );
+        tx.input[0].witness.clear();
+        tx.input[0].script_sig = Script::from(vec![0x51]);
+        assert_eq!(
+            PartiallySignedTransaction::from_unsigned_tx(tx.clone()),
+            Err(Error::UnsignedTxHasScriptSigs)
+        );
+
+        // Deserialization goes through the same checks.
+        let mut raw = serialize(&psbt);
+        // The magic and the unsigned transaction's key are followed by the
+        // length of the transaction, then its version, input count, outpoint
+        // and scriptSig length.
+        let len = 5 + 2;
+        let pos = len + 1 + 4 + 1 + 36;
+        assert_eq!(raw[pos], 0);
+        raw[len] += 1;
+        raw[pos] = 1;
+        raw.insert(pos + 1, 0x51);
+        match deserialize::<PartiallySignedTransaction>(&raw) {
+            Err(::consensus::encode::Error::Psbt(Error::UnsignedTxHasScriptSigs)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
     #[test]
     fn serialize_then_deserialize_output() {
         let secp = &Secp256k1::new();
@@ -311,7 +561,7 @@ mod tests {
                         vout: 0,
                     },
                     script_sig: Script::new(),
-                    sequence: 4294967294,
+                    sequence: Sequence::ENABLE_LOCKTIME_NO_RBF,
                     witness: vec![],
                 }],
                 output: vec![
@@ -379,7 +629,7 @@ mod tests {
                     vout: 1,
                 },
                 script_sig: hex_script!("160014be18d152a9b012039daf3da7de4f53349eecb985"),
-                sequence: 4294967295,
+                sequence: Sequence::MAX,
                 witness: vec![Vec::from_hex("03d2e15674941bad4a996372cb87e1856d3652606d98562fe39c5e9e7e413f2105").unwrap()],
             }],
             output: vec![
@@ -470,7 +720,7 @@ mod tests {
         use hash_types::Txid;
 
         use blockdata::script::Script;
-        use blockdata::transaction::{SigHashType, Transaction, TxIn, TxOut, OutPoint};
+        use blockdata::transaction::{SigHashType, Transaction, TxIn, TxOut, OutPoint, Sequence};
         use consensus::encode::serialize_hex;
         use util::psbt::map::{Map, Global, Input, Output};
         use util::psbt::raw;
@@ -567,7 +817,7 @@ mod tests {
                                 vout: 0,
                             },
                             script_sig: Script::new(),
-                            sequence: 4294967294,
+                            sequence: Sequence::ENABLE_LOCKTIME_NO_RBF,
                             witness: vec![],
                         }],
                         output: vec![
@@ -598,7 +848,7 @@ mod tests {
                                 vout: 1,
                             },
                             script_sig: hex_script!("160014be18d152a9b012039daf3da7de4f53349eecb985"),
-                            sequence: 4294967295,
+                            sequence: Sequence::MAX,
                             witness: vec![
                                 Vec::from_hex("304402202712be22e0270f394f568311dc7ca9a68970b8025fdd3b240229f07f8a5f3a240220018b38d7dcd314e734c9276bd6fb40f673325bc4baa144c800d2f2f02db2765c01").unwrap(),
                                 Vec::from_hex("03d2e15674941bad4a996372cb87e1856d3652606d98562fe39c5e9e7e413f2105").unwrap(),
@@ -612,7 +862,7 @@ mod tests {
                                 vout: 1,
                             },
                             script_sig: hex_script!("160014fe3e9ef1a745e974d902c4355943abcb34bd5353"),
-                            sequence: 4294967295,
+                            sequence: Sequence::MAX,
                             witness: vec![
                                 Vec::from_hex("3045022100d12b852d85dcd961d2f5f4ab660654df6eedcc794c0c33ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e4255b20167c8684031c05d1f2592a01").unwrap(),
                                 Vec::from_hex("0223b72beef0965d10be0778efecd61fcac6f79a4ea169393380734464f84f2ab3").unwrap(),
@@ -651,7 +901,11 @@ mod tests {
                 assert_eq!(PartiallySignedTransaction::from_str(base64str).unwrap(), unserialized);
                 assert_eq!(base64str, unserialized.to_string());
                 assert_eq!(PartiallySignedTransaction::from_str(base64str).unwrap(), hex_psbt!(base16str).unwrap());
+                assert_eq!(PartiallySignedTransaction::from_base64(base64str).unwrap(), unserialized);
+                assert_eq!(unserialized.to_base64(), base64str);
             }
+            assert_eq!(PartiallySignedTransaction::from_hex(base16str).unwrap(), unserialized);
+            assert_eq!(unserialized.to_hex(), base16str);
         }
 
         #[test]
@@ -800,7 +1054,7 @@ mod tests {
                             vout: 0,
                         },
                         script_sig: Script::new(),
-                        sequence: 4294967294,
+                        sequence: Sequence::ENABLE_LOCKTIME_NO_RBF,
                         witness: vec![],
                     }],
                     output: vec![
@@ -831,7 +1085,7 @@ mod tests {
                             vout: 1,
                         },
                         script_sig: hex_script!("160014be18d152a9b012039daf3da7de4f53349eecb985"),
-                        sequence: 4294967295,
+                        sequence: Sequence::MAX,
                         witness: vec![
                             Vec::from_hex("304402202712be22e0270f394f568311dc7ca9a68970b8025fdd3b240229f07f8a5f3a240220018b38d7dcd314e734c9276bd6fb40f673325bc4baa144c800d2f2f02db2765c01").unwrap(),
                             Vec::from_hex("03d2e15674941bad4a996372cb87e1856d3652606d98562fe39c5e9e7e413f2105").unwrap(),
@@ -845,7 +1099,7 @@ mod tests {
                             vout: 1,
                         },
                         script_sig: hex_script!("160014fe3e9ef1a745e974d902c4355943abcb34bd5353"),
-                        sequence: 4294967295,
+                        sequence: Sequence::MAX,
                         witness: vec![
                             Vec::from_hex("3045022100d12b852d85dcd961d2f5f4ab660654df6eedcc794c0c33ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e4255b20167c8684031c05d1f2592a01").unwrap(),
                             Vec::from_hex("0223b72beef0965d10be0778efecd61fcac6f79a4ea169393380734464f84f2ab3").unwrap(),
@@ -902,4 +1156,251 @@ mod tests {
         assert!(!rtt.global.proprietary.is_empty());
     }
 
+    #[test]
+    fn signature_hash_requires_utxos() {
+        use blockdata::transaction::SigHashType;
+        use util::bip143::SigHashCache;
+        use util::psbt::Error;
+
+        // Input 0 spends a P2PKH output given as non-witness UTXO, input 1 a
+        // P2SH-P2WPKH output given as witness UTXO.
+        let psbt: PartiallySignedTransaction = hex_psbt!("70736274ff0100a00200000002ab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40000000000feffffffab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40100000000feffffff02603bea0b000000001976a914768a40bbd740cbe81d988e71de2a4d5c71396b1d88ac8e240000000000001976a9146f4620b553fa095e721b9ee0efe9fa039cca459788ac00000000000100df0200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf6000000006a473044022070b2245123e6bf474d60c5b50c043d4c691a5d2435f09a34a7662a9dc251790a022001329ca9dacf280bdf30740ec0390422422c81cb45839457aeb76fc12edd95b3012102657d118d3357b8e0f4c2cd46db7b39f6d9c38d9a70abcb9b2de5dc8dbfe4ce31feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e13000001012000e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787010416001485d13537f2e265405a34dbafa9e3dda01fb8230800220202ead596687ca806043edc3de116cdf29d5e9257c196cd055cf698c8d02bf24e9910b4a6ba670000008000000080020000800022020394f62be9df19952c5587768aeb7698061ad2c4a25c894f47d8c162b4d7213d0510b4a6ba6700000080010000800200008000").unwrap();
+        let tx = &psbt.global.unsigned_tx;
+
+        let spent = psbt.spent_output(0).unwrap();
+        assert!(spent.script_pubkey.is_p2pkh());
+        assert_eq!(
+            psbt.signature_hash(0, &spent.script_pubkey).unwrap(),
+            tx.signature_hash(0, &spent.script_pubkey, SigHashType::All.as_u32())
+        );
+
+        let spent = psbt.spent_output(1).unwrap();
+        assert_eq!(spent.value, 100_000_000);
+        let wpkh = psbt.inputs[1].redeem_script.as_ref().unwrap();
+        let script_code = Script::new_p2pkh(&PubkeyHash::from_slice(&wpkh[2..]).unwrap());
+        assert_eq!(
+            psbt.signature_hash(1, &script_code).unwrap(),
+            SigHashCache::new(tx).signature_hash(1, &script_code, 100_000_000, SigHashType::All)
+        );
+        assert_eq!(psbt.signature_hash(2, &script_code), Err(Error::InputIndexOutOfBounds(2)));
+
+        // Without the witness UTXO the amount is unknown.
+        let mut missing = psbt.clone();
+        missing.inputs[1].witness_utxo = None;
+        assert_eq!(missing.spent_output(1), Err(Error::MissingUtxo(1)));
+        assert_eq!(missing.signature_hash(1, &script_code), Err(Error::MissingUtxo(1)));
+        // A non-witness UTXO gives the spent output, but segwit still needs the witness UTXO.
+        missing.inputs[1].non_witness_utxo = psbt.inputs[0].non_witness_utxo.clone();
+        assert!(missing.spent_output(1).unwrap().script_pubkey.is_p2sh());
+        assert_eq!(missing.signature_hash(1, &script_code), Err(Error::MissingWitnessUtxo(1)));
+
+        // A non-witness UTXO must be the transaction the input spends from.
+        let mut mismatch = psbt.clone();
+        let mut prev_tx = mismatch.inputs[0].non_witness_utxo.clone().unwrap();
+        let expected = prev_tx.txid();
+        prev_tx.lock_time += 1;
+        let actual = prev_tx.txid();
+        mismatch.inputs[0].non_witness_utxo = Some(prev_tx);
+        let err = Error::NonWitnessUtxoMismatch { index: 0, expected, actual };
+        assert_eq!(mismatch.spent_output(0), Err(err.clone()));
+        assert_eq!(mismatch.signature_hash(0, &script_code), Err(err));
+
+        // A witness UTXO does not make a P2PKH input segwit.
+        let mut legacy = psbt.clone();
+        let p2pkh = psbt.spent_output(0).unwrap().clone();
+        legacy.inputs[0].witness_utxo = Some(p2pkh.clone());
+        assert_eq!(
+            legacy.signature_hash(0, &p2pkh.script_pubkey).unwrap(),
+            tx.signature_hash(0, &p2pkh.script_pubkey, SigHashType::All.as_u32())
+        );
+
+        // Both UTXOs must agree on the spent output.
+        legacy.inputs[0].witness_utxo = psbt.inputs[1].witness_utxo.clone();
+        assert_eq!(legacy.spent_output(0), Err(Error::WitnessUtxoMismatch(0)));
+        assert_eq!(legacy.signature_hash(0, &p2pkh.script_pubkey), Err(Error::WitnessUtxoMismatch(0)));
+    }
+
+    #[test]
+    fn estimated_fee_rate() {
+        use util::amount::FeeRate;
+        use util::psbt::Error;
+
+        let wpkh = Script::new_v0_wpkh(&WPubkeyHash::hash(&[1; 33]));
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: (0..2).map(|vout| TxIn {
+                previous_output: OutPoint::new(Txid::hash(&[2; 32]), vout),
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                witness: vec![],
+            }).collect(),
+            output: vec![TxOut { value: 140_000, script_pubkey: wpkh.clone() }],
+        };
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+        assert_eq!(psbt.estimated_fee_rate(), Err(Error::MissingUtxo(0)));
+        psbt.inputs[0].witness_utxo = Some(TxOut { value: 100_000, script_pubkey: wpkh.clone() });
+        psbt.inputs[1].witness_utxo = Some(TxOut { value: 50_000, script_pubkey: wpkh.clone() });
+
+        // 123 bytes without witnesses, 218 bytes of witness data: 178 vbytes.
+        assert_eq!(psbt.estimated_fee_rate(), Ok(FeeRate::from_fee_and_vsize(10_000, 178)));
+        assert_eq!(psbt.estimated_fee_rate().unwrap().as_sat_per_kvb(), 56179);
+
+        // Finalized inputs count with their actual witness.
+        psbt.inputs[0].final_script_witness = Some(vec![vec![0; 69], vec![0; 33]]);
+        assert_eq!(psbt.estimated_fee_rate(), Ok(FeeRate::from_fee_and_vsize(10_000, 177)));
+
+        let mut unknown = psbt.clone();
+        unknown.inputs[1].witness_utxo.as_mut().unwrap().script_pubkey = Script::new_v0_wsh(&WScriptHash::hash(&[]));
+        assert_eq!(unknown.estimated_fee_rate(), Err(Error::UnknownInputType(1)));
+
+        let mut negative = psbt.clone();
+        negative.global.unsigned_tx.output[0].value = 150_001;
+        assert_eq!(negative.estimated_fee_rate(), Err(Error::NegativeFee));
+
+        // A P2PKH and a P2SH-P2WPKH input: 290 bytes without witnesses, 111
+        // bytes of witness data.
+        let psbt: PartiallySignedTransaction = hex_psbt!("70736274ff0100a00200000002ab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40000000000feffffffab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40100000000feffffff02603bea0b000000001976a914768a40bbd740cbe81d988e71de2a4d5c71396b1d88ac8e240000000000001976a9146f4620b553fa095e721b9ee0efe9fa039cca459788ac00000000000100df0200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf6000000006a473044022070b2245123e6bf474d60c5b50c043d4c691a5d2435f09a34a7662a9dc251790a022001329ca9dacf280bdf30740ec0390422422c81cb45839457aeb76fc12edd95b3012102657d118d3357b8e0f4c2cd46db7b39f6d9c38d9a70abcb9b2de5dc8dbfe4ce31feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e13000001012000e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787010416001485d13537f2e265405a34dbafa9e3dda01fb8230800220202ead596687ca806043edc3de116cdf29d5e9257c196cd055cf698c8d02bf24e9910b4a6ba670000008000000080020000800022020394f62be9df19952c5587768aeb7698061ad2c4a25c894f47d8c162b4d7213d0510b4a6ba6700000080010000800200008000").unwrap();
+        assert_eq!(psbt.estimated_fee_rate(), Ok(FeeRate::from_fee_and_vsize(90_341, 318)));
+    }
+
+    #[test]
+    fn extract_tx() {
+        use util::psbt::Error;
+
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::hash(&[2; 32]), 1),
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                witness: vec![],
+            }],
+            output: vec![TxOut { value: 50_000, script_pubkey: Script::new_v0_wpkh(&WPubkeyHash::hash(&[1; 33])) }],
+        };
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx.clone()).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut { value: 60_000, script_pubkey: tx.output[0].script_pubkey.clone() });
+        assert!(!psbt.is_finalized());
+        assert_eq!(psbt.clone().extract_tx(), Err(Error::NotFinalized(0)));
+
+        let witness = vec![vec![0x30; 71], vec![0x02; 33]];
+        psbt.inputs[0].final_script_witness = Some(witness.clone());
+        assert!(psbt.is_finalized());
+        let extracted = psbt.clone().extract_tx().unwrap();
+        assert_eq!(extracted.txid(), tx.txid());
+        assert_eq!(extracted.input[0].witness, witness);
+        assert_eq!(extracted.input[0].script_sig, Script::new());
+        assert_eq!(extracted.output, tx.output);
+
+        // Inputs missing from the PSBT are not finalized either.
+        let mut missing = psbt.clone();
+        missing.inputs.clear();
+        assert!(!missing.is_finalized());
+        assert_eq!(missing.extract_tx(), Err(Error::NotFinalized(0)));
+    }
+
+    #[test]
+    fn invalid_magic() {
+        use consensus::encode::Error as EncodeError;
+        use hashes::hex;
+        use util::psbt::Error;
+
+        // A bare transaction is not a PSBT.
+        let tx_hex = serialize_hex(&Transaction { version: 2, lock_time: 0, input: vec![], output: vec![] });
+        match PartiallySignedTransaction::from_hex(&tx_hex) {
+            Err(EncodeError::Psbt(Error::InvalidMagic)) => {},
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match PartiallySignedTransaction::from_hex("70736274fe") {
+            Err(EncodeError::Psbt(Error::InvalidSeparator)) => {},
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match PartiallySignedTransaction::from_hex("7073627") {
+            Err(EncodeError::Hex(hex::Error::OddLengthString(7))) => {},
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        #[cfg(feature = "base64")] {
+            use util::psbt::PsbtParseError;
+            match PartiallySignedTransaction::from_base64("cHNidf8=") {
+                Err(PsbtParseError::PsbtEncoding(EncodeError::Psbt(Error::InvalidMagic))) => {},
+                other => panic!("unexpected result: {:?}", other),
+            }
+            match PartiallySignedTransaction::from_base64("cHNidP8!") {
+                Err(PsbtParseError::Base64Encoding(_)) => {},
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn input_accessors() {
+        use util::psbt::Error;
+
+        // Input 1 spends output 1 of the non-witness UTXO of input 0.
+        let psbt: PartiallySignedTransaction = hex_psbt!("70736274ff0100a00200000002ab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40000000000feffffffab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40100000000feffffff02603bea0b000000001976a914768a40bbd740cbe81d988e71de2a4d5c71396b1d88ac8e240000000000001976a9146f4620b553fa095e721b9ee0efe9fa039cca459788ac00000000000100df0200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf6000000006a473044022070b2245123e6bf474d60c5b50c043d4c691a5d2435f09a34a7662a9dc251790a022001329ca9dacf280bdf30740ec0390422422c81cb45839457aeb76fc12edd95b3012102657d118d3357b8e0f4c2cd46db7b39f6d9c38d9a70abcb9b2de5dc8dbfe4ce31feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e13000001012000e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787010416001485d13537f2e265405a34dbafa9e3dda01fb8230800220202ead596687ca806043edc3de116cdf29d5e9257c196cd055cf698c8d02bf24e9910b4a6ba670000008000000080020000800022020394f62be9df19952c5587768aeb7698061ad2c4a25c894f47d8c162b4d7213d0510b4a6ba6700000080010000800200008000").unwrap();
+        let prev_tx = psbt.inputs[0].non_witness_utxo().unwrap().clone();
+        let utxo = psbt.inputs[1].witness_utxo().unwrap().clone();
+        let redeem = psbt.inputs[1].redeem_script().unwrap().clone();
+        assert_eq!(utxo, prev_tx.output[1]);
+        assert!(psbt.inputs[1].witness_script().is_none());
+        assert!(psbt.inputs[1].bip32_derivation().is_empty());
+
+        let mut input = Input::default();
+        input.set_witness_utxo(utxo.clone()).unwrap();
+        input.set_non_witness_utxo(prev_tx.clone()).unwrap();
+        input.set_redeem_script(redeem.clone()).unwrap();
+        assert_eq!(input.non_witness_utxo(), Some(&prev_tx));
+        assert_eq!(input.witness_utxo(), Some(&utxo));
+        assert_eq!(input.redeem_script(), Some(&redeem));
+
+        // A transaction which does not contain the witness UTXO is rejected,
+        // and so is a witness UTXO which is not an output of the transaction.
+        let mut other_tx = prev_tx.clone();
+        other_tx.output[1].value += 1;
+        let before = input.clone();
+        assert_eq!(input.set_non_witness_utxo(other_tx.clone()), Err(Error::ConflictingField("non_witness_utxo")));
+        assert_eq!(input.set_witness_utxo(other_tx.output[1].clone()), Err(Error::ConflictingField("witness_utxo")));
+        // The redeem script must hash to the P2SH script pubkey.
+        assert_eq!(input.set_redeem_script(Script::new()), Err(Error::ConflictingField("redeem_script")));
+        assert_eq!(input, before);
+
+        // P2SH-P2WSH: the witness script must hash to the redeem script.
+        let witness = Script::from(vec![0x51]);
+        let mut input = Input::default();
+        input.set_redeem_script(witness.to_v0_p2wsh()).unwrap();
+        assert_eq!(input.set_witness_script(Script::from(vec![0x52])), Err(Error::ConflictingField("witness_script")));
+        input.set_witness_script(witness.clone()).unwrap();
+        assert_eq!(input.witness_script(), Some(&witness));
+        let p2sh_p2wsh = TxOut { value: 1, script_pubkey: witness.to_v0_p2wsh().to_p2sh() };
+        input.set_witness_utxo(p2sh_p2wsh).unwrap();
+
+        // Native P2WSH: the witness script must hash to the script pubkey.
+        let mut input = Input::default();
+        input.set_witness_utxo(TxOut { value: 1, script_pubkey: witness.to_v0_p2wsh() }).unwrap();
+        assert_eq!(input.set_witness_script(Script::from(vec![0x52])), Err(Error::ConflictingField("witness_script")));
+        input.set_witness_script(witness).unwrap();
+    }
+
+    #[test]
+    fn output_accessors() {
+        use util::psbt::Error;
+
+        let witness = Script::from(vec![0x51]);
+        let mut output = Output::default();
+        output.set_witness_script(witness.clone()).unwrap();
+        assert_eq!(output.set_redeem_script(Script::from(vec![0x52]).to_v0_p2wsh()), Err(Error::ConflictingField("redeem_script")));
+        assert_eq!(output.redeem_script(), None);
+        output.set_redeem_script(witness.to_v0_p2wsh()).unwrap();
+        assert_eq!(output.set_witness_script(Script::from(vec![0x52])), Err(Error::ConflictingField("witness_script")));
+        assert_eq!(output.witness_script(), Some(&witness));
+        assert_eq!(output.redeem_script(), Some(&witness.to_v0_p2wsh()));
+        assert!(output.bip32_derivation().is_empty());
+
+        // Without a P2WSH redeem script any witness script is accepted.
+        let mut output = Output::default();
+        output.set_redeem_script(Script::from(vec![0x51])).unwrap();
+        output.set_witness_script(Script::from(vec![0x52])).unwrap();
+    }
 }
@@ -21,8 +21,8 @@ use std::io;
 
 use blockdata::script::Script;
 use blockdata::transaction::{SigHashType, Transaction, TxOut};
-use consensus::encode::{self, serialize, Decodable};
-use util::bip32::{ChildNumber, Fingerprint, KeySource};
+use consensus::encode::{self, serialize};
+use util::bip32::{DerivationPath, Fingerprint, KeySource};
 use hashes::{hash160, ripemd160, sha256, sha256d, Hash};
 use util::key::PublicKey;
 use util::psbt;
@@ -77,14 +77,8 @@ impl Deserialize for PublicKey {
 
 impl Serialize for KeySource {
     fn serialize(&self) -> Vec<u8> {
-        let mut rv: Vec<u8> = Vec::with_capacity(4 + 4 * (self.1).as_ref().len());
-
-        rv.append(&mut self.0.to_bytes().to_vec());
-
-        for cnum in self.1.into_iter() {
-            rv.append(&mut serialize(&u32::from(*cnum)))
-        }
-
+        let mut rv = serialize(&self.0);
+        rv.extend(self.1.to_consensus_bytes());
         rv
     }
 }
@@ -95,18 +89,11 @@ impl Deserialize for KeySource {
             return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
         }
 
-        let fprint: Fingerprint = Fingerprint::from(&bytes[0..4]);
-        let mut dpath: Vec<ChildNumber> = Default::default();
-
-        let mut d = &bytes[4..];
-        while !d.is_empty() {
-            match u32::consensus_decode(&mut d) {
-                Ok(index) => dpath.push(index.into()),
-                Err(e) => return Err(e),
-            }
-        }
+        let fprint: Fingerprint = encode::deserialize(&bytes[0..4])?;
+        let dpath = DerivationPath::from_consensus_bytes(&bytes[4..])
+            .map_err(|_| io::Error::from(io::ErrorKind::UnexpectedEof))?;
 
-        Ok((fprint, dpath.into()))
+        Ok((fprint, dpath))
     }
 }
 
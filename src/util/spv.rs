@@ -0,0 +1,219 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! SPV request tracking
+//!
+//! Bookkeeping for the `getdata` requests of an SPV client: the replies
+//! (`block`, `tx`, `merkleblock` and `notfound` messages) are matched against
+//! the inventory which was asked for, and requests which were not answered in
+//! time are reported so they can be sent to another peer.
+//!
+
+use std::collections::HashMap;
+
+use hashes::Hash;
+use hash_types::BlockHash;
+use network::message::NetworkMessage;
+use network::message_blockdata::Inventory;
+use util::merkleblock::MerkleBlock;
+
+/// Inventory type of a block requested as a `merkleblock` (BIP37).
+pub const MSG_FILTERED_BLOCK: u32 = 3;
+/// Inventory type of a block requested as a `merkleblock`, whose matched
+/// transactions are sent with their witnesses.
+pub const MSG_FILTERED_WITNESS_BLOCK: u32 = 0x40000003;
+
+/// Returns the inventory item requesting the block with the given hash as a
+/// `merkleblock`.
+///
+/// `Inventory` has no variant for this type, so it is represented as
+/// `Inventory::Unknown`.
+pub fn filtered_block_inventory(hash: BlockHash) -> Inventory {
+    Inventory::Unknown { inv_type: MSG_FILTERED_BLOCK, hash: hash.into_inner() }
+}
+
+/// Tracks inventory requested from a peer until it is received.
+#[derive(Debug, Clone)]
+pub struct RequestTracker {
+    /// Seconds after which an unanswered request times out.
+    timeout: u32,
+    /// Pending requests and the time they were made at.
+    pending: HashMap<Inventory, u32>,
+}
+
+impl RequestTracker {
+    /// Creates a tracker whose requests time out after `timeout` seconds.
+    pub fn new(timeout: u32) -> RequestTracker {
+        RequestTracker {
+            timeout,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Records `inventory` as requested at `now` and returns the `getdata`
+    /// message to send for it.
+    ///
+    /// Items which are already pending keep their original request time.
+    pub fn get_data(&mut self, inventory: Vec<Inventory>, now: u32) -> NetworkMessage {
+        for inv in &inventory {
+            self.pending.entry(*inv).or_insert(now);
+        }
+        NetworkMessage::GetData(inventory)
+    }
+
+    /// Matches a message received from the peer against the pending requests,
+    /// and returns the requests it answered.
+    ///
+    /// `block` and `tx` messages answer the requests for that block or
+    /// transaction, with or without witness. `notfound` messages answer the
+    /// listed requests negatively. Anything else, including unsolicited
+    /// blocks and transactions, answers nothing.
+    pub fn receive(&mut self, message: &NetworkMessage) -> Vec<Inventory> {
+        let candidates = match *message {
+            NetworkMessage::Block(ref block) => {
+                let hash = block.block_hash();
+                vec![Inventory::Block(hash), Inventory::WitnessBlock(hash)]
+            }
+            NetworkMessage::Tx(ref tx) => {
+                let txid = tx.txid();
+                vec![
+                    Inventory::Transaction(txid),
+                    Inventory::WitnessTransaction(txid),
+                    Inventory::WTx(tx.wtxid()),
+                ]
+            }
+            NetworkMessage::NotFound(ref inventory) => inventory.clone(),
+            _ => vec![],
+        };
+        self.take(candidates)
+    }
+
+    /// Matches a received `merkleblock` against the pending requests for
+    /// filtered blocks, and returns the requests it answered.
+    ///
+    /// [`NetworkMessage`] has no variant for this message, so it is passed
+    /// separately from [`receive`].
+    ///
+    /// [`NetworkMessage`]: ../../network/message/enum.NetworkMessage.html
+    /// [`receive`]: #method.receive
+    pub fn receive_merkle_block(&mut self, merkle_block: &MerkleBlock) -> Vec<Inventory> {
+        let hash = merkle_block.header.block_hash().into_inner();
+        self.take(vec![
+            Inventory::Unknown { inv_type: MSG_FILTERED_BLOCK, hash },
+            Inventory::Unknown { inv_type: MSG_FILTERED_WITNESS_BLOCK, hash },
+        ])
+    }
+
+    /// Removes and returns the requests made more than the timeout before
+    /// `now`, in the order they were made.
+    pub fn expire(&mut self, now: u32) -> Vec<Inventory> {
+        let timeout = self.timeout;
+        let mut expired: Vec<(Inventory, u32)> = self.pending.iter()
+            .filter(|&(_, &time)| now.saturating_sub(time) > timeout)
+            .map(|(inv, time)| (*inv, *time))
+            .collect();
+        expired.sort_by_key(|&(inv, time)| (time, inv));
+        for (inv, _) in &expired {
+            self.pending.remove(inv);
+        }
+        expired.into_iter().map(|(inv, _)| inv).collect()
+    }
+
+    /// Checks whether `inventory` was requested and not yet answered.
+    pub fn is_pending(&self, inventory: &Inventory) -> bool {
+        self.pending.contains_key(inventory)
+    }
+
+    /// Returns the number of unanswered requests.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Checks whether all requests were answered.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    fn take(&mut self, candidates: Vec<Inventory>) -> Vec<Inventory> {
+        candidates.into_iter().filter(|inv| self.pending.remove(inv).is_some()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{filtered_block_inventory, RequestTracker};
+
+    use blockdata::constants::genesis_block;
+    use network::constants::Network;
+    use network::message::NetworkMessage;
+    use network::message_blockdata::Inventory;
+    use util::merkleblock::MerkleBlock;
+
+    #[test]
+    fn fulfilled_requests_clear() {
+        let block = genesis_block(Network::Bitcoin);
+        let tx = block.txdata[0].clone();
+        let block_inv = Inventory::WitnessBlock(block.block_hash());
+        let tx_inv = Inventory::Transaction(tx.txid());
+
+        let mut tracker = RequestTracker::new(30);
+        let message = tracker.get_data(vec![block_inv, tx_inv], 100);
+        assert_eq!(message, NetworkMessage::GetData(vec![block_inv, tx_inv]));
+        assert_eq!(tracker.len(), 2);
+
+        assert_eq!(tracker.receive(&NetworkMessage::Block(block.clone())), vec![block_inv]);
+        assert!(!tracker.is_pending(&block_inv));
+        assert!(tracker.is_pending(&tx_inv));
+        // A second copy is unsolicited.
+        assert_eq!(tracker.receive(&NetworkMessage::Block(block.clone())), vec![]);
+
+        assert_eq!(tracker.receive(&NetworkMessage::Tx(tx)), vec![tx_inv]);
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn merkle_block_and_not_found() {
+        let block = genesis_block(Network::Bitcoin);
+        let filtered = filtered_block_inventory(block.block_hash());
+        let missing = Inventory::Block(Default::default());
+
+        let mut tracker = RequestTracker::new(30);
+        tracker.get_data(vec![filtered, missing], 100);
+        // A full block does not answer a request for a filtered one.
+        assert_eq!(tracker.receive(&NetworkMessage::Block(block.clone())), vec![]);
+
+        let txid = block.txdata[0].txid();
+        let merkle_block = MerkleBlock::from_block(&block, &[txid].iter().cloned().collect());
+        assert_eq!(tracker.receive_merkle_block(&merkle_block), vec![filtered]);
+
+        assert_eq!(tracker.receive(&NetworkMessage::NotFound(vec![missing])), vec![missing]);
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn timeouts() {
+        let first = Inventory::Block(Default::default());
+        let second = Inventory::Transaction(Default::default());
+
+        let mut tracker = RequestTracker::new(30);
+        tracker.get_data(vec![first], 100);
+        tracker.get_data(vec![second, first], 120);
+
+        assert_eq!(tracker.expire(130), vec![]);
+        // The repeated request kept its original time.
+        assert_eq!(tracker.expire(131), vec![first]);
+        assert_eq!(tracker.len(), 1);
+        assert_eq!(tracker.expire(151), vec![second]);
+        assert!(tracker.is_empty());
+    }
+}
@@ -0,0 +1,86 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Test utilities
+//!
+//! Helpers for the tests of this library and of downstream crates, available
+//! with the `test-utils` feature.
+//!
+
+use blockdata::block::{Block, BlockHeader};
+use blockdata::constants::genesis_block;
+use blockdata::opcodes;
+use blockdata::script::Builder;
+use consensus::params::Params;
+use network::constants::Network;
+use util::mempool::assemble_block_template;
+
+/// Mines a regtest chain of `n` blocks on top of the genesis block, which is
+/// not included.
+///
+/// Each block only contains a coinbase, which commits to its height and pays
+/// the block subsidy to an `OP_TRUE` output, so that the coins can be spent
+/// without signatures. Blocks are ten minutes apart.
+pub fn build_regtest_chain(n: usize) -> Vec<Block> {
+    let params = Params::new(Network::Regtest);
+    let coinbase_script_pubkey = Builder::new().push_opcode(opcodes::OP_TRUE).into_script();
+    let mut prev = genesis_block(Network::Regtest).header;
+    let mut chain = Vec::with_capacity(n);
+    for height in 1..=n as u32 {
+        let header_base = BlockHeader {
+            version: 4,
+            prev_blockhash: prev.block_hash(),
+            merkle_root: Default::default(),
+            time: prev.time + 600,
+            bits: prev.bits,
+            nonce: 0,
+        };
+        let mut block = assemble_block_template(header_base, height, coinbase_script_pubkey.clone(), &[], &params);
+        while !block.header.target().is_met_by(block.block_hash()) {
+            block.header.nonce += 1;
+        }
+        prev = block.header;
+        chain.push(block);
+    }
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::build_regtest_chain;
+
+    use blockdata::constants::genesis_block;
+    use consensus::params::Params;
+    use consensus::validation::BlockValidator;
+    use network::constants::Network;
+
+    #[test]
+    fn regtest_chain() {
+        let mut params = Params::new(Network::Regtest);
+        // Also check the coinbase heights.
+        params.bip34_height = 1;
+
+        let chain = build_regtest_chain(20);
+        assert_eq!(chain.len(), 20);
+        let mut prev = genesis_block(Network::Regtest).header;
+        for (i, block) in chain.iter().enumerate() {
+            let validator = BlockValidator::new(prev.time, block.header.time);
+            assert_eq!(validator.check_block(block, &prev, i as u32 + 1, &params), Ok(()));
+            assert_eq!(block.txdata.len(), 1);
+            assert_eq!(block.txdata[0].output[0].value, params.block_subsidy(i as u32 + 1));
+            prev = block.header;
+        }
+
+        assert!(build_regtest_chain(0).is_empty());
+    }
+}
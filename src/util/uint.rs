@@ -127,6 +127,29 @@ macro_rules! construct_uint {
                 res
             }
 
+            /// Parses a big integer from a hex string of exactly
+            /// `2 * 8 * words` characters, most significant byte first.
+            ///
+            /// This is the order used by Bitcoin Core's JSON-RPC for fields
+            /// such as `chainwork`, and is unrelated to the little-endian
+            /// order of the internal `u64` limbs or of the consensus encoding.
+            pub fn from_be_hex(s: &str) -> Result<$name, $crate::hashes::hex::Error> {
+                use $crate::hashes::hex::{Error, FromHex};
+                if s.len() != $n_words * 16 {
+                    return Err(Error::InvalidLength($n_words * 16, s.len()));
+                }
+                let bytes = Vec::from_hex(s)?;
+                Ok(Self::_from_be_slice(&bytes))
+            }
+
+            /// Formats a big integer as a zero-padded lowercase hex string,
+            /// most significant byte first. Inverse of
+            /// [`from_be_hex`](#method.from_be_hex).
+            pub fn to_be_hex(&self) -> String {
+                use $crate::hashes::hex::ToHex;
+                self.to_be_bytes().to_hex()
+            }
+
             // divmod like operation, returns (quotient, remainder)
             #[inline]
             fn div_rem(self, other: Self) -> (Self, Self) {
@@ -399,7 +422,77 @@ macro_rules! construct_uint {
             }
         }
 
-        display_from_debug!($name);
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                // Peel off 19 decimal digits at a time, the most that fit in a u64
+                const TEN_19: u128 = 10_000_000_000_000_000_000;
+                let mut words = self.0;
+                let mut chunks = vec![];
+                loop {
+                    let mut rem = 0u128;
+                    for word in words.iter_mut().rev() {
+                        let cur = (rem << 64) | *word as u128;
+                        *word = (cur / TEN_19) as u64;
+                        rem = cur % TEN_19;
+                    }
+                    chunks.push(rem as u64);
+                    if words.iter().all(|w| *w == 0) {
+                        break;
+                    }
+                }
+                let mut s = String::new();
+                let mut iter = chunks.iter().rev();
+                s.push_str(&iter.next().unwrap().to_string());
+                for chunk in iter {
+                    s.push_str(&format!("{:019}", chunk));
+                }
+                f.pad_integral(true, "", &s)
+            }
+        }
+
+        impl ::std::fmt::LowerHex for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                let mut s = String::with_capacity($n_words * 16);
+                for word in self.0.iter().rev() {
+                    s.push_str(&format!("{:016x}", word));
+                }
+                f.pad_integral(true, "0x", &s)
+            }
+        }
+
+        impl ::std::fmt::UpperHex for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                let mut s = String::with_capacity($n_words * 16);
+                for word in self.0.iter().rev() {
+                    s.push_str(&format!("{:016X}", word));
+                }
+                f.pad_integral(true, "0x", &s)
+            }
+        }
+
+        impl ::std::str::FromStr for $name {
+            type Err = $crate::hashes::hex::Error;
+
+            /// Parses a big-endian hex string, with or without a `0x` prefix.
+            /// Strings shorter than the full width are zero-extended.
+            fn from_str(s: &str) -> Result<$name, Self::Err> {
+                use $crate::hashes::hex::Error;
+                let digits = if s.starts_with("0x") || s.starts_with("0X") {
+                    &s[2..]
+                } else {
+                    s
+                };
+                if digits.is_empty() || digits.len() > $n_words * 16 {
+                    return Err(Error::InvalidLength($n_words * 16, digits.len()));
+                }
+                let mut ret = [0u64; $n_words];
+                for (i, &ch) in digits.as_bytes().iter().rev().enumerate() {
+                    let nibble = (ch as char).to_digit(16).ok_or(Error::InvalidChar(ch))?;
+                    ret[i / 16] |= (nibble as u64) << ((i % 16) * 4);
+                }
+                Ok($name(ret))
+            }
+        }
 
         impl $crate::consensus::Encodable for $name {
             #[inline]
@@ -534,11 +627,34 @@ impl Uint256 {
         let &Uint256(data) = self;
         Uint128([data[0], data[1]])
     }
+
+    /// Converts to the nearest `f64`. Every `Uint256` is in range, so this
+    /// never overflows.
+    pub fn to_f64(&self) -> f64 {
+        let bits = self.bits();
+        if bits <= 64 {
+            return self.low_u64() as f64;
+        }
+        // Keep the top 64 bits, folding any bits shifted out into the lowest
+        // one so the conversion below still rounds correctly.
+        let shift = bits - 64;
+        let mut top = (*self >> shift).low_u64();
+        if *self << (256 - shift) != Uint256::default() {
+            top |= 1;
+        }
+        top as f64 * 2f64.powi(shift as i32)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
+
+    use blockdata::constants::genesis_block;
     use consensus::{deserialize, serialize};
+    use consensus::params::Params;
+    use hashes::hex;
+    use network::constants::Network;
     use util::uint::{Uint256, Uint128};
     use util::BitArray;
 
@@ -568,18 +684,100 @@ mod tests {
     }
 
     #[test]
-    pub fn uint256_display_test() {
-        assert_eq!(format!("{}", Uint256::from_u64(0xDEADBEEF).unwrap()),
+    pub fn uint256_to_f64_test() {
+        assert_eq!(Uint256::default().to_f64(), 0.0);
+        assert_eq!(Uint256::from_u64(12345).unwrap().to_f64(), 12345.0);
+        assert_eq!(Uint256::from_u64(u64::max_value()).unwrap().to_f64(), 18446744073709551616.0);
+        assert_eq!((Uint256::from_u64(1).unwrap() << 255).to_f64(), 2f64.powi(255));
+        assert_eq!(Uint256([!0; 4]).to_f64(), 2f64.powi(256));
+
+        // 2**53 + 1 is halfway between two floats and rounds to even, unless
+        // any lower bit tips it over.
+        let halfway = Uint256::from_u64((1 << 53) + 1).unwrap() << 100;
+        assert_eq!(halfway.to_f64(), 2f64.powi(153));
+        let mut above = halfway;
+        above.increment();
+        assert_eq!(above.to_f64(), (2f64.powi(53) + 2.0) * 2f64.powi(100));
+
+        let params = Params::new(Network::Bitcoin);
+        assert_eq!(params.pow_limit.to_f64(), 65535.0 * 2f64.powi(208));
+    }
+
+    #[test]
+    pub fn uint256_debug_test() {
+        assert_eq!(format!("{:?}", Uint256::from_u64(0xDEADBEEF).unwrap()),
                    "0x00000000000000000000000000000000000000000000000000000000deadbeef");
-        assert_eq!(format!("{}", Uint256::from_u64(u64::max_value()).unwrap()),
+        assert_eq!(format!("{:?}", Uint256::from_u64(u64::max_value()).unwrap()),
                    "0x000000000000000000000000000000000000000000000000ffffffffffffffff");
 
         let max_val = Uint256([0xFFFFFFFFFFFFFFFF, 0xFFFFFFFFFFFFFFFF, 0xFFFFFFFFFFFFFFFF,
                                0xFFFFFFFFFFFFFFFF]);
-        assert_eq!(format!("{}", max_val),
+        assert_eq!(format!("{:?}", max_val),
                    "0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff");
     }
 
+    #[test]
+    pub fn uint256_display_test() {
+        assert_eq!(format!("{}", Uint256::from_u64(0).unwrap()), "0");
+        assert_eq!(format!("{}", Uint256::from_u64(0xDEADBEEF).unwrap()), "3735928559");
+        assert_eq!(format!("{:>12}", Uint256::from_u64(0xDEADBEEF).unwrap()), "  3735928559");
+
+        let max_val = Uint256([0xFFFFFFFFFFFFFFFF, 0xFFFFFFFFFFFFFFFF, 0xFFFFFFFFFFFFFFFF,
+                               0xFFFFFFFFFFFFFFFF]);
+        let max_dec = "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+        assert_eq!(format!("{}", max_val), max_dec);
+
+        // Decimal round-trip via repeated multiply-and-add
+        let ten = Uint256::from_u64(10).unwrap();
+        let parsed = max_dec.bytes().fold(Uint256::from_u64(0).unwrap(), |acc, b| {
+            acc * ten + Uint256::from_u64((b - b'0') as u64).unwrap()
+        });
+        assert_eq!(parsed, max_val);
+
+        let limb_boundary = Uint256([0, 1, 0, 0]);
+        assert_eq!(format!("{}", limb_boundary), "18446744073709551616");
+    }
+
+    #[test]
+    pub fn uint256_hex_test() {
+        let pow_limit = Params::new(Network::Bitcoin).pow_limit;
+        assert_eq!(format!("{:x}", pow_limit),
+                   "00000000ffff0000000000000000000000000000000000000000000000000000");
+        assert_eq!(format!("{:#x}", pow_limit),
+                   "0x00000000ffff0000000000000000000000000000000000000000000000000000");
+        assert_eq!(format!("{:X}", Params::new(Network::Regtest).pow_limit),
+                   "7FFFFF0000000000000000000000000000000000000000000000000000000000");
+
+        assert_eq!(Uint256::from_str(&format!("{:x}", pow_limit)).unwrap(), pow_limit);
+        assert_eq!(Uint256::from_str(&format!("{:#x}", pow_limit)).unwrap(), pow_limit);
+        assert_eq!(Uint256::from_str("0xDEADbeef").unwrap(), Uint256::from_u64(0xDEADBEEF).unwrap());
+        assert_eq!(Uint128::from_str("1").unwrap(), Uint128::from_u64(1).unwrap());
+
+        assert_eq!(Uint256::from_str(""), Err(hex::Error::InvalidLength(64, 0)));
+        assert_eq!(Uint256::from_str("0x"), Err(hex::Error::InvalidLength(64, 0)));
+        assert_eq!(Uint256::from_str(&"0".repeat(65)), Err(hex::Error::InvalidLength(64, 65)));
+        assert_eq!(Uint256::from_str("0xg1"), Err(hex::Error::InvalidChar(b'g')));
+    }
+
+    #[test]
+    pub fn uint256_be_hex_test() {
+        // `chainwork` of the mainnet genesis block as reported by `getblockheader`
+        let chainwork = "0000000000000000000000000000000000000000000000000000000100010001";
+        let work = Uint256::from_be_hex(chainwork).unwrap();
+        assert_eq!(work, genesis_block(Network::Bitcoin).header.work());
+        assert_eq!(work, Uint256([0x100010001, 0, 0, 0]));
+        assert_eq!(work.to_be_hex(), chainwork);
+
+        let be = "1badcafedeadbeefdeafbabe2bedfeed";
+        let val = Uint128::from_be_hex(be).unwrap();
+        assert_eq!(val, Uint128([0xdeafbabe2bedfeed, 0x1badcafedeadbeef]));
+        assert_eq!(val.to_be_hex(), be);
+
+        assert_eq!(Uint256::from_be_hex("0x01"), Err(hex::Error::InvalidLength(64, 4)));
+        assert_eq!(Uint256::from_be_hex(&format!("0x{}", &chainwork[2..])),
+                   Err(hex::Error::InvalidChar(b'x')));
+    }
+
     #[test]
     pub fn uint256_comp_test() {
         let small = Uint256([10u64, 0, 0, 0]);
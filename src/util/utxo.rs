@@ -0,0 +1,355 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! UTXO set
+//!
+//! A minimal in-memory set of unspent transaction outputs, updated by
+//! connecting blocks in order. It is meant for simple indexers and does not
+//! perform any script, amount or proof-of-work validation.
+//!
+
+use std::collections::{HashMap, HashSet};
+use std::{error, fmt};
+
+use blockdata::block::Block;
+use blockdata::transaction::{OutPoint, TxOut};
+use consensus::params::Params;
+
+/// An error that might occur when connecting a block to a [`UtxoSet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// An input spends an output which is not in the set.
+    MissingInput(OutPoint),
+    /// An output is spent twice within the same block.
+    DoubleSpend(OutPoint),
+    /// A coinbase output is spent before it reached maturity.
+    ImmatureCoinbase(OutPoint),
+    /// The undo data does not have an entry for every input of the block.
+    UndoMismatch,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::MissingInput(ref op) => write!(f, "input {} spends an unknown output", op),
+            Error::DoubleSpend(ref op) => write!(f, "output {} spent twice in block", op),
+            Error::ImmatureCoinbase(ref op) => write!(f, "coinbase output {} spent before maturity", op),
+            Error::UndoMismatch => f.write_str("undo data does not match block"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// An unspent output together with the data needed to check its spendability.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Utxo {
+    /// The unspent output.
+    pub txout: TxOut,
+    /// Height of the block which created the output.
+    pub height: u32,
+    /// Whether the output was created by a coinbase transaction.
+    pub is_coinbase: bool,
+}
+
+/// The outputs spent by a block, which are needed to disconnect it again.
+///
+/// Unlike a plain list of [`TxOut`]s, the entries keep the height and
+/// coinbase flag of the spent outputs, so that disconnecting a block
+/// restores the set exactly.
+///
+/// [`TxOut`]: ../../blockdata/transaction/struct.TxOut.html
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BlockUndo {
+    /// For every transaction but the coinbase, the outputs spent by its
+    /// inputs, in input order.
+    pub spent: Vec<Vec<Utxo>>,
+}
+
+/// In-memory set of unspent transaction outputs.
+///
+/// Blocks are connected one at a time with [`UtxoSet::apply_block`], or with
+/// [`UtxoSet::apply_block_with_undo`] if they may need to be disconnected in
+/// a reorg. Note that unlike Bitcoin Core, the outputs of the genesis
+/// coinbase are added to the set like any other.
+#[derive(Debug, Clone)]
+pub struct UtxoSet {
+    params: Params,
+    utxos: HashMap<OutPoint, Utxo>,
+}
+
+impl UtxoSet {
+    /// Creates an empty UTXO set for the chain described by `params`.
+    pub fn new(params: Params) -> UtxoSet {
+        UtxoSet {
+            params,
+            utxos: HashMap::new(),
+        }
+    }
+
+    /// Gets the unspent output at `outpoint`, if any.
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&TxOut> {
+        self.utxos.get(outpoint).map(|utxo| &utxo.txout)
+    }
+
+    /// Gets the unspent output at `outpoint` along with its creation height
+    /// and coinbase flag.
+    pub fn get_utxo(&self, outpoint: &OutPoint) -> Option<&Utxo> {
+        self.utxos.get(outpoint)
+    }
+
+    /// Returns the number of unspent outputs in the set.
+    pub fn len(&self) -> usize {
+        self.utxos.len()
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.utxos.is_empty()
+    }
+
+    /// Connects `block` at `height`: removes the outputs spent by its
+    /// transactions and adds the outputs they create.
+    ///
+    /// Transactions may spend outputs created earlier in the same block. The
+    /// block is rejected if an input spends an unknown output, if an output
+    /// is spent twice, or if a coinbase output is spent before
+    /// [`Params::coinbase_maturity`] blocks have been built on top of it. On
+    /// error the set is left unchanged.
+    ///
+    /// [`Params::coinbase_maturity`]: ../../consensus/params/struct.Params.html#structfield.coinbase_maturity
+    pub fn apply_block(&mut self, block: &Block, height: u32) -> Result<(), Error> {
+        self.apply_block_with_undo(block, height).map(|_| ())
+    }
+
+    /// Connects `block` at `height` like [`UtxoSet::apply_block`], and
+    /// returns the outputs it spent so it can be disconnected again with
+    /// [`UtxoSet::undo_block`].
+    pub fn apply_block_with_undo(&mut self, block: &Block, height: u32) -> Result<BlockUndo, Error> {
+        let mut spent = HashSet::new();
+        let mut created = HashMap::new();
+        let mut undo = BlockUndo::default();
+
+        for tx in &block.txdata {
+            let is_coinbase = tx.is_coin_base();
+            if !is_coinbase {
+                let mut tx_undo = Vec::with_capacity(tx.input.len());
+                for input in &tx.input {
+                    let prevout = input.previous_output;
+                    if !spent.insert(prevout) {
+                        return Err(Error::DoubleSpend(prevout));
+                    }
+                    let utxo = match created.get(&prevout).or_else(|| self.utxos.get(&prevout)) {
+                        Some(utxo) => utxo,
+                        None => return Err(Error::MissingInput(prevout)),
+                    };
+                    if utxo.is_coinbase && height.saturating_sub(utxo.height) < self.params.coinbase_maturity {
+                        return Err(Error::ImmatureCoinbase(prevout));
+                    }
+                    tx_undo.push(utxo.clone());
+                }
+                undo.spent.push(tx_undo);
+            }
+
+            let txid = tx.txid();
+            for (vout, txout) in tx.output.iter().enumerate() {
+                let utxo = Utxo {
+                    txout: txout.clone(),
+                    height,
+                    is_coinbase,
+                };
+                created.insert(OutPoint::new(txid, vout as u32), utxo);
+            }
+        }
+
+        for prevout in &spent {
+            if created.remove(prevout).is_none() {
+                self.utxos.remove(prevout);
+            }
+        }
+        self.utxos.extend(created);
+        Ok(undo)
+    }
+
+    /// Disconnects `block`, which must be the last block connected, using
+    /// the undo data returned when it was connected: removes the outputs it
+    /// created and restores the outputs it spent.
+    ///
+    /// On error the set is left unchanged.
+    pub fn undo_block(&mut self, block: &Block, undo: &BlockUndo) -> Result<(), Error> {
+        let spending = block.txdata.iter().filter(|tx| !tx.is_coin_base());
+        if spending.clone().count() != undo.spent.len()
+            || spending.zip(&undo.spent).any(|(tx, tx_undo)| tx.input.len() != tx_undo.len()) {
+            return Err(Error::UndoMismatch);
+        }
+
+        // Go backwards so that outputs spent within the block are restored
+        // before the transaction which created them is disconnected.
+        let mut tx_undos = undo.spent.iter().rev();
+        for tx in block.txdata.iter().rev() {
+            let txid = tx.txid();
+            for vout in 0..tx.output.len() {
+                self.utxos.remove(&OutPoint::new(txid, vout as u32));
+            }
+            if !tx.is_coin_base() {
+                let tx_undo = tx_undos.next().expect("checked above");
+                for (input, utxo) in tx.input.iter().zip(tx_undo) {
+                    self.utxos.insert(input.previous_output, utxo.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockUndo, Error, UtxoSet};
+
+    use blockdata::block::Block;
+    use blockdata::constants::genesis_block;
+    use blockdata::script::Script;
+    use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut, Sequence};
+    use consensus::params::Params;
+    use network::constants::Network;
+
+    fn spend(prevouts: &[OutPoint], values: &[u64]) -> Transaction {
+        Transaction {
+            version: 1,
+            lock_time: 0,
+            input: prevouts.iter().map(|prevout| TxIn {
+                previous_output: *prevout,
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                witness: vec![],
+            }).collect(),
+            output: values.iter().map(|value| TxOut {
+                value: *value,
+                script_pubkey: Script::new(),
+            }).collect(),
+        }
+    }
+
+    fn block_with(txdata: Vec<Transaction>) -> Block {
+        let genesis = genesis_block(Network::Regtest);
+        let mut coinbase = genesis.txdata[0].clone();
+        // Make the coinbase unique so its outputs don't collide with genesis.
+        coinbase.lock_time = txdata.len() as u32 + 1;
+        let mut all = vec![coinbase];
+        all.extend(txdata);
+        Block {
+            header: genesis.header,
+            txdata: all,
+        }
+    }
+
+    #[test]
+    fn apply_genesis_and_spend() {
+        let genesis = genesis_block(Network::Regtest);
+        let genesis_out = OutPoint::new(genesis.txdata[0].txid(), 0);
+
+        let mut utxos = UtxoSet::new(Params::new(Network::Regtest));
+        assert!(utxos.is_empty());
+        utxos.apply_block(&genesis, 0).unwrap();
+        assert_eq!(utxos.len(), 1);
+        assert_eq!(utxos.get(&genesis_out), Some(&genesis.txdata[0].output[0]));
+        assert!(utxos.get_utxo(&genesis_out).unwrap().is_coinbase);
+
+        // Spend the genesis coinbase, and the resulting output again in the same block.
+        let tx1 = spend(&[genesis_out], &[30_0000_0000, 20_0000_0000]);
+        let tx1_out0 = OutPoint::new(tx1.txid(), 0);
+        let tx1_out1 = OutPoint::new(tx1.txid(), 1);
+        let tx2 = spend(&[tx1_out0], &[29_0000_0000]);
+        let tx2_out = OutPoint::new(tx2.txid(), 0);
+        let block = block_with(vec![tx1, tx2]);
+        let coinbase_out = OutPoint::new(block.txdata[0].txid(), 0);
+
+        // Not mature yet.
+        assert_eq!(utxos.apply_block(&block, 99), Err(Error::ImmatureCoinbase(genesis_out)));
+        assert_eq!(utxos.len(), 1);
+
+        utxos.apply_block(&block, 100).unwrap();
+        assert_eq!(utxos.get(&genesis_out), None);
+        assert_eq!(utxos.get(&tx1_out0), None);
+        assert_eq!(utxos.get(&tx1_out1).unwrap().value, 20_0000_0000);
+        assert_eq!(utxos.get(&tx2_out).unwrap().value, 29_0000_0000);
+        assert_eq!(utxos.get_utxo(&coinbase_out).unwrap().height, 100);
+        assert_eq!(utxos.len(), 3);
+    }
+
+    #[test]
+    fn reject_invalid_spends() {
+        let genesis = genesis_block(Network::Regtest);
+        let genesis_out = OutPoint::new(genesis.txdata[0].txid(), 0);
+        let mut utxos = UtxoSet::new(Params::new(Network::Regtest));
+        utxos.apply_block(&genesis, 0).unwrap();
+
+        let double_spend = block_with(vec![
+            spend(&[genesis_out], &[1]),
+            spend(&[genesis_out], &[2]),
+        ]);
+        assert_eq!(utxos.apply_block(&double_spend, 200), Err(Error::DoubleSpend(genesis_out)));
+
+        let missing = OutPoint::new(genesis.txdata[0].txid(), 1);
+        let missing_input = block_with(vec![spend(&[missing], &[1])]);
+        assert_eq!(utxos.apply_block(&missing_input, 200), Err(Error::MissingInput(missing)));
+
+        // Spending the coinbase of the same block is never mature.
+        let block = block_with(vec![]);
+        let coinbase_out = OutPoint::new(block.txdata[0].txid(), 0);
+        let mut immature = block.clone();
+        immature.txdata.push(spend(&[coinbase_out], &[1]));
+        assert_eq!(utxos.apply_block(&immature, 200), Err(Error::ImmatureCoinbase(coinbase_out)));
+
+        // The failed blocks left the set untouched.
+        assert_eq!(utxos.len(), 1);
+        assert!(utxos.get(&genesis_out).is_some());
+    }
+
+    #[test]
+    fn undo_block() {
+        let genesis = genesis_block(Network::Regtest);
+        let genesis_out = OutPoint::new(genesis.txdata[0].txid(), 0);
+        let mut utxos = UtxoSet::new(Params::new(Network::Regtest));
+        utxos.apply_block(&genesis, 0).unwrap();
+        let other = block_with(vec![]);
+        utxos.apply_block(&other, 1).unwrap();
+        let other_out = OutPoint::new(other.txdata[0].txid(), 0);
+
+        // Spend both coinbases, and an output created in the same block.
+        let tx1 = spend(&[genesis_out], &[30_0000_0000, 20_0000_0000]);
+        let tx2 = spend(&[OutPoint::new(tx1.txid(), 0), other_out], &[79_0000_0000]);
+        let mut block = block_with(vec![tx1, tx2]);
+        block.txdata[0].lock_time = 42;
+        let before = utxos.clone();
+
+        let undo = utxos.apply_block_with_undo(&block, 101).unwrap();
+        assert_eq!(undo.spent.len(), 2);
+        assert_eq!(undo.spent[0][0], *before.get_utxo(&genesis_out).unwrap());
+        assert_eq!(undo.spent[1][0].height, 101);
+        assert_eq!(undo.spent[1][1], *before.get_utxo(&other_out).unwrap());
+        assert_eq!(utxos.len(), 3);
+
+        // Mismatching undo data is rejected without touching the set.
+        let after = utxos.clone();
+        assert_eq!(utxos.undo_block(&block, &BlockUndo::default()), Err(Error::UndoMismatch));
+        let mut short = undo.clone();
+        short.spent[1].pop();
+        assert_eq!(utxos.undo_block(&block, &short), Err(Error::UndoMismatch));
+        assert_eq!(utxos.utxos, after.utxos);
+
+        utxos.undo_block(&block, &undo).unwrap();
+        assert_eq!(utxos.utxos, before.utxos);
+        assert!(utxos.get_utxo(&genesis_out).unwrap().is_coinbase);
+    }
+}